@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
 
 use azula_type::prelude::AzulaType;
 
@@ -8,6 +8,9 @@ pub enum Statement<'a> {
     Block(Body<'a>),
     Function {
         name: &'a str,
+        public: bool,
+        attributes: Vec<Attribute<'a>>,
+        type_params: Vec<&'a str>,
         args: Vec<TypedIdentifier<'a>>,
         returns: AzulaType<'a>,
         body: Rc<Statement<'a>>,
@@ -22,7 +25,7 @@ pub enum Statement<'a> {
         Span,
     ),
     ExpressionStatement(ExpressionNode<'a>, Span),
-    If(ExpressionNode<'a>, Body<'a>, Span),
+    If(ExpressionNode<'a>, Body<'a>, Option<Body<'a>>, Span),
     ExternFunction {
         name: &'a str,
         varargs: bool,
@@ -32,11 +35,98 @@ pub enum Statement<'a> {
     },
     Reassign(ExpressionNode<'a>, ExpressionNode<'a>, Span),
     While(ExpressionNode<'a>, Body<'a>, Span),
+    For(String, ExpressionNode<'a>, Body<'a>, Span),
+    Break(Span),
+    Continue(Span),
     Struct {
         name: &'a str,
+        public: bool,
+        type_params: Vec<&'a str>,
         attributes: Vec<TypedIdentifier<'a>>,
         span: Span,
     },
+    Enum {
+        name: &'a str,
+        public: bool,
+        variants: Vec<Variant<'a>>,
+        span: Span,
+    },
+    Impl {
+        name: &'a str,
+        methods: Vec<Statement<'a>>,
+        span: Span,
+    },
+    Import(&'a str, Span),
+    // A generic function or struct with its type parameters substituted for the
+    // type arguments, created by the typechecker for every distinct use
+    Instance(Vec<AzulaType<'a>>, Rc<Statement<'a>>),
+}
+
+impl<'a> Statement<'a> {
+    /// Where the statement is written. Blocks don't keep a span of their own,
+    /// so theirs runs from their first statement to their last
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Function { span, .. }
+            | Statement::Return(_, span)
+            | Statement::Assign(_, _, _, _, span)
+            | Statement::ExpressionStatement(_, span)
+            | Statement::If(_, _, _, span)
+            | Statement::ExternFunction { span, .. }
+            | Statement::Reassign(_, _, span)
+            | Statement::While(_, _, span)
+            | Statement::For(_, _, _, span)
+            | Statement::Break(span)
+            | Statement::Continue(span)
+            | Statement::Struct { span, .. }
+            | Statement::Enum { span, .. }
+            | Statement::Impl { span, .. }
+            | Statement::Import(_, span) => span.clone(),
+            Statement::Root(stmts) | Statement::Block(stmts) => match (stmts.first(), stmts.last())
+            {
+                (Some(first), Some(last)) => Span {
+                    start: first.span().start,
+                    end: last.span().end,
+                },
+                _ => Span { start: 0, end: 0 },
+            },
+            Statement::Instance(_, stmt) => stmt.span(),
+        }
+    }
+
+    /// The keyword or name the statement is known by in errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Statement::Root(..) => "root",
+            Statement::Block(..) => "block",
+            Statement::Function { .. } => "function",
+            Statement::Return(..) => "return",
+            Statement::Assign(..) => "assign",
+            Statement::ExpressionStatement(..) => "expression",
+            Statement::If(..) => "if",
+            Statement::ExternFunction { .. } => "extern",
+            Statement::Reassign(..) => "reassign",
+            Statement::While(..) => "while",
+            Statement::For(..) => "for",
+            Statement::Break(..) => "break",
+            Statement::Continue(..) => "continue",
+            Statement::Struct { .. } => "struct",
+            Statement::Enum { .. } => "enum",
+            Statement::Impl { .. } => "impl",
+            Statement::Import(..) => "import",
+            Statement::Instance(..) => "instance",
+        }
+    }
+
+    /// Whether the statement is a function marked `#[test]`
+    pub fn is_test(&self) -> bool {
+        match self {
+            Statement::Function { attributes, .. } => {
+                attributes.iter().any(|attribute| attribute.name == "test")
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -50,6 +140,8 @@ pub enum Expression<'a> {
     FunctionCall {
         function: Rc<ExpressionNode<'a>>,
         args: Vec<ExpressionNode<'a>>,
+        // Inferred by the typechecker when calling a generic function
+        type_args: Vec<AzulaType<'a>>,
     },
     Not(Rc<ExpressionNode<'a>>),
     Pointer(Rc<ExpressionNode<'a>>),
@@ -57,6 +149,28 @@ pub enum Expression<'a> {
     ArrayAccess(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
     StructInitialisation(Rc<ExpressionNode<'a>>, Vec<(&'a str, ExpressionNode<'a>)>),
     StructAccess(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
+    // Only produced as the iterable of a for loop
+    Range(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
+    Match(Rc<ExpressionNode<'a>>, Vec<MatchArm<'a>>),
+    // Enum name, variant name and payload. Parsed as a struct access or method call,
+    // and turned into this by the typechecker once it knows the name is an enum
+    EnumVariant(String, String, Vec<ExpressionNode<'a>>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm<'a> {
+    pub pattern: Pattern<'a>,
+    pub body: Body<'a>,
+    /// The value of the arm when the match is used as an expression
+    pub value: Option<ExpressionNode<'a>>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern<'a> {
+    // Variant name and the names its payload is bound to
+    Variant(&'a str, Vec<&'a str>),
+    Wildcard,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -84,8 +198,40 @@ pub enum Operator {
     Gte,
 }
 
+/// Operators are written the way they are in source, like `>=`
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Mod => "%",
+            Operator::Power => "**",
+            Operator::Or => "||",
+            Operator::And => "&&",
+            Operator::Eq => "==",
+            Operator::Neq => "!=",
+            Operator::Lt => "<",
+            Operator::Lte => "<=",
+            Operator::Gt => ">",
+            Operator::Gte => ">=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// An attribute written before a function, like `#[allow(unused_variables)]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub args: Vec<&'a str>,
+    pub span: Span,
+}
+
 pub type Body<'a> = Vec<Statement<'a>>;
 pub type TypedIdentifier<'a> = (AzulaType<'a>, &'a str);
+pub type Variant<'a> = (&'a str, Vec<AzulaType<'a>>);
 
 // #[derive(Debug, PartialEq, Clone)]
 // pub enum Type<'a> {
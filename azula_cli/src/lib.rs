@@ -1,13 +1,30 @@
 use std::{
-    fs,
+    fs, io,
+    path::Path,
     process::{exit, Command},
 };
 
-use azula_codegen::prelude::{Backend, Codegen, OptimizationLevel};
-use azula_codegen_llvm::prelude::LLVMCodegen;
-use azula_parser::prelude::{Lexer, Parser};
-use azula_typecheck::prelude::Typechecker;
-use clap::{StructOpt, Subcommand};
+use azula_codegen::prelude::{
+    Artifact, Backend, BackendError, BackendOptions, EmitKind, OptimizationLevel,
+};
+use azula_codegen_c::prelude::CCodegen;
+use azula_codegen_llvm::prelude::{Context, LLVMCodegen};
+use azula_docgen::prelude::{document, render, Format};
+use azula_error::prelude::{AzulaError, ErrorType, Severity};
+use azula_formatter::prelude::format;
+use azula_interpreter::prelude::{Interpreter, RuntimeValue};
+use azula_ir::{
+    passes::PassManager,
+    prelude::{parse_module, verify, Module},
+};
+use azula_resolver::prelude::Resolver;
+use clap::{ArgEnum, Args, StructOpt, Subcommand};
+use linker::{LinkOptions, Linker, LinkerKind};
+use lsp::Server;
+
+mod json;
+mod linker;
+mod lsp;
 
 /// Azula command line
 #[derive(clap::Parser, Debug)]
@@ -15,6 +32,65 @@ use clap::{StructOpt, Subcommand};
 pub struct AzulaCLI {
     #[clap(subcommand)]
     command: Commands,
+
+    #[clap(flatten)]
+    diagnostics: Diagnostics,
+}
+
+/// How errors and warnings are reported
+#[derive(Args, Debug)]
+struct Diagnostics {
+    /// How errors are printed to stderr
+    #[clap(long, arg_enum, global = true, default_value = "human")]
+    message_format: MessageFormat,
+
+    /// Treat warnings as errors
+    #[clap(long, global = true)]
+    deny_warnings: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum MessageFormat {
+    Human,
+    // One JSON object per line, for editors and CI
+    Json,
+}
+
+/// The backend a program is compiled with
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum BackendKind {
+    Llvm,
+    C,
+}
+
+/// What a build produces
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+enum EmitTarget {
+    Exe,
+    // Stop after writing the object file, without linking
+    Obj,
+}
+
+/// What documentation is written as
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum DocFormat {
+    Html,
+    Markdown,
+}
+
+#[derive(Args, Debug)]
+struct LinkArgs {
+    /// The linker to use, found automatically when not given
+    #[clap(long, arg_enum)]
+    linker: Option<LinkerKind>,
+
+    /// A library to link with
+    #[clap(short = 'l', multiple_occurrences = true)]
+    libraries: Vec<String>,
+
+    /// A directory to search for libraries in
+    #[clap(short = 'L', multiple_occurrences = true)]
+    library_paths: Vec<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -27,6 +103,21 @@ enum Commands {
 
         #[clap(long)]
         print_azula_ir: bool,
+
+        /// Print the IR after each optimisation pass
+        #[clap(long)]
+        dump_passes: bool,
+
+        /// Interpret the program's IR instead of compiling it
+        #[clap(long)]
+        interpret: bool,
+
+        /// The backend to compile the program with
+        #[clap(long, arg_enum, default_value = "llvm")]
+        backend: BackendKind,
+
+        #[clap(flatten)]
+        link: LinkArgs,
     },
     Build {
         file: String,
@@ -34,6 +125,7 @@ enum Commands {
         #[clap(long)]
         target: Option<String>,
 
+        /// Also write out the LLVM IR, or the C source with the C backend
         #[clap(long)]
         emit_llvm: bool,
 
@@ -42,6 +134,60 @@ enum Commands {
 
         #[clap(long)]
         print_azula_ir: bool,
+
+        /// Print the IR after each optimisation pass
+        #[clap(long)]
+        dump_passes: bool,
+
+        /// The backend to compile the program with
+        #[clap(long, arg_enum, default_value = "llvm")]
+        backend: BackendKind,
+
+        /// Whether to link an executable or only write the object file
+        #[clap(long, arg_enum, default_value = "exe")]
+        emit: EmitTarget,
+
+        #[clap(flatten)]
+        link: LinkArgs,
+    },
+    /// Run the `#[test]` functions in a file and print which passed
+    Test {
+        file: String,
+
+        #[clap(long)]
+        release: bool,
+
+        /// Interpret the tests' IR instead of compiling them
+        #[clap(long)]
+        interpret: bool,
+
+        /// The backend to compile the tests with
+        #[clap(long, arg_enum, default_value = "llvm")]
+        backend: BackendKind,
+
+        #[clap(flatten)]
+        link: LinkArgs,
+    },
+    /// Start a language server on stdin and stdout, for editors
+    Lsp,
+    /// Format files in the canonical style
+    Fmt {
+        files: Vec<String>,
+
+        /// List the files that aren't formatted instead of changing them
+        #[clap(long)]
+        check: bool,
+    },
+    /// Write a reference for files from their `///` doc comments
+    Doc {
+        files: Vec<String>,
+
+        #[clap(long, arg_enum, default_value = "html")]
+        format: DocFormat,
+
+        /// The directory to write the reference to
+        #[clap(long, short, default_value = "doc")]
+        out: String,
     },
 }
 
@@ -53,8 +199,41 @@ pub fn run() {
             file,
             release,
             print_azula_ir,
+            dump_passes,
+            interpret,
+            backend,
+            link,
         } => {
-            let result = build(file, ".build/", None, false, *release, *print_azula_ir);
+            if *interpret {
+                let mut resolver = Resolver::new();
+                let mut module = resolve(&mut resolver, file, *print_azula_ir, &args.diagnostics);
+                optimise(&mut module, *dump_passes, args.diagnostics.message_format);
+                let mut stdout = std::io::stdout();
+                match Interpreter::new(&module, &mut stdout).run() {
+                    Ok(RuntimeValue::Int(code)) => exit(code as i32),
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("Runtime error: {}", e);
+                        exit(1);
+                    }
+                }
+                return;
+            }
+
+            let result = build(
+                &mut Resolver::new(),
+                file,
+                ".build/",
+                None,
+                false,
+                *release,
+                *print_azula_ir,
+                *dump_passes,
+                *backend,
+                EmitTarget::Exe,
+                link,
+                &args.diagnostics,
+            );
 
             Command::new(format!("./.build/{}", result))
                 .spawn()
@@ -68,74 +247,368 @@ pub fn run() {
             emit_llvm,
             release,
             print_azula_ir,
+            dump_passes,
+            backend,
+            emit,
+            link,
         } => {
             build(
+                &mut Resolver::new(),
                 file,
                 "",
                 target.as_ref(),
                 *emit_llvm,
                 *release,
                 *print_azula_ir,
+                *dump_passes,
+                *backend,
+                *emit,
+                link,
+                &args.diagnostics,
             );
         }
+        Commands::Test {
+            file,
+            release,
+            interpret,
+            backend,
+            link,
+        } => {
+            let mut resolver = Resolver::new();
+            resolver.tests = true;
+
+            if *interpret {
+                let mut module = resolve(&mut resolver, file, false, &args.diagnostics);
+                optimise(&mut module, false, args.diagnostics.message_format);
+                let mut stdout = std::io::stdout();
+                match Interpreter::new(&module, &mut stdout).run() {
+                    Ok(RuntimeValue::Int(code)) => exit(code as i32),
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("Runtime error: {}", e);
+                        exit(1);
+                    }
+                }
+                return;
+            }
+
+            let result = build(
+                &mut resolver,
+                file,
+                ".build/",
+                None,
+                false,
+                *release,
+                false,
+                false,
+                *backend,
+                EmitTarget::Exe,
+                link,
+                &args.diagnostics,
+            );
+
+            // The harness exits with 1 if any test failed
+            let status = Command::new(format!("./.build/{}", result))
+                .status()
+                .unwrap();
+            exit(status.code().unwrap_or(1));
+        }
+        Commands::Lsp => {
+            let stdin = io::stdin();
+            match Server::new(io::stdout()).run(&mut stdin.lock()) {
+                Ok(code) => exit(code),
+                Err(e) => {
+                    eprintln!("Language server failed: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        Commands::Fmt { files, check } => {
+            if !fmt(files, *check, args.diagnostics.message_format) {
+                exit(1);
+            }
+        }
+        Commands::Doc { files, format, out } => {
+            doc(files, *format, out, args.diagnostics.message_format);
+        }
+    }
+}
+
+/// Writes the documentation for some files into a directory, exiting if any
+/// of them can't be read or parsed
+fn doc(files: &[String], format: DocFormat, out: &str, format_kind: MessageFormat) {
+    let mut sources = vec![];
+    for path in files {
+        match fs::read_to_string(path) {
+            Ok(source) => sources.push(source),
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path, e);
+                exit(1);
+            }
+        }
+    }
+
+    let mut pages = vec![];
+    let mut failed = false;
+    for (path, source) in files.iter().zip(&sources) {
+        match document(path, source) {
+            Ok(page) => pages.push(page),
+            Err(errors) => {
+                for err in &errors {
+                    report(err, source, path, format_kind);
+                }
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+
+    let format = match format {
+        DocFormat::Html => Format::Html,
+        DocFormat::Markdown => Format::Markdown,
+    };
+    let result = fs::create_dir_all(out).and_then(|_| {
+        render(&pages, format)
+            .into_iter()
+            .try_for_each(|(name, contents)| fs::write(Path::new(out).join(name), contents))
+    });
+    if let Err(e) = result {
+        eprintln!("Could not write documentation to {}: {}", out, e);
+        exit(1);
+    }
+}
+
+/// Formats each file in place, or with `check` only lists the ones that
+/// would change. Gives whether every file was already formatted, or could be
+fn fmt(files: &[String], check: bool, format_kind: MessageFormat) -> bool {
+    let mut ok = true;
+    for path in files {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path, e);
+                ok = false;
+                continue;
+            }
+        };
+
+        let formatted = match format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for err in &errors {
+                    report(err, &source, path, format_kind);
+                }
+                ok = false;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{}", path);
+            ok = false;
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Could not write {}: {}", path, e);
+            ok = false;
+        }
     }
+    ok
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build<'a>(
+    resolver: &mut Resolver,
     name: &'a str,
     destination: &'a str,
     target: Option<&String>,
     emit_llvm: bool,
     release: bool,
     print_azula_ir: bool,
+    dump_passes: bool,
+    backend: BackendKind,
+    emit: EmitTarget,
+    link: &LinkArgs,
+    diagnostics: &Diagnostics,
 ) -> &'a str {
-    let input = fs::read_to_string(name).unwrap();
-    let lexer: Lexer = input.as_str().into();
-    let mut parser = Parser::new(input.as_str(), lexer);
-    let parsed = parser.parse();
-    for error in &parser.errors {
-        error.print_stdout(&input, name);
+    let mut module = resolve(resolver, name, print_azula_ir, diagnostics);
+    optimise(&mut module, dump_passes, diagnostics.message_format);
+
+    let name = name.trim_end_matches(".azl").trim_end_matches(".azir");
+
+    let options = BackendOptions {
+        target: target.cloned(),
+        opt_level: if release {
+            OptimizationLevel::Aggressive
+        } else {
+            OptimizationLevel::Default
+        },
+    };
+
+    let context = Context::create();
+    let mut backend: Box<dyn Backend> = match backend {
+        BackendKind::Llvm => Box::new(LLVMCodegen::new(&context, options.clone())),
+        BackendKind::C => Box::new(CCodegen::new(options.clone())),
+    };
+    let object = match emit {
+        EmitTarget::Exe => format!(".build/{}.o", name),
+        EmitTarget::Obj => format!("{}{}.o", destination, name),
+    };
+    let object = match compile(backend.as_mut(), &module, name, emit_llvm, &object) {
+        Ok(object) => object,
+        Err(e) => {
+            let err = ErrorType::BuildFailed(name.to_string(), e.to_string());
+            report_unplaced(&err, diagnostics.message_format);
+            exit(1);
+        }
+    };
+
+    if emit == EmitTarget::Exe {
+        let options = LinkOptions {
+            target: options.target,
+            libraries: link.libraries.clone(),
+            library_paths: link.library_paths.clone(),
+        };
+        let output = format!("{}{}", destination, name);
+        let linker = match link.linker {
+            Some(kind) => Some(Linker::new(kind)),
+            None => Linker::detect(options.target.as_deref()),
+        };
+        let result = linker
+            .ok_or(ErrorType::NoLinkerFound)
+            .and_then(|linker| linker.link(&[object], Path::new(&output), &options));
+        if let Err(e) = result {
+            report_unplaced(&e, diagnostics.message_format);
+            exit(1);
+        }
     }
 
-    if !parser.errors.is_empty() {
-        exit(1);
+    return name;
+}
+
+/// Lowers a module with a backend and writes it out as an object file,
+/// along with the backend's IR next to the program if asked
+fn compile<'a>(
+    backend: &mut dyn Backend<'a>,
+    module: &Module<'a>,
+    name: &str,
+    emit_ir: bool,
+    object: &str,
+) -> Result<Artifact, BackendError> {
+    backend.lower(module)?;
+
+    if emit_ir {
+        let path = format!("{}.{}", name, backend.extension(EmitKind::Ir));
+        backend.emit_to_path(EmitKind::Ir, Path::new(&path))?;
     }
 
-    let mut typecheck = Typechecker::new(parsed);
-    let result = typecheck.typecheck();
-    for err in typecheck.errors {
-        err.print_stdout(&input, name);
+    backend.emit_to_path(EmitKind::Object, Path::new(object))
+}
+
+/// Resolves a file and its imports into a module, printing any errors and
+/// exiting if there were some. `.azir` files are read as textual IR
+fn resolve(
+    resolver: &mut Resolver,
+    name: &str,
+    print_azula_ir: bool,
+    diagnostics: &Diagnostics,
+) -> Module<'static> {
+    let result = if name.ends_with(".azir") {
+        parse_ir(name)
+    } else {
+        resolver.resolve(name)
+    };
+    for (file, err) in &resolver.errors {
+        let file = &resolver.files[*file];
+        report(err, file.source, &file.path, diagnostics.message_format);
     }
 
-    if result.is_err() {
+    let denied = diagnostics.deny_warnings && !resolver.warnings.is_empty();
+    for (file, warning) in resolver.warnings.iter_mut() {
+        if denied {
+            warning.severity = Severity::Error;
+        }
+        let file = &resolver.files[*file];
+        report(warning, file.source, &file.path, diagnostics.message_format);
+    }
+    if denied {
         exit(1);
     }
 
-    let root = result.unwrap();
+    let module = match result {
+        Ok(module) => module,
+        Err(e) => {
+            if resolver.errors.is_empty() {
+                eprintln!("{}", e);
+            }
+            exit(1);
+        }
+    };
+
+    if print_azula_ir {
+        println!("{}", module);
+    }
 
-    let name = name.trim_end_matches(".azl");
+    // Codegen output is only verified in debug builds, handwritten IR always
+    if cfg!(debug_assertions) || name.ends_with(".azir") {
+        if let Err(errors) = verify(&module) {
+            for err in errors {
+                report_unplaced(
+                    &ErrorType::InvalidIr(err.to_string()),
+                    diagnostics.message_format,
+                );
+            }
+            exit(1);
+        }
+    }
 
-    let mut codegen = Codegen::new(name, root);
-    codegen.codegen();
-    codegen.insert_implicit_return();
+    module
+}
 
-    if print_azula_ir {
-        println!("{}", codegen.module);
+fn report(err: &AzulaError, source: &str, path: &str, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => err.print_stderr(source, path),
+        MessageFormat::Json => eprintln!("{}", err.to_json(source, path)),
     }
+}
 
-    LLVMCodegen::codegen(
-        name,
-        destination,
-        emit_llvm,
-        target,
-        if release {
-            OptimizationLevel::Aggressive
-        } else {
-            OptimizationLevel::Default
-        },
-        codegen.module,
-    )
-    .unwrap();
+/// Reports an error that isn't tied to a place in any source file
+fn report_unplaced(err: &ErrorType, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => err.print_stderr(),
+        MessageFormat::Json => eprintln!("{}", err.to_json()),
+    }
+}
 
-    return name;
+fn parse_ir(name: &str) -> Result<Module<'static>, String> {
+    let source = match fs::read_to_string(name) {
+        Ok(source) => source,
+        Err(e) => return Err(format!("Could not read {}: {}", name, e)),
+    };
+    let source: &'static str = Box::leak(source.into_boxed_str());
+
+    parse_module(source).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// Runs the IR optimisation passes, printing the IR each one leaves if asked
+fn optimise(module: &mut Module, dump_passes: bool, format: MessageFormat) {
+    PassManager::optimisations().run(module, |pass, module| {
+        if dump_passes {
+            println!("IR after {}:", pass);
+            println!("{}", module);
+        }
+    });
+
+    if cfg!(debug_assertions) {
+        if let Err(errors) = verify(module) {
+            for err in errors {
+                let err = ErrorType::InvalidOptimisedIr(err.to_string());
+                report_unplaced(&err, format);
+            }
+            exit(1);
+        }
+    }
 }
@@ -0,0 +1,204 @@
+use std::{env, path::Path, process::Command};
+
+use azula_codegen::prelude::Artifact;
+use azula_error::prelude::ErrorType;
+use clap::ArgEnum;
+
+/// The programs object files can be linked with
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LinkerKind {
+    Cc,
+    Clang,
+    // Links only what it's given, so the C runtime and library have to be
+    // passed with -l and -L
+    #[clap(name = "ld.lld")]
+    Lld,
+    Zig,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkOptions {
+    pub target: Option<String>,
+    pub libraries: Vec<String>,
+    pub library_paths: Vec<String>,
+}
+
+pub struct Linker {
+    kind: LinkerKind,
+    program: String,
+}
+
+impl Linker {
+    pub fn new(kind: LinkerKind) -> Self {
+        let program = match kind {
+            LinkerKind::Cc => env::var("CC").unwrap_or_else(|_| "cc".to_string()),
+            LinkerKind::Clang => "clang".to_string(),
+            LinkerKind::Lld => "ld.lld".to_string(),
+            LinkerKind::Zig => "zig".to_string(),
+        };
+
+        Linker { kind, program }
+    }
+
+    /// Picks the first linker that's installed. Only zig and clang can link
+    /// for targets other than the host
+    pub fn detect(target: Option<&str>) -> Option<Self> {
+        let candidates = match target {
+            Some(_) => vec![LinkerKind::Zig, LinkerKind::Clang],
+            None => vec![LinkerKind::Cc, LinkerKind::Clang, LinkerKind::Zig],
+        };
+
+        candidates
+            .into_iter()
+            .map(Linker::new)
+            .find(|linker| linker.is_installed())
+    }
+
+    fn is_installed(&self) -> bool {
+        let program = Path::new(&self.program);
+        if program.components().count() > 1 {
+            return program.is_file();
+        }
+
+        env::var_os("PATH")
+            .map(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+            .unwrap_or(false)
+    }
+
+    /// Links object files into an executable
+    pub fn link(
+        &self,
+        objects: &[Artifact],
+        output: &Path,
+        options: &LinkOptions,
+    ) -> Result<(), ErrorType> {
+        let mut command = self.command(objects, output, options)?;
+
+        let status = command
+            .status()
+            .map_err(|e| ErrorType::LinkerNotRun(self.program.clone(), e.to_string()))?;
+        if !status.success() {
+            return Err(ErrorType::LinkFailed(
+                self.program.clone(),
+                output.display().to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn command(
+        &self,
+        objects: &[Artifact],
+        output: &Path,
+        options: &LinkOptions,
+    ) -> Result<Command, ErrorType> {
+        let mut command = Command::new(&self.program);
+
+        match (self.kind, &options.target) {
+            (LinkerKind::Zig, target) => {
+                command.arg("cc");
+                if let Some(target) = target {
+                    command.arg("-target").arg(target);
+                }
+            }
+            (LinkerKind::Clang, Some(target)) => {
+                command.arg(format!("--target={}", target));
+            }
+            // lld works out the target from the objects
+            (LinkerKind::Lld, _) | (_, None) => {}
+            (_, Some(target)) => {
+                return Err(ErrorType::LinkerCannotTarget(
+                    self.program.clone(),
+                    target.clone(),
+                ))
+            }
+        }
+
+        command.arg("-o").arg(output);
+        command.args(objects.iter().map(|object| &object.path));
+        for path in &options.library_paths {
+            command.arg(format!("-L{}", path));
+        }
+        for library in &options.libraries {
+            command.arg(format!("-l{}", library));
+        }
+        // The compiler drivers bring in the C library, but programs using
+        // floats also need the maths library
+        if self.kind != LinkerKind::Lld {
+            command.arg("-lm");
+        }
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use azula_codegen::prelude::EmitKind;
+
+    use super::*;
+
+    fn arguments(kind: LinkerKind, options: &LinkOptions) -> Result<Vec<String>, ErrorType> {
+        let objects = [Artifact {
+            kind: EmitKind::Object,
+            path: PathBuf::from(".build/main.o"),
+        }];
+        let command = Linker::new(kind).command(&objects, Path::new("main"), options)?;
+        Ok(command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_linker_arguments() {
+        let options = LinkOptions {
+            target: None,
+            libraries: vec!["sqlite3".to_string()],
+            library_paths: vec!["/opt/lib".to_string()],
+        };
+        assert_eq!(
+            arguments(LinkerKind::Cc, &options).unwrap(),
+            [
+                "-o",
+                "main",
+                ".build/main.o",
+                "-L/opt/lib",
+                "-lsqlite3",
+                "-lm"
+            ]
+        );
+        assert_eq!(
+            arguments(LinkerKind::Lld, &options).unwrap(),
+            ["-o", "main", ".build/main.o", "-L/opt/lib", "-lsqlite3"]
+        );
+
+        let options = LinkOptions {
+            target: Some("aarch64-linux-gnu".to_string()),
+            ..LinkOptions::default()
+        };
+        assert_eq!(
+            arguments(LinkerKind::Zig, &options).unwrap(),
+            [
+                "cc",
+                "-target",
+                "aarch64-linux-gnu",
+                "-o",
+                "main",
+                ".build/main.o",
+                "-lm"
+            ]
+        );
+        assert_eq!(
+            arguments(LinkerKind::Clang, &options).unwrap()[0],
+            "--target=aarch64-linux-gnu"
+        );
+        assert!(matches!(
+            arguments(LinkerKind::Cc, &options),
+            Err(ErrorType::LinkerCannotTarget(..))
+        ));
+    }
+}
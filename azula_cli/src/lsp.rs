@@ -0,0 +1,878 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    panic::{self, UnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use azula_ast::prelude::*;
+use azula_error::prelude::{AzulaError, ErrorType, Severity};
+use azula_parser::prelude::{Lexer, Parser};
+use azula_resolver::prelude::{public_declarations, rewrite_asserts};
+use azula_type::prelude::AzulaType;
+use azula_typecheck::prelude::{name_span, Linter, Typechecker};
+
+use crate::json::Json;
+
+/// A language server speaking JSON-RPC over stdin and stdout. Documents are
+/// checked again in full every time they change
+pub struct Server<W: Write> {
+    documents: HashMap<String, Document>,
+    output: W,
+    shutdown: bool,
+}
+
+struct Document {
+    source: String,
+    analysis: Analysis,
+}
+
+/// What's known about a document after checking it, owned so it doesn't
+/// borrow from a source that's replaced by the next edit
+#[derive(Default)]
+struct Analysis {
+    diagnostics: Vec<AzulaError>,
+    /// The type of each expression, only known once the document typechecks
+    types: Vec<(Span, String)>,
+    /// Where each name is used, along with where it's defined
+    definitions: Vec<(Span, Span)>,
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            documents: HashMap::new(),
+            output,
+            shutdown: false,
+        }
+    }
+
+    /// Handles messages until the client asks the server to exit, giving the
+    /// code the process should exit with
+    pub fn run(&mut self, input: &mut impl BufRead) -> io::Result<i32> {
+        while let Some(body) = read_message(input)? {
+            let message = match Json::parse(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    self.send(Json::object([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", Json::Null),
+                        ("error", error(-32700, &e)),
+                    ]))?;
+                    continue;
+                }
+            };
+
+            if message.get("method").as_str() == Some("exit") {
+                return Ok(if self.shutdown { 0 } else { 1 });
+            }
+            self.handle(&message)?;
+        }
+
+        Ok(1)
+    }
+
+    fn handle(&mut self, message: &Json) -> io::Result<()> {
+        let method = message.get("method").as_str().unwrap_or_default();
+        let params = message.get("params");
+
+        let result = match method {
+            "initialize" => Ok(Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        // The whole document is sent on every change
+                        ("textDocumentSync", 1.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object([
+                        ("name", "azula".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                if let (Some(uri), Some(text)) =
+                    (document.get("uri").as_str(), document.get("text").as_str())
+                {
+                    self.update(uri, text.to_string())?;
+                }
+                return Ok(());
+            }
+            "textDocument/didChange" => {
+                let uri = params.get("textDocument").get("uri").as_str();
+                let changes = params.get("contentChanges").as_array().unwrap_or_default();
+                if let (Some(uri), Some(change)) = (uri, changes.last()) {
+                    if let Some(text) = change.get("text").as_str() {
+                        self.update(uri, text.to_string())?;
+                    }
+                }
+                return Ok(());
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+                    self.documents.remove(uri);
+                    self.publish(uri, Json::Array(vec![]))?;
+                }
+                return Ok(());
+            }
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            _ => Err(error(-32601, &format!("Unknown method {}", method))),
+        };
+
+        // Notifications have no id and get no response
+        let id = message.get("id");
+        if *id == Json::Null {
+            return Ok(());
+        }
+        let (key, value) = match result {
+            Ok(result) => ("result", result),
+            Err(error) => ("error", error),
+        };
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.clone()),
+            (key, value),
+        ]))
+    }
+
+    fn update(&mut self, uri: &str, source: String) -> io::Result<()> {
+        let path = uri_to_path(uri);
+        let analysis = catch_panics(|| analyse(&path, &source));
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| to_diagnostic(diagnostic, &source, uri))
+            .collect();
+
+        self.documents
+            .insert(uri.to_string(), Document { source, analysis });
+        self.publish(uri, Json::Array(diagnostics))
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Json) -> io::Result<()> {
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([("uri", uri.into()), ("diagnostics", diagnostics)]),
+            ),
+        ]))
+    }
+
+    /// The document a request is about, and the byte offset it points at
+    fn position<'p>(&self, params: &'p Json) -> Option<(&'p str, &Document, usize)> {
+        let uri = params.get("textDocument").get("uri").as_str()?;
+        let document = self.documents.get(uri)?;
+        let position = params.get("position");
+        let offset = offset(
+            &document.source,
+            position.get("line").as_usize()?,
+            position.get("character").as_usize()?,
+        );
+        Some((uri, document, offset))
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let (_, document, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Json::Null,
+        };
+
+        match innermost(&document.analysis.types, offset) {
+            Some((span, typ)) => Json::object([
+                (
+                    "contents",
+                    Json::object([
+                        ("kind", "markdown".into()),
+                        ("value", format!("```azula\n{}\n```", typ).into()),
+                    ]),
+                ),
+                ("range", range(&document.source, span.start, span.end)),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let (uri, document, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Json::Null,
+        };
+
+        match innermost(&document.analysis.definitions, offset) {
+            Some((_, definition)) => Json::object([
+                ("uri", uri.into()),
+                (
+                    "range",
+                    range(&document.source, definition.start, definition.end),
+                ),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+}
+
+/// Reads the body of the next message, or `None` once the input is closed
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = match length {
+        Some(length) => length,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Message without a Content-Length",
+            ))
+        }
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn error(code: i32, message: &str) -> Json {
+    Json::object([
+        ("code", Json::Number(code as f64)),
+        ("message", message.into()),
+    ])
+}
+
+/// Parses, typechecks and lints a document. Imports are read from disk
+/// relative to it, and only their public declarations are looked at
+fn analyse(path: &Path, source: &str) -> Analysis {
+    let lexer: Lexer = source.into();
+    let mut parser = Parser::new(source, lexer);
+    let ast = parser.parse();
+    let mut analysis = Analysis {
+        diagnostics: parser.errors,
+        ..Analysis::default()
+    };
+
+    let stmts = match &ast {
+        Statement::Root(stmts) => stmts.clone(),
+        _ => unreachable!(),
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut imported = vec![];
+    for stmt in &stmts {
+        if let Statement::Import(import, span) = stmt {
+            match fs::read_to_string(dir.join(import)) {
+                Ok(source) => imported.push(source),
+                Err(_) => analysis.diagnostics.push(AzulaError::new(
+                    ErrorType::ImportNotFound(import.to_string()),
+                    span.start,
+                    span.end,
+                )),
+            }
+        }
+    }
+    // Nothing more can be said about a document that doesn't parse
+    if !analysis.diagnostics.is_empty() {
+        index(source, &ast, &mut analysis);
+        return analysis;
+    }
+
+    // Asserts are rewritten as in every build, and tests are checked the way
+    // `azula test` builds them rather than left out
+    let (stmts, errors) = rewrite_asserts(source, stmts, true);
+    analysis.diagnostics.extend(errors);
+    let ast = Statement::Root(stmts);
+
+    let mut typechecker = Typechecker::new(ast.clone());
+    for imported in &imported {
+        let lexer: Lexer = imported.as_str().into();
+        if let Statement::Root(stmts) = Parser::new(imported, lexer).parse() {
+            typechecker.import(public_declarations(&stmts));
+        }
+    }
+    let result = typechecker.typecheck();
+    analysis.diagnostics.extend(typechecker.errors);
+
+    match result {
+        Ok(root) => {
+            let mut linter = Linter::new(source);
+            linter.check(&root);
+            analysis.diagnostics.extend(linter.warnings);
+            index(source, &root, &mut analysis);
+        }
+        Err(_) => index(source, &ast, &mut analysis),
+    }
+
+    analysis
+}
+
+/// Turns a panic while analysing a document into a diagnostic at its start,
+/// so a bug in the compiler doesn't take the server down with it
+fn catch_panics(analyse: impl FnOnce() -> Analysis + UnwindSafe) -> Analysis {
+    match panic::catch_unwind(analyse) {
+        Ok(analysis) => analysis,
+        Err(payload) => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "the compiler panicked".to_string(),
+                },
+            };
+
+            Analysis {
+                diagnostics: vec![AzulaError::new(ErrorType::Internal(message), 0, 0)],
+                ..Analysis::default()
+            }
+        }
+    }
+}
+
+/// Collects the types of expressions and where names are defined
+fn index(source: &str, root: &Statement, analysis: &mut Analysis) {
+    let mut indexer = Indexer {
+        source,
+        globals: HashMap::new(),
+        members: HashMap::new(),
+        scopes: vec![],
+        analysis,
+    };
+    indexer.index(root);
+}
+
+struct Indexer<'s, 'a> {
+    source: &'s str,
+    /// Functions, structs, enums and global variables
+    globals: HashMap<String, Span>,
+    /// The fields and methods of each struct
+    members: HashMap<String, HashMap<String, Span>>,
+    scopes: Vec<Vec<(String, Span)>>,
+    analysis: &'a mut Analysis,
+}
+
+impl<'s, 'a> Indexer<'s, 'a> {
+    fn index(&mut self, root: &Statement) {
+        let stmts = match root {
+            Statement::Root(stmts) => stmts,
+            _ => unreachable!(),
+        };
+
+        // Declarations can be used before they're written
+        for stmt in stmts {
+            match stmt {
+                Statement::Function { name, span, .. }
+                | Statement::ExternFunction { name, span, .. }
+                | Statement::Enum { name, span, .. } => {
+                    let name_span = name_span(self.source, span, name, span.start);
+                    self.globals.insert(name.to_string(), name_span);
+                }
+                Statement::Assign(_, name, _, _, span) => {
+                    let name_span = name_span(self.source, span, name, span.start);
+                    self.globals.insert(name.clone(), name_span);
+                }
+                Statement::Struct {
+                    name,
+                    attributes,
+                    span,
+                    ..
+                } => {
+                    let mut from = name_span(self.source, span, name, span.start).end;
+                    self.globals.insert(
+                        name.to_string(),
+                        name_span(self.source, span, name, span.start),
+                    );
+                    let members = self.members.entry(name.to_string()).or_default();
+                    for (_, field) in attributes {
+                        let field_span = name_span(self.source, span, field, from);
+                        from = field_span.end;
+                        members.insert(field.to_string(), field_span);
+                    }
+                }
+                Statement::Impl { name, methods, .. } => {
+                    let members = self.members.entry(name.to_string()).or_default();
+                    for method in methods {
+                        if let Statement::Function {
+                            name: method, span, ..
+                        } = method
+                        {
+                            let method_span = name_span(self.source, span, method, span.start);
+                            members.insert(method.to_string(), method_span);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for stmt in stmts {
+            match stmt {
+                Statement::Function { .. } => self.index_function(stmt),
+                Statement::Impl { methods, .. } => {
+                    for method in methods {
+                        self.index_function(method);
+                    }
+                }
+                Statement::Assign(_, _, _, value, _) => self.index_expression(value),
+                _ => {}
+            }
+        }
+    }
+
+    fn index_function(&mut self, function: &Statement) {
+        if let Statement::Function {
+            name,
+            args,
+            body,
+            span,
+            ..
+        } = function
+        {
+            let mut from = name_span(self.source, span, name, span.start).end;
+            self.scopes.push(vec![]);
+            for (_, arg) in args {
+                let arg_span = name_span(self.source, span, arg, from);
+                from = arg_span.end;
+                self.declare(arg, arg_span);
+            }
+            if let Statement::Block(stmts) = body.as_ref() {
+                self.index_body(stmts);
+            }
+            self.scopes.pop();
+        }
+    }
+
+    fn index_body(&mut self, stmts: &[Statement]) {
+        self.scopes.push(vec![]);
+        for stmt in stmts {
+            self.index_statement(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn index_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Block(stmts) => self.index_body(stmts),
+            Statement::Return(Some(value), _) => self.index_expression(value),
+            Statement::Assign(_, name, _, value, span) => {
+                self.index_expression(value);
+                let name_span = name_span(self.source, span, name, span.start);
+                self.declare(name, name_span);
+            }
+            Statement::ExpressionStatement(expr, _) => self.index_expression(expr),
+            Statement::If(condition, then, otherwise, _) => {
+                self.index_expression(condition);
+                self.index_body(then);
+                if let Some(otherwise) = otherwise {
+                    self.index_body(otherwise);
+                }
+            }
+            Statement::Reassign(target, value, _) => {
+                self.index_expression(target);
+                self.index_expression(value);
+            }
+            Statement::While(condition, body, _) => {
+                self.index_expression(condition);
+                self.index_body(body);
+            }
+            Statement::For(name, iterable, body, span) => {
+                self.index_expression(iterable);
+                let name_span = name_span(self.source, span, name, span.start);
+                self.scopes.push(vec![]);
+                self.declare(name, name_span);
+                self.index_body(body);
+                self.scopes.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn index_expression(&mut self, expr: &ExpressionNode) {
+        if !matches!(expr.typed, AzulaType::Infer | AzulaType::Error) {
+            self.analysis
+                .types
+                .push((expr.span.clone(), expr.typed.to_string()));
+        }
+
+        match &expr.expression {
+            Expression::Identifier(name) => {
+                let definition = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .flat_map(|scope| scope.iter().rev())
+                    .find(|(local, _)| local == name)
+                    .map(|(_, span)| span)
+                    .or_else(|| self.globals.get(name));
+                if let Some(definition) = definition {
+                    self.analysis
+                        .definitions
+                        .push((expr.span.clone(), definition.clone()));
+                }
+            }
+            Expression::Infix(left, _, right) => {
+                self.index_expression(left);
+                self.index_expression(right);
+            }
+            Expression::FunctionCall { function, args, .. } => {
+                self.index_expression(function);
+                for arg in args {
+                    self.index_expression(arg);
+                }
+            }
+            Expression::Not(inner) | Expression::Pointer(inner) => self.index_expression(inner),
+            Expression::Array(items) => {
+                for item in items {
+                    self.index_expression(item);
+                }
+            }
+            Expression::ArrayAccess(array, index) => {
+                self.index_expression(array);
+                self.index_expression(index);
+            }
+            Expression::StructInitialisation(name, fields) => {
+                self.index_expression(name);
+                for (_, value) in fields {
+                    self.index_expression(value);
+                }
+            }
+            // Members and methods are found through the type of the left
+            // hand side, which is only known once the document typechecks
+            Expression::StructAccess(left, member) => {
+                self.index_expression(left);
+                let definition = match (struct_name(&left.typed), &member.expression) {
+                    (Some(struc), Expression::Identifier(member)) => self
+                        .members
+                        .get(struc)
+                        .and_then(|members| members.get(member)),
+                    _ => None,
+                };
+                if let Some(definition) = definition {
+                    self.analysis
+                        .definitions
+                        .push((member.span.clone(), definition.clone()));
+                }
+            }
+            Expression::Range(start, end) => {
+                self.index_expression(start);
+                self.index_expression(end);
+            }
+            Expression::Match(subject, arms) => {
+                self.index_expression(subject);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    if let Pattern::Variant(_, bindings) = &arm.pattern {
+                        let mut from = arm.span.start;
+                        for binding in bindings {
+                            let span = name_span(self.source, &arm.span, binding, from);
+                            from = span.end;
+                            self.declare(binding, span);
+                        }
+                    }
+                    self.index_body(&arm.body);
+                    if let Some(value) = &arm.value {
+                        self.index_expression(value);
+                    }
+                    self.scopes.pop();
+                }
+            }
+            Expression::EnumVariant(_, _, args) => {
+                for arg in args {
+                    self.index_expression(arg);
+                }
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_) => {}
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: Span) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .push((name.to_string(), span));
+    }
+}
+
+/// The struct a value or pointer to one is an instance of. Instances of
+/// generic structs are looked up by the struct they're made from
+fn struct_name<'t>(typ: &'t AzulaType) -> Option<&'t str> {
+    match typ {
+        AzulaType::Named(name) => Some(name.split('<').next().unwrap_or(name)),
+        AzulaType::Generic(name, _) => Some(name),
+        AzulaType::Pointer(inner) => struct_name(inner),
+        _ => None,
+    }
+}
+
+/// The smallest span containing an offset, for the most specific answer
+fn innermost<T>(spans: &[(Span, T)], offset: usize) -> Option<&(Span, T)> {
+    spans
+        .iter()
+        .filter(|(span, _)| span.start <= offset && offset <= span.end)
+        .min_by_key(|(span, _)| span.end - span.start)
+}
+
+fn to_diagnostic(err: &AzulaError, source: &str, uri: &str) -> Json {
+    let mut message = err.error_type.error_message();
+    for note in &err.notes {
+        message += &format!("\nnote: {}", note);
+    }
+    for help in &err.help {
+        message += &format!("\nhelp: {}", help);
+    }
+
+    let related = err
+        .labels
+        .iter()
+        .map(|label| {
+            Json::object([
+                (
+                    "location",
+                    Json::object([
+                        ("uri", uri.into()),
+                        ("range", range(source, label.start, label.end)),
+                    ]),
+                ),
+                ("message", label.message.as_str().into()),
+            ])
+        })
+        .collect();
+
+    // Editors fade out code that's unused or can't run
+    let tags = match err.error_type {
+        ErrorType::UnusedVariable(_)
+        | ErrorType::UnusedFunction(_)
+        | ErrorType::UnreachableCode => {
+            vec![1.into()]
+        }
+        _ => vec![],
+    };
+
+    Json::object([
+        ("range", range(source, err.start, err.end)),
+        (
+            "severity",
+            match err.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            }
+            .into(),
+        ),
+        ("code", err.error_type.code().into()),
+        ("source", "azula".into()),
+        ("message", message.into()),
+        ("relatedInformation", Json::Array(related)),
+        ("tags", Json::Array(tags)),
+    ])
+}
+
+fn range(source: &str, start: usize, end: usize) -> Json {
+    Json::object([
+        ("start", position(source, start)),
+        ("end", position(source, end)),
+    ])
+}
+
+/// Converts a byte offset into a line and character, which LSP counts in
+/// UTF-16 code units
+fn position(source: &str, offset: usize) -> Json {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    Json::object([
+        ("line", before.matches('\n').count().into()),
+        (
+            "character",
+            before[line_start..].encode_utf16().count().into(),
+        ),
+    ])
+}
+
+/// Converts a line and UTF-16 character back into a byte offset
+fn offset(source: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        _ => match source.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return source.len(),
+        },
+    };
+
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    source.len()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    // Percent-encoded bytes, like %20 for spaces
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = match tail {
+            [high, low, ..] if byte == b'%' => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "struct Point { x: int, y: int }
+func shift(p: Point): int {
+    const moved = p.x + 1;
+    return moved;
+}
+func main { printf(\"%d\", shift(Point { x: 1, y: 2 })); }";
+
+    fn at(source: &str, needle: &str, nth: usize) -> usize {
+        source.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn test_analyse() {
+        let analysis = analyse(Path::new("main.azl"), SOURCE);
+        assert!(analysis.diagnostics.is_empty());
+
+        let hovered = innermost(&analysis.types, at(SOURCE, "moved", 1)).unwrap();
+        assert_eq!(hovered.1, "int");
+        let hovered = innermost(&analysis.types, at(SOURCE, "p.x", 0)).unwrap();
+        assert_eq!(hovered.1, "Point");
+
+        let definition = |offset| {
+            let (_, span) = innermost(&analysis.definitions, offset).unwrap();
+            &SOURCE[span.start..span.end]
+        };
+        assert_eq!(definition(at(SOURCE, "moved", 1)), "moved");
+        assert_eq!(
+            innermost(&analysis.definitions, at(SOURCE, "x + 1", 0))
+                .unwrap()
+                .1,
+            Span { start: 15, end: 16 }
+        );
+        assert_eq!(definition(at(SOURCE, "shift", 1)), "shift");
+        assert_eq!(
+            innermost(&analysis.definitions, at(SOURCE, "shift", 1))
+                .unwrap()
+                .1
+                .start,
+            at(SOURCE, "shift", 0)
+        );
+
+        let analysis = analyse(Path::new("main.azl"), "func main { const x = y; }");
+        assert!(matches!(
+            analysis.diagnostics[0].error_type,
+            ErrorType::UnknownVariable(..)
+        ));
+
+        let analysis = catch_panics(|| panic!("oops"));
+        assert!(matches!(
+            analysis.diagnostics[0].error_type,
+            ErrorType::Internal(ref message) if message == "oops"
+        ));
+    }
+
+    #[test]
+    fn test_positions() {
+        let source = "func main {\n    \"é😀\"; x\n}";
+        let x = source.find('x').unwrap();
+
+        assert_eq!(
+            position(source, x),
+            Json::object([("line", 1.into()), ("character", 11.into())])
+        );
+        assert_eq!(offset(source, 1, 11), x);
+        assert_eq!(offset(source, 1, 100), source.find("\n}").unwrap());
+        assert_eq!(offset(source, 5, 0), source.len());
+        assert_eq!(
+            uri_to_path("file:///home/me/my%20code/main.azl"),
+            PathBuf::from("/home/me/my code/main.azl")
+        );
+    }
+
+    #[test]
+    fn test_server() {
+        let messages = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///main.azl","text":"func main { const x = 1 + true; }"}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ];
+        let input: String = messages
+            .iter()
+            .map(|message| format!("Content-Length: {}\r\n\r\n{}", message.len(), message))
+            .collect();
+
+        let mut output = vec![];
+        let code = Server::new(&mut output).run(&mut input.as_bytes()).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(code, 0);
+        assert!(output.contains(r#""id":1,"result":{"capabilities""#));
+        assert!(output.contains(r#""method":"textDocument/publishDiagnostics""#));
+        assert!(output.contains(r#""severity":1,"code":"E0011""#));
+        assert!(output.contains(r#""id":2,"result":null"#));
+    }
+}
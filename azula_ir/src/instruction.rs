@@ -32,6 +32,7 @@ pub enum Instruction<'a> {
     FunctionCall(String, Vec<Value>, usize),
     Jcond(Value, String, String),
     Jump(String),
+    Unreachable,
     Pointer(String, usize),
     CreateArray(AzulaType<'a>, usize, usize),
     StoreElement(Value, Value, Value),
@@ -39,23 +40,26 @@ pub enum Instruction<'a> {
     CreateStruct(String, Vec<Value>, usize),
     StoreStructMember(Value, usize, Value),
     AccessStructMember(Value, usize, usize, bool),
+    CreateEnum(String, usize, Vec<Value>, usize),
+    // Jumps to the block for the matching value, or the default block
+    Switch(Value, Vec<(i64, String)>, String),
 }
 
 impl<'a> Display for Instruction<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Instruction::Load(name, dest, typ) => write!(f, "%{}: load @{} {:?}", dest, name, typ),
+            Instruction::Load(name, dest, typ) => write!(f, "%{}: load @{} {}", dest, name, typ),
             Instruction::LoadGlobal(name, dest, typ) => {
-                write!(f, "%{}: load_global @{} {:?}", dest, name, typ)
+                write!(f, "%{}: load_global @{} {}", dest, name, typ)
             }
             Instruction::Store(name, value, typ) => {
-                write!(f, "store @{} {} {:?}", name, value, typ)
+                write!(f, "store @{} {} {}", name, value, typ)
             }
             Instruction::LoadArg(arg, dest, typ) => {
-                write!(f, "%{}: load_arg %{} {:?}", dest, arg, typ)
+                write!(f, "%{}: load_arg {} {}", dest, arg, typ)
             }
             Instruction::ConstInt(val, dest) => write!(f, "%{}: const_int {}", dest, val),
-            Instruction::ConstFloat(val, dest) => write!(f, "%{}: const_float {}", dest, val),
+            Instruction::ConstFloat(val, dest) => write!(f, "%{}: const_float {:?}", dest, val),
             Instruction::ConstTrue(dest) => write!(f, "%{}: const_true", dest),
             Instruction::ConstFalse(dest) => write!(f, "%{}: const_false", dest),
             Instruction::ConstNull(dest) => write!(f, "%{}: const_null", dest),
@@ -83,7 +87,7 @@ impl<'a> Display for Instruction<'a> {
                 }
             ),
             Instruction::FunctionCall(name, args, dest) => {
-                write!(f, "%{}: function_call @{} {:?}", dest, name, args)
+                write!(f, "%{}: function_call @{} [{}]", dest, name, join(args))
             }
             Instruction::Jcond(cond, true_block, false_block) => {
                 write!(f, "jcond {} {} {}", cond, true_block, false_block)
@@ -91,21 +95,22 @@ impl<'a> Display for Instruction<'a> {
             Instruction::Jump(block) => {
                 write!(f, "jump {}", block)
             }
-            Instruction::Pointer(val, dest) => write!(f, "%{}: ptr {}", dest, val),
+            Instruction::Unreachable => write!(f, "unreachable"),
+            Instruction::Pointer(val, dest) => write!(f, "%{}: ptr @{}", dest, val),
             Instruction::CreateArray(typ, size, dest) => {
-                write!(f, "%{}: create_array {:?} {}", dest, typ, size)
+                write!(f, "%{}: create_array {} {}", dest, typ, size)
             }
             Instruction::StoreElement(array, index, val) => {
-                write!(f, "store_element %{:?} {} {}", array, index, val)
+                write!(f, "store_element {} {} {}", array, index, val)
             }
             Instruction::AccessElement(array, index, dest) => {
-                write!(f, "%{}: access_element %{:?} {}", dest, array, index)
+                write!(f, "%{}: access_element {} {}", dest, array, index)
             }
             Instruction::CreateStruct(name, vals, dest) => {
-                write!(f, "%{}: create_struct {} [{:?}]", dest, name, vals)
+                write!(f, "%{}: create_struct {} [{}]", dest, name, join(vals))
             }
             Instruction::StoreStructMember(struc, index, val) => {
-                write!(f, "store_struct_member %{}.{} %{}", struc, index, val)
+                write!(f, "store_struct_member {}.{} {}", struc, index, val)
             }
             Instruction::AccessStructMember(struc, index, dest, resolve) => {
                 write!(
@@ -114,8 +119,149 @@ impl<'a> Display for Instruction<'a> {
                     dest, struc, index, resolve
                 )
             }
+            Instruction::CreateEnum(name, variant, vals, dest) => {
+                write!(
+                    f,
+                    "%{}: create_enum {} {} [{}]",
+                    dest,
+                    name,
+                    variant,
+                    join(vals)
+                )
+            }
+            Instruction::Switch(val, cases, default) => {
+                write!(f, "switch {} [", val)?;
+                for (index, (case, block)) in cases.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", case, block)?;
+                }
+                write!(f, "] {}", default)
+            }
+        }
+    }
+}
+
+impl<'a> Instruction<'a> {
+    /// Whether this instruction ends a block
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Return(..)
+                | Instruction::Jcond(..)
+                | Instruction::Jump(..)
+                | Instruction::Switch(..)
+                | Instruction::Unreachable
+        )
+    }
+
+    /// The register this instruction writes its result to
+    pub fn dest(&self) -> Option<usize> {
+        match self {
+            Instruction::Load(_, dest, _)
+            | Instruction::LoadGlobal(_, dest, _)
+            | Instruction::LoadArg(_, dest, _)
+            | Instruction::ConstInt(_, dest)
+            | Instruction::ConstFloat(_, dest)
+            | Instruction::ConstTrue(dest)
+            | Instruction::ConstFalse(dest)
+            | Instruction::ConstNull(dest)
+            | Instruction::Add(_, _, dest)
+            | Instruction::Sub(_, _, dest)
+            | Instruction::Mul(_, _, dest)
+            | Instruction::Div(_, _, dest)
+            | Instruction::Mod(_, _, dest)
+            | Instruction::Pow(_, _, dest)
+            | Instruction::Or(_, _, dest)
+            | Instruction::And(_, _, dest)
+            | Instruction::Eq(_, _, dest)
+            | Instruction::Neq(_, _, dest)
+            | Instruction::Gt(_, _, dest)
+            | Instruction::Gte(_, _, dest)
+            | Instruction::Lt(_, _, dest)
+            | Instruction::Lte(_, _, dest)
+            | Instruction::Not(_, dest)
+            | Instruction::FunctionCall(_, _, dest)
+            | Instruction::Pointer(_, dest)
+            | Instruction::CreateArray(_, _, dest)
+            | Instruction::AccessElement(_, _, dest)
+            | Instruction::CreateStruct(_, _, dest)
+            | Instruction::AccessStructMember(_, _, dest, _)
+            | Instruction::CreateEnum(_, _, _, dest) => Some(*dest),
+            Instruction::Store(..)
+            | Instruction::Return(..)
+            | Instruction::Jcond(..)
+            | Instruction::Jump(..)
+            | Instruction::Unreachable
+            | Instruction::StoreElement(..)
+            | Instruction::StoreStructMember(..)
+            | Instruction::Switch(..) => None,
         }
     }
+
+    /// The values an instruction reads
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Instruction::Store(_, val, _)
+            | Instruction::Not(val, _)
+            | Instruction::Return(Some(val))
+            | Instruction::Jcond(val, ..)
+            | Instruction::Switch(val, ..)
+            | Instruction::AccessStructMember(val, ..) => vec![val],
+            Instruction::Add(val1, val2, _)
+            | Instruction::Sub(val1, val2, _)
+            | Instruction::Mul(val1, val2, _)
+            | Instruction::Div(val1, val2, _)
+            | Instruction::Mod(val1, val2, _)
+            | Instruction::Pow(val1, val2, _)
+            | Instruction::Or(val1, val2, _)
+            | Instruction::And(val1, val2, _)
+            | Instruction::Eq(val1, val2, _)
+            | Instruction::Neq(val1, val2, _)
+            | Instruction::Gt(val1, val2, _)
+            | Instruction::Gte(val1, val2, _)
+            | Instruction::Lt(val1, val2, _)
+            | Instruction::Lte(val1, val2, _)
+            | Instruction::AccessElement(val1, val2, _)
+            | Instruction::StoreStructMember(val1, _, val2) => vec![val1, val2],
+            Instruction::StoreElement(array, index, val) => vec![array, index, val],
+            Instruction::FunctionCall(_, vals, _)
+            | Instruction::CreateStruct(_, vals, _)
+            | Instruction::CreateEnum(_, _, vals, _) => vals.iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The registers an instruction reads
+    pub fn uses(&self) -> Vec<usize> {
+        self.operands()
+            .into_iter()
+            .filter_map(|val| match val {
+                Value::Local(register) => Some(*register),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The blocks a terminator can jump to
+    pub fn targets(&self) -> Vec<&str> {
+        match self {
+            Instruction::Jcond(_, true_block, false_block) => vec![true_block, false_block],
+            Instruction::Jump(block) => vec![block],
+            Instruction::Switch(_, cases, default) => cases
+                .iter()
+                .map(|(_, block)| block.as_str())
+                .chain(std::iter::once(default.as_str()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn join(values: &[Value]) -> String {
+    let values: Vec<_> = values.iter().map(|val| val.to_string()).collect();
+    values.join(", ")
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -1,7 +1,12 @@
 mod instruction;
 mod module;
+mod parser;
+pub mod passes;
+mod verify;
 
 pub mod prelude {
     pub use crate::instruction::{Instruction, Value};
-    pub use crate::module::{Block, ExternFunction, Function, GlobalValue, Module, Struct};
+    pub use crate::module::{Block, Enum, ExternFunction, Function, GlobalValue, Module, Struct};
+    pub use crate::parser::parse_module;
+    pub use crate::verify::{verify, VerifyError, VerifyErrorKind};
 }
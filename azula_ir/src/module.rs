@@ -1,22 +1,26 @@
 use core::fmt;
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{collections::BTreeMap, fmt::Display, rc::Rc};
 
 use azula_type::prelude::AzulaType;
 
 use crate::instruction::{Instruction, Value};
 
+/// Everything is kept in ordered maps so printing or compiling the same
+/// module always gives the same output
+#[derive(PartialEq)]
 pub struct Module<'a> {
     pub name: &'a str,
-    pub functions: HashMap<&'a str, Function<'a>>,
-    pub extern_functions: HashMap<&'a str, ExternFunction<'a>>,
+    pub functions: BTreeMap<String, Function<'a>>,
+    pub extern_functions: BTreeMap<&'a str, ExternFunction<'a>>,
     pub strings: Vec<String>,
-    pub global_values: HashMap<String, GlobalValue>,
-    pub structs: HashMap<&'a str, Struct<'a>>,
+    pub global_values: BTreeMap<String, GlobalValue>,
+    pub structs: BTreeMap<String, Struct<'a>>,
+    pub enums: BTreeMap<&'a str, Enum<'a>>,
 }
 
 impl<'a> Module<'a> {
     pub fn new(name: &'a str) -> Self {
-        let mut extern_functions = HashMap::new();
+        let mut extern_functions = BTreeMap::new();
         extern_functions.insert(
             "printf",
             ExternFunction {
@@ -27,15 +31,16 @@ impl<'a> Module<'a> {
         );
         Module {
             name,
-            functions: HashMap::new(),
+            functions: BTreeMap::new(),
             extern_functions,
             strings: vec![],
-            global_values: HashMap::new(),
-            structs: HashMap::new(),
+            global_values: BTreeMap::new(),
+            structs: BTreeMap::new(),
+            enums: BTreeMap::new(),
         }
     }
 
-    pub fn add_function(&mut self, name: &'a str, function: Function<'a>) {
+    pub fn add_function(&mut self, name: String, function: Function<'a>) {
         self.functions.insert(name, function);
     }
 
@@ -49,42 +54,83 @@ impl<'a> Module<'a> {
         Value::Global(self.strings.len() - 1)
     }
 
-    pub fn add_struct(&mut self, name: &'a str, struc: Struct<'a>) {
+    pub fn add_struct(&mut self, name: String, struc: Struct<'a>) {
         self.structs.insert(name, struc);
     }
+
+    pub fn add_enum(&mut self, name: &'a str, enu: Enum<'a>) {
+        self.enums.insert(name, enu);
+    }
 }
 
+/// Prints the module in the textual IR format that `parse_module` reads back
 impl<'a> Display for Module<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Module: {}", self.name).unwrap();
-        writeln!(f, "Strings:").unwrap();
+        writeln!(f, "Module: {}", self.name)?;
+        writeln!(f, "Strings:")?;
         for (index, string) in self.strings.iter().enumerate() {
-            writeln!(f, "\t{}: {}", index, string).unwrap();
+            writeln!(f, "\t{}: \"{}\"", index, escape(string))?;
         }
 
-        writeln!(f, "Structs:").unwrap();
-        for (name, struc) in self.structs.iter() {
-            writeln!(f, "\t{}: {:?}", name, struc).unwrap();
+        writeln!(f, "Globals:")?;
+        for (name, value) in self.global_values.iter() {
+            writeln!(f, "\t{} = {}", name, value)?;
         }
 
-        writeln!(f).unwrap();
+        writeln!(f, "Externs:")?;
+        for (name, func) in self.extern_functions.iter() {
+            let mut arguments: Vec<_> = func.arguments.iter().map(|typ| typ.to_string()).collect();
+            if func.varargs {
+                arguments.push("...".to_string());
+            }
+            writeln!(f, "\t{}({}): {}", name, arguments.join(", "), func.returns)?;
+        }
+
+        writeln!(f, "Structs:")?;
+        for struc in self.structs.values() {
+            let attributes: Vec<_> = struc
+                .attributes
+                .iter()
+                .map(|(typ, name)| format!("{}: {}", name, typ))
+                .collect();
+            writeln!(f, "\t{} {{{}}}", struc.name, braced(&attributes))?;
+        }
+
+        writeln!(f, "Enums:")?;
+        for enu in self.enums.values() {
+            let variants: Vec<_> = enu
+                .variants
+                .iter()
+                .map(|(name, payload)| {
+                    if payload.is_empty() {
+                        name.to_string()
+                    } else {
+                        let payload: Vec<_> = payload.iter().map(|typ| typ.to_string()).collect();
+                        format!("{}({})", name, payload.join(", "))
+                    }
+                })
+                .collect();
+            writeln!(f, "\t{} {{{}}}", enu.name, braced(&variants))?;
+        }
+
+        writeln!(f)?;
 
         for (name, func) in &self.functions {
-            writeln!(f, "func {}:", name).unwrap();
-            writeln!(f, "\tArguments:").unwrap();
+            writeln!(f, "func {}:", name)?;
+            writeln!(f, "\tArguments:")?;
             for (var, typ) in &func.arguments {
-                writeln!(f, "\t\t{} {:?}", var, typ).unwrap();
+                writeln!(f, "\t\t{}: {}", var, typ)?;
             }
-            writeln!(f, "\tReturns: {:?}", func.returns).unwrap();
-            writeln!(f, "\tVariables:").unwrap();
+            writeln!(f, "\tReturns: {}", func.returns)?;
+            writeln!(f, "\tVariables:")?;
             for (var, typ) in &func.variables {
-                writeln!(f, "\t\t{} {:?}", var, typ).unwrap();
+                writeln!(f, "\t\t{}: {}", var, typ)?;
             }
 
             for (name, block) in &func.blocks {
-                writeln!(f, "\t{}:", name).unwrap();
+                writeln!(f, "\t{}:", name)?;
                 for instruction in &block.instructions {
-                    writeln!(f, "\t\t{}", instruction).unwrap();
+                    writeln!(f, "\t\t{}", instruction)?;
                 }
             }
         }
@@ -93,18 +139,56 @@ impl<'a> Display for Module<'a> {
     }
 }
 
+fn braced(items: &[String]) -> String {
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!(" {} ", items.join(", "))
+    }
+}
+
+/// Escapes a string so it can be printed between double quotes
+pub(crate) fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub struct Function<'a> {
     pub blocks: Vec<(String, Block<'a>)>,
-    pub variables: HashMap<String, AzulaType<'a>>,
+    pub variables: BTreeMap<String, AzulaType<'a>>,
     pub arguments: Vec<(String, AzulaType<'a>)>,
     pub returns: AzulaType<'a>,
 
-    tmp_var_index: usize,
+    pub(crate) tmp_var_index: usize,
     pub if_block_index: usize,
 
     pub current_block: String,
 }
 
+// Functions are equal when their contents are, whatever state the builder
+// was left in
+impl<'a> PartialEq for Function<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.blocks == other.blocks
+            && self.variables == other.variables
+            && self.arguments == other.arguments
+            && self.returns == other.returns
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct ExternFunction<'a> {
     pub varargs: bool,
     pub arguments: Vec<AzulaType<'a>>,
@@ -122,7 +206,7 @@ impl<'a> Function<'a> {
         ));
         Self {
             blocks,
-            variables: HashMap::new(),
+            variables: BTreeMap::new(),
             arguments: vec![],
             returns: AzulaType::Void,
             tmp_var_index: 0,
@@ -347,6 +431,24 @@ impl<'a> Function<'a> {
         self.add_instruction(Instruction::Jump(block));
     }
 
+    pub fn switch(&mut self, val: Value, cases: Vec<(i64, String)>, default: String) {
+        self.add_instruction(Instruction::Switch(val, cases, default));
+    }
+
+    pub fn unreachable(&mut self) {
+        self.add_instruction(Instruction::Unreachable);
+    }
+
+    /// Whether the current block already ends in a terminator
+    pub fn is_terminated(&self) -> bool {
+        self.blocks
+            .iter()
+            .find(|(x, _)| *x == self.current_block)
+            .and_then(|(_, block)| block.instructions.last())
+            .map(|instruction| instruction.is_terminator())
+            .unwrap_or(false)
+    }
+
     pub fn create_array(&mut self, typ: AzulaType<'a>, size: usize) -> Value {
         self.add_instruction(Instruction::CreateArray(typ, size, self.tmp_var_index));
 
@@ -389,6 +491,18 @@ impl<'a> Function<'a> {
         self.add_instruction(Instruction::StoreStructMember(struc, index, value));
     }
 
+    pub fn create_enum(&mut self, enu: String, variant: usize, values: Vec<Value>) -> Value {
+        self.add_instruction(Instruction::CreateEnum(
+            enu,
+            variant,
+            values,
+            self.tmp_var_index,
+        ));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     fn add_instruction(&mut self, instruction: Instruction<'a>) {
         self.blocks
             .iter_mut()
@@ -400,7 +514,7 @@ impl<'a> Function<'a> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Block<'a> {
     pub instructions: Vec<Instruction<'a>>,
 }
@@ -413,7 +527,7 @@ impl<'a> Block<'a> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum GlobalValue {
     Int(i64),
     Float(f64),
@@ -422,8 +536,56 @@ pub enum GlobalValue {
     Array(Vec<GlobalValue>),
 }
 
-#[derive(Debug, Clone)]
+impl Display for GlobalValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlobalValue::Int(val) => write!(f, "{}", val),
+            // Debug keeps the decimal point, so floats read back as floats
+            GlobalValue::Float(val) => write!(f, "{:?}", val),
+            GlobalValue::Bool(val) => write!(f, "{}", val),
+            GlobalValue::String(index) => write!(f, "${}", index),
+            GlobalValue::Array(vals) => {
+                let vals: Vec<_> = vals.iter().map(|val| val.to_string()).collect();
+                write!(f, "[{}]", vals.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Struct<'a> {
-    pub name: &'a str,
+    pub name: String,
     pub attributes: Vec<(AzulaType<'a>, &'a str)>,
 }
+
+/// Enums are lowered to a struct holding the variant's tag followed by the
+/// payload fields of every variant, so a value of any variant fits
+#[derive(Debug, PartialEq, Clone)]
+pub struct Enum<'a> {
+    pub name: &'a str,
+    pub variants: Vec<(&'a str, Vec<AzulaType<'a>>)>,
+}
+
+impl<'a> Enum<'a> {
+    pub fn tag(&self, variant: &str) -> Option<usize> {
+        self.variants.iter().position(|(name, _)| *name == variant)
+    }
+
+    /// The fields of the lowered struct, starting with the int tag
+    pub fn fields(&self) -> Vec<AzulaType<'a>> {
+        let mut fields = vec![AzulaType::Int];
+        for (_, payload) in &self.variants {
+            fields.extend(payload.iter().cloned());
+        }
+        fields
+    }
+
+    /// Index in the lowered struct of a variant's payload field
+    pub fn field_index(&self, variant: usize, field: usize) -> usize {
+        let before: usize = self.variants[..variant]
+            .iter()
+            .map(|(_, payload)| payload.len())
+            .sum();
+        1 + before + field
+    }
+}
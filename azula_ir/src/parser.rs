@@ -0,0 +1,732 @@
+use std::{rc::Rc, str::FromStr};
+
+use azula_type::prelude::AzulaType;
+
+use crate::{
+    instruction::{Instruction, Value},
+    module::{Block, Enum, ExternFunction, Function, GlobalValue, Module, Struct},
+};
+
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Strings,
+    Globals,
+    Externs,
+    Structs,
+    Enums,
+    Arguments,
+    Variables,
+    Blocks,
+}
+
+/// Parses IR in the format `Module` is displayed in, so printed IR can be
+/// edited by hand and compiled
+pub fn parse_module(source: &str) -> Result<Module<'_>, String> {
+    let mut module = Module::new("");
+    let mut section = Section::Header;
+    let mut function: Option<(&str, Function)> = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let mut line = Line::new(text.trim(), index + 1);
+        if line.is_done() {
+            continue;
+        }
+
+        if let Some(name) = line.text.strip_prefix("Module:") {
+            module.name = name.trim();
+            continue;
+        }
+
+        if let Some(name) = line.text.strip_prefix("func ") {
+            if let Some((name, function)) = function.take() {
+                finish_function(&mut module, name, function);
+            }
+
+            let mut new = Function::new();
+            new.blocks.clear();
+            let mut header = Line::new(name, index + 1);
+            function = Some((header.name()?, new));
+            header.expect(":")?;
+            header.end()?;
+            section = Section::Arguments;
+            continue;
+        }
+
+        let keyword = match line.text {
+            "Strings:" => Some(Section::Strings),
+            "Globals:" => Some(Section::Globals),
+            "Externs:" => Some(Section::Externs),
+            "Structs:" => Some(Section::Structs),
+            "Enums:" => Some(Section::Enums),
+            "Arguments:" => Some(Section::Arguments),
+            "Variables:" => Some(Section::Variables),
+            _ => None,
+        };
+        if let Some(keyword) = keyword {
+            let in_function = matches!(keyword, Section::Arguments | Section::Variables);
+            if in_function != function.is_some() {
+                return Err(line.error(&format!("unexpected {}", line.text)));
+            }
+            section = keyword;
+            continue;
+        }
+
+        match section {
+            Section::Header => return Err(line.error("expected a section")),
+            Section::Strings => {
+                let index: usize = line.integer()?;
+                if index != module.strings.len() {
+                    return Err(line.error("strings must be numbered in order"));
+                }
+                line.expect(":")?;
+                let string = line.string()?;
+                module.strings.push(string);
+            }
+            Section::Globals => {
+                let name = line.name()?;
+                line.expect("=")?;
+                let value = line.global_value()?;
+                module.global_values.insert(name.to_string(), value);
+            }
+            Section::Externs => {
+                let name = line.name()?;
+                line.expect("(")?;
+                let mut varargs = false;
+                let mut arguments = vec![];
+                while !line.eat(")") {
+                    if !arguments.is_empty() || varargs {
+                        line.expect(",")?;
+                    }
+                    if line.eat("...") {
+                        varargs = true;
+                    } else {
+                        arguments.push(line.typ()?);
+                    }
+                }
+                line.expect(":")?;
+                let returns = line.typ()?;
+                module.add_extern_function(
+                    name,
+                    ExternFunction {
+                        varargs,
+                        arguments,
+                        returns,
+                    },
+                );
+            }
+            Section::Structs => {
+                let name = line.name()?;
+                let attributes = line.list("{", "}", |line| {
+                    let name = line.name()?;
+                    line.expect(":")?;
+                    Ok((line.typ()?, name))
+                })?;
+                module.add_struct(
+                    name.to_string(),
+                    Struct {
+                        name: name.to_string(),
+                        attributes,
+                    },
+                );
+            }
+            Section::Enums => {
+                let name = line.name()?;
+                let variants = line.list("{", "}", |line| {
+                    let name = line.name()?;
+                    let mut payload = vec![];
+                    if line.peek("(") {
+                        payload = line.list("(", ")", |line| line.typ())?;
+                    }
+                    Ok((name, payload))
+                })?;
+                module.add_enum(name, Enum { name, variants });
+            }
+            Section::Arguments | Section::Variables | Section::Blocks => {
+                let (_, function) = function.as_mut().unwrap();
+
+                if let Some(returns) = line.text.strip_prefix("Returns:") {
+                    let mut returns = Line::new(returns, index + 1);
+                    function.returns = returns.typ()?;
+                    returns.end()?;
+                    continue;
+                }
+
+                // Block labels are the only lines that are a lone name
+                let label = line.name().ok();
+                if let Some(label) = label {
+                    if line.eat(":") && line.is_done() {
+                        function.blocks.push((label.to_string(), Block::new()));
+                        function.current_block = label.to_string();
+                        section = Section::Blocks;
+                        continue;
+                    }
+                }
+
+                match section {
+                    Section::Arguments | Section::Variables => {
+                        let mut line = Line::new(line.text, index + 1);
+                        let name = line.name()?;
+                        line.expect(":")?;
+                        let typ = line.typ()?;
+                        line.end()?;
+                        if section == Section::Arguments {
+                            function.arguments.push((name.to_string(), typ));
+                        } else {
+                            function.variables.insert(name.to_string(), typ);
+                        }
+                    }
+                    _ => {
+                        let mut line = Line::new(line.text, index + 1);
+                        let instruction = line.instruction()?;
+                        function
+                            .blocks
+                            .last_mut()
+                            .unwrap()
+                            .1
+                            .instructions
+                            .push(instruction);
+                    }
+                }
+                continue;
+            }
+        }
+
+        line.end()?;
+    }
+
+    if let Some((name, function)) = function.take() {
+        finish_function(&mut module, name, function);
+    }
+
+    Ok(module)
+}
+
+fn finish_function<'a>(module: &mut Module<'a>, name: &str, mut function: Function<'a>) {
+    // Registers created through the builder afterwards mustn't clash
+    function.tmp_var_index = function
+        .blocks
+        .iter()
+        .flat_map(|(_, block)| block.instructions.iter())
+        .filter_map(|instruction| instruction.dest())
+        .map(|dest| dest + 1)
+        .max()
+        .unwrap_or(0);
+    module.add_function(name.to_string(), function);
+}
+
+/// A cursor over one line of IR
+struct Line<'a> {
+    text: &'a str,
+    position: usize,
+    number: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str, number: usize) -> Self {
+        Self {
+            text,
+            position: 0,
+            number,
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.number, message)
+    }
+
+    fn rest(&mut self) -> &'a str {
+        self.position +=
+            self.text[self.position..].len() - self.text[self.position..].trim_start().len();
+        &self.text[self.position..]
+    }
+
+    fn is_done(&mut self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            let rest = self.rest();
+            Err(self.error(&format!("unexpected {}", rest)))
+        }
+    }
+
+    fn peek(&mut self, expected: &str) -> bool {
+        self.rest().starts_with(expected)
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        if self.peek(expected) {
+            self.position += expected.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            let rest = self.rest();
+            Err(self.error(&format!("expected {} but found {}", expected, rest)))
+        }
+    }
+
+    /// Reads a function, variable, struct or block name. Names can contain
+    /// `::` from modules, `.` from methods, `-` from block numbering and type
+    /// arguments between angle brackets
+    fn name(&mut self) -> Result<&'a str, String> {
+        let rest = self.rest();
+        let bytes = rest.as_bytes();
+        let mut end = 0;
+        while end < bytes.len() {
+            match bytes[end] {
+                b if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b == b'-' => end += 1,
+                b':' if bytes.get(end + 1) == Some(&b':') => end += 2,
+                b'<' => {
+                    let mut depth = 0;
+                    while end < bytes.len() {
+                        match bytes[end] {
+                            b'<' => depth += 1,
+                            b'>' => depth -= 1,
+                            _ => {}
+                        }
+                        end += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    if depth != 0 {
+                        return Err(self.error("unclosed type arguments"));
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        if end == 0 || rest.starts_with('-') {
+            return Err(self.error(&format!("expected a name but found {}", rest)));
+        }
+        self.position += end;
+        Ok(&rest[..end])
+    }
+
+    fn integer<T: FromStr>(&mut self) -> Result<T, String> {
+        let rest = self.rest();
+        let end = rest
+            .char_indices()
+            .find(|(index, c)| !(c.is_ascii_digit() || (*index == 0 && *c == '-')))
+            .map(|(index, _)| index)
+            .unwrap_or(rest.len());
+        match rest[..end].parse() {
+            Ok(val) => {
+                self.position += end;
+                Ok(val)
+            }
+            Err(_) => Err(self.error(&format!("expected a number but found {}", rest))),
+        }
+    }
+
+    /// Reads a literal up to the next separator
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ']')
+            .unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn float(&mut self) -> Result<f64, String> {
+        let word = self.word();
+        word.parse()
+            .map_err(|_| self.error(&format!("expected a float but found {}", word)))
+    }
+
+    fn boolean(&mut self) -> Result<bool, String> {
+        if self.eat("true") {
+            Ok(true)
+        } else if self.eat("false") {
+            Ok(false)
+        } else {
+            let rest = self.rest();
+            Err(self.error(&format!("expected a bool but found {}", rest)))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut string = String::new();
+        let mut chars = self.text[self.position..].char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += index + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => string.push('\n'),
+                    Some((_, 't')) => string.push('\t'),
+                    Some((_, 'r')) => string.push('\r'),
+                    Some((_, '0')) => string.push('\0'),
+                    Some((_, '"')) => string.push('"'),
+                    Some((_, '\\')) => string.push('\\'),
+                    Some((_, 'x')) => {
+                        let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                        match u8::from_str_radix(&hex, 16) {
+                            Ok(byte) => string.push(byte as char),
+                            Err(_) => return Err(self.error("invalid \\x escape")),
+                        }
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                c => string.push(c),
+            }
+        }
+
+        Err(self.error("unterminated string"))
+    }
+
+    fn list<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.expect(open)?;
+        let mut items = vec![];
+        while !self.eat(close) {
+            if !items.is_empty() {
+                self.expect(",")?;
+            }
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn typ(&mut self) -> Result<AzulaType<'a>, String> {
+        if self.eat("&") {
+            return Ok(AzulaType::Pointer(Rc::new(self.typ()?)));
+        }
+
+        if self.eat("[") {
+            let nested = Rc::new(self.typ()?);
+            let mut size = None;
+            if self.eat(";") {
+                size = Some(self.integer()?);
+            }
+            self.expect("]")?;
+            return Ok(AzulaType::Array(nested, size));
+        }
+
+        Ok(AzulaType::from(self.name()?))
+    }
+
+    fn register(&mut self) -> Result<usize, String> {
+        self.expect("%")?;
+        self.integer()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.peek("%") {
+            Ok(Value::Local(self.register()?))
+        } else if self.eat("$") {
+            Ok(Value::Global(self.integer()?))
+        } else {
+            // Not every backend can use a constant in place of a register, so
+            // they're loaded into one first
+            let word = self.word();
+            Err(self.error(&format!(
+                "expected a register or string but found {}, constants need loading with const_int, const_true or const_false",
+                word
+            )))
+        }
+    }
+
+    fn values(&mut self) -> Result<Vec<Value>, String> {
+        self.list("[", "]", |line| line.value())
+    }
+
+    fn variable(&mut self) -> Result<String, String> {
+        self.expect("@")?;
+        Ok(self.name()?.to_string())
+    }
+
+    fn global_value(&mut self) -> Result<GlobalValue, String> {
+        if self.peek("[") {
+            return Ok(GlobalValue::Array(
+                self.list("[", "]", |line| line.global_value())?,
+            ));
+        }
+        if self.eat("$") {
+            return Ok(GlobalValue::String(self.integer()?));
+        }
+        if self.peek("true") || self.peek("false") {
+            return Ok(GlobalValue::Bool(self.boolean()?));
+        }
+
+        let word = self.word();
+        if let Ok(val) = word.parse() {
+            Ok(GlobalValue::Int(val))
+        } else if let Ok(val) = word.parse() {
+            Ok(GlobalValue::Float(val))
+        } else {
+            Err(self.error(&format!("expected a global value but found {}", word)))
+        }
+    }
+
+    fn instruction(&mut self) -> Result<Instruction<'a>, String> {
+        let mut dest = None;
+        if self.peek("%") {
+            dest = Some(self.register()?);
+            self.expect(":")?;
+        }
+
+        let op = self.name()?;
+        let has_dest = !matches!(
+            op,
+            "store"
+                | "ret"
+                | "jcond"
+                | "jump"
+                | "unreachable"
+                | "store_element"
+                | "store_struct_member"
+                | "switch"
+        );
+        let dest = match (dest, has_dest) {
+            (Some(dest), true) => dest,
+            (None, false) => 0,
+            (None, true) => return Err(self.error(&format!("{} needs a destination", op))),
+            (Some(_), false) => return Err(self.error(&format!("{} has no destination", op))),
+        };
+
+        let instruction = match op {
+            "load" => Instruction::Load(self.variable()?, dest, self.typ()?),
+            "load_global" => Instruction::LoadGlobal(self.variable()?, dest, self.typ()?),
+            "store" => Instruction::Store(self.variable()?, self.value()?, self.typ()?),
+            "load_arg" => Instruction::LoadArg(self.integer()?, dest, self.typ()?),
+            "const_int" => Instruction::ConstInt(self.integer()?, dest),
+            "const_float" => Instruction::ConstFloat(self.float()?, dest),
+            "const_true" => Instruction::ConstTrue(dest),
+            "const_false" => Instruction::ConstFalse(dest),
+            "const_null" => Instruction::ConstNull(dest),
+            "add" => Instruction::Add(self.value()?, self.value()?, dest),
+            "sub" => Instruction::Sub(self.value()?, self.value()?, dest),
+            "mul" => Instruction::Mul(self.value()?, self.value()?, dest),
+            "div" => Instruction::Div(self.value()?, self.value()?, dest),
+            "mod" => Instruction::Mod(self.value()?, self.value()?, dest),
+            "pow" => Instruction::Pow(self.value()?, self.value()?, dest),
+            "or" => Instruction::Or(self.value()?, self.value()?, dest),
+            "and" => Instruction::And(self.value()?, self.value()?, dest),
+            "eq" => Instruction::Eq(self.value()?, self.value()?, dest),
+            "neq" => Instruction::Neq(self.value()?, self.value()?, dest),
+            "gt" => Instruction::Gt(self.value()?, self.value()?, dest),
+            "gte" => Instruction::Gte(self.value()?, self.value()?, dest),
+            "lt" => Instruction::Lt(self.value()?, self.value()?, dest),
+            "lte" => Instruction::Lte(self.value()?, self.value()?, dest),
+            "not" => Instruction::Not(self.value()?, dest),
+            "ret" => {
+                if self.is_done() {
+                    Instruction::Return(None)
+                } else {
+                    Instruction::Return(Some(self.value()?))
+                }
+            }
+            "function_call" => Instruction::FunctionCall(self.variable()?, self.values()?, dest),
+            "jcond" => Instruction::Jcond(
+                self.value()?,
+                self.name()?.to_string(),
+                self.name()?.to_string(),
+            ),
+            "jump" => Instruction::Jump(self.name()?.to_string()),
+            "unreachable" => Instruction::Unreachable,
+            "ptr" => Instruction::Pointer(self.variable()?, dest),
+            "create_array" => Instruction::CreateArray(self.typ()?, self.integer()?, dest),
+            "store_element" => {
+                Instruction::StoreElement(self.value()?, self.value()?, self.value()?)
+            }
+            "access_element" => Instruction::AccessElement(self.value()?, self.value()?, dest),
+            "create_struct" => {
+                Instruction::CreateStruct(self.name()?.to_string(), self.values()?, dest)
+            }
+            "store_struct_member" => {
+                let struc = self.value()?;
+                self.expect(".")?;
+                Instruction::StoreStructMember(struc, self.integer()?, self.value()?)
+            }
+            "access_struct_member" => {
+                let struc = self.value()?;
+                self.expect(".")?;
+                Instruction::AccessStructMember(struc, self.integer()?, dest, self.boolean()?)
+            }
+            "create_enum" => Instruction::CreateEnum(
+                self.name()?.to_string(),
+                self.integer()?,
+                self.values()?,
+                dest,
+            ),
+            "switch" => {
+                let value = self.value()?;
+                let cases = self.list("[", "]", |line| {
+                    let case = line.integer()?;
+                    line.expect(":")?;
+                    Ok((case, line.name()?.to_string()))
+                })?;
+                Instruction::Switch(value, cases, self.name()?.to_string())
+            }
+            _ => return Err(self.error(&format!("unknown instruction {}", op))),
+        };
+
+        self.end()?;
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "Module: shapes
+Strings:
+\t0: \"area is %d\\n\"
+\t1: \"say \\\"hi\\\"\\t\\\\\"
+Globals:
+\tSCALE = 2
+Externs:
+\tprintf(&str, ...): void
+Structs:
+\tPair<int, bool> { first: int, second: bool }
+Enums:
+\tShape { Circle(int), Rect(int, int), Empty }
+
+func area:
+\tArguments:
+\t\tshape: Shape
+\tReturns: int
+\tVariables:
+\t\t__match0: int
+\tentry:
+\t\t%0: load_arg 0 Shape
+\t\t%1: access_struct_member %0.0 true
+\t\tswitch %1 [0: arm-0-0, 1: arm-0-1] end-0
+\tarm-0-0:
+\t\t%2: access_struct_member %0.1 true
+\t\t%3: load_global @SCALE int
+\t\t%4: mul %2 %3
+\t\tstore @__match0 %4 int
+\t\tjump end-0
+\tarm-0-1:
+\t\t%5: create_array [&str; 2] 2
+\t\t%6: const_int 0
+\t\tstore_element %5 %6 $1
+\t\t%7: const_int -1
+\t\t%8: function_call @printf [$0, %7]
+\t\t%9: const_true
+\t\t%10: create_struct Pair<int, bool> [%8, %9]
+\t\tstore_struct_member %10.0 %8
+\t\t%11: const_float 2.0
+\t\tstore @__match0 %6 int
+\t\tjump end-0
+\tend-0:
+\t\t%12: load @__match0 int
+\t\tret %12
+";
+
+    #[test]
+    fn test_parse_module() {
+        let module = parse_module(FIXTURE).unwrap();
+
+        assert_eq!(module.name, "shapes");
+        assert_eq!(module.strings, vec!["area is %d\n", "say \"hi\"\t\\"]);
+        assert_eq!(module.global_values["SCALE"], GlobalValue::Int(2));
+        assert!(module.extern_functions["printf"].varargs);
+        assert_eq!(
+            module.structs["Pair<int, bool>"].attributes,
+            vec![(AzulaType::Int, "first"), (AzulaType::Bool, "second")]
+        );
+        assert_eq!(module.enums["Shape"].field_index(1, 1), 3);
+
+        let area = &module.functions["area"];
+        assert_eq!(
+            area.arguments,
+            vec![("shape".to_string(), AzulaType::Named("Shape".to_string()))]
+        );
+        assert_eq!(area.returns, AzulaType::Int);
+        assert_eq!(area.variables["__match0"], AzulaType::Int);
+
+        let blocks: Vec<_> = area.blocks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(blocks, vec!["entry", "arm-0-0", "arm-0-1", "end-0"]);
+        assert_eq!(
+            area.blocks[0].1.instructions[2],
+            Instruction::Switch(
+                Value::Local(1),
+                vec![(0, "arm-0-0".to_string()), (1, "arm-0-1".to_string())],
+                "end-0".to_string()
+            )
+        );
+        assert_eq!(
+            area.blocks[2].1.instructions[0],
+            Instruction::CreateArray(
+                AzulaType::Array(
+                    Rc::new(AzulaType::Pointer(Rc::new(AzulaType::Str))),
+                    Some(2)
+                ),
+                2,
+                5
+            )
+        );
+        assert_eq!(
+            area.blocks[2].1.instructions[4],
+            Instruction::FunctionCall(
+                "printf".to_string(),
+                vec![Value::Global(0), Value::Local(7)],
+                8
+            )
+        );
+        assert_eq!(area.tmp_var_index, 13);
+
+        // Every section has a single entry, so the order it prints in is fixed
+        assert_eq!(module.to_string(), FIXTURE);
+    }
+
+    #[test]
+    fn test_parse_module_errors() {
+        let tests = vec![
+            (
+                "Strings:\n\t1: \"a\"",
+                "line 2: strings must be numbered in order",
+            ),
+            (
+                "func main:\n\tentry:\n\t\t%0: frobnicate",
+                "line 3: unknown instruction frobnicate",
+            ),
+            (
+                "func main:\n\tentry:\n\t\tret %0 %1",
+                "line 3: unexpected %1",
+            ),
+            (
+                "func main:\n\tentry:\n\t\tconst_int 1",
+                "line 3: const_int needs a destination",
+            ),
+            (
+                "func main:\n\tentry:\n\t\tret 1",
+                "line 3: expected a register or string but found 1, constants need loading with const_int, const_true or const_false",
+            ),
+            (
+                "func main:\n\tentry:\n\t\t%0: load @x Pair<int",
+                "line 3: unclosed type arguments",
+            ),
+            ("Strings:\n\t0: \"abc", "line 2: unterminated string"),
+            ("Arguments:", "line 1: unexpected Arguments:"),
+        ];
+
+        for (source, expected) in tests {
+            assert_eq!(parse_module(source).err().unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use crate::{
+    instruction::{Instruction, Value},
+    module::Function,
+    passes::Pass,
+};
+
+#[derive(Clone, Copy)]
+enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// Evaluates operations on constants at compile time, replacing them with
+/// the constant they produce, and turns branches on constants into jumps.
+/// The registers folded away are left for `DeadRegisters` to remove
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut constants = HashMap::new();
+        let mut changed = false;
+
+        // Registers are only assigned once, so a constant is known everywhere
+        // in the function once found. Keep going until folding one
+        // instruction stops making others foldable
+        loop {
+            let mut folded = false;
+            for (_, block) in function.blocks.iter_mut() {
+                for instruction in block.instructions.iter_mut() {
+                    if let Some(dest) = instruction.dest() {
+                        if constants.contains_key(&dest) {
+                            continue;
+                        }
+                    }
+
+                    match constant(instruction) {
+                        Some((dest, constant)) => {
+                            constants.insert(dest, constant);
+                        }
+                        None => {
+                            if let Some(replacement) = fold(instruction, &constants) {
+                                *instruction = replacement;
+                                folded = true;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if !folded {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+fn constant(instruction: &Instruction) -> Option<(usize, Constant)> {
+    match instruction {
+        Instruction::ConstInt(val, dest) => Some((*dest, Constant::Int(*val))),
+        Instruction::ConstFloat(val, dest) => Some((*dest, Constant::Float(*val))),
+        Instruction::ConstTrue(dest) => Some((*dest, Constant::Bool(true))),
+        Instruction::ConstFalse(dest) => Some((*dest, Constant::Bool(false))),
+        _ => None,
+    }
+}
+
+fn value(val: &Value, constants: &HashMap<usize, Constant>) -> Option<Constant> {
+    match val {
+        Value::LiteralInteger(val) => Some(Constant::Int(*val)),
+        Value::LiteralBoolean(val) => Some(Constant::Bool(*val)),
+        Value::Local(register) => constants.get(register).copied(),
+        Value::Global(_) => None,
+    }
+}
+
+fn instruction<'a>(constant: Constant, dest: usize) -> Instruction<'a> {
+    match constant {
+        Constant::Int(val) => Instruction::ConstInt(val, dest),
+        Constant::Float(val) => Instruction::ConstFloat(val, dest),
+        Constant::Bool(true) => Instruction::ConstTrue(dest),
+        Constant::Bool(false) => Instruction::ConstFalse(dest),
+    }
+}
+
+fn fold<'a>(
+    instruction: &Instruction<'a>,
+    constants: &HashMap<usize, Constant>,
+) -> Option<Instruction<'a>> {
+    match instruction {
+        Instruction::Jcond(cond, true_block, false_block) => {
+            return match value(cond, constants)? {
+                Constant::Bool(true) => Some(Instruction::Jump(true_block.clone())),
+                Constant::Bool(false) => Some(Instruction::Jump(false_block.clone())),
+                _ => None,
+            };
+        }
+        Instruction::Switch(val, cases, default) => {
+            let val = match value(val, constants)? {
+                Constant::Int(val) => val,
+                _ => return None,
+            };
+            let target = cases
+                .iter()
+                .find(|(case, _)| *case == val)
+                .map(|(_, block)| block)
+                .unwrap_or(default);
+            return Some(Instruction::Jump(target.clone()));
+        }
+        Instruction::Not(val, dest) => {
+            return match value(val, constants)? {
+                Constant::Bool(val) => Some(self::instruction(Constant::Bool(!val), *dest)),
+                _ => None,
+            };
+        }
+        _ => {}
+    }
+
+    let (val1, val2, dest) = match instruction {
+        Instruction::Add(val1, val2, dest)
+        | Instruction::Sub(val1, val2, dest)
+        | Instruction::Mul(val1, val2, dest)
+        | Instruction::Div(val1, val2, dest)
+        | Instruction::Mod(val1, val2, dest)
+        | Instruction::Or(val1, val2, dest)
+        | Instruction::And(val1, val2, dest)
+        | Instruction::Eq(val1, val2, dest)
+        | Instruction::Neq(val1, val2, dest)
+        | Instruction::Gt(val1, val2, dest)
+        | Instruction::Gte(val1, val2, dest)
+        | Instruction::Lt(val1, val2, dest)
+        | Instruction::Lte(val1, val2, dest) => (val1, val2, *dest),
+        _ => return None,
+    };
+
+    let result = match (value(val1, constants)?, value(val2, constants)?) {
+        (Constant::Int(val1), Constant::Int(val2)) => match instruction {
+            Instruction::Add(..) => Constant::Int(val1.wrapping_add(val2)),
+            Instruction::Sub(..) => Constant::Int(val1.wrapping_sub(val2)),
+            Instruction::Mul(..) => Constant::Int(val1.wrapping_mul(val2)),
+            // Dividing by zero is left to fail at runtime
+            Instruction::Div(..) if val2 != 0 => Constant::Int(val1.wrapping_div(val2)),
+            Instruction::Mod(..) if val2 != 0 => Constant::Int(val1.wrapping_rem(val2)),
+            Instruction::Eq(..) => Constant::Bool(val1 == val2),
+            Instruction::Neq(..) => Constant::Bool(val1 != val2),
+            Instruction::Gt(..) => Constant::Bool(val1 > val2),
+            Instruction::Gte(..) => Constant::Bool(val1 >= val2),
+            Instruction::Lt(..) => Constant::Bool(val1 < val2),
+            Instruction::Lte(..) => Constant::Bool(val1 <= val2),
+            _ => return None,
+        },
+        (Constant::Float(val1), Constant::Float(val2)) => match instruction {
+            Instruction::Add(..) => Constant::Float(val1 + val2),
+            Instruction::Sub(..) => Constant::Float(val1 - val2),
+            Instruction::Mul(..) => Constant::Float(val1 * val2),
+            Instruction::Div(..) => Constant::Float(val1 / val2),
+            Instruction::Mod(..) => Constant::Float(val1 % val2),
+            Instruction::Eq(..) => Constant::Bool(val1 == val2),
+            Instruction::Neq(..) => Constant::Bool(val1 != val2),
+            Instruction::Gt(..) => Constant::Bool(val1 > val2),
+            Instruction::Gte(..) => Constant::Bool(val1 >= val2),
+            Instruction::Lt(..) => Constant::Bool(val1 < val2),
+            Instruction::Lte(..) => Constant::Bool(val1 <= val2),
+            _ => return None,
+        },
+        (Constant::Bool(val1), Constant::Bool(val2)) => match instruction {
+            Instruction::Or(..) => Constant::Bool(val1 || val2),
+            Instruction::And(..) => Constant::Bool(val1 && val2),
+            Instruction::Eq(..) => Constant::Bool(val1 == val2),
+            Instruction::Neq(..) => Constant::Bool(val1 != val2),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(self::instruction(result, dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_module;
+
+    use super::*;
+
+    #[test]
+    fn test_constant_folding() {
+        let mut module = parse_module(
+            "func main:
+            entry:
+                %0: const_int 6
+                %1: const_int 7
+                %2: mul %0 %1
+                %3: sub %2 %1
+                %4: gt %3 %0
+                %5: not %4
+                %6: const_int 0
+                %7: div %0 %6
+                %8: load_arg 0 int
+                %9: add %8 %3
+                jcond %5 then else
+            then:
+                ret %7
+            else:
+                %10: const_float 1.5
+                %11: mul %10 %10
+                ret %9",
+        )
+        .unwrap();
+        let function = module.functions.get_mut("main").unwrap();
+
+        assert!(ConstantFolding.run(function));
+        let entry = &function.blocks[0].1.instructions;
+        assert_eq!(entry[2], Instruction::ConstInt(42, 2));
+        assert_eq!(entry[3], Instruction::ConstInt(35, 3));
+        assert_eq!(entry[4], Instruction::ConstTrue(4));
+        assert_eq!(entry[5], Instruction::ConstFalse(5));
+        // Division by zero and unknown values are left alone
+        assert_eq!(
+            entry[7],
+            Instruction::Div(Value::Local(0), Value::Local(6), 7)
+        );
+        assert_eq!(
+            entry[9],
+            Instruction::Add(Value::Local(8), Value::Local(3), 9)
+        );
+        assert_eq!(entry[10], Instruction::Jump("else".to_string()));
+        assert_eq!(
+            function.blocks[2].1.instructions[1],
+            Instruction::ConstFloat(2.25, 11)
+        );
+
+        assert!(!ConstantFolding.run(function));
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use crate::{instruction::Instruction, module::Function, passes::Pass};
+
+/// Removes instructions whose result is never read, as long as they have no
+/// effect beyond producing it
+pub struct DeadRegisters;
+
+impl Pass for DeadRegisters {
+    fn name(&self) -> &'static str {
+        "dead-registers"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut changed = false;
+
+        // Removing an instruction can leave the registers it read unused too
+        loop {
+            let used: HashSet<usize> = function
+                .blocks
+                .iter()
+                .flat_map(|(_, block)| block.instructions.iter())
+                .flat_map(|instruction| instruction.uses())
+                .collect();
+
+            let mut removed = false;
+            for (_, block) in function.blocks.iter_mut() {
+                let before = block.instructions.len();
+                block.instructions.retain(|instruction| {
+                    !is_pure(instruction)
+                        || instruction.dest().is_none_or(|dest| used.contains(&dest))
+                });
+                removed |= block.instructions.len() != before;
+            }
+
+            if !removed {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+/// Whether an instruction does nothing but produce its result. Calls may
+/// have side effects, and so are always kept
+fn is_pure(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::FunctionCall(..)
+            | Instruction::Store(..)
+            | Instruction::StoreElement(..)
+            | Instruction::StoreStructMember(..)
+    ) && !instruction.is_terminator()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_module;
+
+    use super::*;
+
+    #[test]
+    fn test_dead_registers() {
+        let mut module = parse_module(
+            "func main:
+            entry:
+                %0: const_int 1
+                %1: const_int 2
+                %2: add %0 %1
+                %3: const_int 3
+                %4: function_call @printf [$0, %3]
+                %5: load @x int
+                store @y %5 int
+                ret",
+        )
+        .unwrap();
+        let function = module.functions.get_mut("main").unwrap();
+
+        assert!(DeadRegisters.run(function));
+        let dests: Vec<_> = function.blocks[0]
+            .1
+            .instructions
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect();
+        assert_eq!(
+            dests,
+            vec![
+                "%3: const_int 3",
+                "%4: function_call @printf [$0, %3]",
+                "%5: load @x int",
+                "store @y %5 int",
+                "ret "
+            ]
+        );
+
+        assert!(!DeadRegisters.run(function));
+    }
+}
@@ -0,0 +1,62 @@
+mod constant_folding;
+mod dead_registers;
+mod unreachable_blocks;
+
+pub use constant_folding::ConstantFolding;
+pub use dead_registers::DeadRegisters;
+pub use unreachable_blocks::UnreachableBlocks;
+
+use crate::module::{Function, Module};
+
+/// A transformation over a single function
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the function was changed
+    fn run(&self, function: &mut Function) -> bool;
+}
+
+/// Runs a sequence of passes over every function in a module
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self { passes: vec![] }
+    }
+
+    /// The passes run on every build, ordered so each leaves work for the
+    /// next: folded branches make blocks unreachable, and folding and
+    /// removing blocks leave registers unused
+    pub fn optimisations() -> Self {
+        let mut manager = Self::new();
+        manager.add(ConstantFolding);
+        manager.add(UnreachableBlocks);
+        manager.add(DeadRegisters);
+        manager
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Runs every pass in order, calling `after_pass` with the name of the
+    /// pass and the module it left
+    pub fn run(&self, module: &mut Module, mut after_pass: impl FnMut(&str, &Module)) -> bool {
+        let mut changed = false;
+        for pass in &self.passes {
+            for function in module.functions.values_mut() {
+                changed |= pass.run(function);
+            }
+            after_pass(pass.name(), module);
+        }
+        changed
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
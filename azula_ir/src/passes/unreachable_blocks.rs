@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use crate::{module::Function, passes::Pass};
+
+/// Removes blocks that can't be reached from the function's entry
+pub struct UnreachableBlocks;
+
+impl Pass for UnreachableBlocks {
+    fn name(&self) -> &'static str {
+        "unreachable-blocks"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let entry = match function.blocks.first() {
+            Some((entry, _)) => entry.clone(),
+            None => return false,
+        };
+
+        let mut reachable = HashSet::new();
+        let mut pending = vec![entry];
+        while let Some(name) = pending.pop() {
+            if !reachable.insert(name.clone()) {
+                continue;
+            }
+
+            if let Some((_, block)) = function.blocks.iter().find(|(block, _)| *block == name) {
+                for instruction in &block.instructions {
+                    pending.extend(
+                        instruction
+                            .targets()
+                            .into_iter()
+                            .map(|target| target.to_string()),
+                    );
+                }
+            }
+        }
+
+        let before = function.blocks.len();
+        function.blocks.retain(|(name, _)| reachable.contains(name));
+        function.blocks.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_module;
+
+    use super::*;
+
+    #[test]
+    fn test_unreachable_blocks() {
+        let mut module = parse_module(
+            "func main:
+            entry:
+                jump loop
+            loop:
+                %0: const_true
+                jcond %0 loop end
+            dead:
+                jump end
+            end:
+                ret",
+        )
+        .unwrap();
+        let function = module.functions.get_mut("main").unwrap();
+
+        assert!(UnreachableBlocks.run(function));
+        let blocks: Vec<_> = function
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(blocks, vec!["entry", "loop", "end"]);
+
+        assert!(!UnreachableBlocks.run(function));
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    instruction::{Instruction, Value},
+    module::{Function, Module},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum VerifyErrorKind {
+    MissingTerminator,
+    TerminatorNotLast,
+    DuplicateBlock(String),
+    UnknownBlock(String),
+    UndefinedLocal(usize),
+    UnknownFunction(String),
+    LiteralOperand(String),
+}
+
+/// A problem found in a module, and the function, block and instruction
+/// index it was found at
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerifyError {
+    pub kind: VerifyErrorKind,
+    pub function: String,
+    pub block: String,
+    pub instruction: Option<usize>,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, block {}", self.function, self.block)?;
+        if let Some(instruction) = self.instruction {
+            write!(f, ", instruction {}", instruction)?;
+        }
+        write!(f, ": ")?;
+
+        match &self.kind {
+            VerifyErrorKind::MissingTerminator => write!(f, "block does not end in a terminator"),
+            VerifyErrorKind::TerminatorNotLast => {
+                write!(f, "terminator is not the last instruction in its block")
+            }
+            VerifyErrorKind::DuplicateBlock(name) => write!(f, "block {} is defined twice", name),
+            VerifyErrorKind::UnknownBlock(name) => write!(f, "jump to unknown block {}", name),
+            VerifyErrorKind::UndefinedLocal(register) => {
+                write!(f, "%{} is used before it is defined", register)
+            }
+            VerifyErrorKind::UnknownFunction(name) => {
+                write!(f, "call to unknown function {}", name)
+            }
+            VerifyErrorKind::LiteralOperand(literal) => write!(
+                f,
+                "literal {} is used as an operand, but not every backend can lower it",
+                literal
+            ),
+        }
+    }
+}
+
+/// Checks a module is well formed before a backend is given it: blocks end
+/// in exactly one terminator that jumps to blocks that exist, operands are
+/// registers or strings, registers are defined on every path to their uses
+/// and called functions exist
+pub fn verify(module: &Module) -> Result<(), Vec<VerifyError>> {
+    let mut errors = vec![];
+    for (name, function) in &module.functions {
+        verify_function(module, name, function, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn verify_function(
+    module: &Module,
+    name: &str,
+    function: &Function,
+    errors: &mut Vec<VerifyError>,
+) {
+    let mut error = |kind, block: &str, instruction| {
+        errors.push(VerifyError {
+            kind,
+            function: name.to_string(),
+            block: block.to_string(),
+            instruction,
+        })
+    };
+
+    let mut indices = HashMap::new();
+    for (index, (block, _)) in function.blocks.iter().enumerate() {
+        if indices.insert(block.as_str(), index).is_some() {
+            error(VerifyErrorKind::DuplicateBlock(block.clone()), block, None);
+        }
+    }
+
+    let mut successors = vec![vec![]; function.blocks.len()];
+    for (index, (block, instructions)) in function.blocks.iter().enumerate() {
+        let instructions = &instructions.instructions;
+        if !instructions.last().is_some_and(|last| last.is_terminator()) {
+            error(VerifyErrorKind::MissingTerminator, block, None);
+        }
+
+        for (position, instruction) in instructions.iter().enumerate() {
+            if instruction.is_terminator() && position + 1 != instructions.len() {
+                error(VerifyErrorKind::TerminatorNotLast, block, Some(position));
+            }
+
+            for target in instruction.targets() {
+                match indices.get(target) {
+                    Some(target) => successors[index].push(*target),
+                    None => error(
+                        VerifyErrorKind::UnknownBlock(target.to_string()),
+                        block,
+                        Some(position),
+                    ),
+                }
+            }
+
+            // Constants are loaded with const_int and friends instead
+            for operand in instruction.operands() {
+                if let Value::LiteralInteger(..) | Value::LiteralBoolean(..) = operand {
+                    error(
+                        VerifyErrorKind::LiteralOperand(operand.to_string()),
+                        block,
+                        Some(position),
+                    );
+                }
+            }
+
+            if let Instruction::FunctionCall(callee, ..) = instruction {
+                if !module.functions.contains_key(callee)
+                    && !module.extern_functions.contains_key(callee.as_str())
+                {
+                    error(
+                        VerifyErrorKind::UnknownFunction(callee.clone()),
+                        block,
+                        Some(position),
+                    );
+                }
+            }
+        }
+    }
+
+    // A register is defined at the start of a block when every path from
+    // the entry defines it, so find what each block can rely on by
+    // intersecting its predecessors until nothing changes
+    let mut defined_in: Vec<Option<HashSet<usize>>> = vec![None; function.blocks.len()];
+    if !function.blocks.is_empty() {
+        defined_in[0] = Some(HashSet::new());
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..function.blocks.len() {
+            let mut defined = match &defined_in[index] {
+                Some(defined) => defined.clone(),
+                None => continue,
+            };
+            defined.extend(
+                function.blocks[index]
+                    .1
+                    .instructions
+                    .iter()
+                    .filter_map(|instruction| instruction.dest()),
+            );
+
+            for successor in &successors[index] {
+                let merged = match &defined_in[*successor] {
+                    Some(existing) => existing.intersection(&defined).cloned().collect(),
+                    None => defined.clone(),
+                };
+                if defined_in[*successor].as_ref() != Some(&merged) {
+                    defined_in[*successor] = Some(merged);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for (index, (block, instructions)) in function.blocks.iter().enumerate() {
+        // Blocks nothing jumps to can't rely on anything
+        let mut defined = defined_in[index].clone().unwrap_or_default();
+        for (position, instruction) in instructions.instructions.iter().enumerate() {
+            for register in instruction.uses() {
+                if !defined.contains(&register) {
+                    error(
+                        VerifyErrorKind::UndefinedLocal(register),
+                        block,
+                        Some(position),
+                    );
+                }
+            }
+            defined.extend(instruction.dest());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_module;
+
+    use super::*;
+
+    fn errors(source: &str) -> Vec<(VerifyErrorKind, String, Option<usize>)> {
+        let module = parse_module(source).unwrap();
+        match verify(&module) {
+            Ok(()) => vec![],
+            Err(errors) => errors
+                .into_iter()
+                .map(|error| (error.kind, error.block, error.instruction))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let valid = "func main:
+            entry:
+                %0: const_int 1
+                %1: const_true
+                jcond %1 then end
+            then:
+                %2: add %0 %0
+                %3: function_call @printf [$0, %2]
+                jump end
+            end:
+                ret %0";
+        assert_eq!(errors(valid), vec![]);
+
+        let invalid = "func main:
+            entry:
+                %0: const_true
+                jcond %0 then missing
+            then:
+                %1: const_int 1
+                jump end
+                %2: const_int 2
+            other:
+                %3: function_call @nothing []
+            end:
+                %4: add %1 %3
+                ret %5";
+        assert_eq!(
+            errors(invalid),
+            vec![
+                (
+                    VerifyErrorKind::UnknownBlock("missing".to_string()),
+                    "entry".to_string(),
+                    Some(1)
+                ),
+                (VerifyErrorKind::MissingTerminator, "then".to_string(), None),
+                (
+                    VerifyErrorKind::TerminatorNotLast,
+                    "then".to_string(),
+                    Some(1)
+                ),
+                (
+                    VerifyErrorKind::MissingTerminator,
+                    "other".to_string(),
+                    None
+                ),
+                (
+                    VerifyErrorKind::UnknownFunction("nothing".to_string()),
+                    "other".to_string(),
+                    Some(0)
+                ),
+                // %1 is defined on the only path to end, but %3 isn't
+                (
+                    VerifyErrorKind::UndefinedLocal(3),
+                    "end".to_string(),
+                    Some(0)
+                ),
+                (
+                    VerifyErrorKind::UndefinedLocal(5),
+                    "end".to_string(),
+                    Some(1)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_literal_operands() {
+        // The parser won't read these, but passes and other tools building
+        // modules directly could make them
+        let mut module = parse_module(
+            "func main:
+            entry:
+                %0: const_int 1
+                %1: add %0 %0
+                ret %1",
+        )
+        .unwrap();
+        let entry = &mut module.functions.get_mut("main").unwrap().blocks[0].1;
+        entry.instructions[1] = Instruction::Add(Value::Local(0), Value::LiteralBoolean(true), 1);
+        entry.instructions[2] = Instruction::Return(Some(Value::LiteralInteger(2)));
+
+        let errors: Vec<_> = verify(&module)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.kind, error.instruction))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (VerifyErrorKind::LiteralOperand("true".to_string()), Some(1)),
+                (VerifyErrorKind::LiteralOperand("2".to_string()), Some(2)),
+            ]
+        );
+    }
+}
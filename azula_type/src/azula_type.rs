@@ -1,4 +1,4 @@
-use std::{ops::Deref, rc::Rc};
+use std::{fmt, ops::Deref, rc::Rc};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AzulaType<'a> {
@@ -15,6 +15,12 @@ pub enum AzulaType<'a> {
     Named(String),
     UnknownType(&'a str),
     Array(Rc<AzulaType<'a>>, Option<usize>),
+    // A generic struct applied to type arguments, like `Pair<int, bool>`. The
+    // typechecker replaces these with the struct monomorphised for them
+    Generic(String, Vec<AzulaType<'a>>),
+    // Given to values that failed to typecheck, so using them doesn't
+    // report further errors
+    Error,
 }
 
 impl<'a> From<&'a str> for AzulaType<'a> {
@@ -49,3 +55,33 @@ impl<'a> AzulaType<'a> {
         }
     }
 }
+
+impl<'a> fmt::Display for AzulaType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AzulaType::Int => write!(f, "int"),
+            AzulaType::SizedSignedInt(size) => write!(f, "i{}", size),
+            AzulaType::SizedUnsignedInt(size) => write!(f, "u{}", size),
+            AzulaType::Str => write!(f, "str"),
+            AzulaType::Float => write!(f, "float"),
+            AzulaType::SizedFloat(size) => write!(f, "f{}", size),
+            AzulaType::Bool => write!(f, "bool"),
+            AzulaType::Void => write!(f, "void"),
+            AzulaType::Pointer(nested) => write!(f, "&{}", nested),
+            AzulaType::Infer => write!(f, "_"),
+            AzulaType::Named(name) => write!(f, "{}", name),
+            AzulaType::UnknownType(name) => write!(f, "{}", name),
+            AzulaType::Array(nested, Some(size)) => write!(f, "[{}; {}]", nested, size),
+            AzulaType::Array(nested, None) => write!(f, "[{}]", nested),
+            AzulaType::Generic(name, args) => write!(f, "{}", mangle(name, args)),
+            AzulaType::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+/// The name a generic function or struct is emitted under once monomorphised
+/// for some type arguments, written the way the instance is spelled in source
+pub fn mangle(name: &str, type_args: &[AzulaType]) -> String {
+    let args: Vec<_> = type_args.iter().map(|arg| arg.to_string()).collect();
+    format!("{}<{}>", name, args.join(", "))
+}
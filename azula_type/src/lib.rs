@@ -1,5 +1,5 @@
 mod azula_type;
 
 pub mod prelude {
-    pub use crate::azula_type::AzulaType;
+    pub use crate::azula_type::{mangle, AzulaType};
 }
@@ -1,21 +1,150 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
 use azula_ir::prelude::Module;
 
+/// Turns Azula IR into something a machine can run. A backend is made with
+/// the options it should use, lowers a module into its own representation
+/// once and can then emit that as many times and in as many forms as needed
 pub trait Backend<'a> {
-    fn codegen(
-        name: &'a str,
-        destination: &'a str,
-        emit: bool,
-        target: Option<&String>,
-        opt_level: OptimizationLevel,
-        module: Module<'a>,
-    ) -> Result<(), Box<dyn Error>>;
+    /// Translates a module, replacing any module lowered before it
+    fn lower(&mut self, module: &Module<'a>) -> Result<(), BackendError>;
+
+    /// Writes the lowered module out in the given form
+    fn emit(&self, kind: EmitKind, output: &mut dyn Write) -> Result<(), BackendError>;
+
+    /// The file extension conventionally used for a form of output
+    fn extension(&self, kind: EmitKind) -> &'static str;
+
+    /// Writes the lowered module to a file, creating its directory if needed
+    fn emit_to_path(&self, kind: EmitKind, path: &Path) -> Result<Artifact, BackendError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(path)?;
+        self.emit(kind, &mut file)?;
+
+        Ok(Artifact {
+            kind,
+            path: path.to_path_buf(),
+        })
+    }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
 pub enum OptimizationLevel {
     None,
+    #[default]
     Default,
     Aggressive,
 }
+
+#[derive(Clone, Debug, Default)]
+pub struct BackendOptions {
+    /// The target triple to compile for, or the host when not given
+    pub target: Option<String>,
+    pub opt_level: OptimizationLevel,
+}
+
+/// The forms a backend can emit a lowered module in
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EmitKind {
+    Object,
+    Assembly,
+    // The backend's own textual representation, like LLVM IR or C source
+    Ir,
+}
+
+/// A file a backend has written
+#[derive(PartialEq, Clone, Debug)]
+pub struct Artifact {
+    pub kind: EmitKind,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    // Emitting was asked for before a module was lowered
+    NotLowered,
+    // Something in the module the backend has no way to express
+    Unsupported(String),
+    // The target couldn't be found or set up
+    Target(String),
+    // An external tool like a compiler or linker failed
+    Tool(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NotLowered => write!(f, "No module has been lowered"),
+            BackendError::Unsupported(reason) => {
+                write!(f, "Unsupported by the backend: {}", reason)
+            }
+            BackendError::Target(reason) => write!(f, "Invalid target: {}", reason),
+            BackendError::Tool(reason) => write!(f, "{}", reason),
+            BackendError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<io::Error> for BackendError {
+    fn from(e: io::Error) -> Self {
+        BackendError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo(Option<String>);
+
+    impl<'a> Backend<'a> for Echo {
+        fn lower(&mut self, module: &Module<'a>) -> Result<(), BackendError> {
+            self.0 = Some(module.name.to_string());
+            Ok(())
+        }
+
+        fn emit(&self, _: EmitKind, output: &mut dyn Write) -> Result<(), BackendError> {
+            let name = self.0.as_ref().ok_or(BackendError::NotLowered)?;
+            output.write_all(name.as_bytes())?;
+            Ok(())
+        }
+
+        fn extension(&self, _: EmitKind) -> &'static str {
+            "txt"
+        }
+    }
+
+    #[test]
+    fn test_emit_to_path() {
+        let mut backend = Echo(None);
+        let path = std::env::temp_dir()
+            .join("azula_codegen_backend")
+            .join("echo.txt");
+        assert!(matches!(
+            backend.emit_to_path(EmitKind::Ir, &path),
+            Err(BackendError::NotLowered)
+        ));
+
+        backend.lower(&Module::new("echo")).unwrap();
+        let artifact = backend.emit_to_path(EmitKind::Ir, &path).unwrap();
+        assert_eq!(
+            artifact,
+            Artifact {
+                kind: EmitKind::Ir,
+                path: path.clone()
+            }
+        );
+        assert_eq!(fs::read_to_string(path).unwrap(), "echo");
+    }
+}
@@ -1,3 +1,3 @@
 pub mod backend;
 
-pub use backend::{Backend, OptimizationLevel};
+pub use backend::{Artifact, Backend, BackendError, BackendOptions, EmitKind, OptimizationLevel};
@@ -3,13 +3,20 @@ use std::{collections::HashMap, ops::Deref};
 
 use azula_ast::prelude::*;
 use azula_ir::prelude::*;
-use azula_type::prelude::AzulaType;
+use azula_type::prelude::{mangle, AzulaType};
 
 pub struct Codegen<'a> {
     root: Statement<'a>,
 
     pub module: Module<'a>,
     pub function_calls: HashMap<String, Vec<AzulaType<'a>>>,
+    /// Maps names in the current file to the names they're emitted under
+    pub symbols: HashMap<String, String>,
+    /// The (continue, break) blocks of each loop being lowered, innermost last
+    loop_targets: Vec<(String, String)>,
+    /// Loop variables by their name in the source, mapped to the hidden
+    /// variable each is kept in so it can't clobber an outer one
+    loop_variables: HashMap<String, String>,
 }
 
 impl<'a> Codegen<'a> {
@@ -18,6 +25,24 @@ impl<'a> Codegen<'a> {
             root,
             module: Module::new(name),
             function_calls: HashMap::new(),
+            symbols: HashMap::new(),
+            loop_targets: vec![],
+            loop_variables: HashMap::new(),
+        }
+    }
+
+    /// Lowers another file into the same module, so a whole program ends up
+    /// in a single IR module
+    pub fn codegen_unit(&mut self, root: Statement<'a>, symbols: HashMap<String, String>) {
+        self.root = root;
+        self.symbols = symbols;
+        self.codegen();
+    }
+
+    fn symbol(&self, name: &str) -> String {
+        match self.symbols.get(name) {
+            Some(symbol) => symbol.clone(),
+            None => name.to_string(),
         }
     }
 
@@ -25,6 +50,13 @@ impl<'a> Codegen<'a> {
         if let Statement::Root(stmts) = &self.root {
             for stmt in stmts.clone() {
                 match stmt {
+                    // Only the instances of generic declarations are emitted
+                    Statement::Function {
+                        ref type_params, ..
+                    }
+                    | Statement::Struct {
+                        ref type_params, ..
+                    } if !type_params.is_empty() => {}
                     Statement::Function { .. } => self.codegen_function(stmt.clone()),
                     Statement::ExternFunction {
                         name,
@@ -56,13 +88,31 @@ impl<'a> Codegen<'a> {
                             _ => unreachable!(),
                         };
 
-                        self.module.global_values.insert(name, value);
+                        self.module.global_values.insert(self.symbol(&name), value);
                     }
                     Statement::Struct {
                         name, attributes, ..
                     } => {
-                        self.module.add_struct(name, Struct { name, attributes });
+                        self.module.add_struct(
+                            name.to_string(),
+                            Struct {
+                                name: name.to_string(),
+                                attributes,
+                            },
+                        );
                     }
+                    Statement::Instance(type_args, def) => {
+                        self.codegen_instance(type_args, def.deref().clone())
+                    }
+                    Statement::Enum { name, variants, .. } => {
+                        self.module.add_enum(name, Enum { name, variants });
+                    }
+                    Statement::Impl { name, methods, .. } => {
+                        for method in methods {
+                            self.codegen_method(name, method);
+                        }
+                    }
+                    Statement::Import(..) => {}
                     _ => unreachable!(),
                 }
             }
@@ -72,10 +122,37 @@ impl<'a> Codegen<'a> {
     pub fn insert_implicit_return(&mut self) {
         for (_, func) in self.module.functions.iter_mut() {
             let cloned = func.blocks.clone();
+
+            // Blocks nothing jumps to (e.g. after an if/else that returns in every
+            // branch) can't fall through, so don't give them a return
+            let mut targets = vec!["entry".to_string()];
+            for (_, block) in &cloned {
+                for instruction in &block.instructions {
+                    match instruction {
+                        Instruction::Jcond(_, true_block, false_block) => {
+                            targets.push(true_block.clone());
+                            targets.push(false_block.clone());
+                        }
+                        Instruction::Jump(block) => targets.push(block.clone()),
+                        Instruction::Switch(_, cases, default) => {
+                            targets.extend(cases.iter().map(|(_, block)| block.clone()));
+                            targets.push(default.clone());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
             for (index, (block_name, ref block)) in cloned.iter().enumerate() {
                 let mut block = block.clone();
+                let terminator = if targets.contains(block_name) {
+                    Instruction::Return(None)
+                } else {
+                    Instruction::Unreachable
+                };
+
                 if block.instructions.is_empty() {
-                    block.instructions.push(Instruction::Return(None));
+                    block.instructions.push(terminator);
                     *func.blocks.get_mut(index).unwrap() = (block_name.to_string(), block.clone());
                     continue;
                 }
@@ -83,9 +160,11 @@ impl<'a> Codegen<'a> {
                 match block.instructions.last().unwrap() {
                     Instruction::Jcond(..) => continue,
                     Instruction::Jump(..) => continue,
+                    Instruction::Switch(..) => continue,
                     Instruction::Return(..) => continue,
+                    Instruction::Unreachable => continue,
                     _ => {
-                        block.instructions.push(Instruction::Return(None));
+                        block.instructions.push(terminator);
                         *func.blocks.get_mut(index).unwrap() = (block_name.clone(), block.clone());
                         continue;
                     }
@@ -95,6 +174,39 @@ impl<'a> Codegen<'a> {
     }
 
     pub fn codegen_function(&mut self, stmt: Statement<'a>) {
+        let (name, function) = self.build_function(stmt);
+        self.module.add_function(self.symbol(name), function)
+    }
+
+    /// Emits a monomorphised copy of a generic function or struct, named after
+    /// its type arguments
+    pub fn codegen_instance(&mut self, type_args: Vec<AzulaType<'a>>, def: Statement<'a>) {
+        match def {
+            Statement::Function { .. } => {
+                let (name, function) = self.build_function(def);
+                self.module
+                    .add_function(mangle(&self.symbol(name), &type_args), function)
+            }
+            Statement::Struct {
+                name, attributes, ..
+            } => {
+                let name = mangle(name, &type_args);
+                self.module
+                    .add_struct(name.clone(), Struct { name, attributes });
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Methods are lowered to plain functions named `Struct.method`, taking
+    /// a pointer to the receiver as their first argument
+    pub fn codegen_method(&mut self, struc: &str, stmt: Statement<'a>) {
+        let (name, function) = self.build_function(stmt);
+        self.module
+            .add_function(format!("{}.{}", struc, name), function)
+    }
+
+    fn build_function(&mut self, stmt: Statement<'a>) -> (&'a str, Function<'a>) {
         if let Statement::Function {
             name,
             args,
@@ -118,21 +230,37 @@ impl<'a> Codegen<'a> {
                 }
             }
 
-            self.module.add_function(name, function)
+            (name, function)
         } else {
             unreachable!()
         }
     }
 
     pub fn codegen_statement(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        // Statements after a return are dead, so like those after a break
+        // they go in a block of their own
+        if func.is_terminated() {
+            let after_name = format!("after-{}", func.if_block_index);
+            func.if_block_index += 1;
+
+            func.blocks.push((after_name.clone(), Block::new()));
+            func.current_block = after_name;
+        }
+
         match stmt {
             Statement::Assign(..) => self.codegen_assign(stmt, func),
             Statement::Return(..) => self.codegen_return(stmt, func),
             Statement::ExpressionStatement(expr, ..) => {
-                self.codegen_expr(expr.clone(), func, true);
+                if let Expression::Match(..) = expr.expression {
+                    self.codegen_match(expr, func);
+                } else {
+                    self.codegen_expr(expr.clone(), func, true);
+                }
             }
             Statement::If(..) => self.codegen_if(stmt, func),
             Statement::While(..) => self.codegen_while(stmt, func),
+            Statement::For(..) => self.codegen_for(stmt, func),
+            Statement::Break(..) | Statement::Continue(..) => self.codegen_loop_jump(stmt, func),
             Statement::Reassign(..) => self.codegen_reassign(stmt, func),
             _ => panic!(),
         }
@@ -141,6 +269,7 @@ impl<'a> Codegen<'a> {
     pub fn codegen_assign(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
         if let Statement::Assign(_, name, _, expr, _) = stmt {
             let value = self.codegen_expr(expr.clone(), func, true);
+            let name = self.variable(name);
             func.store(name.clone(), value, expr.typed.clone());
             func.variables.insert(name, expr.typed);
         } else {
@@ -152,7 +281,7 @@ impl<'a> Codegen<'a> {
         if let Statement::Reassign(var, val, _) = stmt {
             let value = self.codegen_expr(val.clone(), func, true);
             match var.expression {
-                Expression::Identifier(v) => func.store(v.clone(), value, val.typed.clone()),
+                Expression::Identifier(v) => func.store(self.variable(v), value, val.typed.clone()),
                 Expression::ArrayAccess(array, index) => {
                     let array = self.codegen_expr(array.deref().clone(), func, true);
                     let index = self.codegen_expr(index.deref().clone(), func, true);
@@ -205,15 +334,20 @@ impl<'a> Codegen<'a> {
     }
 
     pub fn codegen_if(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
-        if let Statement::If(cond, body, ..) = stmt {
+        if let Statement::If(cond, body, else_body, ..) = stmt {
             let cond = self.codegen_expr(cond, func, true);
 
             let true_name = format!("true-{}", func.if_block_index);
+            let else_name = format!("else-{}", func.if_block_index);
             let end_name = format!("end-{}", func.if_block_index);
 
             func.if_block_index += 1;
 
-            func.jcond(cond, true_name.clone(), end_name.clone());
+            if else_body.is_some() {
+                func.jcond(cond, true_name.clone(), else_name.clone());
+            } else {
+                func.jcond(cond, true_name.clone(), end_name.clone());
+            }
             func.blocks.push((true_name.clone(), Block::new()));
 
             func.current_block = true_name.clone();
@@ -222,15 +356,25 @@ impl<'a> Codegen<'a> {
                 self.codegen_statement(stmt, func);
             }
 
-            for (name, block) in &func.blocks.clone() {
-                if name.clone() == func.current_block {
-                    if let Some(Instruction::Return(_)) = block.instructions.last() {
-                    } else {
-                        func.jump(end_name.clone());
-                    }
+            if !func.is_terminated() {
+                func.jump(end_name.clone());
+            }
+
+            if let Some(else_body) = else_body {
+                func.blocks.push((else_name.clone(), Block::new()));
+                func.current_block = else_name.clone();
+
+                for stmt in else_body {
+                    self.codegen_statement(stmt, func);
+                }
+
+                if !func.is_terminated() {
+                    func.jump(end_name.clone());
                 }
             }
 
+            // If every branch returned, nothing jumps here and the block is
+            // marked unreachable by insert_implicit_return
             func.blocks.push((end_name.clone(), Block::new()));
             func.current_block = end_name.clone();
         } else {
@@ -255,9 +399,116 @@ impl<'a> Codegen<'a> {
             func.blocks.push((true_name.clone(), Block::new()));
             func.current_block = true_name.clone();
 
+            self.loop_targets
+                .push((eval_name.clone(), end_name.clone()));
             for stmt in body {
                 self.codegen_statement(stmt, func);
             }
+            self.loop_targets.pop();
+
+            if !func.is_terminated() {
+                func.jump(eval_name.clone());
+            }
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name.clone();
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Lowers a for loop to the same eval/loop/end blocks as a while loop, with an
+    /// extra step block to advance the counter. Arrays are walked with a hidden index.
+    pub fn codegen_for(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::For(ident, iterable, body, ..) = stmt {
+            let eval_name = format!("eval-{}", func.if_block_index);
+            let true_name = format!("loop-{}", func.if_block_index);
+            let step_name = format!("step-{}", func.if_block_index);
+            let end_name = format!("end-{}", func.if_block_index);
+
+            let end_var = format!("__for_end{}", func.if_block_index);
+            let array_var = format!("__for_array{}", func.if_block_index);
+            let index_var = format!("__for_index{}", func.if_block_index);
+            let loop_var = format!("__for_var{}", func.if_block_index);
+
+            func.if_block_index += 1;
+
+            // Ranges count with the loop variable itself, arrays with a hidden index
+            let (counter, element) = match iterable.expression {
+                Expression::Range(start, end) => {
+                    let start = self.codegen_expr(start.deref().clone(), func, true);
+                    func.store(loop_var.clone(), start, AzulaType::Int);
+                    func.variables.insert(loop_var.clone(), AzulaType::Int);
+
+                    let end = self.codegen_expr(end.deref().clone(), func, true);
+                    func.store(end_var.clone(), end, AzulaType::Int);
+
+                    (loop_var.clone(), None)
+                }
+                _ => {
+                    let (element, size) = match &iterable.typed {
+                        AzulaType::Array(element, Some(size)) => (element.deref().clone(), *size),
+                        _ => unreachable!("{:?}", iterable.typed),
+                    };
+
+                    let array = self.codegen_expr(iterable.clone(), func, true);
+                    func.store(array_var.clone(), array, iterable.typed.clone());
+                    func.variables
+                        .insert(array_var.clone(), iterable.typed.clone());
+
+                    let start = func.const_int(0);
+                    func.store(index_var.clone(), start, AzulaType::Int);
+                    func.variables.insert(index_var.clone(), AzulaType::Int);
+
+                    let end = func.const_int(size as i64);
+                    func.store(end_var.clone(), end, AzulaType::Int);
+
+                    (index_var, Some(element))
+                }
+            };
+            func.variables.insert(end_var.clone(), AzulaType::Int);
+
+            func.jump(eval_name.clone());
+            func.blocks.push((eval_name.clone(), Block::new()));
+            func.current_block = eval_name.clone();
+            let index = func.load(counter.clone(), AzulaType::Int);
+            let end = func.load(end_var, AzulaType::Int);
+            let cond_val = func.lt(index, end);
+            func.jcond(cond_val, true_name.clone(), end_name.clone());
+
+            func.blocks.push((true_name.clone(), Block::new()));
+            func.current_block = true_name.clone();
+
+            if let Some(element) = element {
+                let array = func.load(array_var, iterable.typed.clone());
+                let index = func.load(counter.clone(), AzulaType::Int);
+                let value = func.access_element(array, index);
+                func.store(loop_var.clone(), value, element.clone());
+                func.variables.insert(loop_var.clone(), element);
+            }
+
+            let outer = self.loop_variables.insert(ident.clone(), loop_var);
+            self.loop_targets
+                .push((step_name.clone(), end_name.clone()));
+            for stmt in body {
+                self.codegen_statement(stmt, func);
+            }
+            self.loop_targets.pop();
+            match outer {
+                Some(outer) => self.loop_variables.insert(ident, outer),
+                None => self.loop_variables.remove(&ident),
+            };
+
+            if !func.is_terminated() {
+                func.jump(step_name.clone());
+            }
+
+            func.blocks.push((step_name.clone(), Block::new()));
+            func.current_block = step_name.clone();
+            let index = func.load(counter.clone(), AzulaType::Int);
+            let one = func.const_int(1);
+            let next = func.add(index, one);
+            func.store(counter, next, AzulaType::Int);
             func.jump(eval_name.clone());
 
             func.blocks.push((end_name.clone(), Block::new()));
@@ -267,6 +518,32 @@ impl<'a> Codegen<'a> {
         }
     }
 
+    /// The variable a name in the source is kept in
+    fn variable(&self, name: String) -> String {
+        match self.loop_variables.get(&name) {
+            Some(var) => var.clone(),
+            None => name,
+        }
+    }
+
+    pub fn codegen_loop_jump(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        let (continue_name, break_name) = self.loop_targets.last().unwrap().clone();
+
+        match stmt {
+            Statement::Break(..) => func.jump(break_name),
+            Statement::Continue(..) => func.jump(continue_name),
+            _ => unreachable!(),
+        }
+
+        // Anything after the jump is dead, so give it a block of its own
+        // which insert_implicit_return will mark unreachable
+        let after_name = format!("after-{}", func.if_block_index);
+        func.if_block_index += 1;
+
+        func.blocks.push((after_name.clone(), Block::new()));
+        func.current_block = after_name;
+    }
+
     pub fn codegen_expr(
         &mut self,
         expr: ExpressionNode<'a>,
@@ -277,6 +554,14 @@ impl<'a> Codegen<'a> {
             Expression::Infix(..) => self.codegen_infix(expr, func, resolve_pointer),
             Expression::Integer(val) => func.const_int(val),
             Expression::Float(val) => func.const_float(val),
+            Expression::Identifier(name) if self.loop_variables.contains_key(&name) => {
+                let var = self.variable(name);
+                if resolve_pointer {
+                    func.load(var, expr.typed)
+                } else {
+                    func.ptr(var)
+                }
+            }
             Expression::Identifier(name) if resolve_pointer => {
                 if let Some((index, _)) = func
                     .arguments
@@ -289,11 +574,11 @@ impl<'a> Codegen<'a> {
                     func.load_arg(index, expr.typed)
                 } else if func.variables.contains_key(&name) {
                     func.load(name, expr.typed)
-                } else if let Some(val) = self.module.global_values.get(&name) {
+                } else if let Some(val) = self.module.global_values.get(&self.symbol(&name)) {
                     if let GlobalValue::String(v) = val {
                         return Value::Global(*v);
                     }
-                    func.load_global(name, expr.typed)
+                    func.load_global(self.symbol(&name), expr.typed)
                 } else if name == "nil" {
                     func.const_null()
                 } else {
@@ -309,9 +594,21 @@ impl<'a> Codegen<'a> {
                     func.const_false()
                 }
             }
-            Expression::FunctionCall { function, args } => {
+            Expression::FunctionCall {
+                function,
+                args,
+                type_args,
+            } => {
                 let name = match &function.expression {
                     Expression::Identifier(name) => name,
+                    Expression::StructAccess(receiver, method) => {
+                        return self.codegen_method_call(
+                            receiver.deref().clone(),
+                            method.deref().clone(),
+                            args,
+                            func,
+                        )
+                    }
                     _ => todo!(),
                 };
 
@@ -326,7 +623,13 @@ impl<'a> Codegen<'a> {
                     .iter()
                     .map(|arg| self.codegen_expr(arg.clone(), func, true))
                     .collect();
-                func.function_call(name.clone(), args)
+
+                let name = if type_args.is_empty() {
+                    self.symbol(name)
+                } else {
+                    mangle(&self.symbol(name), &type_args)
+                };
+                func.function_call(name, args)
             }
             Expression::Not(expr) => {
                 let val = self.codegen_expr(expr.as_ref().clone(), func, true);
@@ -399,9 +702,176 @@ impl<'a> Codegen<'a> {
 
                 func.access_struct_member(struct_value, index, resolve_pointer)
             }
+            Expression::Range(..) => unreachable!("ranges are only lowered by for loops"),
+            Expression::Match(..) => self.codegen_match(expr, func).unwrap(),
+            Expression::EnumVariant(enu, variant, args) => {
+                let tag = self
+                    .module
+                    .enums
+                    .get(enu.as_str())
+                    .and_then(|e| e.tag(&variant))
+                    .unwrap();
+
+                let values = args
+                    .iter()
+                    .map(|arg| self.codegen_expr(arg.clone(), func, true))
+                    .collect();
+
+                func.create_enum(enu, tag, values)
+            }
+        }
+    }
+
+    /// Lowers a match to a switch on the enum's tag with a block for each arm. When the
+    /// match gives a value, each arm stores it to a hidden variable loaded at the end.
+    fn codegen_match(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        func: &mut Function<'a>,
+    ) -> Option<Value> {
+        if let Expression::Match(subject, arms) = expr.expression {
+            let index = func.if_block_index;
+            func.if_block_index += 1;
+
+            let end_name = format!("end-{}", index);
+            let result_var = format!("__match{}", index);
+            let arm_name = |arm: usize| format!("arm-{}-{}", index, arm);
+
+            let enum_name = match &subject.typed {
+                AzulaType::Named(name) => name.clone(),
+                AzulaType::Pointer(nested) => match nested.deref() {
+                    AzulaType::Named(name) => name.clone(),
+                    _ => unreachable!("{:?}", subject.typed),
+                },
+                _ => unreachable!("{:?}", subject.typed),
+            };
+            let enu = self.module.enums.get(enum_name.as_str()).unwrap().clone();
+
+            let value = self.codegen_expr(subject.deref().clone(), func, true);
+            let tag = func.access_struct_member(value.clone(), 0, true);
+
+            // Only the first arm for a variant or wildcard can ever be taken
+            let mut cases: Vec<(i64, String)> = vec![];
+            let mut default = None;
+            for (index, arm) in arms.iter().enumerate() {
+                match &arm.pattern {
+                    Pattern::Variant(variant, _) => {
+                        let tag = enu.tag(variant).unwrap() as i64;
+                        if !cases.iter().any(|(case, _)| *case == tag) {
+                            cases.push((tag, arm_name(index)));
+                        }
+                    }
+                    Pattern::Wildcard => {
+                        default.get_or_insert(arm_name(index));
+                    }
+                }
+            }
+            func.switch(tag, cases, default.unwrap_or_else(|| end_name.clone()));
+
+            for (index, arm) in arms.into_iter().enumerate() {
+                func.blocks.push((arm_name(index), Block::new()));
+                func.current_block = arm_name(index);
+
+                if let Pattern::Variant(variant, bindings) = &arm.pattern {
+                    let tag = enu.tag(variant).unwrap();
+                    let payload = enu.variants[tag].1.clone();
+
+                    for (field, binding) in bindings.iter().enumerate() {
+                        if *binding == "_" {
+                            continue;
+                        }
+
+                        let val = func.access_struct_member(
+                            value.clone(),
+                            enu.field_index(tag, field),
+                            true,
+                        );
+                        func.store(binding.to_string(), val, payload[field].clone());
+                        func.variables
+                            .insert(binding.to_string(), payload[field].clone());
+                    }
+                }
+
+                for stmt in arm.body {
+                    self.codegen_statement(stmt, func);
+                }
+
+                if let Some(arm_value) = arm.value {
+                    if !func.is_terminated() {
+                        let val = self.codegen_expr(arm_value, func, true);
+                        if expr.typed != AzulaType::Void {
+                            func.store(result_var.clone(), val, expr.typed.clone());
+                        }
+                    }
+                }
+
+                if !func.is_terminated() {
+                    func.jump(end_name.clone());
+                }
+            }
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name;
+
+            if expr.typed == AzulaType::Void {
+                return None;
+            }
+
+            func.variables
+                .insert(result_var.clone(), expr.typed.clone());
+            Some(func.load(result_var, expr.typed))
+        } else {
+            unreachable!()
         }
     }
 
+    fn codegen_method_call(
+        &mut self,
+        receiver: ExpressionNode<'a>,
+        method: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let method_name = match &method.expression {
+            Expression::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+
+        // Methods always take their receiver by pointer
+        let (struct_name, self_ptr) = match &receiver.typed {
+            AzulaType::Pointer(nested) => match nested.deref().clone() {
+                AzulaType::Named(name) => (name, self.codegen_expr(receiver.clone(), func, true)),
+                _ => unreachable!("{:?}", receiver.typed),
+            },
+            AzulaType::Named(name) => match &receiver.expression {
+                Expression::Identifier(var)
+                    if func.variables.contains_key(&self.variable(var.clone())) =>
+                {
+                    (name.clone(), func.ptr(self.variable(var.clone())))
+                }
+                _ => {
+                    // Temporaries are spilled to a variable so there's something to point to
+                    let value = self.codegen_expr(receiver.clone(), func, true);
+                    let var = match value {
+                        Value::Local(index) => format!("__receiver{}", index),
+                        _ => unreachable!(),
+                    };
+                    func.store(var.clone(), value, receiver.typed.clone());
+                    func.variables.insert(var.clone(), receiver.typed.clone());
+                    (name.clone(), func.ptr(var))
+                }
+            },
+            _ => unreachable!("{:?}", receiver.typed),
+        };
+
+        let mut values = vec![self_ptr];
+        for arg in args {
+            values.push(self.codegen_expr(arg, func, true));
+        }
+
+        func.function_call(format!("{}.{}", struct_name, method_name), values)
+    }
+
     pub fn codegen_infix(
         &mut self,
         expr: ExpressionNode<'a>,
@@ -516,6 +986,9 @@ mod tests {
 
         codegen.codegen_function(Statement::Function {
             name: "test",
+            public: false,
+            attributes: vec![],
+            type_params: vec![],
             args: vec![(AzulaType::Bool, "x")],
             returns: AzulaType::Int,
             body: Rc::new(Statement::Block(vec![])),
@@ -530,6 +1003,143 @@ mod tests {
         assert_eq!(function.returns, AzulaType::Int);
     }
 
+    #[test]
+    fn test_codegen_unit_symbols() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut symbols = HashMap::new();
+        symbols.insert("add".to_string(), "math::add".to_string());
+
+        codegen.codegen_unit(
+            Statement::Root(vec![Statement::Function {
+                name: "add",
+                public: true,
+                attributes: vec![],
+                type_params: vec![],
+                args: vec![],
+                returns: AzulaType::Void,
+                body: Rc::new(Statement::Block(vec![Statement::ExpressionStatement(
+                    ExpressionNode {
+                        expression: Expression::FunctionCall {
+                            function: Rc::new(ExpressionNode {
+                                expression: Expression::Identifier("add".to_string()),
+                                typed: AzulaType::Void,
+                                span: Span { start: 0, end: 1 },
+                            }),
+                            args: vec![],
+                            type_args: vec![],
+                        },
+                        typed: AzulaType::Void,
+                        span: Span { start: 0, end: 1 },
+                    },
+                    Span { start: 0, end: 1 },
+                )])),
+                span: Span { start: 0, end: 1 },
+            }]),
+            symbols,
+        );
+
+        let function = codegen.module.functions.get("math::add").unwrap();
+        assert_eq!(
+            function.blocks[0].1.instructions,
+            vec![Instruction::FunctionCall(
+                "math::add".to_string(),
+                vec![],
+                0
+            )]
+        );
+    }
+
+    #[test]
+    fn test_codegen_instances() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let span = Span { start: 0, end: 1 };
+        let pair = AzulaType::Named("Pair<int, bool>".to_string());
+        let type_args = vec![AzulaType::Int, AzulaType::Bool];
+
+        let mut symbols = HashMap::new();
+        symbols.insert("first".to_string(), "util::first".to_string());
+        codegen.codegen_unit(
+            Statement::Root(vec![
+                Statement::Instance(
+                    type_args.clone(),
+                    Rc::new(Statement::Struct {
+                        name: "Pair",
+                        public: false,
+                        type_params: vec![],
+                        attributes: vec![(AzulaType::Int, "first"), (AzulaType::Bool, "second")],
+                        span: span.clone(),
+                    }),
+                ),
+                Statement::Instance(
+                    type_args.clone(),
+                    Rc::new(Statement::Function {
+                        name: "first",
+                        public: false,
+                        attributes: vec![],
+                        type_params: vec![],
+                        args: vec![(pair.clone(), "p")],
+                        returns: AzulaType::Int,
+                        body: Rc::new(Statement::Block(vec![])),
+                        span: span.clone(),
+                    }),
+                ),
+                // The generic declaration itself isn't emitted
+                Statement::Function {
+                    name: "first",
+                    public: false,
+                    attributes: vec![],
+                    type_params: vec!["A", "B"],
+                    args: vec![],
+                    returns: AzulaType::Named("A".to_string()),
+                    body: Rc::new(Statement::Block(vec![])),
+                    span: span.clone(),
+                },
+                Statement::Function {
+                    name: "main",
+                    public: false,
+                    attributes: vec![],
+                    type_params: vec![],
+                    args: vec![],
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(vec![Statement::ExpressionStatement(
+                        ExpressionNode {
+                            expression: Expression::FunctionCall {
+                                function: Rc::new(ExpressionNode {
+                                    expression: Expression::Identifier("first".to_string()),
+                                    typed: AzulaType::Int,
+                                    span: span.clone(),
+                                }),
+                                args: vec![],
+                                type_args: type_args.clone(),
+                            },
+                            typed: AzulaType::Int,
+                            span: span.clone(),
+                        },
+                        span.clone(),
+                    )])),
+                    span: span.clone(),
+                },
+            ]),
+            symbols,
+        );
+
+        assert_eq!(
+            codegen.module.structs["Pair<int, bool>"].attributes,
+            vec![(AzulaType::Int, "first"), (AzulaType::Bool, "second")]
+        );
+        let mut functions: Vec<_> = codegen.module.functions.keys().cloned().collect();
+        functions.sort();
+        assert_eq!(functions, vec!["main", "util::first<int, bool>"]);
+        assert_eq!(
+            codegen.module.functions["main"].blocks[0].1.instructions,
+            vec![Instruction::FunctionCall(
+                "util::first<int, bool>".to_string(),
+                vec![],
+                0
+            )]
+        );
+    }
+
     #[test]
     fn test_codegen_consts() {
         let mut codegen = Codegen::new("test", Statement::Root(vec![]));
@@ -596,6 +1206,7 @@ mod tests {
                     span: Span { start: 0, end: 0 },
                 },
                 vec![],
+                None,
                 Span { start: 0, end: 0 },
             ),
             &mut func,
@@ -604,6 +1215,306 @@ mod tests {
         assert_eq!(func.blocks.len(), 3);
     }
 
+    #[test]
+    fn test_codegen_for() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        func.variables.insert("i".to_string(), AzulaType::Bool);
+
+        let int = |val| ExpressionNode {
+            expression: Expression::Integer(val),
+            typed: AzulaType::Int,
+            span: Span { start: 0, end: 0 },
+        };
+        codegen.codegen_for(
+            Statement::For(
+                "i".to_string(),
+                ExpressionNode {
+                    expression: Expression::Range(Rc::new(int(0)), Rc::new(int(10))),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        let names: Vec<_> = func.blocks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["entry", "eval-0", "loop-0", "step-0", "end-0"]);
+        // The loop variable gets its own slot, leaving the outer `i` alone
+        assert_eq!(func.variables.get("i"), Some(&AzulaType::Bool));
+        assert_eq!(func.variables.get("__for_var0"), Some(&AzulaType::Int));
+        assert!(codegen.loop_variables.is_empty());
+        assert_eq!(
+            func.blocks[1].1.instructions,
+            vec![
+                Instruction::Load("__for_var0".to_string(), 2, AzulaType::Int),
+                Instruction::Load("__for_end0".to_string(), 3, AzulaType::Int),
+                Instruction::Lt(Value::Local(2), Value::Local(3), 4),
+                Instruction::Jcond(Value::Local(4), "loop-0".to_string(), "end-0".to_string()),
+            ]
+        );
+
+        // Arrays load each element into the loop variable
+        let mut func = Function::new();
+        let array = AzulaType::Array(Rc::new(AzulaType::Bool), Some(2));
+        func.variables.insert("arr".to_string(), array.clone());
+        codegen.codegen_for(
+            Statement::For(
+                "x".to_string(),
+                ExpressionNode {
+                    expression: Expression::Identifier("arr".to_string()),
+                    typed: array,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        assert_eq!(func.variables.get("__for_var0"), Some(&AzulaType::Bool));
+        assert!(matches!(
+            func.blocks[2].1.instructions[2],
+            Instruction::AccessElement(..)
+        ));
+    }
+
+    #[test]
+    fn test_codegen_loop_jump() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let span = Span { start: 0, end: 0 };
+        let condition = ExpressionNode {
+            expression: Expression::Boolean(true),
+            typed: AzulaType::Bool,
+            span: span.clone(),
+        };
+        codegen.codegen_while(
+            Statement::While(
+                condition.clone(),
+                vec![
+                    Statement::While(
+                        condition,
+                        vec![Statement::Break(span.clone())],
+                        span.clone(),
+                    ),
+                    Statement::Continue(span.clone()),
+                ],
+                span,
+            ),
+            &mut func,
+        );
+
+        let block = |name: &str| {
+            func.blocks
+                .iter()
+                .find(|(block, _)| block == name)
+                .map(|(_, block)| block.instructions.clone())
+                .unwrap()
+        };
+
+        // The inner break leaves the inner loop, the outer continue re-evaluates the outer one
+        assert_eq!(
+            block("loop-1"),
+            vec![Instruction::Jump("end-1".to_string())]
+        );
+        assert_eq!(
+            block("end-1").last(),
+            Some(&Instruction::Jump("eval-0".to_string()))
+        );
+        assert!(codegen.loop_targets.is_empty());
+    }
+
+    #[test]
+    fn test_codegen_after_return() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let span = Span { start: 0, end: 0 };
+        codegen.codegen_statement(Statement::Return(None, span.clone()), &mut func);
+        codegen.codegen_statement(
+            Statement::Assign(
+                false,
+                "x".to_string(),
+                None,
+                ExpressionNode {
+                    expression: Expression::Integer(1),
+                    typed: AzulaType::Int,
+                    span: span.clone(),
+                },
+                span,
+            ),
+            &mut func,
+        );
+
+        // The dead assignment can't follow the return in the same block
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![Instruction::Return(None)]
+        );
+        assert_eq!(func.current_block, "after-0");
+    }
+
+    #[test]
+    fn test_codegen_match() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.module.add_enum(
+            "Shape",
+            Enum {
+                name: "Shape",
+                variants: vec![
+                    ("Circle", vec![AzulaType::Int]),
+                    ("Rect", vec![AzulaType::Int, AzulaType::Int]),
+                    ("Empty", vec![]),
+                ],
+            },
+        );
+
+        let mut func = Function::new();
+        func.variables
+            .insert("s".to_string(), AzulaType::Named("Shape".to_string()));
+
+        let span = Span { start: 0, end: 0 };
+        let node = |expression, typed| ExpressionNode {
+            expression,
+            typed,
+            span: span.clone(),
+        };
+        let arm = |pattern, value| MatchArm {
+            pattern,
+            body: vec![],
+            value: Some(value),
+            span: span.clone(),
+        };
+
+        let value = codegen.codegen_match(
+            node(
+                Expression::Match(
+                    Rc::new(node(
+                        Expression::Identifier("s".to_string()),
+                        AzulaType::Named("Shape".to_string()),
+                    )),
+                    vec![
+                        arm(
+                            Pattern::Variant("Rect", vec!["_", "h"]),
+                            node(Expression::Identifier("h".to_string()), AzulaType::Int),
+                        ),
+                        arm(
+                            Pattern::Wildcard,
+                            node(Expression::Integer(0), AzulaType::Int),
+                        ),
+                    ],
+                ),
+                AzulaType::Int,
+            ),
+            &mut func,
+        );
+
+        let names: Vec<_> = func.blocks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["entry", "arm-0-0", "arm-0-1", "end-0"]);
+        assert_eq!(
+            func.blocks[0].1.instructions.last(),
+            Some(&Instruction::Switch(
+                Value::Local(1),
+                vec![(1, "arm-0-0".to_string())],
+                "arm-0-1".to_string()
+            ))
+        );
+
+        // Payload fields sit after the tag and every earlier variant's payload
+        assert_eq!(
+            func.blocks[1].1.instructions[0],
+            Instruction::AccessStructMember(Value::Local(0), 3, 2, true)
+        );
+        assert_eq!(func.variables.get("h"), Some(&AzulaType::Int));
+        assert_eq!(func.variables.get("_"), None);
+        assert_eq!(value, Some(Value::Local(5)));
+    }
+
+    #[test]
+    fn test_codegen_if_else() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        codegen.codegen_if(
+            Statement::If(
+                ExpressionNode {
+                    expression: Expression::Boolean(true),
+                    typed: AzulaType::Bool,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                Some(vec![]),
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        let names: Vec<_> = func.blocks.iter().map(|(name, _)| name.clone()).collect();
+        assert_eq!(names, vec!["entry", "true-0", "else-0", "end-0"]);
+        assert_eq!(
+            func.blocks[0].1.instructions.last(),
+            Some(&Instruction::Jcond(
+                Value::Local(0),
+                "true-0".to_string(),
+                "else-0".to_string()
+            ))
+        );
+        assert_eq!(
+            func.blocks[1].1.instructions,
+            vec![Instruction::Jump("end-0".to_string())]
+        );
+        assert_eq!(
+            func.blocks[2].1.instructions,
+            vec![Instruction::Jump("end-0".to_string())]
+        );
+
+        // Both branches return, so the end block is unreachable
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "test",
+            public: false,
+            attributes: vec![],
+            type_params: vec![],
+            args: vec![],
+            returns: AzulaType::Int,
+            body: Rc::new(Statement::Block(vec![Statement::If(
+                ExpressionNode {
+                    expression: Expression::Boolean(true),
+                    typed: AzulaType::Bool,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(1),
+                        typed: AzulaType::Int,
+                        span: Span { start: 0, end: 0 },
+                    }),
+                    Span { start: 0, end: 0 },
+                )],
+                Some(vec![Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(2),
+                        typed: AzulaType::Int,
+                        span: Span { start: 0, end: 0 },
+                    }),
+                    Span { start: 0, end: 0 },
+                )]),
+                Span { start: 0, end: 0 },
+            )])),
+            span: Span { start: 0, end: 1 },
+        });
+        codegen.insert_implicit_return();
+
+        let function = codegen.module.functions.get("test").unwrap();
+        let (name, end) = function.blocks.last().unwrap();
+        assert_eq!(name, "end-0");
+        assert_eq!(end.instructions, vec![Instruction::Unreachable]);
+    }
+
     #[test]
     fn test_codegen_infix() {
         let mut codegen = Codegen::new("test", Statement::Root(vec![]));
@@ -768,4 +1679,64 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_codegen_method_call() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let point = AzulaType::Named("Point".to_string());
+
+        codegen.codegen_method(
+            "Point",
+            Statement::Function {
+                name: "len",
+                public: false,
+                attributes: vec![],
+                type_params: vec![],
+                args: vec![(AzulaType::Pointer(Rc::new(point.clone())), "self")],
+                returns: AzulaType::Int,
+                body: Rc::new(Statement::Block(vec![])),
+                span: Span { start: 0, end: 1 },
+            },
+        );
+        assert!(codegen.module.functions.contains_key("Point.len"));
+
+        let mut func = Function::new();
+        func.variables.insert("p".to_string(), point.clone());
+        codegen.codegen_expr(
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(ExpressionNode {
+                        expression: Expression::StructAccess(
+                            Rc::new(ExpressionNode {
+                                expression: Expression::Identifier("p".to_string()),
+                                typed: point,
+                                span: Span { start: 0, end: 1 },
+                            }),
+                            Rc::new(ExpressionNode {
+                                expression: Expression::Identifier("len".to_string()),
+                                typed: AzulaType::Int,
+                                span: Span { start: 2, end: 5 },
+                            }),
+                        ),
+                        typed: AzulaType::Int,
+                        span: Span { start: 0, end: 5 },
+                    }),
+                    args: vec![],
+                    type_args: vec![],
+                },
+                typed: AzulaType::Int,
+                span: Span { start: 0, end: 7 },
+            },
+            &mut func,
+            true,
+        );
+
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::Pointer("p".to_string(), 0),
+                Instruction::FunctionCall("Point.len".to_string(), vec![Value::Local(0)], 1)
+            ]
+        );
+    }
 }
@@ -2,6 +2,8 @@ mod backend;
 mod codegen;
 
 pub mod prelude {
-    pub use crate::backend::{Backend, OptimizationLevel};
+    pub use crate::backend::{
+        Artifact, Backend, BackendError, BackendOptions, EmitKind, OptimizationLevel,
+    };
     pub use crate::codegen::Codegen;
 }
@@ -0,0 +1,1129 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{self, Command};
+
+use azula_codegen::prelude::{Backend, BackendError, BackendOptions, EmitKind, OptimizationLevel};
+use azula_ir::prelude::{Function, GlobalValue, Instruction, Module, Value};
+use azula_type::prelude::AzulaType;
+
+/// Functions the included C headers already declare, which externs must not
+/// redeclare with Azula's idea of their signature
+const HEADER_FUNCTIONS: &[&str] = &[
+    "printf", "puts", "putchar", "getchar", "scanf", "sprintf", "snprintf", "fflush", "malloc",
+    "calloc", "realloc", "free", "exit", "abort", "atoi", "rand", "srand", "abs", "pow", "sqrt",
+    "fmod", "floor", "ceil", "sin", "cos", "tan", "exp", "log",
+];
+
+/// `**` on integers, which C's `pow` would give as a double. Overflow wraps
+/// and negative exponents go through floats, as in the interpreter
+const INTEGER_POW: &str = "static int64_t azula_pow(int64_t base, int64_t exp) {
+    if (exp < 0) return (int64_t)pow((double)base, (double)exp);
+    uint64_t result = 1, factor = (uint64_t)base;
+    for (uint32_t e = (uint32_t)exp; e > 0; e >>= 1) {
+        if (e & 1) result *= factor;
+        factor *= factor;
+    }
+    return (int64_t)result;
+}
+";
+
+/// Translates a module into C99, so programs can be built with whatever C
+/// compiler a platform has. Objects and assembly are made by handing the
+/// source to the host's C compiler (`$CC`, or `cc`), or `zig cc` when cross
+/// compiling
+pub struct CCodegen {
+    options: BackendOptions,
+    // The module's name and the C source it was lowered to
+    lowered: Option<(String, String)>,
+}
+
+impl CCodegen {
+    pub fn new(options: BackendOptions) -> Self {
+        CCodegen {
+            options,
+            lowered: None,
+        }
+    }
+
+    fn compiler(&self) -> Command {
+        match &self.options.target {
+            Some(target) => {
+                let mut command = Command::new("zig");
+                command.arg("cc").arg("-target").arg(target);
+                command
+            }
+            None => Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string())),
+        }
+    }
+}
+
+impl<'a> Backend<'a> for CCodegen {
+    fn lower(&mut self, module: &Module<'a>) -> Result<(), BackendError> {
+        let source = Generator::generate(module).map_err(BackendError::Unsupported)?;
+        self.lowered = Some((module.name.to_string(), source));
+        Ok(())
+    }
+
+    fn emit(&self, kind: EmitKind, output: &mut dyn Write) -> Result<(), BackendError> {
+        let (name, source) = self.lowered.as_ref().ok_or(BackendError::NotLowered)?;
+        if kind == EmitKind::Ir {
+            output.write_all(source.as_bytes())?;
+            return Ok(());
+        }
+
+        // The compiler only reads and writes files, so go through a scratch
+        // directory
+        let dir = env::temp_dir().join(format!("azula-c-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        let source_file = dir.join(format!("{}.c", name));
+        let output_file = dir.join(format!("{}.{}", name, self.extension(kind)));
+        fs::write(&source_file, source)?;
+
+        let mut command = self.compiler();
+        command
+            .arg(if kind == EmitKind::Object { "-c" } else { "-S" })
+            .arg("-std=c99")
+            // Azula integers wrap on overflow rather than being undefined
+            .arg("-fwrapv")
+            .arg(match self.options.opt_level {
+                OptimizationLevel::None => "-O0",
+                OptimizationLevel::Default => "-O2",
+                OptimizationLevel::Aggressive => "-O3",
+            })
+            .arg("-o")
+            .arg(&output_file)
+            .arg(&source_file);
+
+        let compiler = format!("{:?}", command.get_program());
+        let result = command.output();
+        let compiled = fs::read(&output_file);
+        fs::remove_dir_all(&dir)?;
+
+        let result = result.map_err(|e| {
+            BackendError::Tool(format!("Could not run C compiler {}: {}", compiler, e))
+        })?;
+        if !result.status.success() {
+            return Err(BackendError::Tool(format!(
+                "C compiler {} failed:\n{}",
+                compiler,
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        output.write_all(&compiled?)?;
+        Ok(())
+    }
+
+    fn extension(&self, kind: EmitKind) -> &'static str {
+        match kind {
+            EmitKind::Object => "o",
+            EmitKind::Assembly => "s",
+            EmitKind::Ir => "c",
+        }
+    }
+}
+
+/// Writes out the C for a module. Every register becomes a local declared at
+/// the top of its function and every block a label, with terminators as gotos
+struct Generator<'m, 'a> {
+    module: &'m Module<'a>,
+    output: String,
+}
+
+impl<'m, 'a> Generator<'m, 'a> {
+    fn generate(module: &'m Module<'a>) -> Result<String, String> {
+        let mut codegen = Generator {
+            module,
+            output: String::new(),
+        };
+
+        codegen.line(&format!("/* Generated by azula from {} */", module.name));
+        for header in ["stdbool.h", "stdint.h", "stdio.h", "stdlib.h", "math.h"] {
+            codegen.line(&format!("#include <{}>", header));
+        }
+        codegen.line("");
+        codegen.line(INTEGER_POW);
+
+        codegen.generate_types()?;
+        codegen.generate_globals()?;
+        codegen.generate_declarations()?;
+
+        for (name, function) in &module.functions {
+            codegen.generate_function(name, function)?;
+        }
+
+        if let Some(main) = module.functions.get("main") {
+            codegen.line("int main(void) {");
+            if main.returns == AzulaType::Void {
+                codegen.line(&format!("    {}();", function_name("main")));
+                codegen.line("    return 0;");
+            } else {
+                codegen.line(&format!("    return (int){}();", function_name("main")));
+            }
+            codegen.line("}");
+        }
+
+        Ok(codegen.output)
+    }
+
+    fn line(&mut self, line: &str) {
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn generate_types(&mut self) -> Result<(), String> {
+        let mut fields = BTreeMap::new();
+        for (name, struc) in &self.module.structs {
+            let types: Vec<_> = struc
+                .attributes
+                .iter()
+                .map(|(typ, _)| typ.clone())
+                .collect();
+            fields.insert(name.as_str(), types);
+        }
+        for (name, enu) in &self.module.enums {
+            fields.insert(*name, enu.fields());
+        }
+
+        for name in fields.keys() {
+            let name = type_name(name);
+            self.line(&format!("typedef struct {} {};", name, name));
+        }
+        self.line("");
+
+        // A struct holding another by value needs it defined first
+        let mut order = vec![];
+        let mut visiting = vec![];
+        for name in fields.keys() {
+            sort_types(name, &fields, &mut visiting, &mut order)?;
+        }
+
+        for name in order {
+            self.line(&format!("struct {} {{", type_name(name)));
+            if fields[name].is_empty() {
+                // C doesn't allow empty structs
+                self.line("    char empty;");
+            }
+            for (index, typ) in fields[name].iter().enumerate() {
+                self.line(&format!(
+                    "    {};",
+                    declaration(typ, &format!("f{}", index))?
+                ));
+            }
+            self.line("};");
+        }
+        self.line("");
+
+        Ok(())
+    }
+
+    fn generate_globals(&mut self) -> Result<(), String> {
+        for (index, string) in self.module.strings.iter().enumerate() {
+            self.line(&format!(
+                "static char s{}[] = \"{}\";",
+                index,
+                escape(string)
+            ));
+        }
+
+        for (name, value) in &self.module.global_values {
+            let global = global_name(name);
+            match value {
+                GlobalValue::Array(values) => {
+                    let typ = match values.first() {
+                        Some(value) => global_type(value)?,
+                        None => AzulaType::Int,
+                    };
+                    let values = values
+                        .iter()
+                        .map(global_value)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let data = format!("{}_data[]", global);
+                    self.line(&format!(
+                        "static {} = {{{}}};",
+                        declaration(&typ, &data)?,
+                        values.join(", ")
+                    ));
+                    self.line(&format!(
+                        "static {} = {}_data;",
+                        declaration(&AzulaType::Array(typ.into(), None), &global)?,
+                        global
+                    ));
+                }
+                _ => self.line(&format!(
+                    "static {} = {};",
+                    declaration(&global_type(value)?, &global)?,
+                    global_value(value)?
+                )),
+            }
+        }
+        self.line("");
+
+        Ok(())
+    }
+
+    fn generate_declarations(&mut self) -> Result<(), String> {
+        for (name, extern_function) in &self.module.extern_functions {
+            if HEADER_FUNCTIONS.contains(name) {
+                continue;
+            }
+
+            let mut arguments = extern_function
+                .arguments
+                .iter()
+                .map(c_type)
+                .collect::<Result<Vec<_>, _>>()?;
+            if extern_function.varargs && !arguments.is_empty() {
+                arguments.push("...".to_string());
+            }
+            self.line(&format!(
+                "{} {}({});",
+                c_type(&extern_function.returns)?,
+                name,
+                arguments.join(", ")
+            ));
+        }
+
+        for (name, function) in &self.module.functions {
+            let signature = signature(name, function)?;
+            self.line(&format!("{};", signature));
+        }
+        self.line("");
+
+        Ok(())
+    }
+
+    fn generate_function(&mut self, name: &str, function: &Function<'a>) -> Result<(), String> {
+        let registers = self.register_types(function).map_err(|register| {
+            format!("{}: could not work out the type of %{}", name, register)
+        })?;
+
+        self.line(&format!("{} {{", signature(name, function)?));
+
+        let mut variables = function.variables.clone();
+        for (_, block) in &function.blocks {
+            for instruction in &block.instructions {
+                if let Instruction::Store(name, _, typ) | Instruction::Load(name, _, typ) =
+                    instruction
+                {
+                    variables.entry(name.clone()).or_insert_with(|| typ.clone());
+                }
+            }
+        }
+        for (name, typ) in &variables {
+            self.line(&format!("    {};", declaration(typ, &variable_name(name))?));
+        }
+        for (register, typ) in &registers {
+            if *typ != AzulaType::Void {
+                self.line(&format!(
+                    "    {};",
+                    declaration(typ, &format!("r{}", register))?
+                ));
+            }
+        }
+
+        for (index, (block, instructions)) in function.blocks.iter().enumerate() {
+            // The entry block is fallen into, so only needs a label if
+            // something jumps back to it
+            if index > 0 || jumped_to(function, block) {
+                self.line(&format!("{}:;", label(block)));
+            }
+            for instruction in &instructions.instructions {
+                let statement = self.instruction(instruction, &registers)?;
+                self.line(&format!("    {}", statement));
+            }
+        }
+
+        self.line("}");
+        self.line("");
+
+        Ok(())
+    }
+
+    fn instruction(
+        &self,
+        instruction: &Instruction<'a>,
+        registers: &BTreeMap<usize, AzulaType<'a>>,
+    ) -> Result<String, String> {
+        let register = |dest: &usize| format!("r{}", dest);
+
+        let statement = match instruction {
+            Instruction::Load(name, dest, _) => {
+                format!("{} = {};", register(dest), variable_name(name))
+            }
+            Instruction::LoadGlobal(name, dest, _) => {
+                format!("{} = {};", register(dest), global_name(name))
+            }
+            Instruction::LoadArg(arg, dest, _) => format!("{} = a{};", register(dest), arg),
+            Instruction::Store(name, val, _) => {
+                format!("{} = {};", variable_name(name), value(val))
+            }
+            Instruction::ConstInt(val, dest) => {
+                format!("{} = {};", register(dest), integer(*val))
+            }
+            Instruction::ConstFloat(val, dest) => {
+                format!("{} = {};", register(dest), float(*val))
+            }
+            Instruction::ConstTrue(dest) => format!("{} = true;", register(dest)),
+            Instruction::ConstFalse(dest) => format!("{} = false;", register(dest)),
+            Instruction::ConstNull(dest) => format!("{} = NULL;", register(dest)),
+            Instruction::Mod(val1, val2, dest) if is_float(&value_type(val1, registers)) => {
+                format!(
+                    "{} = fmod({}, {});",
+                    register(dest),
+                    value(val1),
+                    value(val2)
+                )
+            }
+            Instruction::Pow(val1, val2, dest) if is_float(&value_type(val1, registers)) => {
+                format!(
+                    "{} = pow({}, {});",
+                    register(dest),
+                    value(val1),
+                    value(val2)
+                )
+            }
+            Instruction::Pow(val1, val2, dest) => {
+                format!(
+                    "{} = azula_pow({}, {});",
+                    register(dest),
+                    value(val1),
+                    value(val2)
+                )
+            }
+            Instruction::Add(val1, val2, dest)
+            | Instruction::Sub(val1, val2, dest)
+            | Instruction::Mul(val1, val2, dest)
+            | Instruction::Div(val1, val2, dest)
+            | Instruction::Mod(val1, val2, dest)
+            | Instruction::Or(val1, val2, dest)
+            | Instruction::And(val1, val2, dest)
+            | Instruction::Eq(val1, val2, dest)
+            | Instruction::Neq(val1, val2, dest)
+            | Instruction::Gt(val1, val2, dest)
+            | Instruction::Gte(val1, val2, dest)
+            | Instruction::Lt(val1, val2, dest)
+            | Instruction::Lte(val1, val2, dest) => {
+                let operator = match instruction {
+                    Instruction::Add(..) => "+",
+                    Instruction::Sub(..) => "-",
+                    Instruction::Mul(..) => "*",
+                    Instruction::Div(..) => "/",
+                    Instruction::Mod(..) => "%",
+                    Instruction::Or(..) => "||",
+                    Instruction::And(..) => "&&",
+                    Instruction::Eq(..) => "==",
+                    Instruction::Neq(..) => "!=",
+                    Instruction::Gt(..) => ">",
+                    Instruction::Gte(..) => ">=",
+                    Instruction::Lt(..) => "<",
+                    _ => "<=",
+                };
+                format!(
+                    "{} = {} {} {};",
+                    register(dest),
+                    value(val1),
+                    operator,
+                    value(val2)
+                )
+            }
+            Instruction::Not(val, dest) => format!("{} = !{};", register(dest), value(val)),
+            Instruction::Return(Some(val)) => format!("return {};", value(val)),
+            Instruction::Return(None) => "return;".to_string(),
+            Instruction::FunctionCall(name, args, dest) => {
+                let callee = if self.module.functions.contains_key(name) {
+                    function_name(name)
+                } else {
+                    name.clone()
+                };
+                let args: Vec<_> = args.iter().map(value).collect();
+                let call = format!("{}({});", callee, args.join(", "));
+                match registers.get(dest) {
+                    Some(AzulaType::Void) | None => call,
+                    Some(_) => format!("{} = {}", register(dest), call),
+                }
+            }
+            Instruction::Jcond(cond, true_block, false_block) => format!(
+                "if ({}) goto {}; else goto {};",
+                value(cond),
+                label(true_block),
+                label(false_block)
+            ),
+            Instruction::Jump(block) => format!("goto {};", label(block)),
+            Instruction::Switch(val, cases, default) => {
+                let mut statement = format!("switch ({}) {{ ", value(val));
+                for (case, block) in cases {
+                    statement.push_str(&format!(
+                        "case {}: goto {}; ",
+                        integer(*case),
+                        label(block)
+                    ));
+                }
+                statement.push_str(&format!("default: goto {}; }}", label(default)));
+                statement
+            }
+            Instruction::Unreachable => "abort();".to_string(),
+            Instruction::Pointer(name, dest) => {
+                format!("{} = &{};", register(dest), variable_name(name))
+            }
+            // Arrays live on the heap like they do in the other backends, so
+            // they can be passed around and returned without copying
+            Instruction::CreateArray(typ, size, dest) => format!(
+                "{} = calloc({}, sizeof({}));",
+                register(dest),
+                size,
+                c_type(typ)?
+            ),
+            Instruction::StoreElement(array, index, val) => {
+                format!("{}[{}] = {};", value(array), value(index), value(val))
+            }
+            Instruction::AccessElement(array, index, dest) => {
+                format!("{} = {}[{}];", register(dest), value(array), value(index))
+            }
+            Instruction::CreateStruct(name, vals, dest) => {
+                let vals: Vec<_> = vals.iter().map(value).collect();
+                format!(
+                    "{} = ({}){{{}}};",
+                    register(dest),
+                    type_name(name),
+                    if vals.is_empty() {
+                        "0".to_string()
+                    } else {
+                        vals.join(", ")
+                    }
+                )
+            }
+            Instruction::CreateEnum(name, tag, vals, dest) => {
+                let enu = match self.module.enums.get(name.as_str()) {
+                    Some(enu) => enu,
+                    None => return Err(format!("unknown enum {}", name)),
+                };
+                let mut fields = vec![format!(".f0 = {}", tag)];
+                for (field, val) in vals.iter().enumerate() {
+                    fields.push(format!(
+                        ".f{} = {}",
+                        enu.field_index(*tag, field),
+                        value(val)
+                    ));
+                }
+                format!(
+                    "{} = ({}){{{}}};",
+                    register(dest),
+                    type_name(name),
+                    fields.join(", ")
+                )
+            }
+            Instruction::StoreStructMember(struc, index, val) => {
+                format!("{} = {};", member(struc, *index, registers), value(val))
+            }
+            Instruction::AccessStructMember(struc, index, dest, resolve) => {
+                let member = member(struc, *index, registers);
+                if !resolve && is_pointer(&value_type(struc, registers)) {
+                    format!("{} = &{};", register(dest), member)
+                } else {
+                    format!("{} = {};", register(dest), member)
+                }
+            }
+        };
+
+        Ok(statement)
+    }
+
+    /// Works out the type of every register from the instruction defining
+    /// it, going round until nothing new is learnt since blocks aren't in
+    /// the order they run. Fails with a register no type could be found for
+    fn register_types(
+        &self,
+        function: &Function<'a>,
+    ) -> Result<BTreeMap<usize, AzulaType<'a>>, usize> {
+        let mut types = BTreeMap::new();
+
+        loop {
+            let mut changed = false;
+            for (_, block) in &function.blocks {
+                for instruction in &block.instructions {
+                    let dest = match instruction.dest() {
+                        Some(dest) if !types.contains_key(&dest) => dest,
+                        _ => continue,
+                    };
+                    if let Some(typ) = self.result_type(instruction, function, &types) {
+                        types.insert(dest, typ);
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        for (_, block) in &function.blocks {
+            for instruction in &block.instructions {
+                if let Some(dest) = instruction.dest() {
+                    if !types.contains_key(&dest) {
+                        return Err(dest);
+                    }
+                }
+            }
+        }
+
+        Ok(types)
+    }
+
+    fn result_type(
+        &self,
+        instruction: &Instruction<'a>,
+        function: &Function<'a>,
+        types: &BTreeMap<usize, AzulaType<'a>>,
+    ) -> Option<AzulaType<'a>> {
+        let typ = match instruction {
+            Instruction::Load(_, _, typ)
+            | Instruction::LoadGlobal(_, _, typ)
+            | Instruction::LoadArg(_, _, typ) => typ.clone(),
+            Instruction::ConstInt(..) => AzulaType::Int,
+            Instruction::ConstFloat(..) => AzulaType::Float,
+            Instruction::ConstTrue(..)
+            | Instruction::ConstFalse(..)
+            | Instruction::Or(..)
+            | Instruction::And(..)
+            | Instruction::Eq(..)
+            | Instruction::Neq(..)
+            | Instruction::Gt(..)
+            | Instruction::Gte(..)
+            | Instruction::Lt(..)
+            | Instruction::Lte(..)
+            | Instruction::Not(..) => AzulaType::Bool,
+            Instruction::ConstNull(..) => AzulaType::Pointer(AzulaType::Void.into()),
+            Instruction::Add(val1, val2, _)
+            | Instruction::Sub(val1, val2, _)
+            | Instruction::Mul(val1, val2, _)
+            | Instruction::Div(val1, val2, _)
+            | Instruction::Mod(val1, val2, _)
+            | Instruction::Pow(val1, val2, _) => match (val1, val2) {
+                (Value::Local(_), _) => value_type(val1, types)?,
+                _ => value_type(val2, types)?,
+            },
+            Instruction::FunctionCall(name, _, _) => match self.module.functions.get(name) {
+                Some(function) => function.returns.clone(),
+                None => self
+                    .module
+                    .extern_functions
+                    .get(name.as_str())?
+                    .returns
+                    .clone(),
+            },
+            Instruction::Pointer(name, _) => {
+                AzulaType::Pointer(function.variables.get(name)?.clone().into())
+            }
+            Instruction::CreateArray(typ, _, _) => AzulaType::Array(typ.clone().into(), None),
+            Instruction::AccessElement(array, _, _) => match value_type(array, types)? {
+                AzulaType::Array(typ, _) | AzulaType::Pointer(typ) => (*typ).clone(),
+                _ => return None,
+            },
+            Instruction::CreateStruct(name, ..) | Instruction::CreateEnum(name, ..) => {
+                AzulaType::Named(name.clone())
+            }
+            Instruction::AccessStructMember(struc, index, _, resolve) => {
+                match value_type(struc, types)? {
+                    AzulaType::Named(name) => self.field_type(&name, *index)?,
+                    AzulaType::Pointer(typ) => match &*typ {
+                        AzulaType::Named(name) if *resolve => self.field_type(name, *index)?,
+                        AzulaType::Named(name) => {
+                            AzulaType::Pointer(self.field_type(name, *index)?.into())
+                        }
+                        _ => return None,
+                    },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(typ)
+    }
+
+    fn field_type(&self, name: &str, index: usize) -> Option<AzulaType<'a>> {
+        match self.module.structs.get(name) {
+            Some(struc) => struc.attributes.get(index).map(|(typ, _)| typ.clone()),
+            None => self.module.enums.get(name)?.fields().get(index).cloned(),
+        }
+    }
+}
+
+/// Orders structs so each comes after the ones it holds by value
+fn sort_types<'s>(
+    name: &'s str,
+    fields: &BTreeMap<&'s str, Vec<AzulaType>>,
+    visiting: &mut Vec<&'s str>,
+    order: &mut Vec<&'s str>,
+) -> Result<(), String> {
+    if order.contains(&name) {
+        return Ok(());
+    }
+    if visiting.contains(&name) {
+        return Err(format!("struct {} contains itself", name));
+    }
+
+    visiting.push(name);
+    for typ in &fields[name] {
+        if let AzulaType::Named(field) = typ {
+            if let Some((field, _)) = fields.get_key_value(field.as_str()) {
+                sort_types(field, fields, visiting, order)?;
+            }
+        }
+    }
+    visiting.pop();
+    order.push(name);
+
+    Ok(())
+}
+
+fn signature(name: &str, function: &Function) -> Result<String, String> {
+    let arguments = function
+        .arguments
+        .iter()
+        .enumerate()
+        .map(|(index, (_, typ))| declaration(typ, &format!("a{}", index)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(format!(
+        "static {} {}({})",
+        c_type(&function.returns)?,
+        function_name(name),
+        if arguments.is_empty() {
+            "void".to_string()
+        } else {
+            arguments.join(", ")
+        }
+    ))
+}
+
+fn jumped_to(function: &Function, block: &str) -> bool {
+    function.blocks.iter().any(|(_, instructions)| {
+        instructions
+            .instructions
+            .iter()
+            .any(|instruction| instruction.targets().contains(&block))
+    })
+}
+
+fn c_type(typ: &AzulaType) -> Result<String, String> {
+    let typ = match typ {
+        AzulaType::Int | AzulaType::SizedSignedInt(64) => "int64_t".to_string(),
+        AzulaType::SizedSignedInt(size @ (8 | 16 | 32)) => format!("int{}_t", size),
+        AzulaType::SizedUnsignedInt(size @ (8 | 16 | 32 | 64)) => format!("uint{}_t", size),
+        AzulaType::Str => "char".to_string(),
+        AzulaType::Float | AzulaType::SizedFloat(64) => "double".to_string(),
+        // C has no half floats, so they get the next size up
+        AzulaType::SizedFloat(16 | 32) => "float".to_string(),
+        AzulaType::Bool => "bool".to_string(),
+        AzulaType::Void => "void".to_string(),
+        AzulaType::Pointer(typ) | AzulaType::Array(typ, _) => format!("{}*", c_type(typ)?),
+        AzulaType::Named(name) => type_name(name),
+        _ => return Err(format!("type {} has no C equivalent", typ)),
+    };
+
+    Ok(typ)
+}
+
+fn declaration(typ: &AzulaType, name: &str) -> Result<String, String> {
+    Ok(format!("{} {}", c_type(typ)?, name))
+}
+
+fn global_type<'a>(value: &GlobalValue) -> Result<AzulaType<'a>, String> {
+    match value {
+        GlobalValue::Int(_) => Ok(AzulaType::Int),
+        GlobalValue::Float(_) => Ok(AzulaType::Float),
+        GlobalValue::Bool(_) => Ok(AzulaType::Bool),
+        GlobalValue::String(_) => Ok(AzulaType::Pointer(AzulaType::Str.into())),
+        GlobalValue::Array(_) => Err("nested global arrays are not supported".to_string()),
+    }
+}
+
+fn global_value(value: &GlobalValue) -> Result<String, String> {
+    match value {
+        GlobalValue::Int(val) => Ok(integer(*val)),
+        GlobalValue::Float(val) => Ok(float(*val)),
+        GlobalValue::Bool(val) => Ok(val.to_string()),
+        GlobalValue::String(index) => Ok(format!("s{}", index)),
+        GlobalValue::Array(_) => Err("nested global arrays are not supported".to_string()),
+    }
+}
+
+fn value(val: &Value) -> String {
+    match val {
+        Value::Local(register) => format!("r{}", register),
+        Value::Global(index) => format!("s{}", index),
+        Value::LiteralInteger(val) => integer(*val),
+        Value::LiteralBoolean(val) => val.to_string(),
+    }
+}
+
+fn value_type<'a>(val: &Value, types: &BTreeMap<usize, AzulaType<'a>>) -> Option<AzulaType<'a>> {
+    match val {
+        Value::Local(register) => types.get(register).cloned(),
+        Value::Global(_) => Some(AzulaType::Pointer(AzulaType::Str.into())),
+        Value::LiteralInteger(_) => Some(AzulaType::Int),
+        Value::LiteralBoolean(_) => Some(AzulaType::Bool),
+    }
+}
+
+/// A struct member, through a pointer when the struct is behind one
+fn member(struc: &Value, index: usize, types: &BTreeMap<usize, AzulaType>) -> String {
+    if is_pointer(&value_type(struc, types)) {
+        format!("{}->f{}", value(struc), index)
+    } else {
+        format!("{}.f{}", value(struc), index)
+    }
+}
+
+fn is_pointer(typ: &Option<AzulaType>) -> bool {
+    matches!(typ, Some(AzulaType::Pointer(..)))
+}
+
+fn is_float(typ: &Option<AzulaType>) -> bool {
+    matches!(typ, Some(AzulaType::Float | AzulaType::SizedFloat(..)))
+}
+
+fn integer(val: i64) -> String {
+    // The minimum can't be written as a literal, as it's the negation of one
+    // too big to fit
+    if val == i64::MIN {
+        "INT64_MIN".to_string()
+    } else {
+        format!("INT64_C({})", val)
+    }
+}
+
+fn float(val: f64) -> String {
+    if val.is_nan() {
+        "NAN".to_string()
+    } else if val == f64::INFINITY {
+        "INFINITY".to_string()
+    } else if val == f64::NEG_INFINITY {
+        "-INFINITY".to_string()
+    } else {
+        // Debug keeps the decimal point and uses exponents C understands
+        format!("{:?}", val)
+    }
+}
+
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for byte in string.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b'\n' => escaped.push_str("\\n"),
+            b'\t' => escaped.push_str("\\t"),
+            b'\r' => escaped.push_str("\\r"),
+            // Octal escapes are always three digits, so unlike hex ones they
+            // can't run into the characters after them
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Turns an Azula name into a C identifier. Names can contain characters
+/// like `:`, `.` and `<`, which are written as `_` and their hex code, with
+/// `_` itself doubled so no two names can end up the same
+fn identifier(prefix: &str, name: &str) -> String {
+    let mut identifier = prefix.to_string();
+    for c in name.chars() {
+        match c {
+            '_' => identifier.push_str("__"),
+            c if c.is_ascii_alphanumeric() => identifier.push(c),
+            c => identifier.push_str(&format!("_{:x}_", c as u32)),
+        }
+    }
+    identifier
+}
+
+fn function_name(name: &str) -> String {
+    identifier("f_", name)
+}
+
+fn type_name(name: &str) -> String {
+    identifier("t_", name)
+}
+
+fn global_name(name: &str) -> String {
+    identifier("g_", name)
+}
+
+fn variable_name(name: &str) -> String {
+    identifier("v_", name)
+}
+
+fn label(block: &str) -> String {
+    identifier("l_", block)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use azula_ir::prelude::parse_module;
+    use azula_resolver::prelude::Resolver;
+
+    use super::*;
+
+    fn generate(backend: &mut CCodegen, module: &Module) -> String {
+        backend.lower(module).unwrap();
+        let mut source = vec![];
+        backend.emit(EmitKind::Ir, &mut source).unwrap();
+        String::from_utf8(source).unwrap()
+    }
+
+    /// Compiles C source with the system compiler and runs it, giving its
+    /// output and exit code. None when there's no C compiler to use
+    fn compile_and_run(name: &str, source: &str) -> Option<(String, i32)> {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return None;
+        }
+
+        let dir = env::temp_dir().join("azula_codegen_c");
+        fs::create_dir_all(&dir).unwrap();
+        let source_file = dir.join(format!("{}.c", name));
+        let binary = dir.join(name);
+        fs::write(&source_file, source).unwrap();
+
+        let compiled = Command::new("cc")
+            .args(["-std=c99", "-fwrapv", "-Wall", "-Werror", "-Wno-unused"])
+            .arg("-o")
+            .arg(&binary)
+            .arg(&source_file)
+            .arg("-lm")
+            .output()
+            .unwrap();
+        assert!(
+            compiled.status.success(),
+            "{} failed to compile:\n{}\n{}",
+            name,
+            String::from_utf8_lossy(&compiled.stderr),
+            source
+        );
+
+        let output = Command::new(&binary).output().unwrap();
+        Some((
+            String::from_utf8(output.stdout).unwrap(),
+            output.status.code().unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_c_examples() {
+        let tests = vec![
+            ("factorial.azl", "3628800\n"),
+            ("loops.azl", "sum of 0..10 is 45\n2 is prime\n3 is prime\n5 is prime\n7 is prime\n11 is prime\n1 is odd\n3 is odd\n5 is odd\n7 is odd\n"),
+            ("methods.azl", "9\n"),
+            ("enums.azl", "area is 12\narea is 12\nempty\n"),
+            ("generics.azl", "7\n2.500000\n1\n"),
+            ("modules/main.azl", ""),
+        ];
+
+        let examples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        for (example, expected) in tests {
+            let mut resolver = Resolver::new();
+            let module = resolver
+                .resolve(examples.join(example).to_str().unwrap())
+                .unwrap();
+            let source = generate(&mut CCodegen::new(BackendOptions::default()), &module);
+
+            let name = example.replace(['/', '.'], "_");
+            let (output, _) = match compile_and_run(&name, &source) {
+                Some(result) => result,
+                None => return,
+            };
+            if !expected.is_empty() {
+                assert_eq!(output, expected, "{}", example);
+            }
+        }
+    }
+
+    #[test]
+    fn test_c_examples_deterministic() {
+        let examples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let compile = |example: &str| {
+            let module = Resolver::new()
+                .resolve(examples.join(example).to_str().unwrap())
+                .unwrap();
+            generate(&mut CCodegen::new(BackendOptions::default()), &module)
+        };
+
+        for example in [
+            "enums.azl",
+            "factorial.azl",
+            "fib.azl",
+            "fizzbuzz.azl",
+            "generics.azl",
+            "loops.azl",
+            "methods.azl",
+            "modules/main.azl",
+            "testing.azl",
+        ] {
+            assert_eq!(
+                compile(example),
+                compile(example),
+                "{} generated different C twice",
+                example
+            );
+        }
+    }
+
+    #[test]
+    fn test_c_module() {
+        let module = parse_module(
+            "Module: test
+Strings:
+\t0: \"%d %s\\n\"
+\t1: \"say \\\"hi\\\"\"
+Globals:
+\tLIMIT = 3
+Externs:
+\tprintf(&str, ...): void
+Structs:
+\tPoint { x: int, y: int }
+Enums:
+
+func main:
+\tArguments:
+\tReturns: int
+\tVariables:
+\t\tp: Point
+\tentry:
+\t\t%0: load_global @LIMIT int
+\t\t%1: const_int 4
+\t\t%2: create_struct Point [%0, %1]
+\t\tstore @p %2 Point
+\t\t%3: ptr @p
+\t\t%4: access_struct_member %3.1 false
+\t\t%5: access_struct_member %3.0 true
+\t\t%6: function_call @test::add [%5, %1]
+\t\tstore_struct_member %3.1 %6
+\t\t%7: access_struct_member %3.1 true
+\t\t%8: function_call @printf [$0, %7, $1]
+\t\t%9: const_float 7.5
+\t\t%10: const_float 2.0
+\t\t%11: mod %9 %10
+\t\t%12: gt %11 %10
+\t\tjcond %12 big small
+\tbig:
+\t\tret %7
+\tsmall:
+\t\tret %1
+func test::add:
+\tArguments:
+\t\ta: int
+\t\tb: int
+\tReturns: int
+\tVariables:
+\tentry:
+\t\t%0: load_arg 0 int
+\t\t%1: load_arg 1 int
+\t\t%2: add %0 %1
+\t\tret %2
+",
+        )
+        .unwrap();
+
+        let mut backend = CCodegen::new(BackendOptions::default());
+        let source = generate(&mut backend, &module);
+        assert!(source.contains("static char s1[] = \"say \\\"hi\\\"\";"));
+        assert!(source.contains("static int64_t g_LIMIT = INT64_C(3);"));
+        assert!(source.contains("static int64_t f_test_3a__3a_add(int64_t a0, int64_t a1)"));
+        assert!(source.contains("r4 = &r3->f1;"));
+        assert!(source.contains("r11 = fmod(r9, r10);"));
+
+        if let Some((output, code)) = compile_and_run("module", &source) {
+            assert_eq!(output, "7 say \"hi\"\n");
+            assert_eq!(code, 4);
+
+            // Going through an object file gives the same
+            let dir = env::temp_dir().join("azula_codegen_c");
+            let object = backend
+                .emit_to_path(EmitKind::Object, &dir.join("linked.o"))
+                .unwrap();
+            let status = Command::new("cc")
+                .arg("-o")
+                .arg(dir.join("linked"))
+                .arg(object.path)
+                .arg("-lm")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let linked = Command::new(dir.join("linked")).output().unwrap();
+            assert_eq!(String::from_utf8(linked.stdout).unwrap(), output);
+            assert_eq!(linked.status.code(), Some(4));
+        }
+    }
+
+    #[test]
+    fn test_c_sized_ints() {
+        // Wraps the same way the interpreter does
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/sized_ints.azir");
+        let ir = std::fs::read_to_string(path).unwrap();
+        let module = parse_module(&ir).unwrap();
+
+        let source = generate(&mut CCodegen::new(BackendOptions::default()), &module);
+        if let Some((output, _)) = compile_and_run("sized_ints", &source) {
+            assert_eq!(output, "-56 4 44\n");
+        }
+    }
+
+    #[test]
+    fn test_c_integer_pow() {
+        // Integer powers stay integers, rather than going through a double
+        let module = parse_module(
+            "Module: test
+Strings:
+\t0: \"%ld %ld\\n\"
+Globals:
+Externs:
+\tprintf(&str, ...): void
+Structs:
+Enums:
+
+func main:
+\tArguments:
+\tReturns: int
+\tVariables:
+\tentry:
+\t\t%0: const_int 3
+\t\t%1: pow %0 %0
+\t\t%2: const_int 63
+\t\t%3: const_int 2
+\t\t%4: pow %3 %2
+\t\t%5: function_call @printf [$0, %1, %4]
+\t\tret %1
+",
+        )
+        .unwrap();
+
+        let source = generate(&mut CCodegen::new(BackendOptions::default()), &module);
+        assert!(source.contains("r1 = azula_pow(r0, r0);"));
+        assert!(source.contains("int64_t r1;"));
+
+        if let Some((output, code)) = compile_and_run("pow", &source) {
+            assert_eq!(output, format!("27 {}\n", i64::MIN));
+            assert_eq!(code, 27);
+        }
+    }
+
+    #[test]
+    fn test_c_identifiers() {
+        assert_eq!(function_name("main"), "f_main");
+        assert_eq!(function_name("Point.len"), "f_Point_2e_len");
+        assert_eq!(
+            type_name("Pair<int, bool>"),
+            "t_Pair_3c_int_2c__20_bool_3e_"
+        );
+        // Underscores are doubled so they can't be mistaken for an escape
+        assert_ne!(variable_name("a_2e_b"), variable_name("a.b"));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod c;
+
+pub mod prelude {
+    pub use crate::c::CCodegen;
+}
@@ -2,4 +2,5 @@ pub mod llvm;
 
 pub mod prelude {
     pub use crate::llvm::LLVMCodegen;
+    pub use inkwell::context::Context;
 }
@@ -1,12 +1,9 @@
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::ops::Deref;
-use std::path::Path;
-use std::process::Command;
 
-use azula_codegen::prelude::Backend;
-use azula_codegen::prelude::OptimizationLevel;
-use azula_ir::prelude::{GlobalValue, Instruction, Module, Value};
+use azula_codegen::prelude::{Backend, BackendError, BackendOptions, EmitKind, OptimizationLevel};
+use azula_ir::prelude::{Enum, GlobalValue, Instruction, Module, Value};
 use azula_type::prelude::AzulaType;
 use inkwell::basic_block::BasicBlock;
 use inkwell::module::{Linkage, Module as LLVMModule};
@@ -22,61 +19,60 @@ pub struct LLVMCodegen<'ctx> {
     module: LLVMModule<'ctx>,
     builder: Builder<'ctx>,
 
-    strings: HashMap<usize, BasicValueEnum<'ctx>>,
-    string_size: HashMap<usize, usize>,
-    globals: HashMap<String, BasicValueEnum<'ctx>>,
-    structs: HashMap<String, StructType<'ctx>>,
+    strings: BTreeMap<usize, BasicValueEnum<'ctx>>,
+    string_size: BTreeMap<usize, usize>,
+    globals: BTreeMap<String, BasicValueEnum<'ctx>>,
+    structs: BTreeMap<String, StructType<'ctx>>,
+    enums: BTreeMap<String, Enum<'ctx>>,
 
-    target: Option<String>,
-    opt_level: OptimizationLevel,
+    options: BackendOptions,
+    lowered: bool,
 }
 
 struct FunctionLocals<'a> {
-    registers: HashMap<usize, BasicValueEnum<'a>>,
-    variables: HashMap<String, BasicValueEnum<'a>>,
+    registers: BTreeMap<usize, BasicValueEnum<'a>>,
+    variables: BTreeMap<String, BasicValueEnum<'a>>,
 
-    blocks: HashMap<String, BasicBlock<'a>>,
+    blocks: BTreeMap<String, BasicBlock<'a>>,
 }
 
-impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
-    fn codegen(
-        name: &'ctx str,
-        destination: &'ctx str,
-        emit: bool,
-        target: Option<&String>,
-        opt_level: OptimizationLevel,
-        module: Module<'ctx>,
-    ) -> Result<(), Box<dyn Error>> {
-        let context = Context::create();
-        let llvm_module = context.create_module(module.name);
-        let target = if let Some(val) = target {
-            Some(val.clone())
-        } else {
-            None
-        };
-        let mut codegen = LLVMCodegen {
-            context: &context,
-            module: llvm_module,
+impl<'ctx> LLVMCodegen<'ctx> {
+    pub fn new(context: &'ctx Context, options: BackendOptions) -> Self {
+        LLVMCodegen {
+            context,
+            module: context.create_module("azula"),
             builder: context.create_builder(),
-            strings: HashMap::new(),
-            string_size: HashMap::new(),
-            globals: HashMap::new(),
-            structs: HashMap::new(),
-            target,
-            opt_level,
-        };
+            strings: BTreeMap::new(),
+            string_size: BTreeMap::new(),
+            globals: BTreeMap::new(),
+            structs: BTreeMap::new(),
+            enums: BTreeMap::new(),
+            options,
+            lowered: false,
+        }
+    }
+}
+
+impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
+    fn lower(&mut self, module: &Module<'ctx>) -> Result<(), BackendError> {
+        self.module = self.context.create_module(module.name);
+        self.strings.clear();
+        self.string_size.clear();
+        self.globals.clear();
+        self.structs.clear();
+        self.enums.clear();
 
-        codegen.generate_structs(&module);
+        self.generate_structs(module);
 
         for (name, extern_func) in &module.extern_functions {
             let args: Vec<_> = extern_func
                 .arguments
                 .iter()
-                .map(|arg| codegen.azula_type_to_llvm_basic_type(arg.clone()).into())
+                .map(|arg| self.azula_type_to_llvm_basic_type(arg.clone()).into())
                 .collect();
-            codegen.module.add_function(
+            self.module.add_function(
                 name,
-                codegen.azula_type_to_function_llvm_type_with_varargs(
+                self.azula_type_to_function_llvm_type_with_varargs(
                     extern_func.returns.clone(),
                     &args,
                     extern_func.varargs,
@@ -85,12 +81,12 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
             );
         }
 
-        codegen.module.add_function(
+        self.module.add_function(
             "pow",
-            codegen.context.f64_type().fn_type(
+            self.context.f64_type().fn_type(
                 &[
-                    codegen.context.f64_type().as_basic_type_enum().into(),
-                    codegen.context.f64_type().as_basic_type_enum().into(),
+                    self.context.f64_type().as_basic_type_enum().into(),
+                    self.context.f64_type().as_basic_type_enum().into(),
                 ],
                 false,
             ),
@@ -104,14 +100,14 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
             if *name == "main" {
                 linkage = None;
             }
-            codegen.module.add_function(
+            self.module.add_function(
                 name,
-                codegen.azula_type_to_function_llvm_type(
+                self.azula_type_to_function_llvm_type(
                     func.returns.clone(),
                     &func
                         .arguments
                         .iter()
-                        .map(|(_, typ)| codegen.azula_type_to_llvm_basic_type(typ.clone()).into())
+                        .map(|(_, typ)| self.azula_type_to_llvm_basic_type(typ.clone()).into())
                         .collect::<Vec<_>>(),
                 ),
                 linkage,
@@ -120,59 +116,61 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
 
         for (name, func) in &module.functions {
             let mut locals = FunctionLocals::new();
-            let function = codegen.module.get_function(name).unwrap();
+            let function = self.module.get_function(name).ok_or_else(|| {
+                BackendError::Unsupported(format!("function {} was not declared", name))
+            })?;
 
             for (name, block) in &func.blocks {
                 let basic = if locals.blocks.contains_key(name) {
                     *locals.blocks.get(name).unwrap()
                 } else {
-                    codegen.context.append_basic_block(function, &name)
+                    self.context.append_basic_block(function, &name)
                 };
-                codegen.builder.position_at_end(basic);
+                self.builder.position_at_end(basic);
                 if i == 0 {
-                    codegen.store_globals(&module);
+                    self.store_globals(module);
                     i += 1;
                 }
                 for instruction in &block.instructions {
-                    codegen.codegen_instruction(instruction.clone(), &function, &mut locals);
+                    self.codegen_instruction(instruction.clone(), &function, &mut locals)?;
                 }
             }
         }
 
-        if emit {
-            codegen
-                .module
-                .print_to_file(format!("{}.ll", name))
-                .unwrap();
-        }
+        self.lowered = true;
+        Ok(())
+    }
 
-        let object_file = format!(".build/{}.o", name);
-        codegen.build_object_file(object_file.clone());
-
-        if let Some(target) = codegen.target {
-            Command::new("zig")
-                .arg("cc")
-                .arg(format!("-o{}{}", destination, name))
-                .arg(object_file)
-                .arg("-target")
-                .arg(target)
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-        } else {
-            Command::new("zig")
-                .arg("cc")
-                .arg(format!("-o{}{}", destination, name))
-                .arg(object_file)
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
+    fn emit(&self, kind: EmitKind, output: &mut dyn Write) -> Result<(), BackendError> {
+        if !self.lowered {
+            return Err(BackendError::NotLowered);
         }
 
+        let file_type = match kind {
+            EmitKind::Ir => {
+                output.write_all(self.module.print_to_string().to_string().as_bytes())?;
+                return Ok(());
+            }
+            EmitKind::Object => FileType::Object,
+            EmitKind::Assembly => FileType::Assembly,
+        };
+
+        let buffer = self
+            .create_machine()?
+            .write_to_memory_buffer(&self.module, file_type)
+            .map_err(|e| BackendError::Target(e.to_string()))?;
+        output.write_all(buffer.as_slice())?;
+
         Ok(())
     }
+
+    fn extension(&self, kind: EmitKind) -> &'static str {
+        match kind {
+            EmitKind::Object => "o",
+            EmitKind::Assembly => "s",
+            EmitKind::Ir => "ll",
+        }
+    }
 }
 
 impl<'a> LLVMCodegen<'a> {
@@ -251,6 +249,11 @@ impl<'a> LLVMCodegen<'a> {
             let struc = self.context.opaque_struct_type(i);
             self.structs.insert(i.to_string(), struc);
         }
+        for (i, enu) in &module.enums {
+            let struc = self.context.opaque_struct_type(i);
+            self.structs.insert(i.to_string(), struc);
+            self.enums.insert(i.to_string(), enu.clone());
+        }
 
         // Set the body of the structs
         for (i, str) in &module.structs {
@@ -265,6 +268,20 @@ impl<'a> LLVMCodegen<'a> {
                 .unwrap()
                 .set_body(&args, false);
         }
+
+        // Enums are a tag followed by the payload of every variant
+        for (i, enu) in &module.enums {
+            let args: Vec<_> = enu
+                .fields()
+                .into_iter()
+                .map(|arg| self.azula_type_to_llvm_basic_type(arg))
+                .collect();
+
+            self.structs
+                .get(&i.to_string())
+                .unwrap()
+                .set_body(&args, false);
+        }
     }
 
     fn codegen_instruction(
@@ -272,7 +289,7 @@ impl<'a> LLVMCodegen<'a> {
         instruction: Instruction<'a>,
         func: &FunctionValue<'a>,
         locals: &mut FunctionLocals<'a>,
-    ) {
+    ) -> Result<(), BackendError> {
         match instruction {
             Instruction::Load(name, dest, _) => {
                 let alloca = locals.variables.get(&name).unwrap();
@@ -292,8 +309,9 @@ impl<'a> LLVMCodegen<'a> {
             Instruction::Store(name, val, typ) => {
                 let value = match val {
                     Value::Local(val) => locals.load(val),
-                    Value::LiteralInteger(_) => todo!(),
-                    Value::LiteralBoolean(_) => todo!(),
+                    Value::LiteralInteger(_) | Value::LiteralBoolean(_) => {
+                        unreachable!("literal operands are rejected by the verifier")
+                    }
                     Value::Global(y) => {
                         // let alloca = self.builder.build_alloca(
                         //     azula_type_to_llvm_basic_type(self.context, typ),
@@ -403,8 +421,9 @@ impl<'a> LLVMCodegen<'a> {
                             self.strings.get(i).unwrap().as_basic_value_enum().into()
                         }
                         Value::Local(..) => locals.load(value_to_local(arg.clone())).into(),
-                        Value::LiteralInteger(_) => todo!(),
-                        Value::LiteralBoolean(_) => todo!(),
+                        Value::LiteralInteger(_) | Value::LiteralBoolean(_) => {
+                            unreachable!("literal operands are rejected by the verifier")
+                        }
                     })
                     .collect();
 
@@ -520,6 +539,32 @@ impl<'a> LLVMCodegen<'a> {
                     self.builder.position_at_end(jump_block);
                 }
             }
+            Instruction::Switch(val, cases, default) => {
+                let local = locals.load(value_to_local(val)).into_int_value();
+
+                let mut block = |name: String| {
+                    *locals
+                        .blocks
+                        .entry(name.clone())
+                        .or_insert_with(|| self.context.append_basic_block(*func, name.as_str()))
+                };
+
+                let default_block = block(default);
+                let cases: Vec<_> = cases
+                    .into_iter()
+                    .map(|(tag, name)| {
+                        (
+                            self.context.i64_type().const_int(tag as u64, false),
+                            block(name),
+                        )
+                    })
+                    .collect();
+
+                self.builder.build_switch(local, default_block, &cases);
+            }
+            Instruction::Unreachable => {
+                self.builder.build_unreachable();
+            }
             Instruction::Gt(val1, val2, dest) => {
                 let local1 = locals.load(value_to_local(val1));
                 let local2 = locals.load(value_to_local(val2));
@@ -714,8 +759,9 @@ impl<'a> LLVMCodegen<'a> {
             Instruction::StoreStructMember(struc, index, val) => {
                 let val = match val {
                     Value::Local(ptr) => locals.load(ptr),
-                    Value::LiteralInteger(_) => todo!(),
-                    Value::LiteralBoolean(_) => todo!(),
+                    Value::LiteralInteger(_) | Value::LiteralBoolean(_) => {
+                        unreachable!("literal operands are rejected by the verifier")
+                    }
                     Value::Global(v) => *self.strings.get(&v).unwrap(),
                 };
 
@@ -750,8 +796,9 @@ impl<'a> LLVMCodegen<'a> {
                     .iter()
                     .map(|val| match val {
                         Value::Local(ptr) => locals.load(*ptr),
-                        Value::LiteralInteger(_) => todo!(),
-                        Value::LiteralBoolean(_) => todo!(),
+                        Value::LiteralInteger(_) | Value::LiteralBoolean(_) => {
+                            unreachable!("literal operands are rejected by the verifier")
+                        }
                         Value::Global(v) => *self.strings.get(&v).unwrap(),
                     })
                     .collect();
@@ -772,6 +819,57 @@ impl<'a> LLVMCodegen<'a> {
 
                 locals.store(dest, agg.as_basic_value_enum());
             }
+            Instruction::CreateEnum(enu, tag, values, dest) => {
+                let struc = self.structs.get(&enu).ok_or_else(|| {
+                    BackendError::Unsupported(format!("enum {} was not declared", enu))
+                })?;
+                let layout = self.enums.get(&enu).ok_or_else(|| {
+                    BackendError::Unsupported(format!("enum {} has no layout", enu))
+                })?;
+
+                let mut agg = self
+                    .builder
+                    .build_insert_value(
+                        struc.const_named_struct(&[]),
+                        self.context.i64_type().const_int(tag as u64, false),
+                        0,
+                        "tag",
+                    )
+                    .ok_or_else(|| {
+                        BackendError::Unsupported(format!("could not set the tag of {}", enu))
+                    })?
+                    .into_struct_value();
+
+                for (field, val) in values.iter().enumerate() {
+                    let arg = match val {
+                        Value::Local(ptr) => locals.load(*ptr),
+                        Value::LiteralInteger(_) | Value::LiteralBoolean(_) => {
+                            unreachable!("literal operands are rejected by the verifier")
+                        }
+                        Value::Global(v) => *self.strings.get(v).ok_or_else(|| {
+                            BackendError::Unsupported(format!("unknown string ${}", v))
+                        })?,
+                    };
+
+                    agg = self
+                        .builder
+                        .build_insert_value(
+                            agg,
+                            arg,
+                            layout.field_index(tag, field) as u32,
+                            "insert",
+                        )
+                        .ok_or_else(|| {
+                            BackendError::Unsupported(format!(
+                                "could not set field {} of {}",
+                                field, enu
+                            ))
+                        })?
+                        .into_struct_value();
+                }
+
+                locals.store(dest, agg.as_basic_value_enum());
+            }
             Instruction::AccessStructMember(struc, index, dest, resolve) => {
                 let struc = locals.load(value_to_local(struc));
 
@@ -803,6 +901,8 @@ impl<'a> LLVMCodegen<'a> {
                 }
             }
         };
+
+        Ok(())
     }
 
     fn codegen_add(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
@@ -925,54 +1025,47 @@ impl<'a> LLVMCodegen<'a> {
         }
     }
 
-    fn build_object_file(&self, dest: String) {
-        let target_machine = self.create_machine(self.target.clone()).unwrap();
-
-        target_machine
-            .write_to_file(&self.module, FileType::Object, Path::new(&dest))
-            .unwrap();
-    }
-
-    fn create_machine(&self, name: Option<String>) -> Option<TargetMachine> {
-        if let Some(target) = name {
-            let triple = TargetTriple::create(&target);
+    fn create_machine(&self) -> Result<TargetMachine, BackendError> {
+        let opt_level = match self.options.opt_level {
+            OptimizationLevel::None => inkwell::OptimizationLevel::None,
+            OptimizationLevel::Default => inkwell::OptimizationLevel::Default,
+            OptimizationLevel::Aggressive => inkwell::OptimizationLevel::Aggressive,
+        };
 
-            self.module.set_triple(&triple);
-            Target::initialize_all(&InitializationConfig::default());
-            let target = Target::from_triple(&triple).unwrap();
-            let mut opt_level = inkwell::OptimizationLevel::Default;
-            if self.opt_level == OptimizationLevel::Aggressive {
-                opt_level = inkwell::OptimizationLevel::Aggressive;
+        let (triple, cpu, features) = match &self.options.target {
+            Some(target) => {
+                Target::initialize_all(&InitializationConfig::default());
+                (TargetTriple::create(target), String::new(), String::new())
+            }
+            None => {
+                Target::initialize_native(&InitializationConfig::default())
+                    .map_err(BackendError::Target)?;
+                (
+                    TargetMachine::get_default_triple(),
+                    TargetMachine::get_host_cpu_name().to_string(),
+                    TargetMachine::get_host_cpu_features().to_string(),
+                )
             }
-            return target.create_target_machine(
+        };
+
+        self.module.set_triple(&triple);
+        let target =
+            Target::from_triple(&triple).map_err(|e| BackendError::Target(e.to_string()))?;
+        target
+            .create_target_machine(
                 &triple,
-                "",
-                "",
+                &cpu,
+                &features,
                 opt_level,
                 inkwell::targets::RelocMode::Default,
                 inkwell::targets::CodeModel::Default,
-            );
-        }
-
-        let triple = TargetMachine::get_default_triple();
-        let cpu = TargetMachine::get_host_cpu_name().to_string();
-        let features = TargetMachine::get_host_cpu_features().to_string();
-
-        self.module.set_triple(&triple);
-        Target::initialize_native(&InitializationConfig::default()).unwrap();
-        let target = Target::from_triple(&triple).unwrap();
-        let mut opt_level = inkwell::OptimizationLevel::Default;
-        if self.opt_level == OptimizationLevel::Aggressive {
-            opt_level = inkwell::OptimizationLevel::Aggressive;
-        }
-        target.create_target_machine(
-            &triple,
-            &cpu,
-            &features,
-            opt_level,
-            inkwell::targets::RelocMode::Default,
-            inkwell::targets::CodeModel::Default,
-        )
+            )
+            .ok_or_else(|| {
+                BackendError::Target(format!(
+                    "could not create a machine for {}",
+                    self.options.target.as_deref().unwrap_or("the host")
+                ))
+            })
     }
 
     fn azula_type_to_llvm_basic_type(&self, t: AzulaType<'a>) -> BasicTypeEnum<'a> {
@@ -1008,6 +1101,8 @@ impl<'a> LLVMCodegen<'a> {
                 typ.ptr_type(AddressSpace::Generic).as_basic_type_enum()
             }
             AzulaType::Infer => unreachable!(),
+            AzulaType::Error => unreachable!(),
+            AzulaType::Generic(..) => unreachable!(),
             AzulaType::Named(name) => self
                 .structs
                 .get(&name.to_string())
@@ -1063,6 +1158,8 @@ impl<'a> LLVMCodegen<'a> {
                 typ.ptr_type(AddressSpace::Generic).fn_type(args, false)
             }
             AzulaType::Infer => todo!(),
+            AzulaType::Error => unreachable!(),
+            AzulaType::Generic(..) => unreachable!(),
             AzulaType::Named(name) => self
                 .structs
                 .get(&name.to_string())
@@ -1119,6 +1216,8 @@ impl<'a> LLVMCodegen<'a> {
                 typ.ptr_type(AddressSpace::Generic).fn_type(args, varargs)
             }
             AzulaType::Infer => todo!(),
+            AzulaType::Error => unreachable!(),
+            AzulaType::Generic(..) => unreachable!(),
             AzulaType::Named(name) => self
                 .structs
                 .get(&name.to_string())
@@ -1138,9 +1237,9 @@ impl<'a> LLVMCodegen<'a> {
 impl<'ctx> FunctionLocals<'ctx> {
     pub fn new() -> Self {
         Self {
-            registers: HashMap::new(),
-            variables: HashMap::new(),
-            blocks: HashMap::new(),
+            registers: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            blocks: BTreeMap::new(),
         }
     }
 
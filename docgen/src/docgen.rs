@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+
+use azula_ast::prelude::*;
+use azula_error::prelude::AzulaError;
+use azula_parser::prelude::{Lexer, Parser};
+use azula_type::prelude::AzulaType;
+
+/// What the reference is written as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// The documented items of a single file
+#[derive(Debug)]
+pub struct Page<'a> {
+    pub path: String,
+    pub items: Vec<Item<'a>>,
+}
+
+#[derive(Debug)]
+pub struct Item<'a> {
+    pub name: String,
+    pub docs: Option<String>,
+    pub kind: ItemKind<'a>,
+}
+
+#[derive(Debug)]
+pub enum ItemKind<'a> {
+    Function {
+        public: bool,
+        type_params: Vec<&'a str>,
+        args: Vec<TypedIdentifier<'a>>,
+        returns: AzulaType<'a>,
+    },
+    ExternFunction {
+        varargs: bool,
+        args: Vec<AzulaType<'a>>,
+        returns: AzulaType<'a>,
+    },
+    Struct {
+        public: bool,
+        type_params: Vec<&'a str>,
+        fields: Vec<TypedIdentifier<'a>>,
+        // The functions of its impl blocks in the same file
+        methods: Vec<Item<'a>>,
+    },
+    Enum {
+        public: bool,
+        variants: Vec<Variant<'a>>,
+    },
+    Const {
+        typ: Option<AzulaType<'a>>,
+        // The value as it's written in the source
+        value: &'a str,
+    },
+}
+
+/// Collects the functions, extern functions, structs, enums and global
+/// consts of a file along with their `///` doc comments, or gives the errors
+/// stopping it from parsing
+pub fn document<'a>(path: &str, source: &'a str) -> Result<Page<'a>, Vec<AzulaError>> {
+    let lexer: Lexer = source.into();
+    let mut parser = Parser::new(source, lexer);
+    let stmts = match parser.parse() {
+        Statement::Root(stmts) => stmts,
+        _ => unreachable!(),
+    };
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+
+    let docs = |stmt: &Statement| parser.docs.get(&stmt.span().start).cloned();
+    let mut items = vec![];
+    let mut methods: HashMap<&str, Vec<Item>> = HashMap::new();
+    for stmt in &stmts {
+        let kind = match stmt {
+            Statement::Function { .. } => function(stmt),
+            Statement::ExternFunction {
+                varargs,
+                args,
+                returns,
+                ..
+            } => ItemKind::ExternFunction {
+                varargs: *varargs,
+                args: args.clone(),
+                returns: returns.clone(),
+            },
+            Statement::Struct {
+                public,
+                type_params,
+                attributes,
+                ..
+            } => ItemKind::Struct {
+                public: *public,
+                type_params: type_params.clone(),
+                fields: attributes.clone(),
+                methods: vec![],
+            },
+            Statement::Enum {
+                public, variants, ..
+            } => ItemKind::Enum {
+                public: *public,
+                variants: variants.clone(),
+            },
+            Statement::Assign(false, _, typ, value, _) => ItemKind::Const {
+                typ: typ.clone(),
+                value: &source[value.span.start..value.span.end],
+            },
+            Statement::Impl {
+                name,
+                methods: functions,
+                ..
+            } => {
+                for method in functions {
+                    methods.entry(name).or_default().push(Item {
+                        name: statement_name(method),
+                        docs: docs(method),
+                        kind: function(method),
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        items.push(Item {
+            name: statement_name(stmt),
+            docs: docs(stmt),
+            kind,
+        });
+    }
+
+    for item in items.iter_mut() {
+        if let ItemKind::Struct { methods: found, .. } = &mut item.kind {
+            *found = methods.remove(item.name.as_str()).unwrap_or_default();
+        }
+    }
+
+    Ok(Page {
+        path: path.to_string(),
+        items,
+    })
+}
+
+fn function<'a>(stmt: &Statement<'a>) -> ItemKind<'a> {
+    match stmt {
+        Statement::Function {
+            public,
+            type_params,
+            args,
+            returns,
+            ..
+        } => ItemKind::Function {
+            public: *public,
+            type_params: type_params.clone(),
+            args: args.clone(),
+            returns: returns.clone(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn statement_name(stmt: &Statement) -> String {
+    match stmt {
+        Statement::Function { name, .. }
+        | Statement::ExternFunction { name, .. }
+        | Statement::Struct { name, .. }
+        | Statement::Enum { name, .. } => name.to_string(),
+        Statement::Assign(_, name, ..) => name.clone(),
+        _ => unreachable!(),
+    }
+}
+
+/// Writes the reference for some pages, giving the name and contents of each
+/// file: one per page, and an index linking to them all
+pub fn render(pages: &[Page], format: Format) -> Vec<(String, String)> {
+    let files = page_files(pages, format);
+
+    // Where each struct and enum is documented, so types can link to them
+    let mut types = HashMap::new();
+    for (page, file) in pages.iter().zip(&files) {
+        for item in &page.items {
+            if let ItemKind::Struct { .. } | ItemKind::Enum { .. } = item.kind {
+                types.insert(item.name.clone(), format!("{}#{}", file, anchor(item)));
+            }
+        }
+    }
+
+    let renderer = Renderer { format, types };
+    let mut out = vec![(
+        format!("index.{}", format.extension()),
+        renderer.index(pages, &files),
+    )];
+    for (page, file) in pages.iter().zip(&files) {
+        out.push((file.clone(), renderer.page(page)));
+    }
+    out
+}
+
+/// The file each page is written to, named after the file it documents
+fn page_files(pages: &[Page], format: Format) -> Vec<String> {
+    let mut files: Vec<String> = vec![];
+    for page in pages {
+        let stem = page
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&page.path)
+            .trim_end_matches(".azl");
+
+        // Files with the same name in different directories
+        let mut file = format!("{}.{}", stem, format.extension());
+        let mut count = 1;
+        while files.contains(&file) || file == format!("index.{}", format.extension()) {
+            count += 1;
+            file = format!("{}-{}.{}", stem, count, format.extension());
+        }
+        files.push(file);
+    }
+    files
+}
+
+fn anchor(item: &Item) -> String {
+    let prefix = match item.kind {
+        ItemKind::Function { .. } => "func",
+        ItemKind::ExternFunction { .. } => "extern",
+        ItemKind::Struct { .. } => "struct",
+        ItemKind::Enum { .. } => "enum",
+        ItemKind::Const { .. } => "const",
+    };
+    format!("{}.{}", prefix, item.name)
+}
+
+struct Renderer {
+    format: Format,
+    /// The link to each struct and enum
+    types: HashMap<String, String>,
+}
+
+impl Renderer {
+    /// Lists every page along with the items on it
+    fn index(&self, pages: &[Page], files: &[String]) -> String {
+        let mut out = String::new();
+        match self.format {
+            Format::Html => {
+                out += "<h1>Documentation</h1>\n<ul>\n";
+                for (page, file) in pages.iter().zip(files) {
+                    out += &format!(
+                        "<li><a href=\"{}\">{}</a>\n<ul>\n",
+                        file,
+                        escape(&page.path)
+                    );
+                    for item in &page.items {
+                        out += &format!(
+                            "<li><a href=\"{}#{}\">{}</a></li>\n",
+                            file,
+                            anchor(item),
+                            escape(&item.name)
+                        );
+                    }
+                    out += "</ul>\n</li>\n";
+                }
+                out += "</ul>\n";
+                html_document("Documentation", &out)
+            }
+            Format::Markdown => {
+                out += "# Documentation\n\n";
+                for (page, file) in pages.iter().zip(files) {
+                    out += &format!("- [{}]({})\n", page.path, file);
+                    for item in &page.items {
+                        out += &format!("  - [{}]({}#{})\n", item.name, file, anchor(item));
+                    }
+                }
+                out
+            }
+        }
+    }
+
+    fn page(&self, page: &Page) -> String {
+        let sections = [
+            ("Structs", "struct"),
+            ("Enums", "enum"),
+            ("Functions", "func"),
+            ("Extern Functions", "extern"),
+            ("Constants", "const"),
+        ];
+
+        let mut out = match self.format {
+            Format::Html => format!("<h1>{}</h1>\n", escape(&page.path)),
+            Format::Markdown => format!("# {}\n", page.path),
+        };
+        for (title, prefix) in sections {
+            let items: Vec<_> = page
+                .items
+                .iter()
+                .filter(|item| anchor(item).starts_with(&format!("{}.", prefix)))
+                .collect();
+            if items.is_empty() {
+                continue;
+            }
+
+            out += &match self.format {
+                Format::Html => format!("<h2>{}</h2>\n", title),
+                Format::Markdown => format!("\n## {}\n", title),
+            };
+            for item in items {
+                self.item(item, &anchor(item), 3, &mut out);
+            }
+        }
+
+        match self.format {
+            Format::Html => html_document(&page.path, &out),
+            Format::Markdown => out,
+        }
+    }
+
+    fn item(&self, item: &Item, anchor: &str, level: usize, out: &mut String) {
+        match self.format {
+            Format::Html => {
+                *out += &format!("<h{} id=\"{}\">{}</h{}>\n", level, anchor, item.name, level);
+            }
+            Format::Markdown => {
+                *out += &format!(
+                    "\n<a id=\"{}\"></a>\n{} {}\n\n",
+                    anchor,
+                    "#".repeat(level),
+                    item.name
+                );
+            }
+        }
+
+        // Signatures are HTML in both, since Markdown can't link inside code
+        *out += &format!("<pre><code>{}</code></pre>\n", self.signature(item));
+
+        if let Some(docs) = &item.docs {
+            match self.format {
+                Format::Html => {
+                    for paragraph in docs.split("\n\n") {
+                        *out += &format!("<p>{}</p>\n", escape(paragraph.trim()));
+                    }
+                }
+                Format::Markdown => *out += &format!("\n{}\n", docs),
+            }
+        }
+
+        if let ItemKind::Struct { methods, .. } = &item.kind {
+            for method in methods {
+                self.item(
+                    method,
+                    &format!("{}.{}", anchor, method.name),
+                    level + 1,
+                    out,
+                );
+            }
+        }
+    }
+
+    /// The item as it's declared, escaped for HTML with links to the types in it
+    fn signature(&self, item: &Item) -> String {
+        let name = escape(&item.name);
+        match &item.kind {
+            ItemKind::Function {
+                public,
+                type_params,
+                args,
+                returns,
+            } => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|(typ, name)| match *name {
+                        "self" => "self".to_string(),
+                        _ => format!("{}: {}", name, self.typ(typ)),
+                    })
+                    .collect();
+                format!(
+                    "{}func {}{}({}){}",
+                    if *public { "pub " } else { "" },
+                    name,
+                    type_params_list(type_params),
+                    args.join(", "),
+                    self.returns(returns)
+                )
+            }
+            ItemKind::ExternFunction {
+                varargs,
+                args,
+                returns,
+            } => {
+                let args: Vec<_> = args.iter().map(|typ| self.typ(typ)).collect();
+                format!(
+                    "extern {}func {}({}){};",
+                    if *varargs { "varargs " } else { "" },
+                    name,
+                    args.join(", "),
+                    self.returns(returns)
+                )
+            }
+            ItemKind::Struct {
+                public,
+                type_params,
+                fields,
+                ..
+            } => {
+                let mut out = format!(
+                    "{}struct {}{} {{\n",
+                    if *public { "pub " } else { "" },
+                    name,
+                    type_params_list(type_params)
+                );
+                for (typ, field) in fields {
+                    out += &format!("    {}: {},\n", field, self.typ(typ));
+                }
+                out + "}"
+            }
+            ItemKind::Enum { public, variants } => {
+                let mut out = format!("{}enum {} {{\n", if *public { "pub " } else { "" }, name);
+                for (variant, payload) in variants {
+                    out += &format!("    {}", variant);
+                    if !payload.is_empty() {
+                        let payload: Vec<_> = payload.iter().map(|typ| self.typ(typ)).collect();
+                        out += &format!("({})", payload.join(", "));
+                    }
+                    out += ",\n";
+                }
+                out + "}"
+            }
+            ItemKind::Const { typ, value } => {
+                let typ = typ
+                    .as_ref()
+                    .map_or(String::new(), |typ| format!(": {}", self.typ(typ)));
+                format!("const {}{} = {};", name, typ, escape(value))
+            }
+        }
+    }
+
+    fn returns(&self, returns: &AzulaType) -> String {
+        match returns {
+            AzulaType::Void => String::new(),
+            _ => format!(": {}", self.typ(returns)),
+        }
+    }
+
+    /// Writes a type like its Display, but with structs and enums linked
+    fn typ(&self, typ: &AzulaType) -> String {
+        match typ {
+            AzulaType::Pointer(nested) => format!("&amp;{}", self.typ(nested)),
+            AzulaType::Array(nested, Some(size)) => format!("[{}; {}]", self.typ(nested), size),
+            AzulaType::Array(nested, None) => format!("[{}]", self.typ(nested)),
+            AzulaType::Named(name) => self.link(name),
+            AzulaType::Generic(name, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.typ(arg)).collect();
+                format!("{}&lt;{}&gt;", self.link(name), args.join(", "))
+            }
+            _ => escape(&typ.to_string()),
+        }
+    }
+
+    fn link(&self, name: &str) -> String {
+        match self.types.get(name) {
+            Some(href) => format!("<a href=\"{}\">{}</a>", href, escape(name)),
+            None => escape(name),
+        }
+    }
+}
+
+fn type_params_list(type_params: &[&str]) -> String {
+    match type_params.len() {
+        0 => String::new(),
+        _ => format!("&lt;{}&gt;", type_params.join(", ")),
+    }
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ font-family: sans-serif; max-width: 50em; margin: auto; }}\npre {{ background: #f4f4f4; padding: 0.5em; }}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    const SOURCE: &str = "/// A point on the grid
+///
+/// Both coordinates can be negative
+pub struct Point {
+    x: int,
+    y: int,
+}
+
+impl Point {
+    /// Moves the point right by `by`
+    func shift(self, by: int) {}
+}
+
+/// How far apart two points are
+func distance(a: &Point, b: [Point; 2]): float {
+    return 0.0;
+}
+
+/// Prints to stdout
+extern varargs func printf(str): int;
+
+/// Whether one is less than two
+const LIMIT: bool = 1 < 2;
+
+func undocumented {}
+";
+
+    #[test]
+    fn test_document() {
+        let page = document("geometry.azl", SOURCE).unwrap();
+
+        let names: Vec<_> = page.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Point", "distance", "printf", "LIMIT", "undocumented"]
+        );
+        assert_eq!(
+            page.items[0].docs.as_deref(),
+            Some("A point on the grid\n\nBoth coordinates can be negative")
+        );
+        assert_eq!(page.items[4].docs, None);
+
+        match &page.items[0].kind {
+            ItemKind::Struct {
+                public: true,
+                fields,
+                methods,
+                ..
+            } => {
+                assert_eq!(fields.len(), 2);
+                assert_eq!(methods[0].name, "shift");
+                assert_eq!(
+                    methods[0].docs.as_deref(),
+                    Some("Moves the point right by `by`")
+                );
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert!(matches!(
+            page.items[3].kind,
+            ItemKind::Const {
+                value: "1 < 2",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_render() {
+        let pages = vec![document("src/geometry.azl", SOURCE).unwrap()];
+
+        let html = render(&pages, Format::Html);
+        assert_eq!(html[0].0, "index.html");
+        assert!(html[0]
+            .1
+            .contains("<a href=\"geometry.html#func.distance\">distance</a>"));
+        assert_eq!(html[1].0, "geometry.html");
+        let page = &html[1].1;
+        assert!(page.contains("<h3 id=\"struct.Point\">Point</h3>"));
+        assert!(page.contains("<h4 id=\"struct.Point.shift\">shift</h4>"));
+        assert!(page.contains("func shift(self, by: int)"));
+        assert!(page.contains(
+            "func distance(a: &amp;<a href=\"geometry.html#struct.Point\">Point</a>, b: [<a href=\"geometry.html#struct.Point\">Point</a>; 2]): float"
+        ));
+        assert!(page.contains("extern varargs func printf(str): int;"));
+        assert!(page.contains("const LIMIT: bool = 1 &lt; 2;"));
+        assert!(page.contains("<p>Both coordinates can be negative</p>"));
+
+        let markdown = render(&pages, Format::Markdown);
+        assert_eq!(markdown[1].0, "geometry.md");
+        let page = &markdown[1].1;
+        assert!(page.starts_with("# src/geometry.azl\n"));
+        assert!(page.contains("## Structs\n"));
+        assert!(page.contains("<a id=\"struct.Point\"></a>\n### Point\n"));
+        assert!(page.contains("\nA point on the grid\n\nBoth coordinates can be negative\n"));
+    }
+
+    #[test]
+    fn test_render_examples() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/modules");
+        let sources: Vec<_> = ["shapes.azl", "main.azl"]
+            .iter()
+            .map(|file| fs::read_to_string(dir.join(file)).unwrap())
+            .collect();
+        let pages: Vec<_> = ["shapes.azl", "main.azl"]
+            .iter()
+            .zip(&sources)
+            .map(|(path, source)| document(path, source).unwrap())
+            .collect();
+
+        let files = render(&pages, Format::Markdown);
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["index.md", "shapes.md", "main.md"]);
+        assert!(files[1]
+            .1
+            .contains("How many square units the rectangle covers"));
+        assert!(files[0].1.contains("  - [main](main.md#func.main)"));
+    }
+}
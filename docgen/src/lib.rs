@@ -0,0 +1,5 @@
+mod docgen;
+
+pub mod prelude {
+    pub use crate::docgen::{document, render, Format, Item, ItemKind, Page};
+}
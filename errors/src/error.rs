@@ -1,6 +1,6 @@
-use ansi_term::Colour::*;
-use line_col::LineColLookup;
-
+/// Every kind of error the compiler can report. Each has a stable code, given
+/// by `code`, and new kinds take the next free number rather than renumbering
+/// the others
 #[derive(Debug)]
 pub enum ErrorType {
     // Lex & Parse
@@ -11,6 +11,11 @@ pub enum ErrorType {
     UnexpectedEOF,
     InvalidEscape,
     ArrayInitialiserSizeNonConstant,
+    MethodWithoutSelf(String),
+    MisplacedAttribute,
+    UnknownAttribute(String),
+    UnknownLint(String),
+    SelfOutsideMethod,
 
     // Typecheck
     UnknownVariable(String),
@@ -27,10 +32,141 @@ pub enum ErrorType {
     AccessNonStruct,
     UnknownStruct(String),
     UnknownStructMember(String, String),
+    UnknownMethod(String, String),
+    WrongArgumentCount(String, usize, usize),
+    WrongArgumentType(String, String, String),
+    NonIntRange(String),
+    NotIterable(String),
+    OutsideLoop(String),
+    UnknownVariant(String, String),
+    NonExhaustiveMatch(String, String),
+    MatchNonEnum(String),
+    MatchArmWithoutValue,
+    CannotInferType(String, String),
+    WrongTypeArgumentCount(String, usize, usize),
+    GenericMethod(String),
+    MisplacedStatement(String),
+    InvalidAssignTarget,
+    NotCallable(String),
+    DuplicateMethod(String, String),
+
+    // Resolve
+    ImportNotFound(String),
+    DuplicateStruct(String),
+    InvalidTest(String),
+
+    // Link
+    NoLinkerFound,
+    LinkerCannotTarget(String, String),
+    LinkerNotRun(String, String),
+    LinkFailed(String, String),
+
+    // Backend
+    BuildFailed(String, String),
+    InvalidIr(String),
+    InvalidOptimisedIr(String),
+
+    // Bugs in the compiler itself
+    Internal(String),
+
+    // Warnings
+    UnusedVariable(String),
+    UnusedFunction(String),
+    UnreachableCode,
+    NeverReassigned(String),
 }
 
+/// The names warnings can be allowed by with `#[allow(...)]`
+pub const LINTS: [&str; 4] = [
+    "unused_variables",
+    "unused_functions",
+    "unreachable_code",
+    "never_reassigned",
+];
+
 impl<'a> ErrorType {
-    fn error_message(&self) -> String {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorType::UnknownToken => "E0001",
+            ErrorType::ExpectedToken(..) => "E0002",
+            ErrorType::ExpectedExpression(..) => "E0003",
+            ErrorType::ExpectedStatement(..) => "E0004",
+            ErrorType::UnexpectedEOF => "E0005",
+            ErrorType::InvalidEscape => "E0006",
+            ErrorType::ArrayInitialiserSizeNonConstant => "E0007",
+            ErrorType::MethodWithoutSelf(..) => "E0008",
+            ErrorType::UnknownVariable(..) => "E0009",
+            ErrorType::MismatchedTypes(..) => "E0010",
+            ErrorType::NonOperatorType(..) => "E0011",
+            ErrorType::IncorrectFunctionReturn(..) => "E0012",
+            ErrorType::NonBoolCondition(..) => "E0013",
+            ErrorType::FunctionNotFound(..) => "E0014",
+            ErrorType::MismatchedAssignTypes(..) => "E0015",
+            ErrorType::NonGlobalConstant => "E0016",
+            ErrorType::NonIntIndex(..) => "E0017",
+            ErrorType::NonArrayInIndex(..) => "E0018",
+            ErrorType::ConstantAssign => "E0019",
+            ErrorType::AccessNonStruct => "E0020",
+            ErrorType::UnknownStruct(..) => "E0021",
+            ErrorType::UnknownStructMember(..) => "E0022",
+            ErrorType::UnknownMethod(..) => "E0023",
+            ErrorType::WrongArgumentCount(..) => "E0024",
+            ErrorType::WrongArgumentType(..) => "E0025",
+            ErrorType::NonIntRange(..) => "E0026",
+            ErrorType::NotIterable(..) => "E0027",
+            ErrorType::OutsideLoop(..) => "E0028",
+            ErrorType::UnknownVariant(..) => "E0029",
+            ErrorType::NonExhaustiveMatch(..) => "E0030",
+            ErrorType::MatchNonEnum(..) => "E0031",
+            ErrorType::MatchArmWithoutValue => "E0032",
+            ErrorType::CannotInferType(..) => "E0033",
+            ErrorType::WrongTypeArgumentCount(..) => "E0034",
+            ErrorType::GenericMethod(..) => "E0035",
+            ErrorType::ImportNotFound(..) => "E0036",
+            ErrorType::DuplicateStruct(..) => "E0037",
+            ErrorType::NoLinkerFound => "E0038",
+            ErrorType::LinkerCannotTarget(..) => "E0039",
+            ErrorType::LinkerNotRun(..) => "E0040",
+            ErrorType::LinkFailed(..) => "E0041",
+            ErrorType::MisplacedAttribute => "E0042",
+            ErrorType::UnknownAttribute(..) => "E0043",
+            ErrorType::UnknownLint(..) => "E0044",
+            ErrorType::InvalidTest(..) => "E0045",
+            ErrorType::MisplacedStatement(..) => "E0046",
+            ErrorType::InvalidAssignTarget => "E0047",
+            ErrorType::NotCallable(..) => "E0048",
+            ErrorType::Internal(..) => "E0049",
+            ErrorType::BuildFailed(..) => "E0050",
+            ErrorType::InvalidIr(..) => "E0051",
+            ErrorType::InvalidOptimisedIr(..) => "E0052",
+            ErrorType::SelfOutsideMethod => "E0053",
+            ErrorType::DuplicateMethod(..) => "E0054",
+            ErrorType::UnusedVariable(..) => "W0001",
+            ErrorType::UnusedFunction(..) => "W0002",
+            ErrorType::UnreachableCode => "W0003",
+            ErrorType::NeverReassigned(..) => "W0004",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.lint() {
+            Some(_) => Severity::Warning,
+            None => Severity::Error,
+        }
+    }
+
+    /// The name a warning can be allowed by
+    pub fn lint(&self) -> Option<&'static str> {
+        match self {
+            ErrorType::UnusedVariable(..) => Some(LINTS[0]),
+            ErrorType::UnusedFunction(..) => Some(LINTS[1]),
+            ErrorType::UnreachableCode => Some(LINTS[2]),
+            ErrorType::NeverReassigned(..) => Some(LINTS[3]),
+            _ => None,
+        }
+    }
+
+    pub fn error_message(&self) -> String {
         match self {
             ErrorType::UnknownToken => "Unknown token found".to_string(),
             ErrorType::ExpectedToken(expected, got) => match got {
@@ -44,16 +180,25 @@ impl<'a> ErrorType {
             ErrorType::ArrayInitialiserSizeNonConstant => {
                 "Array initialiser size must be a constant int".to_string()
             }
+            ErrorType::MethodWithoutSelf(method) => {
+                format!("Method {} must take self as its first argument", method)
+            }
+            ErrorType::MisplacedAttribute => "Attributes can only be put on functions".to_string(),
+            ErrorType::UnknownAttribute(name) => format!("Unknown attribute {}", name),
+            ErrorType::UnknownLint(name) => format!("Unknown warning {} in allow", name),
+            ErrorType::SelfOutsideMethod => {
+                "self can only be the first argument of a method".to_string()
+            }
             ErrorType::UnknownVariable(name) => format!("Unknown variable {:?}", name),
             ErrorType::MismatchedTypes(left, right) => {
-                format!("Mismatched types: {:?} and {:?}", left, right)
+                format!("Mismatched types: {} and {}", left, right)
             }
             ErrorType::NonOperatorType(typ, operator) => {
-                format!("Type {:?} cannot be used with operator {:?}", typ, operator)
+                format!("Type {} cannot be used with operator {}", typ, operator)
             }
             ErrorType::IncorrectFunctionReturn(wanted, got) => {
                 format!(
-                    "Function returns wrong value, wanted {:?}, got {:?}",
+                    "Function returns wrong value, wanted {}, got {}",
                     wanted, got
                 )
             }
@@ -86,75 +231,159 @@ impl<'a> ErrorType {
             ErrorType::UnknownStructMember(struc, member) => {
                 format!("Struct {} attribute {} not found", struc, member)
             }
+            ErrorType::UnknownMethod(struc, method) => {
+                format!("Struct {} has no method {}", struc, method)
+            }
+            ErrorType::WrongArgumentCount(func, expected, got) => {
+                format!(
+                    "Function {} takes {} arguments, got {}",
+                    func, expected, got
+                )
+            }
+            ErrorType::WrongArgumentType(func, expected, got) => {
+                format!(
+                    "Mismatched argument type in call to {}, expected {}, got {}",
+                    func, expected, got
+                )
+            }
+            ErrorType::NonIntRange(got) => {
+                format!("Range bounds must be integers, got {}", got)
+            }
+            ErrorType::NotIterable(got) => {
+                format!(
+                    "Cannot iterate over {}, expected a range or sized array",
+                    got
+                )
+            }
+            ErrorType::OutsideLoop(keyword) => {
+                format!("{} can only be used inside a loop", keyword)
+            }
+            ErrorType::UnknownVariant(enu, variant) => {
+                format!("Enum {} has no variant {}", enu, variant)
+            }
+            ErrorType::NonExhaustiveMatch(enu, missing) => {
+                format!("Match on {} is missing variants: {}", enu, missing)
+            }
+            ErrorType::MatchNonEnum(got) => format!("Can only match on enums, got {}", got),
+            ErrorType::MatchArmWithoutValue => {
+                "Every match arm must give a value when the match is used as one".to_string()
+            }
+            ErrorType::CannotInferType(name, param) => {
+                format!("Cannot infer type parameter {} of {}", param, name)
+            }
+            ErrorType::WrongTypeArgumentCount(name, expected, got) => {
+                format!("{} takes {} type arguments, got {}", name, expected, got)
+            }
+            ErrorType::GenericMethod(name) => {
+                format!("Method {} can't have type parameters", name)
+            }
+            ErrorType::MisplacedStatement(stmt) => {
+                format!("{} statements aren't allowed here", stmt)
+            }
+            ErrorType::InvalidAssignTarget => {
+                "Can only assign to variables, array elements and struct fields".to_string()
+            }
+            ErrorType::NotCallable(typ) => {
+                format!("Expression of type {} is not callable", typ)
+            }
+            ErrorType::DuplicateMethod(struc, method) => {
+                format!("Struct {} already has a method {}", struc, method)
+            }
+            ErrorType::ImportNotFound(path) => format!("Could not find import {:?}", path),
+            ErrorType::DuplicateStruct(struc) => {
+                format!("Struct {} is already defined", struc)
+            }
+            ErrorType::InvalidTest(name) => {
+                format!(
+                    "Test {} can't take arguments, type parameters or return a value",
+                    name
+                )
+            }
+            ErrorType::NoLinkerFound => {
+                "No linker found, install a C compiler or pass --linker".to_string()
+            }
+            ErrorType::LinkerCannotTarget(linker, target) => {
+                format!("Linker {} cannot link for target {}", linker, target)
+            }
+            ErrorType::LinkerNotRun(linker, reason) => {
+                format!("Could not run linker {}: {}", linker, reason)
+            }
+            ErrorType::LinkFailed(linker, output) => {
+                format!("Linker {} failed to link {}", linker, output)
+            }
+            ErrorType::BuildFailed(name, reason) => format!("Failed to build {}: {}", name, reason),
+            ErrorType::InvalidIr(err) => format!("Invalid IR in {}", err),
+            ErrorType::InvalidOptimisedIr(err) => {
+                format!("Optimisation produced invalid IR in {}", err)
+            }
+            ErrorType::Internal(message) => format!("Internal compiler error: {}", message),
+            ErrorType::UnusedVariable(name) => format!("Unused variable {}", name),
+            ErrorType::UnusedFunction(name) => format!("Function {} is never called", name),
+            ErrorType::UnreachableCode => "Unreachable code".to_string(),
+            ErrorType::NeverReassigned(name) => {
+                format!("Variable {} is never reassigned", name)
+            }
         }
     }
 }
 
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A secondary span shown alongside an error, pointing at other code
+/// involved in it
+#[derive(Debug)]
+pub struct Label {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+/// An error at a byte range in a source file, with any labels, notes and
+/// help that explain it further
 #[derive(Debug)]
 pub struct AzulaError {
     pub error_type: ErrorType,
+    pub severity: Severity,
     pub start: usize,
     pub end: usize,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
 }
 
 impl AzulaError {
     pub fn new(error_type: ErrorType, start: usize, end: usize) -> Self {
         Self {
+            severity: error_type.severity(),
             error_type,
             start,
             end,
+            labels: vec![],
+            notes: vec![],
+            help: vec![],
         }
     }
 
-    pub fn print_stdout(&self, source: &str, filename: &str) {
-        let lookup = LineColLookup::new(source);
-        println!(
-            "{}: {}",
-            Red.paint("ERROR"),
-            self.error_type.error_message()
-        );
-        let show_start = self.read_back_until_new_line(source, self.start - 1);
-        let show_end = self.read_forward_until_new_line(source, self.end - 1);
-        let (line_number, col) = lookup.get(self.start);
-        // println!(
-        //     "{}",
-        //     Red.paint(format!("Line: {} Column: {}", line_number, col))
-        // );
-        println!(
-            "{}",
-            Red.paint(format!("-> {}:{}:{}", filename, line_number, col))
-        );
-        print!("{}", &source[show_start + 1..self.start]);
-        print!("{}", White.paint(&source[self.start..self.end]));
-        print!("{}\n", &source[self.end..show_end]);
-        println!(
-            "{}{}",
-            " ".repeat(col - 1),
-            Red.paint("^".repeat(self.end - self.start))
-        );
+    pub fn with_label(mut self, start: usize, end: usize, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            start,
+            end,
+            message: message.into(),
+        });
+        self
     }
 
-    fn read_back_until_new_line(&self, source: &str, mut point: usize) -> usize {
-        let mut char = source.as_bytes()[point] as char;
-        while char != '\n' {
-            point -= 1;
-            if point == 0 {
-                return point;
-            }
-            char = source.as_bytes()[point] as char;
-        }
-        return point;
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
     }
 
-    fn read_forward_until_new_line(&self, source: &str, mut point: usize) -> usize {
-        let mut char = source.as_bytes()[point] as char;
-        while char != '\n' {
-            point += 1;
-            if point >= source.len() {
-                return point;
-            }
-            char = source.as_bytes()[point] as char;
-        }
-        return point;
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
     }
 }
@@ -1,5 +1,6 @@
 mod error;
+mod render;
 
 pub mod prelude {
-    pub use crate::error::{AzulaError, ErrorType};
+    pub use crate::error::{AzulaError, ErrorType, Label, Severity, LINTS};
 }
@@ -0,0 +1,389 @@
+use std::io::{self, IsTerminal};
+
+use ansi_term::{Colour::*, Style};
+use line_col::LineColLookup;
+
+use crate::error::{AzulaError, ErrorType, Severity};
+
+// Spans longer than this only show their first and last few lines
+const MAX_SPAN_LINES: usize = 6;
+
+/// A span underlined in the rendered source
+struct Annotation<'e> {
+    start: usize,
+    end: usize,
+    message: Option<&'e str>,
+    primary: bool,
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn style(&self) -> Style {
+        match self {
+            Severity::Error => Red.bold(),
+            Severity::Warning => Yellow.bold(),
+        }
+    }
+}
+
+impl ErrorType {
+    /// Renders an error that isn't tied to a place in the source
+    pub fn render(&self, colour: bool) -> String {
+        header(Severity::Error, self, colour)
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"code\":{},\"severity\":\"error\",\"message\":{},\"file\":null,\"spans\":[],\"notes\":[],\"help\":[],\"rendered\":{}}}",
+            json_string(self.code()),
+            json_string(&self.error_message()),
+            json_string(&self.render(false)),
+        )
+    }
+
+    /// Prints the error to stderr, in colour when that's a terminal
+    pub fn print_stderr(&self) {
+        eprintln!("{}", self.render(io::stderr().is_terminal()));
+    }
+}
+
+impl AzulaError {
+    /// Renders the error with the lines of source it points at, underlining
+    /// its span with `^` and each label's with `-`
+    pub fn render(&self, source: &str, filename: &str, colour: bool) -> String {
+        let lookup = LineColLookup::new(source);
+        let lines: Vec<_> = source
+            .split('\n')
+            .map(|line| line.trim_end_matches('\r'))
+            .collect();
+
+        let mut annotations = vec![Annotation {
+            start: self.start,
+            end: self.end,
+            message: None,
+            primary: true,
+        }];
+        annotations.extend(self.labels.iter().map(|label| Annotation {
+            start: label.start,
+            end: label.end,
+            message: Some(&label.message),
+            primary: false,
+        }));
+        for annotation in annotations.iter_mut() {
+            annotation.start = annotation.start.min(source.len());
+            annotation.end = annotation.end.clamp(annotation.start, source.len());
+        }
+        annotations.sort_by_key(|annotation| (annotation.start, !annotation.primary));
+
+        let line_range = |annotation: &Annotation| {
+            let first = lookup.get(annotation.start).0;
+            let last = lookup.get(annotation.end.max(annotation.start + 1) - 1).0;
+            (first, last.max(first))
+        };
+        let width = annotations
+            .iter()
+            .map(|annotation| line_range(annotation).1.to_string().len())
+            .max()
+            .unwrap_or(1);
+        // Spans covering several lines are marked in a column of their own
+        let multiline = annotations.iter().any(|annotation| {
+            let (first, last) = line_range(annotation);
+            first != last
+        });
+
+        let paint = |style: Style, text: &str| {
+            if colour {
+                style.paint(text).to_string()
+            } else {
+                text.to_string()
+            }
+        };
+        let marks = |annotation: &Annotation, text: &str| {
+            if annotation.primary {
+                paint(self.severity.style(), text)
+            } else {
+                paint(Blue.bold(), text)
+            }
+        };
+        let gutter = |number: Option<usize>| {
+            let number = number.map(|n| n.to_string()).unwrap_or_default();
+            paint(Blue.bold(), &format!("{:>width$} |", number, width = width))
+        };
+
+        let (line, col) = lookup.get(self.start.min(source.len()));
+        let mut out = header(self.severity, &self.error_type, colour);
+        out += &format!(
+            "{}{} {}:{}:{}\n",
+            " ".repeat(width),
+            paint(Blue.bold(), "-->"),
+            filename,
+            line,
+            col
+        );
+        out += &format!("{}\n", gutter(None));
+
+        let mut shown: Vec<usize> = vec![];
+        for annotation in &annotations {
+            let (first, last) = line_range(annotation);
+            if last - first + 1 > MAX_SPAN_LINES {
+                shown.extend(first..first + MAX_SPAN_LINES / 2);
+                shown.extend(last + 1 - MAX_SPAN_LINES / 2..=last);
+            } else {
+                shown.extend(first..=last);
+            }
+        }
+        shown.sort_unstable();
+        shown.dedup();
+
+        let mut previous = None;
+        for number in shown {
+            if previous.is_some_and(|previous| number > previous + 1) {
+                out += &format!("{}\n", paint(Blue.bold(), "..."));
+            }
+            previous = Some(number);
+
+            // The span covering several lines this one is part of, if any
+            let spanning = annotations.iter().find(|annotation| {
+                let (first, last) = line_range(annotation);
+                first != last && first <= number && number <= last
+            });
+            // Source lines mark where the span starts, underlines only that
+            // they're inside it
+            let margin = |source_line: bool| match spanning {
+                Some(annotation) if source_line && line_range(annotation).0 == number => {
+                    marks(annotation, "/ ")
+                }
+                Some(annotation) => marks(annotation, "| "),
+                None if multiline => "  ".to_string(),
+                None => "".to_string(),
+            };
+            out += &format!(
+                "{} {}{}",
+                gutter(Some(number)),
+                margin(true),
+                lines[number - 1]
+            )
+            .trim_end();
+            out += "\n";
+
+            for annotation in &annotations {
+                if line_range(annotation) != (number, number) {
+                    continue;
+                }
+                let line_start = annotation.start - (lookup.get(annotation.start).1 - 1);
+                let indent = source[line_start..annotation.start].chars().count();
+                let length = source[annotation.start..annotation.end].chars().count();
+                let mark = if annotation.primary { "^" } else { "-" };
+                let mut underline = mark.repeat(length.max(1));
+                if let Some(message) = annotation.message {
+                    underline = format!("{} {}", underline, message);
+                }
+                out += &format!(
+                    "{} {}{}{}\n",
+                    gutter(None),
+                    margin(false),
+                    " ".repeat(indent),
+                    marks(annotation, &underline)
+                );
+            }
+
+            for annotation in &annotations {
+                let (first, last) = line_range(annotation);
+                if first == last || last != number {
+                    continue;
+                }
+                let end = annotation.end.max(annotation.start + 1) - 1;
+                let column = source[end - (lookup.get(end).1 - 1)..end].chars().count();
+                let mark = if annotation.primary { "^" } else { "-" };
+                let mut underline = format!("|{}{}", "_".repeat(column + 1), mark);
+                if let Some(message) = annotation.message {
+                    underline = format!("{} {}", underline, message);
+                }
+                out += &format!("{} {}\n", gutter(None), marks(annotation, &underline));
+            }
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() {
+            out += &format!("{}\n", gutter(None));
+        }
+        let notes = self.notes.iter().map(|note| ("note:", note));
+        for (kind, text) in notes.chain(self.help.iter().map(|help| ("help:", help))) {
+            out += &format!(
+                "{} {} {} {}\n",
+                " ".repeat(width),
+                paint(Blue.bold(), "="),
+                paint(Style::new().bold(), kind),
+                text
+            );
+        }
+
+        out
+    }
+
+    /// The error as a single line of JSON, for editors and other tools
+    pub fn to_json(&self, source: &str, filename: &str) -> String {
+        let lookup = LineColLookup::new(source);
+        let span = |start: usize, end: usize, label: Option<&str>, primary: bool| {
+            let start = start.min(source.len());
+            let end = end.clamp(start, source.len());
+            let (line_start, column_start) = lookup.get(start);
+            let (line_end, column_end) = lookup.get(end);
+            format!(
+                "{{\"start\":{},\"end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{},\"primary\":{},\"label\":{}}}",
+                start,
+                end,
+                line_start,
+                column_start,
+                line_end,
+                column_end,
+                primary,
+                label.map(json_string).unwrap_or_else(|| "null".to_string())
+            )
+        };
+
+        let mut spans = vec![span(self.start, self.end, None, true)];
+        spans.extend(
+            self.labels
+                .iter()
+                .map(|label| span(label.start, label.end, Some(&label.message), false)),
+        );
+
+        format!(
+            "{{\"code\":{},\"severity\":{},\"message\":{},\"file\":{},\"spans\":[{}],\"notes\":{},\"help\":{},\"rendered\":{}}}",
+            json_string(self.error_type.code()),
+            json_string(self.severity.name()),
+            json_string(&self.error_type.error_message()),
+            json_string(filename),
+            spans.join(","),
+            json_list(&self.notes),
+            json_list(&self.help),
+            json_string(&self.render(source, filename, false)),
+        )
+    }
+
+    /// Prints the error to stderr, in colour when that's a terminal
+    pub fn print_stderr(&self, source: &str, filename: &str) {
+        eprintln!(
+            "{}",
+            self.render(source, filename, io::stderr().is_terminal())
+        );
+    }
+}
+
+fn header(severity: Severity, error_type: &ErrorType, colour: bool) -> String {
+    let kind = format!("{}[{}]", severity.name(), error_type.code());
+    let message = error_type.error_message();
+    if colour {
+        format!(
+            "{}{}\n",
+            severity.style().paint(kind),
+            Style::new().bold().paint(format!(": {}", message))
+        )
+    } else {
+        format!("{}: {}\n", kind, message)
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_list(items: &[String]) -> String {
+    let items: Vec<_> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "func main {\n    var x = 1 + \"a\";\n    x = 2;\n}\n";
+
+    #[test]
+    fn test_render() {
+        let err = AzulaError::new(
+            ErrorType::MismatchedTypes("int".to_string(), "&str".to_string()),
+            24,
+            31,
+        )
+        .with_label(24, 25, "int")
+        .with_label(28, 31, "&str")
+        .with_help("convert one side so both have the same type");
+
+        assert_eq!(
+            err.render(SOURCE, "main.azl", false),
+            r#"error[E0010]: Mismatched types: int and &str
+ --> main.azl:2:13
+  |
+2 |     var x = 1 + "a";
+  |             ^^^^^^^
+  |             - int
+  |                 --- &str
+  |
+  = help: convert one side so both have the same type
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_multiline() {
+        let err = AzulaError::new(ErrorType::ConstantAssign, 37, 42).with_label(
+            0,
+            SOURCE.len() - 1,
+            "in this function",
+        );
+
+        assert_eq!(
+            err.render(SOURCE, "main.azl", false),
+            r#"error[E0019]: Cannot assign to constant
+ --> main.azl:3:5
+  |
+1 | / func main {
+2 | |     var x = 1 + "a";
+3 | |     x = 2;
+  | |     ^^^^^
+4 | | }
+  | |_- in this function
+"#
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let err = AzulaError::new(ErrorType::UnknownVariable("y".to_string()), 37, 38)
+            .with_note("variables must be declared before they're used");
+
+        assert_eq!(
+            err.to_json(SOURCE, "main.azl"),
+            concat!(
+                r#"{"code":"E0009","severity":"error","message":"Unknown variable \"y\"","file":"main.azl","#,
+                r#""spans":[{"start":37,"end":38,"line_start":3,"column_start":5,"line_end":3,"column_end":6,"primary":true,"label":null}],"#,
+                r#""notes":["variables must be declared before they're used"],"help":[],"#,
+                r#""rendered":"error[E0009]: Unknown variable \"y\"\n --> main.azl:3:5\n  |\n3 |     x = 2;\n  |     ^\n  |\n  = note: variables must be declared before they're used\n"}"#
+            )
+        );
+        assert_eq!(
+            ErrorType::NoLinkerFound.to_json(),
+            r#"{"code":"E0038","severity":"error","message":"No linker found, install a C compiler or pass --linker","file":null,"spans":[],"notes":[],"help":[],"rendered":"error[E0038]: No linker found, install a C compiler or pass --linker\n"}"#
+        );
+    }
+}
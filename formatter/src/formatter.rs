@@ -0,0 +1,791 @@
+use azula_ast::prelude::*;
+use azula_error::prelude::AzulaError;
+use azula_parser::prelude::{Lexer, Parser, Span as TokenSpan, Token, TokenKind};
+use azula_type::prelude::AzulaType;
+
+const INDENT: &str = "    ";
+
+// How tightly expressions bind, matching the parser. Anything that isn't an
+// infix or prefix expression can't be split up by the operators around it
+const PREFIX: u8 = 6;
+const ATOM: u8 = 10;
+
+/// Formats a file in the canonical style, or gives the errors stopping it from
+/// parsing. Comments are kept where they were written, and a single blank
+/// line is kept wherever there were any
+pub fn format(source: &str) -> Result<String, Vec<AzulaError>> {
+    let lexer: Lexer = source.into();
+    let mut parser = Parser::new(source, lexer);
+    let root = parser.parse();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+
+    let mut formatter = Formatter::new(source);
+    if let Statement::Root(stmts) = &root {
+        formatter.body(stmts, source.len(), true);
+    }
+
+    let out = formatter.out.trim_end();
+    if out.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}\n", out))
+}
+
+struct Formatter<'s> {
+    source: &'s str,
+    /// Every token in the file. The AST doesn't keep comments or where
+    /// braces are, so they're found from these
+    tokens: Vec<Token<'s>>,
+    comments: Vec<TokenSpan>,
+    /// The next comment to be written
+    next_comment: usize,
+    /// Where the last thing written in the current block ends in the
+    /// source, to see if there was a blank line after it
+    last: Option<usize>,
+    /// Forces a blank line before the next line, between top level items
+    blank: bool,
+    indent: usize,
+    out: String,
+}
+
+impl<'s> Formatter<'s> {
+    fn new(source: &'s str) -> Self {
+        let lexer: Lexer = source.into();
+        let tokens: Vec<_> = lexer.collect();
+        let comments = tokens
+            .iter()
+            .filter(|token| token.kind.is_comment())
+            .map(|token| token.span.clone())
+            .collect();
+
+        Self {
+            source,
+            tokens,
+            comments,
+            next_comment: 0,
+            last: None,
+            blank: false,
+            indent: 0,
+            out: String::new(),
+        }
+    }
+
+    /// Writes statements on lines of their own, along with the comments
+    /// between them, up to `end` in the source
+    fn body(&mut self, stmts: &[Statement], end: usize, top_level: bool) {
+        // Declarations at the top of a file are kept apart by a blank line,
+        // as are imports from what comes after them
+        let declaration = |stmt: &Statement| {
+            matches!(
+                stmt,
+                Statement::Function { .. }
+                    | Statement::Struct { .. }
+                    | Statement::Enum { .. }
+                    | Statement::Impl { .. }
+            )
+        };
+        let import = |stmt: &Statement| matches!(stmt, Statement::Import(..));
+
+        self.last = None;
+        let mut previous: Option<&Statement> = None;
+        for stmt in stmts {
+            let span = stmt.span();
+            let start = match stmt {
+                Statement::Function { attributes, .. } if !attributes.is_empty() => {
+                    attributes[0].span.start
+                }
+                _ => span.start,
+            };
+
+            self.blank = top_level
+                && previous.is_some_and(|previous| {
+                    declaration(previous)
+                        || declaration(stmt)
+                        || (import(previous) && !import(stmt))
+                });
+            self.comments_before(start);
+            self.line(start);
+            self.statement(stmt);
+            self.last = Some(span.end);
+            self.trailing_comment(span.end);
+            self.out.push('\n');
+            previous = Some(stmt);
+        }
+        self.blank = top_level && previous.is_some_and(declaration);
+        self.comments_before(end);
+        self.blank = false;
+    }
+
+    /// Writes a `{ }` block of statements, giving where its closing brace
+    /// ends in the source
+    fn block(&mut self, stmts: &[Statement], open: usize) -> usize {
+        let from = stmts.last().map_or(open + 1, |stmt| stmt.span().end);
+        let close = self.find(TokenKind::BraceClose, from);
+
+        if stmts.is_empty() && !self.has_comment_before(close.start) {
+            self.out += "{}";
+            return close.end;
+        }
+
+        self.out += "{\n";
+        self.indent += 1;
+        self.body(stmts, close.start, false);
+        self.indent -= 1;
+        self.indent();
+        self.out += "}";
+        close.end
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Function { .. } => self.function(stmt),
+            Statement::Return(value, _) => {
+                self.out += "return";
+                if let Some(value) = value {
+                    self.out += " ";
+                    self.expression(value, true);
+                }
+                self.out += ";";
+            }
+            Statement::Assign(mutable, name, typ, value, _) => {
+                self.out += if *mutable { "var " } else { "const " };
+                self.out += name;
+                if let Some(typ) = typ {
+                    self.out += &format!(": {}", typ);
+                }
+                self.out += " = ";
+                self.expression(value, true);
+                self.out += ";";
+            }
+            Statement::ExpressionStatement(expr, _) => {
+                self.expression(expr, true);
+                // Like if and while, a match on its own doesn't need one
+                if !matches!(expr.expression, Expression::Match(..)) {
+                    self.out += ";";
+                }
+            }
+            Statement::If(..) => self.if_statement(stmt),
+            Statement::ExternFunction {
+                name,
+                varargs,
+                args,
+                returns,
+                ..
+            } => {
+                self.out += "extern ";
+                if *varargs {
+                    self.out += "varargs ";
+                }
+                self.out += &format!("func {}", name);
+                if !args.is_empty() {
+                    self.out += &format!("({})", join(args));
+                }
+                if *returns != AzulaType::Void {
+                    self.out += &format!(": {}", returns);
+                }
+                self.out += ";";
+            }
+            Statement::Reassign(target, value, _) => {
+                self.expression(target, true);
+                self.out += " = ";
+                self.expression(value, true);
+                self.out += ";";
+            }
+            Statement::While(condition, body, _) => {
+                self.out += "while ";
+                self.expression(condition, false);
+                self.out += " ";
+                let open = self.find(TokenKind::BraceOpen, condition.span.end);
+                self.block(body, open.start);
+            }
+            Statement::For(name, iterable, body, _) => {
+                self.out += &format!("for {} in ", name);
+                self.expression(iterable, false);
+                self.out += " ";
+                let open = self.find(TokenKind::BraceOpen, iterable.span.end);
+                self.block(body, open.start);
+            }
+            Statement::Break(_) => self.out += "break;",
+            Statement::Continue(_) => self.out += "continue;",
+            Statement::Struct {
+                name,
+                public,
+                type_params,
+                attributes,
+                ..
+            } => {
+                if *public {
+                    self.out += "pub ";
+                }
+                self.out += &format!("struct {}{}", name, type_params_list(type_params));
+                if attributes.is_empty() {
+                    self.out += " {}";
+                    return;
+                }
+
+                self.out += " {\n";
+                for (typ, field) in attributes {
+                    self.out += &format!("{}{}: {},\n", INDENT.repeat(self.indent + 1), field, typ);
+                }
+                self.indent();
+                self.out += "}";
+            }
+            Statement::Enum {
+                name,
+                public,
+                variants,
+                span,
+            } => {
+                if *public {
+                    self.out += "pub ";
+                }
+                self.out += &format!("enum {} {{\n", name);
+                self.indent += 1;
+                self.last = None;
+
+                // Variants don't have spans, so they're found from their names
+                let mut from = self.find(TokenKind::BraceOpen, span.start).end;
+                for (variant, payload) in variants {
+                    let name = self.find(TokenKind::Identifier(variant), from);
+                    from = match payload.is_empty() {
+                        true => name.end,
+                        false => self.find(TokenKind::BracketClose, name.end).end,
+                    };
+
+                    self.comments_before(name.start);
+                    self.line(name.start);
+                    self.out += variant;
+                    if !payload.is_empty() {
+                        self.out += &format!("({})", join(payload));
+                    }
+                    self.out += ",";
+                    self.last = Some(from);
+                    self.trailing_comment(from);
+                    self.out.push('\n');
+                }
+                self.comments_before(span.end - 1);
+
+                self.indent -= 1;
+                self.indent();
+                self.out += "}";
+            }
+            Statement::Impl {
+                name,
+                methods,
+                span,
+            } => {
+                self.out += &format!("impl {} ", name);
+                let open = self.find(TokenKind::BraceOpen, span.start);
+                self.block(methods, open.start);
+            }
+            Statement::Import(path, _) => self.out += &format!("import \"{}\";", path),
+            Statement::Block(stmts) => {
+                let open = self.find(TokenKind::BraceOpen, stmt.span().start);
+                self.block(stmts, open.start);
+            }
+            Statement::Root(_) | Statement::Instance(..) => {
+                unreachable!("only found at the top of a file or after typechecking")
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Statement) {
+        if let Statement::Function {
+            name,
+            public,
+            attributes,
+            type_params,
+            args,
+            returns,
+            body,
+            span,
+        } = function
+        {
+            for (i, attribute) in attributes.iter().enumerate() {
+                if i > 0 {
+                    self.comments_before(attribute.span.start);
+                    self.indent();
+                }
+                self.out += &format!("#[{}", attribute.name);
+                if !attribute.args.is_empty() {
+                    self.out += &format!("({})", attribute.args.join(", "));
+                }
+                self.out += "]\n";
+                self.last = Some(attribute.span.end);
+            }
+            if !attributes.is_empty() {
+                self.comments_before(span.start);
+                self.indent();
+            }
+
+            if *public {
+                self.out += "pub ";
+            }
+            self.out += &format!("func {}{}", name, type_params_list(type_params));
+            if !args.is_empty() {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|(typ, arg)| match *arg {
+                        // Method receivers are typed by their impl block
+                        "self" => arg.to_string(),
+                        _ => format!("{}: {}", arg, typ),
+                    })
+                    .collect();
+                self.out += &format!("({})", args.join(", "));
+            }
+            if *returns != AzulaType::Void {
+                self.out += &format!(": {}", returns);
+            }
+            self.out += " ";
+
+            if let Statement::Block(stmts) = body.as_ref() {
+                let open = self.find(TokenKind::BraceOpen, span.start);
+                self.block(stmts, open.start);
+            }
+        }
+    }
+
+    /// Writes an if statement, and any `else if`s chained onto it
+    fn if_statement(&mut self, stmt: &Statement) {
+        if let Statement::If(condition, then, otherwise, _) = stmt {
+            self.out += "if ";
+            self.expression(condition, false);
+            self.out += " ";
+            let open = self.find(TokenKind::BraceOpen, condition.span.end);
+            let close = self.block(then, open.start);
+
+            if let Some(otherwise) = otherwise {
+                self.out += " else ";
+                let keyword = self.find(TokenKind::Else, close);
+                match self.find_any(keyword.end) {
+                    Some(Token {
+                        kind: TokenKind::If,
+                        ..
+                    }) => self.if_statement(&otherwise[0]),
+                    _ => {
+                        let open = self.find(TokenKind::BraceOpen, keyword.end);
+                        self.block(otherwise, open.start);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes an expression. Struct initialisations can't be written where
+    /// they'd be mistaken for a block, like an if condition, so they're put
+    /// in brackets when `struct_init` is false
+    fn expression(&mut self, expr: &ExpressionNode, struct_init: bool) {
+        match &expr.expression {
+            // Literals are written as they were, to keep escapes in strings
+            Expression::Integer(_) | Expression::Float(_) | Expression::String(_) => {
+                self.out += &self.source[expr.span.start..expr.span.end]
+            }
+            Expression::Boolean(value) => self.out += &value.to_string(),
+            Expression::Identifier(name) => self.out += name,
+            Expression::Infix(left, operator, right) => {
+                let precedence = precedence(operator);
+                self.operand(left, binding(left) < precedence, struct_init);
+                self.out += &format!(" {} ", operator);
+                // Operators are left associative
+                self.operand(right, binding(right) <= precedence, struct_init);
+            }
+            Expression::Not(inner) => {
+                self.out += "!";
+                self.operand(inner, binding(inner) < PREFIX, struct_init);
+            }
+            Expression::Pointer(inner) => {
+                self.out += "&";
+                self.operand(inner, binding(inner) < PREFIX, true);
+            }
+            Expression::FunctionCall { function, args, .. } => {
+                self.operand(function, binding(function) <= PREFIX, struct_init);
+                self.out += "(";
+                self.list(args);
+                self.out += ")";
+            }
+            Expression::Array(items) => {
+                self.out += "[";
+                // `[0; 20]` is parsed into twenty copies of 0
+                let initialiser = items.first().is_some_and(|first| {
+                    self.source[first.span.end..expr.span.end]
+                        .trim_start()
+                        .starts_with(';')
+                });
+                if initialiser {
+                    self.expression(&items[0], true);
+                    self.out += &format!("; {}", items.len());
+                } else {
+                    self.list(items);
+                }
+                self.out += "]";
+            }
+            Expression::ArrayAccess(array, index) => {
+                self.operand(array, binding(array) <= PREFIX, struct_init);
+                self.out += "[";
+                self.expression(index, true);
+                self.out += "]";
+            }
+            Expression::StructInitialisation(name, fields) => {
+                if !struct_init {
+                    self.out += "(";
+                }
+                self.expression(name, true);
+                if fields.is_empty() {
+                    self.out += " {}";
+                } else {
+                    self.out += " { ";
+                    for (i, (field, value)) in fields.iter().enumerate() {
+                        if i > 0 {
+                            self.out += ", ";
+                        }
+                        self.out += &format!("{}: ", field);
+                        self.expression(value, true);
+                    }
+                    self.out += " }";
+                }
+                if !struct_init {
+                    self.out += ")";
+                }
+            }
+            Expression::StructAccess(left, member) => {
+                self.operand(left, binding(left) <= PREFIX, struct_init);
+                self.out += ".";
+                self.expression(member, false);
+            }
+            Expression::Range(start, end) => {
+                self.expression(start, false);
+                self.out += "..";
+                self.expression(end, false);
+            }
+            Expression::Match(subject, arms) => self.match_expression(expr, subject, arms),
+            Expression::EnumVariant(enu, variant, args) => {
+                self.out += &format!("{}.{}", enu, variant);
+                if !args.is_empty() {
+                    self.out += "(";
+                    self.list(args);
+                    self.out += ")";
+                }
+            }
+        }
+    }
+
+    fn match_expression(
+        &mut self,
+        expr: &ExpressionNode,
+        subject: &ExpressionNode,
+        arms: &[MatchArm],
+    ) {
+        self.out += "match ";
+        self.expression(subject, false);
+        self.out += " {\n";
+
+        self.indent += 1;
+        self.last = None;
+        for arm in arms {
+            self.comments_before(arm.span.start);
+            self.line(arm.span.start);
+            match &arm.pattern {
+                Pattern::Variant(name, bindings) if bindings.is_empty() => self.out += name,
+                Pattern::Variant(name, bindings) => {
+                    self.out += &format!("{}({})", name, bindings.join(", "))
+                }
+                Pattern::Wildcard => self.out += "_",
+            }
+            self.out += " => ";
+
+            match &arm.value {
+                Some(value) => {
+                    self.expression(value, true);
+                    self.out += ",";
+                }
+                None => {
+                    let open = self.find(TokenKind::BraceOpen, arm.span.start);
+                    self.block(&arm.body, open.start);
+                }
+            }
+            self.last = Some(arm.span.end);
+            self.trailing_comment(arm.span.end);
+            self.out.push('\n');
+        }
+        self.comments_before(expr.span.end - 1);
+        self.indent -= 1;
+
+        self.indent();
+        self.out += "}";
+    }
+
+    /// Writes part of a larger expression, in brackets if it would otherwise
+    /// be parsed differently
+    fn operand(&mut self, expr: &ExpressionNode, bracketed: bool, struct_init: bool) {
+        if bracketed {
+            self.out += "(";
+            self.expression(expr, true);
+            self.out += ")";
+        } else {
+            self.expression(expr, struct_init);
+        }
+    }
+
+    fn list(&mut self, exprs: &[ExpressionNode]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out += ", ";
+            }
+            self.expression(expr, true);
+        }
+    }
+
+    /// Writes the comments before `position` on lines of their own
+    fn comments_before(&mut self, position: usize) {
+        while let Some(comment) = self.comment(self.next_comment) {
+            if comment.start >= position {
+                break;
+            }
+            let (start, end) = (comment.start, comment.end);
+            self.next_comment += 1;
+
+            self.line(start);
+            self.out += self.source[start..end].trim_end();
+            self.out.push('\n');
+            self.last = Some(end);
+        }
+    }
+
+    /// Writes a comment on the same line as the code before it
+    fn trailing_comment(&mut self, after: usize) {
+        if let Some(comment) = self.comment(self.next_comment) {
+            let (start, end) = (comment.start, comment.end);
+            if !self.source[after.min(start)..start].contains('\n') {
+                self.next_comment += 1;
+                self.out += " ";
+                self.out += self.source[start..end].trim_end();
+                self.last = Some(end);
+            }
+        }
+    }
+
+    fn has_comment_before(&self, position: usize) -> bool {
+        self.comment(self.next_comment)
+            .is_some_and(|comment| comment.start < position)
+    }
+
+    fn comment(&self, index: usize) -> Option<&TokenSpan> {
+        self.comments.get(index)
+    }
+
+    /// Starts a line for something at `start` in the source, with a blank
+    /// line before it if there was one. The gap can hold more than
+    /// whitespace, like the `else` a comment before it is moved past
+    fn line(&mut self, start: usize) {
+        if let Some(last) = self.last {
+            let gap: Vec<_> = self.source[last.min(start)..start].split('\n').collect();
+            let blank_line = gap.len() > 2
+                && gap[1..gap.len() - 1]
+                    .iter()
+                    .any(|line| line.trim().is_empty());
+            if self.blank || blank_line {
+                self.out.push('\n');
+            }
+        }
+        self.blank = false;
+        self.indent();
+    }
+
+    fn indent(&mut self) {
+        self.out += &INDENT.repeat(self.indent);
+    }
+
+    /// The first token of a kind at or after `from`
+    fn find(&self, kind: TokenKind, from: usize) -> TokenSpan {
+        self.tokens
+            .iter()
+            .find(|token| token.span.start >= from && token.kind == kind)
+            .map(|token| token.span.clone())
+            .unwrap_or(TokenSpan {
+                start: from,
+                end: from,
+            })
+    }
+
+    /// The first token that isn't a comment at or after `from`
+    fn find_any(&self, from: usize) -> Option<&Token<'s>> {
+        self.tokens
+            .iter()
+            .find(|token| token.span.start >= from && !token.kind.is_comment())
+    }
+}
+
+/// How tightly an expression holds together when it's next to an operator
+fn binding(expr: &ExpressionNode) -> u8 {
+    match &expr.expression {
+        Expression::Infix(_, operator, _) => precedence(operator),
+        Expression::Not(_) | Expression::Pointer(_) => PREFIX,
+        _ => ATOM,
+    }
+}
+
+fn precedence(operator: &Operator) -> u8 {
+    match operator {
+        Operator::Or | Operator::And => 1,
+        Operator::Eq | Operator::Neq => 2,
+        Operator::Lt | Operator::Lte | Operator::Gt | Operator::Gte => 3,
+        Operator::Add | Operator::Sub => 4,
+        Operator::Mul | Operator::Div | Operator::Mod | Operator::Power => 5,
+    }
+}
+
+fn type_params_list(params: &[&str]) -> String {
+    match params.is_empty() {
+        true => String::new(),
+        false => format!("<{}>", params.join(", ")),
+    }
+}
+
+fn join(types: &[AzulaType]) -> String {
+    let types: Vec<_> = types.iter().map(|typ| typ.to_string()).collect();
+    types.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let input = "import \"lib.azl\";
+struct Point{x:int,y:int}
+func main{
+  var a=(1+2)*3-(4-5);
+    const b:[int;3]=[0;3];
+if !(a>1)&&b[0]==0{printf(\"%d\\n\",a);}else if a<0{return;}else{a=&b;}
+
+
+    while (Point{x:1,y:2}).x<a {a=a-1;}
+  for i in 0..a {}
+}";
+
+        assert_eq!(
+            format(input).unwrap(),
+            "import \"lib.azl\";
+
+struct Point {
+    x: int,
+    y: int,
+}
+
+func main {
+    var a = (1 + 2) * 3 - (4 - 5);
+    const b: [int; 3] = [0; 3];
+    if !(a > 1) && b[0] == 0 {
+        printf(\"%d\\n\", a);
+    } else if a < 0 {
+        return;
+    } else {
+        a = &b;
+    }
+
+    while (Point { x: 1, y: 2 }).x < a {
+        a = a - 1;
+    }
+    for i in 0..a {}
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = "// Shapes
+enum Shape {
+    // Round
+    Circle(int), // radius
+    Empty
+}
+#[allow(unused_variables)]
+func main { // entry
+    const x = match s {
+        Circle(r) => r, // round
+        // Nothing
+        _ => { return; }
+    };
+
+    // Done
+}
+// The end";
+
+        let formatted = format(input).unwrap();
+        assert_eq!(
+            formatted,
+            "// Shapes
+enum Shape {
+    // Round
+    Circle(int), // radius
+    Empty,
+}
+
+#[allow(unused_variables)]
+func main {
+    // entry
+    const x = match s {
+        Circle(r) => r, // round
+        // Nothing
+        _ => {
+            return;
+        }
+    };
+
+    // Done
+}
+
+// The end
+"
+        );
+        assert_eq!(format(&formatted).unwrap(), formatted);
+
+        // Comments before an else are moved into it
+        let input = "if a {
+    b();
+} // not a
+// so
+else {
+    c();
+}";
+        assert_eq!(
+            format(input).unwrap(),
+            "if a {
+    b();
+} else {
+    // not a
+    // so
+    c();
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_format_examples() {
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if path.extension().is_none_or(|ext| ext != "azl") {
+                    continue;
+                }
+
+                let source = fs::read_to_string(&path).unwrap();
+                let formatted = format(&source).unwrap();
+                assert_eq!(formatted, source, "{} isn't formatted", path.display());
+                assert_eq!(format(&formatted).unwrap(), formatted);
+            }
+        }
+    }
+}
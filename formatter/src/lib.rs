@@ -0,0 +1,5 @@
+mod formatter;
+
+pub mod prelude {
+    pub use crate::formatter::format;
+}
@@ -0,0 +1,1081 @@
+use std::{collections::HashMap, error::Error, fmt, io::Write, rc::Rc};
+
+use azula_ir::prelude::{Function, GlobalValue, Instruction, Module, Value};
+use azula_type::prelude::AzulaType;
+
+use crate::printf::format;
+
+/// Calls nested deeper than this are reported as a stack overflow
+const MAX_CALL_DEPTH: usize = 100_000;
+
+/// A value produced while interpreting. Integers of every size are kept as
+/// i64, wrapped to their size after each operation, and floats as f64
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    // A string constant and the offset into it
+    Str(Rc<str>, usize),
+    Pointer(Pointer),
+    // Arrays only live behind pointers, like the memory a native backend
+    // would malloc for them
+    Array(Vec<RuntimeValue>),
+    Struct(Vec<RuntimeValue>),
+    Null,
+    Undefined,
+}
+
+/// Points at a stack or heap cell, then at a field or element inside it
+/// for every index in `path`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pointer {
+    pub heap: bool,
+    pub cell: usize,
+    pub path: Vec<usize>,
+}
+
+impl Pointer {
+    fn offset(&self, index: usize) -> Pointer {
+        let mut path = self.path.clone();
+        path.push(index);
+        Pointer {
+            heap: self.heap,
+            cell: self.cell,
+            path,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    UnknownFunction(String),
+    UnknownBlock(String),
+    UnknownVariable(String),
+    UnknownGlobal(String),
+    UnsupportedExtern(String),
+    // The name of the operation given values it can't handle
+    TypeMismatch(String),
+    InvalidFormat(String),
+    DivisionByZero,
+    IndexOutOfBounds(i64, usize),
+    InvalidPointer,
+    NullPointer,
+    Unreachable,
+    MissingTerminator(String),
+    StackOverflow,
+    // Not a failure, `exit` unwinds the program with its status
+    Exit(i64),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::UnknownFunction(name) => write!(f, "Unknown function {}", name),
+            RuntimeError::UnknownBlock(name) => write!(f, "Unknown block {}", name),
+            RuntimeError::UnknownVariable(name) => write!(f, "Unknown variable {}", name),
+            RuntimeError::UnknownGlobal(name) => write!(f, "Unknown global {}", name),
+            RuntimeError::UnsupportedExtern(name) => {
+                write!(
+                    f,
+                    "Extern function {} is not supported by the interpreter",
+                    name
+                )
+            }
+            RuntimeError::TypeMismatch(op) => write!(f, "Invalid values given to {}", op),
+            RuntimeError::InvalidFormat(reason) => write!(f, "Invalid printf format: {}", reason),
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::IndexOutOfBounds(index, len) => {
+                write!(f, "Index {} out of bounds for length {}", index, len)
+            }
+            RuntimeError::InvalidPointer => write!(f, "Invalid pointer"),
+            RuntimeError::NullPointer => write!(f, "Null pointer dereference"),
+            RuntimeError::Unreachable => write!(f, "Reached unreachable code"),
+            RuntimeError::MissingTerminator(block) => {
+                write!(f, "Block {} does not end with a terminator", block)
+            }
+            RuntimeError::StackOverflow => write!(f, "Stack overflow"),
+            RuntimeError::Exit(code) => write!(f, "Exited with status {}", code),
+            RuntimeError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+impl From<std::io::Error> for RuntimeError {
+    fn from(e: std::io::Error) -> Self {
+        RuntimeError::Io(e)
+    }
+}
+
+struct Frame<'m, 'a> {
+    name: &'m str,
+    function: &'m Function<'a>,
+    block: usize,
+    instruction: usize,
+
+    registers: Vec<RuntimeValue>,
+    variables: HashMap<String, usize>,
+    args: Vec<RuntimeValue>,
+    stack_base: usize,
+    // The caller's register receiving the return value
+    dest: Option<usize>,
+    // Registers holding sized integers, whose values are kept wrapped to them
+    widths: HashMap<usize, Width>,
+}
+
+impl<'m, 'a> Frame<'m, 'a> {
+    fn new(
+        name: &'m str,
+        function: &'m Function<'a>,
+        args: Vec<RuntimeValue>,
+        stack_base: usize,
+        dest: Option<usize>,
+    ) -> Self {
+        Self {
+            name,
+            function,
+            block: 0,
+            instruction: 0,
+            registers: vec![],
+            variables: HashMap::new(),
+            args,
+            stack_base,
+            dest,
+            widths: HashMap::new(),
+        }
+    }
+
+    fn get(&self, register: usize) -> RuntimeValue {
+        self.registers
+            .get(register)
+            .cloned()
+            .unwrap_or(RuntimeValue::Undefined)
+    }
+
+    fn set(&mut self, register: usize, value: RuntimeValue) {
+        if register >= self.registers.len() {
+            self.registers.resize(register + 1, RuntimeValue::Undefined);
+        }
+        self.registers[register] = wrap(value, self.widths.get(&register).copied());
+    }
+
+    /// Sets a register to a value of a known type
+    fn set_typed(&mut self, register: usize, value: RuntimeValue, typ: &AzulaType) {
+        self.set_width(register, width(typ));
+        self.set(register, value);
+    }
+
+    fn set_width(&mut self, register: usize, width: Option<Width>) {
+        match width {
+            Some(width) => self.widths.insert(register, width),
+            None => self.widths.remove(&register),
+        };
+    }
+
+    fn width(&self, value: &Value) -> Option<Width> {
+        match value {
+            Value::Local(register) => self.widths.get(register).copied(),
+            _ => None,
+        }
+    }
+}
+
+enum Flow {
+    Next,
+    Jump(String),
+    Call(String, Vec<RuntimeValue>, usize),
+    Return(RuntimeValue),
+}
+
+/// Executes a module directly, without going through a native backend
+pub struct Interpreter<'m, 'a> {
+    module: &'m Module<'a>,
+    output: &'m mut dyn Write,
+    strings: Vec<Rc<str>>,
+    globals: HashMap<String, RuntimeValue>,
+    blocks: HashMap<&'m str, HashMap<&'m str, usize>>,
+
+    stack: Vec<RuntimeValue>,
+    heap: Vec<RuntimeValue>,
+}
+
+impl<'m, 'a> Interpreter<'m, 'a> {
+    pub fn new(module: &'m Module<'a>, output: &'m mut dyn Write) -> Self {
+        let blocks = module
+            .functions
+            .iter()
+            .map(|(name, function)| {
+                let indices = function
+                    .blocks
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (block, _))| (block.as_str(), index))
+                    .collect();
+                (name.as_str(), indices)
+            })
+            .collect();
+
+        let mut interpreter = Self {
+            module,
+            output,
+            strings: module
+                .strings
+                .iter()
+                .map(|s| Rc::from(s.as_str()))
+                .collect(),
+            globals: HashMap::new(),
+            blocks,
+            stack: vec![],
+            heap: vec![],
+        };
+
+        for (name, value) in &module.global_values {
+            let value = interpreter.global_value(value);
+            interpreter.globals.insert(name.clone(), value);
+        }
+
+        interpreter
+    }
+
+    /// Runs the module's main function, returning its result, or the status
+    /// given to `exit` if it's called
+    pub fn run(&mut self) -> Result<RuntimeValue, RuntimeError> {
+        let result = match self.call("main", vec![]) {
+            Err(RuntimeError::Exit(code)) => Ok(RuntimeValue::Int(code)),
+            result => result,
+        };
+        self.output.flush()?;
+        result
+    }
+
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError> {
+        let module = self.module;
+        let (name, function) = match module.functions.get_key_value(name) {
+            Some(function) => function,
+            None => return self.call_extern(name, args),
+        };
+
+        // Calls made by the program push onto this rather than recursing, so
+        // deep recursion in Azula doesn't overflow the interpreter's stack
+        let stack_base = self.stack.len();
+        let mut frames = vec![Frame::new(name, function, args, stack_base, None)];
+        let result = self.execute_frames(&mut frames);
+
+        // Variables die with their frame, arrays are on the heap and outlive it
+        self.stack.truncate(stack_base);
+
+        result
+    }
+
+    fn execute_frames(
+        &mut self,
+        frames: &mut Vec<Frame<'m, 'a>>,
+    ) -> Result<RuntimeValue, RuntimeError> {
+        let module = self.module;
+        loop {
+            let frame = frames.last_mut().unwrap();
+            let function = frame.function;
+            let (block_name, block) = &function.blocks[frame.block];
+            let instruction = match block.instructions.get(frame.instruction) {
+                Some(instruction) => instruction,
+                None => return Err(RuntimeError::MissingTerminator(block_name.clone())),
+            };
+            frame.instruction += 1;
+
+            match self.execute(instruction, frame)? {
+                Flow::Next => {}
+                Flow::Jump(target) => {
+                    frame.block = match self.blocks[frame.name].get(target.as_str()) {
+                        Some(index) => *index,
+                        None => return Err(RuntimeError::UnknownBlock(target)),
+                    };
+                    frame.instruction = 0;
+                }
+                Flow::Call(name, args, dest) => match module.functions.get_key_value(&name) {
+                    Some((name, function)) => {
+                        if frames.len() == MAX_CALL_DEPTH {
+                            return Err(RuntimeError::StackOverflow);
+                        }
+                        let stack_base = self.stack.len();
+                        frames.push(Frame::new(name, function, args, stack_base, Some(dest)));
+                    }
+                    None => {
+                        let result = self.call_extern(&name, args)?;
+                        frame.set(dest, result);
+                    }
+                },
+                Flow::Return(value) => {
+                    let frame = frames.pop().unwrap();
+                    self.stack.truncate(frame.stack_base);
+                    match frame.dest {
+                        Some(dest) => frames.last_mut().unwrap().set(dest, value),
+                        None => return Ok(value),
+                    }
+                }
+            }
+        }
+    }
+
+    fn execute(
+        &mut self,
+        instruction: &Instruction<'a>,
+        frame: &mut Frame<'m, 'a>,
+    ) -> Result<Flow, RuntimeError> {
+        match instruction {
+            Instruction::Load(name, dest, typ) => {
+                let cell = match frame.variables.get(name) {
+                    Some(cell) => *cell,
+                    None => return Err(RuntimeError::UnknownVariable(name.clone())),
+                };
+                frame.set_typed(*dest, self.stack[cell].clone(), typ);
+            }
+            Instruction::LoadGlobal(name, dest, typ) => {
+                let value = match self.globals.get(name) {
+                    Some(value) => value.clone(),
+                    None => return Err(RuntimeError::UnknownGlobal(name.clone())),
+                };
+                frame.set_typed(*dest, value, typ);
+            }
+            Instruction::Store(name, value, typ) => {
+                let value = wrap(self.value(value, frame), width(typ));
+                let cell = self.variable(name, frame);
+                self.stack[cell] = value;
+            }
+            Instruction::LoadArg(index, dest, typ) => {
+                let value = frame
+                    .args
+                    .get(*index)
+                    .cloned()
+                    .unwrap_or(RuntimeValue::Undefined);
+                frame.set_typed(*dest, value, typ);
+            }
+            Instruction::ConstInt(val, dest) => frame.set(*dest, RuntimeValue::Int(*val)),
+            Instruction::ConstFloat(val, dest) => frame.set(*dest, RuntimeValue::Float(*val)),
+            Instruction::ConstTrue(dest) => frame.set(*dest, RuntimeValue::Bool(true)),
+            Instruction::ConstFalse(dest) => frame.set(*dest, RuntimeValue::Bool(false)),
+            Instruction::ConstNull(dest) => frame.set(*dest, RuntimeValue::Null),
+            Instruction::Add(val1, val2, dest)
+            | Instruction::Sub(val1, val2, dest)
+            | Instruction::Mul(val1, val2, dest)
+            | Instruction::Div(val1, val2, dest)
+            | Instruction::Mod(val1, val2, dest)
+            | Instruction::Pow(val1, val2, dest) => {
+                // Constants take the size of what they're used with
+                let width = frame.width(val1).or(frame.width(val2));
+                let val1 = self.value(val1, frame);
+                let val2 = self.value(val2, frame);
+                frame.set_width(*dest, width);
+                frame.set(*dest, arithmetic(instruction, val1, val2)?);
+            }
+            Instruction::Or(val1, val2, dest) | Instruction::And(val1, val2, dest) => {
+                let val1 = self.value(val1, frame);
+                let val2 = self.value(val2, frame);
+                let result = match (val1, val2) {
+                    (RuntimeValue::Bool(val1), RuntimeValue::Bool(val2)) => {
+                        if matches!(instruction, Instruction::Or(..)) {
+                            val1 || val2
+                        } else {
+                            val1 && val2
+                        }
+                    }
+                    _ => return Err(mismatch(instruction)),
+                };
+                frame.set(*dest, RuntimeValue::Bool(result));
+            }
+            Instruction::Eq(val1, val2, dest)
+            | Instruction::Neq(val1, val2, dest)
+            | Instruction::Gt(val1, val2, dest)
+            | Instruction::Gte(val1, val2, dest)
+            | Instruction::Lt(val1, val2, dest)
+            | Instruction::Lte(val1, val2, dest) => {
+                let val1 = self.value(val1, frame);
+                let val2 = self.value(val2, frame);
+                frame.set(*dest, RuntimeValue::Bool(compare(instruction, val1, val2)?));
+            }
+            Instruction::Not(val, dest) => {
+                let width = frame.width(val);
+                let result = match self.value(val, frame) {
+                    RuntimeValue::Bool(val) => RuntimeValue::Bool(!val),
+                    RuntimeValue::Int(val) => RuntimeValue::Int(!val),
+                    _ => return Err(mismatch(instruction)),
+                };
+                frame.set_width(*dest, width);
+                frame.set(*dest, result);
+            }
+            Instruction::Return(val) => {
+                let value = match val {
+                    Some(val) => wrap(self.value(val, frame), width(&frame.function.returns)),
+                    None => RuntimeValue::Undefined,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Instruction::FunctionCall(name, args, dest) => {
+                let returns = match self.module.functions.get(name) {
+                    Some(function) => Some(&function.returns),
+                    None => self
+                        .module
+                        .extern_functions
+                        .get(name.as_str())
+                        .map(|function| &function.returns),
+                };
+                frame.set_width(*dest, returns.and_then(width));
+
+                let args = args.iter().map(|arg| self.value(arg, frame)).collect();
+                return Ok(Flow::Call(name.clone(), args, *dest));
+            }
+            Instruction::Jcond(cond, true_block, false_block) => {
+                return match self.value(cond, frame) {
+                    RuntimeValue::Bool(true) => Ok(Flow::Jump(true_block.clone())),
+                    RuntimeValue::Bool(false) => Ok(Flow::Jump(false_block.clone())),
+                    _ => Err(mismatch(instruction)),
+                };
+            }
+            Instruction::Jump(block) => return Ok(Flow::Jump(block.clone())),
+            Instruction::Switch(val, cases, default) => {
+                let val = match self.value(val, frame) {
+                    RuntimeValue::Int(val) => val,
+                    _ => return Err(mismatch(instruction)),
+                };
+                let target = cases
+                    .iter()
+                    .find(|(case, _)| *case == val)
+                    .map(|(_, block)| block)
+                    .unwrap_or(default);
+                return Ok(Flow::Jump(target.clone()));
+            }
+            Instruction::Unreachable => return Err(RuntimeError::Unreachable),
+            Instruction::Pointer(name, dest) => {
+                let cell = self.variable(name, frame);
+                frame.set(
+                    *dest,
+                    RuntimeValue::Pointer(Pointer {
+                        heap: false,
+                        cell,
+                        path: vec![],
+                    }),
+                );
+            }
+            Instruction::CreateArray(typ, size, dest) => {
+                let array = RuntimeValue::Array(vec![self.zero(typ); *size]);
+                let pointer = self.allocate(array);
+                frame.set(*dest, pointer);
+            }
+            Instruction::StoreElement(array, index, val) => {
+                let pointer = self.element(array, index, frame)?;
+                let value = self.value(val, frame);
+                *self.place(&pointer)? = value;
+            }
+            Instruction::AccessElement(array, index, dest) => {
+                let value = match (self.value(array, frame), self.value(index, frame)) {
+                    // Indexing a string gives its bytes, with the terminating 0
+                    (RuntimeValue::Str(string, offset), RuntimeValue::Int(index)) => {
+                        let bytes = string.as_bytes();
+                        let position = offset as i64 + index;
+                        if position < 0 || position as usize > bytes.len() {
+                            return Err(RuntimeError::IndexOutOfBounds(index, bytes.len()));
+                        }
+                        RuntimeValue::Int(*bytes.get(position as usize).unwrap_or(&0) as i64)
+                    }
+                    _ => {
+                        let pointer = self.element(array, index, frame)?;
+                        self.place(&pointer)?.clone()
+                    }
+                };
+                frame.set(*dest, value);
+            }
+            Instruction::CreateStruct(name, values, dest) => {
+                let mut fields: Vec<_> = values.iter().map(|val| self.value(val, frame)).collect();
+                if let Some(struc) = self.module.structs.get(name) {
+                    fields.resize(struc.attributes.len(), RuntimeValue::Undefined);
+                }
+                frame.set(*dest, RuntimeValue::Struct(fields));
+            }
+            Instruction::CreateEnum(name, tag, values, dest) => {
+                let enu = match self.module.enums.get(name.as_str()) {
+                    Some(enu) => enu,
+                    None => return Err(RuntimeError::TypeMismatch(name.clone())),
+                };
+                let mut fields: Vec<_> = enu.fields().iter().map(|typ| self.zero(typ)).collect();
+                fields[0] = RuntimeValue::Int(*tag as i64);
+                for (index, val) in values.iter().enumerate() {
+                    fields[enu.field_index(*tag, index)] = self.value(val, frame);
+                }
+                frame.set(*dest, RuntimeValue::Struct(fields));
+            }
+            Instruction::StoreStructMember(struc, index, val) => {
+                let value = self.value(val, frame);
+                match self.value(struc, frame) {
+                    // Struct values are updated in their register, like an
+                    // insertvalue replacing it
+                    RuntimeValue::Struct(mut fields) => {
+                        let register = match struc {
+                            Value::Local(register) => *register,
+                            _ => return Err(mismatch(instruction)),
+                        };
+                        match fields.get_mut(*index) {
+                            Some(field) => *field = value,
+                            None => return Err(RuntimeError::InvalidPointer),
+                        }
+                        frame.set(register, RuntimeValue::Struct(fields));
+                    }
+                    RuntimeValue::Pointer(pointer) => {
+                        *self.place(&pointer.offset(*index))? = value;
+                    }
+                    RuntimeValue::Null => return Err(RuntimeError::NullPointer),
+                    _ => return Err(mismatch(instruction)),
+                }
+            }
+            Instruction::AccessStructMember(struc, index, dest, resolve) => {
+                let value = match self.value(struc, frame) {
+                    RuntimeValue::Struct(fields) => match fields.get(*index) {
+                        Some(field) => field.clone(),
+                        None => return Err(RuntimeError::InvalidPointer),
+                    },
+                    RuntimeValue::Pointer(pointer) => {
+                        let pointer = pointer.offset(*index);
+                        if *resolve {
+                            self.place(&pointer)?.clone()
+                        } else {
+                            RuntimeValue::Pointer(pointer)
+                        }
+                    }
+                    RuntimeValue::Null => return Err(RuntimeError::NullPointer),
+                    _ => return Err(mismatch(instruction)),
+                };
+                frame.set(*dest, value);
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn call_extern(
+        &mut self,
+        name: &str,
+        args: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError> {
+        if !self.module.extern_functions.contains_key(name) {
+            return Err(RuntimeError::UnknownFunction(name.to_string()));
+        }
+
+        match name {
+            "printf" => {
+                let (fmt, args) = match args.split_first() {
+                    Some((RuntimeValue::Str(fmt, offset), args)) => (&fmt[*offset..], args),
+                    _ => return Err(RuntimeError::TypeMismatch("printf".to_string())),
+                };
+                let output = format(fmt, args)?;
+                self.output.write_all(output.as_bytes())?;
+                Ok(RuntimeValue::Int(output.len() as i64))
+            }
+            "puts" => match args.first() {
+                Some(RuntimeValue::Str(string, offset)) => {
+                    writeln!(self.output, "{}", &string[*offset..])?;
+                    Ok(RuntimeValue::Int(0))
+                }
+                _ => Err(RuntimeError::TypeMismatch("puts".to_string())),
+            },
+            "putchar" => match args.first() {
+                Some(RuntimeValue::Int(c)) => {
+                    self.output.write_all(&[*c as u8])?;
+                    Ok(RuntimeValue::Int(*c))
+                }
+                _ => Err(RuntimeError::TypeMismatch("putchar".to_string())),
+            },
+            "exit" => match args.first() {
+                Some(RuntimeValue::Int(code)) => Err(RuntimeError::Exit(*code)),
+                _ => Err(RuntimeError::TypeMismatch("exit".to_string())),
+            },
+            _ => Err(RuntimeError::UnsupportedExtern(name.to_string())),
+        }
+    }
+
+    fn value(&self, value: &Value, frame: &Frame) -> RuntimeValue {
+        match value {
+            Value::LiteralInteger(val) => RuntimeValue::Int(*val),
+            Value::LiteralBoolean(val) => RuntimeValue::Bool(*val),
+            Value::Local(register) => frame.get(*register),
+            Value::Global(string) => RuntimeValue::Str(self.strings[*string].clone(), 0),
+        }
+    }
+
+    /// The stack cell holding a variable, allocated on first use
+    fn variable(&mut self, name: &str, frame: &mut Frame) -> usize {
+        if let Some(cell) = frame.variables.get(name) {
+            return *cell;
+        }
+
+        self.stack.push(RuntimeValue::Undefined);
+        frame
+            .variables
+            .insert(name.to_string(), self.stack.len() - 1);
+        self.stack.len() - 1
+    }
+
+    fn allocate(&mut self, value: RuntimeValue) -> RuntimeValue {
+        self.heap.push(value);
+        RuntimeValue::Pointer(Pointer {
+            heap: true,
+            cell: self.heap.len() - 1,
+            path: vec![],
+        })
+    }
+
+    fn element(
+        &self,
+        array: &Value,
+        index: &Value,
+        frame: &Frame<'m, 'a>,
+    ) -> Result<Pointer, RuntimeError> {
+        let index = match self.value(index, frame) {
+            RuntimeValue::Int(index) if index >= 0 => index as usize,
+            RuntimeValue::Int(index) => return Err(RuntimeError::IndexOutOfBounds(index, 0)),
+            _ => return Err(RuntimeError::TypeMismatch("array index".to_string())),
+        };
+
+        match self.value(array, frame) {
+            RuntimeValue::Pointer(pointer) => Ok(pointer.offset(index)),
+            RuntimeValue::Null => Err(RuntimeError::NullPointer),
+            _ => Err(RuntimeError::TypeMismatch("array access".to_string())),
+        }
+    }
+
+    /// The value a pointer refers to. Indexing a pointer to a single value
+    /// at 0 gives the value itself
+    fn place(&mut self, pointer: &Pointer) -> Result<&mut RuntimeValue, RuntimeError> {
+        let memory = if pointer.heap {
+            &mut self.heap
+        } else {
+            &mut self.stack
+        };
+
+        let mut place = match memory.get_mut(pointer.cell) {
+            Some(place) => place,
+            None => return Err(RuntimeError::InvalidPointer),
+        };
+        for index in &pointer.path {
+            place = match place {
+                RuntimeValue::Array(values) => {
+                    let len = values.len();
+                    match values.get_mut(*index) {
+                        Some(value) => value,
+                        None => return Err(RuntimeError::IndexOutOfBounds(*index as i64, len)),
+                    }
+                }
+                RuntimeValue::Struct(fields) => match fields.get_mut(*index) {
+                    Some(field) => field,
+                    None => return Err(RuntimeError::InvalidPointer),
+                },
+                place if *index == 0 => place,
+                _ => return Err(RuntimeError::InvalidPointer),
+            };
+        }
+
+        Ok(place)
+    }
+
+    fn global_value(&mut self, value: &GlobalValue) -> RuntimeValue {
+        match value {
+            GlobalValue::Int(val) => RuntimeValue::Int(*val),
+            GlobalValue::Float(val) => RuntimeValue::Float(*val),
+            GlobalValue::Bool(val) => RuntimeValue::Bool(*val),
+            GlobalValue::String(string) => RuntimeValue::Str(self.strings[*string].clone(), 0),
+            GlobalValue::Array(values) => {
+                let values = values.iter().map(|val| self.global_value(val)).collect();
+                self.allocate(RuntimeValue::Array(values))
+            }
+        }
+    }
+
+    /// The value memory of a type starts out with, so members of structs
+    /// in fresh arrays can be assigned to
+    fn zero(&self, typ: &AzulaType<'a>) -> RuntimeValue {
+        match typ {
+            AzulaType::Int | AzulaType::SizedSignedInt(_) | AzulaType::SizedUnsignedInt(_) => {
+                RuntimeValue::Int(0)
+            }
+            AzulaType::Float | AzulaType::SizedFloat(_) => RuntimeValue::Float(0.0),
+            AzulaType::Bool => RuntimeValue::Bool(false),
+            AzulaType::Pointer(_) | AzulaType::Array(..) => RuntimeValue::Null,
+            AzulaType::Named(name) => {
+                if let Some(struc) = self.module.structs.get(name) {
+                    RuntimeValue::Struct(
+                        struc
+                            .attributes
+                            .iter()
+                            .map(|(typ, _)| self.zero(typ))
+                            .collect(),
+                    )
+                } else if let Some(enu) = self.module.enums.get(name.as_str()) {
+                    RuntimeValue::Struct(enu.fields().iter().map(|typ| self.zero(typ)).collect())
+                } else {
+                    RuntimeValue::Undefined
+                }
+            }
+            _ => RuntimeValue::Undefined,
+        }
+    }
+}
+
+/// The bits in a sized integer and whether it's signed
+type Width = (usize, bool);
+
+fn width(typ: &AzulaType) -> Option<Width> {
+    match typ {
+        AzulaType::SizedSignedInt(bits) => Some((*bits, true)),
+        AzulaType::SizedUnsignedInt(bits) => Some((*bits, false)),
+        _ => None,
+    }
+}
+
+/// Wraps an integer to its size, as a native backend's fixed width
+/// arithmetic would
+fn wrap(value: RuntimeValue, width: Option<Width>) -> RuntimeValue {
+    match (value, width) {
+        (RuntimeValue::Int(val), Some((bits, signed))) if bits < 64 => {
+            let shift = 64 - bits;
+            let wrapped = if signed {
+                (val << shift) >> shift
+            } else {
+                (((val as u64) << shift) >> shift) as i64
+            };
+            RuntimeValue::Int(wrapped)
+        }
+        (value, _) => value,
+    }
+}
+
+fn mismatch(instruction: &Instruction) -> RuntimeError {
+    let name = format!("{}", instruction);
+    let op = name
+        .split_whitespace()
+        .find(|word| !word.starts_with('%'))
+        .unwrap_or("")
+        .to_string();
+    RuntimeError::TypeMismatch(op)
+}
+
+fn arithmetic(
+    instruction: &Instruction,
+    val1: RuntimeValue,
+    val2: RuntimeValue,
+) -> Result<RuntimeValue, RuntimeError> {
+    match (val1, val2) {
+        (RuntimeValue::Int(val1), RuntimeValue::Int(val2)) => {
+            let result = match instruction {
+                Instruction::Add(..) => val1.wrapping_add(val2),
+                Instruction::Sub(..) => val1.wrapping_sub(val2),
+                Instruction::Mul(..) => val1.wrapping_mul(val2),
+                Instruction::Div(..) | Instruction::Mod(..) if val2 == 0 => {
+                    return Err(RuntimeError::DivisionByZero)
+                }
+                Instruction::Div(..) => val1.wrapping_div(val2),
+                Instruction::Mod(..) => val1.wrapping_rem(val2),
+                Instruction::Pow(..) if val2 < 0 => {
+                    return Ok(RuntimeValue::Int((val1 as f64).powf(val2 as f64) as i64))
+                }
+                Instruction::Pow(..) => val1.wrapping_pow(val2 as u32),
+                _ => unreachable!(),
+            };
+            Ok(RuntimeValue::Int(result))
+        }
+        (RuntimeValue::Float(val1), RuntimeValue::Float(val2)) => {
+            let result = match instruction {
+                Instruction::Add(..) => val1 + val2,
+                Instruction::Sub(..) => val1 - val2,
+                Instruction::Mul(..) => val1 * val2,
+                Instruction::Div(..) => val1 / val2,
+                Instruction::Mod(..) => val1 % val2,
+                Instruction::Pow(..) => val1.powf(val2),
+                _ => unreachable!(),
+            };
+            Ok(RuntimeValue::Float(result))
+        }
+        _ => Err(mismatch(instruction)),
+    }
+}
+
+fn compare(
+    instruction: &Instruction,
+    val1: RuntimeValue,
+    val2: RuntimeValue,
+) -> Result<bool, RuntimeError> {
+    let ordering = match (&val1, &val2) {
+        (RuntimeValue::Int(val1), RuntimeValue::Int(val2)) => val1.partial_cmp(val2),
+        (RuntimeValue::Float(val1), RuntimeValue::Float(val2)) => val1.partial_cmp(val2),
+        (RuntimeValue::Bool(val1), RuntimeValue::Bool(val2)) => val1.partial_cmp(val2),
+        _ => None,
+    };
+
+    match instruction {
+        Instruction::Eq(..) => Ok(val1 == val2),
+        Instruction::Neq(..) => Ok(val1 != val2),
+        _ => {
+            let ordering = match ordering {
+                Some(ordering) => ordering,
+                None => return Err(mismatch(instruction)),
+            };
+            Ok(match instruction {
+                Instruction::Gt(..) => ordering.is_gt(),
+                Instruction::Gte(..) => ordering.is_ge(),
+                Instruction::Lt(..) => ordering.is_lt(),
+                Instruction::Lte(..) => ordering.is_le(),
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use azula_ir::{
+        passes::PassManager,
+        prelude::{parse_module, verify},
+    };
+    use azula_resolver::prelude::Resolver;
+
+    use super::*;
+
+    fn run(module: &Module) -> (Result<RuntimeValue, RuntimeError>, String) {
+        let mut output = vec![];
+        let result = Interpreter::new(module, &mut output).run();
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    /// Interprets a file, checking optimising it doesn't change what it does
+    fn interpret(path: &Path) -> (Result<RuntimeValue, RuntimeError>, String) {
+        interpret_with(Resolver::new(), path)
+    }
+
+    fn interpret_with(
+        mut resolver: Resolver,
+        path: &Path,
+    ) -> (Result<RuntimeValue, RuntimeError>, String) {
+        let mut module = resolver.resolve(path.to_str().unwrap()).unwrap();
+        let (result, output) = run(&module);
+
+        PassManager::optimisations().run(&mut module, |_, _| {});
+        assert!(verify(&module).is_ok());
+        let (optimised_result, optimised_output) = run(&module);
+        assert_eq!(optimised_output, output);
+        assert_eq!(format!("{:?}", optimised_result), format!("{:?}", result));
+
+        (result, output)
+    }
+
+    fn interpret_source(name: &str, source: &str) -> (Result<RuntimeValue, RuntimeError>, String) {
+        let path = std::env::temp_dir().join(format!("{}.azl", name));
+        fs::write(&path, source).unwrap();
+        interpret(&path)
+    }
+
+    #[test]
+    fn test_interpret_examples() {
+        let tests = vec![
+            ("factorial.azl", "3628800\n"),
+            ("loops.azl", "sum of 0..10 is 45\n2 is prime\n3 is prime\n5 is prime\n7 is prime\n11 is prime\n1 is odd\n3 is odd\n5 is odd\n7 is odd\n"),
+            ("methods.azl", "9\n"),
+            ("enums.azl", "area is 12\narea is 12\nempty\n"),
+            ("generics.azl", "7\n2.500000\n1\n"),
+            ("modules/main.azl", ""),
+            ("testing.azl", "6\n"),
+        ];
+
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        for (example, expected) in tests {
+            let (result, output) = interpret(&examples.join(example));
+            assert!(result.is_ok(), "{}: {}", example, result.unwrap_err());
+            if !expected.is_empty() {
+                assert_eq!(output, expected, "{}", example);
+            }
+        }
+
+        let (_, output) = interpret(&examples.join("fizzbuzz.azl"));
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 101);
+        assert_eq!(lines[..6], ["fizzbuzz", "1", "2", "fizz", "4", "buzz"]);
+    }
+
+    #[test]
+    fn test_interpret_recursion() {
+        let (result, output) = interpret_source(
+            "azula_interpreter_fib",
+            "func fib(n: int): int {
+                if n <= 1 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+            func main {
+                printf(\"%d %d %d %d\\n\", fib(0), fib(1), fib(10), fib(20));
+            }",
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output, "0 1 55 6765\n");
+    }
+
+    #[test]
+    fn test_interpret_sized_ints() {
+        // Source can't make a sized integer yet, so this is run from IR the
+        // way `azula run --interpret` runs a `.azir` file
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/sized_ints.azir");
+        let source = fs::read_to_string(path).unwrap();
+        let mut module = parse_module(&source).unwrap();
+        assert!(verify(&module).is_ok());
+
+        let (result, output) = run(&module);
+        assert!(result.is_ok());
+        assert_eq!(output, "-56 4 44\n");
+
+        PassManager::optimisations().run(&mut module, |_, _| {});
+        assert_eq!(run(&module).1, output);
+    }
+
+    #[test]
+    fn test_interpret_loop_shadowing() {
+        let (result, output) = interpret_source(
+            "azula_interpreter_shadowing",
+            "func count(n: int): int {
+                var total = 0;
+                for n in 0..n {
+                    total = total + n;
+                }
+                return total + n;
+            }
+            func main {
+                const i = 100;
+                for i in 0..3 { }
+                const flag = true;
+                for flag in [7, 8] {
+                    printf(\"%d \", flag);
+                }
+                if flag {
+                    printf(\"%d %d\\n\", i, count(4));
+                }
+            }",
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output, "7 8 100 10\n");
+    }
+
+    #[test]
+    fn test_interpret_memory() {
+        let (result, output) = interpret_source(
+            "azula_interpreter_memory",
+            "struct Point { x: int, y: int }
+            func fill(points: [Point; 3]) {
+                for i in 0..3 {
+                    points[i] = Point { x: i, y: i * i };
+                }
+            }
+            func main: int {
+                const points = [Point { x: 0, y: 0 }, Point { x: 0, y: 0 }, Point { x: 0, y: 0 }];
+                fill(points);
+                var total = 0;
+                for p in points {
+                    total = total + p.x + p.y;
+                }
+                const name = \"azula\";
+                printf(\"%s %d %c\\n\", name, total, name[1]);
+                return total;
+            }",
+        );
+
+        assert_eq!(output, "azula 8 z\n");
+        assert!(matches!(result, Ok(RuntimeValue::Int(8))));
+    }
+
+    #[test]
+    fn test_interpret_errors() {
+        let tests = vec![
+            (
+                "func main { var x = 0; printf(\"%d\", 1 / x); }",
+                RuntimeError::DivisionByZero,
+            ),
+            (
+                "func main { const a = [1, 2]; var i = 2; printf(\"%d\", a[i]); }",
+                RuntimeError::IndexOutOfBounds(2, 2),
+            ),
+            (
+                "func forever(n: int): int { return forever(n + 1); } func main { forever(0); }",
+                RuntimeError::StackOverflow,
+            ),
+        ];
+
+        for (index, (source, expected)) in tests.into_iter().enumerate() {
+            let (result, _) =
+                interpret_source(&format!("azula_interpreter_error{}", index), source);
+            let error = result.unwrap_err();
+            assert_eq!(format!("{}", error), format!("{}", expected));
+        }
+    }
+
+    #[test]
+    fn test_interpret_tests() {
+        let mut resolver = Resolver::new();
+        resolver.tests = true;
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/testing.azl");
+        let (result, output) = interpret_with(resolver, &path);
+        assert_eq!(
+            output,
+            "running 2 tests\ntest gcd_of_coprimes ... ok\ntest gcd_is_symmetric ... ok\n\ntest result: ok. 2 passed; 0 failed\n"
+        );
+        assert!(matches!(result, Ok(RuntimeValue::Int(0))));
+
+        let path = std::env::temp_dir().join("azula_interpreter_tests.azl");
+        fs::write(
+            &path,
+            "func main { }
+            #[test] func passes { assert(true, \"never printed\"); return; }
+            #[test] func fails {
+                const name = \"second\";
+                var assertion = 1;
+                if true {
+                    assert(assertion == 1, \"first\");
+                    assertion = 2;
+                    assert(assertion == 3, name);
+                }
+                printf(\"unreachable\\n\");
+            }",
+        )
+        .unwrap();
+        let mut resolver = Resolver::new();
+        resolver.tests = true;
+        let (result, output) = interpret_with(resolver, &path);
+        assert_eq!(
+            output,
+            "running 2 tests\ntest passes ... ok\n    assertion failed on line 9: second\ntest fails ... FAILED\n\ntest result: FAILED. 1 passed; 1 failed\n"
+        );
+        assert!(matches!(result, Ok(RuntimeValue::Int(1))));
+
+        // Helpers can assert too, but a failure there ends the whole run
+        fs::write(
+            &path,
+            "func main { }
+            func check(x: int) { assert(x > 0, \"not positive\"); }
+            #[test] func first { check(1); }
+            #[test] func second { check(0); }
+            #[test] func third { }",
+        )
+        .unwrap();
+        let mut resolver = Resolver::new();
+        resolver.tests = true;
+        let (result, output) = interpret_with(resolver, &path);
+        assert_eq!(
+            output,
+            "running 3 tests\ntest first ... ok\n    assertion failed on line 2: not positive\n"
+        );
+        assert!(matches!(result, Ok(RuntimeValue::Int(1))));
+    }
+}
@@ -0,0 +1,6 @@
+mod interpreter;
+mod printf;
+
+pub mod prelude {
+    pub use crate::interpreter::{Interpreter, Pointer, RuntimeError, RuntimeValue};
+}
@@ -0,0 +1,335 @@
+use crate::interpreter::{RuntimeError, RuntimeValue};
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Formats printf arguments the way the C library would for the
+/// conversions Azula programs can produce
+pub fn format(fmt: &str, args: &[RuntimeValue]) -> Result<String, RuntimeError> {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            spec.width = int_arg(args.next())? as usize;
+        } else {
+            spec.width = number(&mut chars);
+        }
+
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                spec.precision = Some(int_arg(args.next())? as usize);
+            } else {
+                spec.precision = Some(number(&mut chars));
+            }
+        }
+
+        // Every value is already 64 bit, so the length is ignored
+        while matches!(chars.peek(), Some('h' | 'l' | 'L' | 'q' | 'j' | 'z' | 't')) {
+            chars.next();
+        }
+
+        let conversion = match chars.next() {
+            Some(conversion) => conversion,
+            None => return Err(RuntimeError::InvalidFormat("trailing %".to_string())),
+        };
+
+        let formatted = match conversion {
+            '%' => {
+                output.push('%');
+                continue;
+            }
+            'd' | 'i' => {
+                let val = int_arg(args.next())?;
+                let digits = with_precision(val.unsigned_abs().to_string(), &spec);
+                signed(val < 0, digits, &spec, spec.precision.is_none())
+            }
+            'u' | 'x' | 'X' | 'o' => {
+                let val = int_arg(args.next())? as u64;
+                let digits = match conversion {
+                    'u' => val.to_string(),
+                    'x' => format!("{:x}", val),
+                    'X' => format!("{:X}", val),
+                    _ => format!("{:o}", val),
+                };
+                let digits = with_precision(digits, &spec);
+                let prefix = match conversion {
+                    'x' if spec.alternate && val != 0 => "0x",
+                    'X' if spec.alternate && val != 0 => "0X",
+                    'o' if spec.alternate && !digits.starts_with('0') => "0",
+                    _ => "",
+                };
+                pad_number(prefix, digits, &spec, spec.precision.is_none())
+            }
+            'c' => pad((int_arg(args.next())? as u8 as char).to_string(), &spec),
+            's' => {
+                let string = match args.next() {
+                    Some(RuntimeValue::Str(string, offset)) => string[*offset..].to_string(),
+                    Some(RuntimeValue::Null) => "(null)".to_string(),
+                    _ => return Err(RuntimeError::TypeMismatch("printf %s".to_string())),
+                };
+                let string = match spec.precision {
+                    Some(precision) => string.chars().take(precision).collect(),
+                    None => string,
+                };
+                pad(string, &spec)
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let val = match args.next() {
+                    Some(RuntimeValue::Float(val)) => *val,
+                    _ => return Err(RuntimeError::TypeMismatch("printf %f".to_string())),
+                };
+                let digits = float(val.abs(), conversion, &spec);
+                signed(
+                    val.is_sign_negative() && !val.is_nan(),
+                    digits,
+                    &spec,
+                    val.is_finite(),
+                )
+            }
+            'p' => match args.next() {
+                Some(RuntimeValue::Pointer(pointer)) => pad(format!("0x{:x}", pointer.cell), &spec),
+                Some(RuntimeValue::Null) => pad("(nil)".to_string(), &spec),
+                _ => return Err(RuntimeError::TypeMismatch("printf %p".to_string())),
+            },
+            _ => {
+                return Err(RuntimeError::InvalidFormat(format!(
+                    "unknown conversion %{}",
+                    conversion
+                )))
+            }
+        };
+
+        output.push_str(&formatted);
+    }
+
+    Ok(output)
+}
+
+fn number(chars: &mut std::iter::Peekable<std::str::Chars>) -> usize {
+    let mut number = 0;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = number * 10 + digit as usize;
+        chars.next();
+    }
+    number
+}
+
+fn int_arg(arg: Option<&RuntimeValue>) -> Result<i64, RuntimeError> {
+    match arg {
+        Some(RuntimeValue::Int(val)) => Ok(*val),
+        Some(RuntimeValue::Bool(val)) => Ok(*val as i64),
+        Some(_) => Err(RuntimeError::TypeMismatch("printf %d".to_string())),
+        None => Err(RuntimeError::InvalidFormat("missing argument".to_string())),
+    }
+}
+
+fn with_precision(digits: String, spec: &Spec) -> String {
+    match spec.precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(precision) if digits.len() < precision => {
+            format!("{}{}", "0".repeat(precision - digits.len()), digits)
+        }
+        _ => digits,
+    }
+}
+
+fn signed(negative: bool, digits: String, spec: &Spec, zeros: bool) -> String {
+    let sign = if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    };
+    pad_number(sign, digits, spec, zeros)
+}
+
+/// Pads a number to the width, putting zeros between its sign or prefix and
+/// its digits when the `0` flag applies to it
+fn pad_number(prefix: &str, digits: String, spec: &Spec, zeros: bool) -> String {
+    let len = prefix.len() + digits.len();
+    if zeros && spec.zero && !spec.left && len < spec.width {
+        format!("{}{}{}", prefix, "0".repeat(spec.width - len), digits)
+    } else {
+        pad(format!("{}{}", prefix, digits), spec)
+    }
+}
+
+fn pad(string: String, spec: &Spec) -> String {
+    let len = string.chars().count();
+    if len >= spec.width {
+        string
+    } else if spec.left {
+        format!("{}{}", string, " ".repeat(spec.width - len))
+    } else {
+        format!("{}{}", " ".repeat(spec.width - len), string)
+    }
+}
+
+fn float(val: f64, conversion: char, spec: &Spec) -> String {
+    let upper = conversion.is_ascii_uppercase();
+    if val.is_nan() || val.is_infinite() {
+        let name = if val.is_nan() { "nan" } else { "inf" };
+        return if upper {
+            name.to_uppercase()
+        } else {
+            name.to_string()
+        };
+    }
+
+    let precision = spec.precision.unwrap_or(6);
+    let formatted = match conversion {
+        'f' | 'F' => format!("{:.*}", precision, val),
+        'e' | 'E' => exponent(val, precision),
+        _ => {
+            // %g picks the shorter of %f and %e with precision significant
+            // digits, then drops trailing zeros
+            let precision = precision.max(1);
+            let exp = exponent(val, precision - 1);
+            let power: i32 = exp[exp.find('e').unwrap() + 1..].parse().unwrap();
+            let formatted = if power < -4 || power >= precision as i32 {
+                exp
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - power) as usize, val)
+            };
+            if spec.alternate {
+                formatted
+            } else {
+                strip_zeros(formatted)
+            }
+        }
+    };
+
+    if upper {
+        formatted.to_uppercase()
+    } else {
+        formatted
+    }
+}
+
+/// Formats like C's %e, which always has a signed exponent of at least two
+/// digits
+fn exponent(val: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, val);
+    let (mantissa, power) = formatted.split_at(formatted.find('e').unwrap());
+    let power: i32 = power[1..].parse().unwrap();
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if power < 0 { '-' } else { '+' },
+        power.abs()
+    )
+}
+
+fn strip_zeros(formatted: String) -> String {
+    let (number, exp) = match formatted.find('e') {
+        Some(index) => formatted.split_at(index),
+        None => (formatted.as_str(), ""),
+    };
+    let number = if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    };
+    format!("{}{}", number, exp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let tests = vec![
+            ("%d\n", vec![RuntimeValue::Int(-42)], "-42\n"),
+            (
+                "%5d|%-5d|%05d",
+                vec![RuntimeValue::Int(42); 3],
+                "   42|42   |00042",
+            ),
+            (
+                "%+d %x %#X %o",
+                vec![RuntimeValue::Int(255); 4],
+                "+255 ff 0XFF 377",
+            ),
+            ("%f", vec![RuntimeValue::Float(2.5)], "2.500000"),
+            (
+                "%.2f|%8.3f",
+                vec![RuntimeValue::Float(-1.005), RuntimeValue::Float(1.23456)],
+                "-1.00|   1.235",
+            ),
+            ("%e", vec![RuntimeValue::Float(12345.678)], "1.234568e+04"),
+            (
+                "%g %g",
+                vec![RuntimeValue::Float(0.0001), RuntimeValue::Float(1e20)],
+                "0.0001 1e+20",
+            ),
+            ("%g", vec![RuntimeValue::Float(100.0)], "100"),
+            (
+                "%s, %.3s!",
+                vec![
+                    RuntimeValue::Str(Rc::from("hello"), 0),
+                    RuntimeValue::Str(Rc::from("world"), 0),
+                ],
+                "hello, wor!",
+            ),
+            (
+                "%c%c %d%%",
+                vec![
+                    RuntimeValue::Int(104),
+                    RuntimeValue::Int(105),
+                    RuntimeValue::Bool(true),
+                ],
+                "hi 1%",
+            ),
+        ];
+
+        for (fmt, args, expected) in tests {
+            assert_eq!(format(fmt, &args).unwrap(), expected, "{}", fmt);
+        }
+
+        assert!(matches!(
+            format("%d", &[]),
+            Err(RuntimeError::InvalidFormat(..))
+        ));
+        assert!(matches!(
+            format("%f", &[RuntimeValue::Int(1)]),
+            Err(RuntimeError::TypeMismatch(..))
+        ));
+    }
+}
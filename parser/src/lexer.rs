@@ -8,6 +8,10 @@ pub struct Lexer<'a> {
     pub peekable: Peekable<Chars<'a>>,
 
     pub index: usize,
+
+    /// Whether whitespace is kept as tokens, so every byte of the input is in
+    /// exactly one token
+    pub trivia: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -17,16 +21,38 @@ impl<'a> Lexer<'a> {
             input,
             peekable: input.chars().peekable(),
             index: 0,
+            trivia: false,
+        }
+    }
+
+    /// Create a Lexer that keeps whitespace as well as comments, for tools
+    /// that need to reconstruct the exact source from the tokens
+    pub fn lossless(input: &'a str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(input)
         }
     }
 
     fn next(&mut self) -> Option<char> {
-        self.index += 1;
-        self.peekable.next()
+        // Spans are byte offsets so they can be used to slice the input
+        let char = self.peekable.next()?;
+        self.index += char.len_utf8();
+        Some(char)
     }
 
     fn next_token(&mut self) -> Option<Token<'a>> {
+        let start = self.index;
         self.skip_whitespace();
+        if self.trivia && self.index > start {
+            let whitespace = &self.input[start..self.index];
+            return Some(Token::new(
+                TokenKind::Whitespace(whitespace),
+                start,
+                self.index,
+            ));
+        }
+
         let start = self.index;
         if let Some(char) = self.next() {
             return Some(match char {
@@ -36,7 +62,13 @@ impl<'a> Lexer<'a> {
                 ']' => Token::new(TokenKind::SquareClose, start, self.index),
                 '{' => Token::new(TokenKind::BraceOpen, start, self.index),
                 '}' => Token::new(TokenKind::BraceClose, start, self.index),
-                '.' => Token::new(TokenKind::Dot, start, self.index),
+                '.' => match self.peekable.peek() {
+                    Some('.') => {
+                        self.next();
+                        Token::new(TokenKind::DotDot, start, self.index)
+                    }
+                    _ => Token::new(TokenKind::Dot, start, self.index),
+                },
                 ',' => Token::new(TokenKind::Comma, start, self.index),
                 ';' => Token::new(TokenKind::SemiColon, start, self.index),
                 ':' => Token::new(TokenKind::Colon, start, self.index),
@@ -44,15 +76,22 @@ impl<'a> Lexer<'a> {
                 '-' => Token::new(TokenKind::Minus, start, self.index),
                 '/' => match self.peekable.peek() {
                     Some('/') => {
-                        // We found a comment - skip for now (maybe use for docs later)
                         while let Some(val) = self.peekable.peek() {
                             match val {
-                                '\n' => break,
+                                '\n' | '\r' => break,
                                 _ => self.next(),
                             };
                         }
 
-                        Token::new(TokenKind::Comment, start, self.index)
+                        // Three slashes documents the item after it, but four
+                        // or more is an ordinary comment, like in Rust
+                        let comment = &self.input[start..self.index];
+                        match comment.strip_prefix("///") {
+                            Some(text) if !text.starts_with('/') => {
+                                Token::new(TokenKind::DocComment(text), start, self.index)
+                            }
+                            _ => Token::new(TokenKind::Comment(&comment[2..]), start, self.index),
+                        }
                     }
                     _ => Token::new(TokenKind::Slash, start, self.index),
                 },
@@ -68,6 +107,10 @@ impl<'a> Lexer<'a> {
                         self.next();
                         Token::new(TokenKind::Equal, start, self.index)
                     }
+                    Some('>') => {
+                        self.next();
+                        Token::new(TokenKind::FatArrow, start, self.index)
+                    }
                     _ => Token::new(TokenKind::Assign, start, self.index),
                 },
                 '!' => match self.peekable.peek() {
@@ -106,6 +149,7 @@ impl<'a> Lexer<'a> {
                     _ => Token::new(TokenKind::Greater, start, self.index),
                 },
                 '%' => Token::new(TokenKind::Modulo, start, self.index),
+                '#' => Token::new(TokenKind::Hash, start, self.index),
                 '"' => {
                     while let Some(val) = self.peekable.peek() {
                         match val {
@@ -114,7 +158,10 @@ impl<'a> Lexer<'a> {
                         };
                     }
 
-                    self.next();
+                    // Unterminated string
+                    if self.next().is_none() {
+                        return Some(Token::new(TokenKind::UnknownToken, start, self.index));
+                    }
 
                     let str = &self.input[start + 1..self.index - 1];
                     Token::new(TokenKind::String(str), start, self.index)
@@ -127,7 +174,9 @@ impl<'a> Lexer<'a> {
                         };
                     }
 
-                    self.next();
+                    if self.next().is_none() {
+                        return Some(Token::new(TokenKind::UnknownToken, start, self.index));
+                    }
 
                     let str = &self.input[start + 1..self.index - 1];
                     Token::new(TokenKind::Char(str), start, self.index)
@@ -141,11 +190,11 @@ impl<'a> Lexer<'a> {
                     }
 
                     let identifier = &self.input[start..self.index];
-                    Token::new(
-                        TokenKind::Integer(identifier.parse().unwrap()),
-                        start,
-                        self.index,
-                    )
+                    match identifier.parse() {
+                        Ok(i) => Token::new(TokenKind::Integer(i), start, self.index),
+                        // Too big to fit in an int
+                        Err(_) => Token::new(TokenKind::UnknownToken, start, self.index),
+                    }
                 }
                 'a'..='z' | 'A'..='Z' | '_' => {
                     while let Some(val) = self.peekable.peek() {
@@ -168,7 +217,7 @@ impl<'a> Lexer<'a> {
     fn skip_whitespace(&mut self) {
         while let Some(val) = self.peekable.peek() {
             match val {
-                ' ' | '\n' => self.next(),
+                ' ' | '\n' | '\t' | '\r' => self.next(),
                 _ => break,
             };
         }
@@ -183,10 +232,20 @@ impl<'a> Lexer<'a> {
             "true" => Token::new(TokenKind::True, start, self.index),
             "false" => Token::new(TokenKind::False, start, self.index),
             "if" => Token::new(TokenKind::If, start, self.index),
+            "else" => Token::new(TokenKind::Else, start, self.index),
             "extern" => Token::new(TokenKind::Extern, start, self.index),
             "varargs" => Token::new(TokenKind::VarArgs, start, self.index),
             "while" => Token::new(TokenKind::While, start, self.index),
             "struct" => Token::new(TokenKind::Struct, start, self.index),
+            "impl" => Token::new(TokenKind::Impl, start, self.index),
+            "pub" => Token::new(TokenKind::Pub, start, self.index),
+            "import" => Token::new(TokenKind::Import, start, self.index),
+            "for" => Token::new(TokenKind::For, start, self.index),
+            "in" => Token::new(TokenKind::In, start, self.index),
+            "break" => Token::new(TokenKind::Break, start, self.index),
+            "continue" => Token::new(TokenKind::Continue, start, self.index),
+            "enum" => Token::new(TokenKind::Enum, start, self.index),
+            "match" => Token::new(TokenKind::Match, start, self.index),
             _ => Token::new(TokenKind::Identifier(value), start, self.index),
         }
     }
@@ -195,11 +254,7 @@ impl<'a> Lexer<'a> {
 // Allows us to call .into() on a &str
 impl<'a> From<&'a str> for Lexer<'a> {
     fn from(source: &'a str) -> Self {
-        Self {
-            input: source,
-            peekable: source.chars().peekable(),
-            index: 0,
-        }
+        Self::new(source)
     }
 }
 
@@ -249,6 +304,14 @@ mod tests {
             ".",
             vec![Token::new(TokenKind::Dot, 0, 1)],
         ),
+        dot_dot: (
+            "0..n",
+            vec![
+                Token::new(TokenKind::Integer(0), 0, 1),
+                Token::new(TokenKind::DotDot, 1, 3),
+                Token::new(TokenKind::Identifier("n"), 3, 4),
+            ],
+        ),
         comma: (
             ",",
             vec![Token::new(TokenKind::Comma, 0, 1)],
@@ -289,6 +352,10 @@ mod tests {
             "==",
             vec![Token::new(TokenKind::Equal, 0, 2)],
         ),
+        fat_arrow: (
+            "=>",
+            vec![Token::new(TokenKind::FatArrow, 0, 2)],
+        ),
         not_equal: (
             "!=",
             vec![Token::new(TokenKind::NotEqual, 0, 2)],
@@ -333,6 +400,10 @@ mod tests {
             "%",
             vec![Token::new(TokenKind::Modulo, 0, 1)],
         ),
+        hash: (
+            "#",
+            vec![Token::new(TokenKind::Hash, 0, 1)],
+        ),
         string: (
             "\"test\" \"another_test$$%\"",
             vec![Token::new(TokenKind::String("test"), 0, 6), Token::new(TokenKind::String("another_test$$%"), 7, 24)],
@@ -341,6 +412,18 @@ mod tests {
             "'a' '$'",
             vec![Token::new(TokenKind::Char("a"), 0, 3), Token::new(TokenKind::Char("$"), 4, 7)],
         ),
+        unterminated_string: (
+            "\"test",
+            vec![Token::new(TokenKind::UnknownToken, 0, 5)],
+        ),
+        unicode_string: (
+            "\"héllo\" x",
+            vec![Token::new(TokenKind::String("héllo"), 0, 8), Token::new(TokenKind::Identifier("x"), 9, 10)],
+        ),
+        number_overflow: (
+            "99999999999999999999",
+            vec![Token::new(TokenKind::UnknownToken, 0, 20)],
+        ),
         number: (
             "49102",
             vec![Token::new(TokenKind::Integer(49102), 0, 5)],
@@ -350,7 +433,14 @@ mod tests {
             // a comment
             test
             ",
-            vec![Token::new(TokenKind::Comment, 13, 25), Token::new(TokenKind::Identifier("test"), 38, 42)],
+            vec![Token::new(TokenKind::Comment(" a comment"), 13, 25), Token::new(TokenKind::Identifier("test"), 38, 42)],
+        ),
+        doc_comment: (
+            "/// Docs\n//// Not docs",
+            vec![
+                Token::new(TokenKind::DocComment(" Docs"), 0, 8),
+                Token::new(TokenKind::Comment("// Not docs"), 9, 22),
+            ],
         ),
         identifier: (
             "identifier_test",
@@ -384,6 +474,10 @@ mod tests {
             "if",
             vec![Token::new(TokenKind::If, 0, 2)],
         ),
+        else_stmt: (
+            "else",
+            vec![Token::new(TokenKind::Else, 0, 4)],
+        ),
         while_stmt: (
             "while",
             vec![Token::new(TokenKind::While, 0, 5)],
@@ -392,5 +486,62 @@ mod tests {
             "struct",
             vec![Token::new(TokenKind::Struct, 0, 6)],
         ),
+        impl_stmt: (
+            "impl",
+            vec![Token::new(TokenKind::Impl, 0, 4)],
+        ),
+        pub_stmt: (
+            "pub",
+            vec![Token::new(TokenKind::Pub, 0, 3)],
+        ),
+        for_stmt: (
+            "for x in",
+            vec![Token::new(TokenKind::For, 0, 3), Token::new(TokenKind::Identifier("x"), 4, 5), Token::new(TokenKind::In, 6, 8)],
+        ),
+        break_stmt: (
+            "break",
+            vec![Token::new(TokenKind::Break, 0, 5)],
+        ),
+        continue_stmt: (
+            "continue",
+            vec![Token::new(TokenKind::Continue, 0, 8)],
+        ),
+        enum_stmt: (
+            "enum",
+            vec![Token::new(TokenKind::Enum, 0, 4)],
+        ),
+        match_stmt: (
+            "match",
+            vec![Token::new(TokenKind::Match, 0, 5)],
+        ),
+        import_stmt: (
+            "import \"math.azl\"",
+            vec![Token::new(TokenKind::Import, 0, 6), Token::new(TokenKind::String("math.azl"), 7, 17)],
+        ),
+    }
+
+    #[test]
+    fn test_lossless() {
+        let input = "  /// Adds\n\tfunc add(a: int) { // one\r\n return a + 1; }\n\"open";
+        let lexer = Lexer::lossless(input);
+        let tokens: Vec<Token> = lexer.collect();
+
+        assert_eq!(tokens[0], Token::new(TokenKind::Whitespace("  "), 0, 2));
+        assert_eq!(tokens[1], Token::new(TokenKind::DocComment(" Adds"), 2, 10));
+        assert_eq!(tokens[2], Token::new(TokenKind::Whitespace("\n\t"), 10, 12));
+        assert!(tokens.contains(&Token::new(TokenKind::Comment(" one"), 31, 37)));
+
+        // Every byte is in exactly one token, in order
+        let mut end = 0;
+        for token in &tokens {
+            assert_eq!(token.span.start, end);
+            end = token.span.end;
+        }
+        assert_eq!(end, input.len());
+        let source: String = tokens
+            .iter()
+            .map(|token| &input[token.span.start..token.span.end])
+            .collect();
+        assert_eq!(source, input);
     }
 }
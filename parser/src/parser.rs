@@ -1,4 +1,4 @@
-use std::{iter::Peekable, rc::Rc};
+use std::{collections::HashMap, iter::Peekable, rc::Rc};
 
 use azula_ast::prelude::*;
 use azula_error::prelude::*;
@@ -25,8 +25,14 @@ const ACCESS: OperatorPrecedence = 9;
 pub struct Parser<'a> {
     source: &'a str,
     lexer: Peekable<Lexer<'a>>,
+    /// Doc comment lines waiting for the item they document
+    doc_lines: Vec<&'a str>,
+    /// Whether the functions being parsed are methods, which can take `self`
+    in_impl: bool,
 
     pub errors: Vec<AzulaError>,
+    /// The `///` doc comments written before items, by where the item starts
+    pub docs: HashMap<usize, String>,
 }
 
 impl<'a> Parser<'a> {
@@ -34,7 +40,10 @@ impl<'a> Parser<'a> {
         Self {
             source,
             lexer: lexer.peekable(),
+            doc_lines: vec![],
+            in_impl: false,
             errors: vec![],
+            docs: HashMap::new(),
         }
     }
 
@@ -53,16 +62,29 @@ impl<'a> Parser<'a> {
             TokenKind::Function => self.parse_function(),
             TokenKind::Extern => self.parse_extern_function(),
             TokenKind::Struct => self.parse_struct(),
+            TokenKind::Enum => self.parse_enum(),
+            TokenKind::Impl => self.parse_impl(),
+            TokenKind::Pub => self.parse_pub(),
+            TokenKind::Hash => self.parse_attributed(),
+            TokenKind::Import => self.parse_import(),
             TokenKind::Return => self.parse_return(),
             TokenKind::Var => self.parse_assign(true),
             TokenKind::Const => self.parse_assign(false),
             TokenKind::If => self.parse_if(),
             TokenKind::While => self.parse_while(),
+            TokenKind::For => self.parse_for(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_jump(),
+            TokenKind::Match => self.parse_match_statement(),
             TokenKind::SemiColon => {
                 self.lexer.next();
                 None
             }
-            TokenKind::Comment => {
+            TokenKind::Comment(_) => {
+                self.lexer.next();
+                None
+            }
+            TokenKind::DocComment(line) => {
+                self.doc_lines.push(line);
                 self.lexer.next();
                 None
             }
@@ -72,7 +94,7 @@ impl<'a> Parser<'a> {
                     None => return None,
                 };
 
-                if self.lexer.peek().unwrap().kind == TokenKind::Assign {
+                if self.peek_kind() == TokenKind::Assign {
                     return self.parse_reassign(expr.clone());
                 }
 
@@ -86,45 +108,110 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_block(&mut self, ending: TokenKind) -> Vec<Statement<'a>> {
-        let mut tok = self.lexer.peek().is_some();
         let mut statements = vec![];
-        while tok {
-            if self.lexer.peek().unwrap().kind == ending {
+        while let Some(tok) = self.lexer.peek() {
+            if tok.kind == ending {
                 break;
             }
-            let stmt = self.parse_statement();
-            tok = self.lexer.peek().is_some();
-            if stmt.is_none() {
-                // This could cause issues - not sure how it would occur
-                continue;
+            let start = tok.span.start;
+            let errors = self.errors.len();
+            // Taken first so the statements in a function's body don't get its docs
+            let mut docs = std::mem::take(&mut self.doc_lines);
+
+            match self.parse_statement() {
+                Some(stmt) => {
+                    // Docs left inside the statement, with nothing after them
+                    self.doc_lines.clear();
+                    self.attach_docs(&stmt, docs);
+                    statements.push(stmt);
+                }
+                // Skip the rest of a broken statement so parsing can carry on after it
+                None if self.errors.len() > errors => self.synchronise(start),
+                // A comment, which may be more docs for the next statement
+                None => {
+                    docs.append(&mut self.doc_lines);
+                    self.doc_lines = docs;
+                }
             }
-            statements.push(stmt.unwrap());
         }
         statements
     }
 
+    /// Gives the doc comment lines before a declaration to it, with the space
+    /// after each `///` taken off. Docs on anything else are dropped
+    fn attach_docs(&mut self, stmt: &Statement, lines: Vec<&str>) {
+        let declaration = matches!(
+            stmt,
+            Statement::Function { .. }
+                | Statement::ExternFunction { .. }
+                | Statement::Struct { .. }
+                | Statement::Enum { .. }
+                | Statement::Impl { .. }
+                | Statement::Assign(..)
+        );
+        if lines.is_empty() || !declaration {
+            return;
+        }
+
+        let lines: Vec<&str> = lines
+            .into_iter()
+            .map(|line| line.strip_prefix(' ').unwrap_or(line))
+            .collect();
+        self.docs.insert(stmt.span().start, lines.join("\n"));
+    }
+
+    /// Skips tokens until the end of the current statement or the start of the next one.
+    /// Always moves past the token at `start` so a failed statement can't be retried forever.
+    fn synchronise(&mut self, start: usize) {
+        if let Some(tok) = self.lexer.peek() {
+            if tok.span.start == start {
+                self.lexer.next();
+            }
+        }
+
+        while let Some(tok) = self.lexer.peek() {
+            match tok.kind {
+                TokenKind::SemiColon => {
+                    self.lexer.next();
+                    return;
+                }
+                TokenKind::BraceClose
+                | TokenKind::Function
+                | TokenKind::Extern
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Match
+                | TokenKind::Impl
+                | TokenKind::Pub
+                | TokenKind::Import
+                | TokenKind::Var
+                | TokenKind::Const
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Return => return,
+                _ => {
+                    self.lexer.next();
+                }
+            }
+        }
+    }
+
     fn parse_function(&mut self) -> Option<Statement<'a>> {
         // func
         let start_token = self.lexer.next().unwrap();
 
         // Parse name of the function
-        let tok = self.lexer.next();
-        let ident = match tok {
-            Some(v) if matches!(v.kind, TokenKind::Identifier(_)) => {
-                if let TokenKind::Identifier(val) = v.kind {
-                    val
-                } else {
-                    "anon"
-                }
-            }
-            _ => return None,
-        };
+        let ident = self.parse_identifier()?;
+        let type_params = self.parse_type_params()?;
 
         // Parse function arguments
         let mut args = vec![];
         if let Some(tok) = self.lexer.peek() {
             if tok.kind == TokenKind::BracketOpen {
-                args = self.parse_typed_identifier_list(TokenKind::BracketOpen);
+                args = self.parse_typed_identifier_list(TokenKind::BracketOpen).0;
             }
         }
 
@@ -154,6 +241,9 @@ impl<'a> Parser<'a> {
 
         Some(Statement::Function {
             name: ident,
+            public: false,
+            attributes: vec![],
+            type_params,
             args,
             returns,
             body: Rc::new(Statement::Block(body)),
@@ -168,7 +258,13 @@ impl<'a> Parser<'a> {
         // extern
         let start_token = self.lexer.next().unwrap();
 
-        let next = self.lexer.next().unwrap();
+        let next = match self.lexer.next() {
+            Some(tok) => tok,
+            None => {
+                self.unexpected_eof();
+                return None;
+            }
+        };
 
         let varargs = match next.kind {
             TokenKind::VarArgs => {
@@ -179,17 +275,7 @@ impl<'a> Parser<'a> {
         };
 
         // Parse name of the function
-        let tok = self.lexer.next();
-        let ident = match tok {
-            Some(v) if matches!(v.kind, TokenKind::Identifier(_)) => {
-                if let TokenKind::Identifier(val) = v.kind {
-                    val
-                } else {
-                    "anon"
-                }
-            }
-            _ => return None,
-        };
+        let ident = self.parse_identifier()?;
 
         // Parse function arguments
         let mut args = vec![];
@@ -232,213 +318,729 @@ impl<'a> Parser<'a> {
         let start_token = self.lexer.next().unwrap();
 
         // Parse name of the struct
-        let tok = self.lexer.next();
-        let ident = match tok {
-            Some(v) if matches!(v.kind, TokenKind::Identifier(_)) => {
-                if let TokenKind::Identifier(val) = v.kind {
-                    val
-                } else {
-                    "anon"
-                }
-            }
-            _ => return None,
-        };
+        let ident = self.parse_identifier()?;
+        let type_params = self.parse_type_params()?;
 
         // Parse struct arguments
         let mut args = vec![];
+        let mut end = start_token.span.end;
         if let Some(tok) = self.lexer.peek() {
             if tok.kind == TokenKind::BraceOpen {
-                args = self.parse_typed_identifier_list(TokenKind::BraceOpen);
+                (args, end) = self.parse_typed_identifier_list(TokenKind::BraceOpen);
             }
         }
 
         Some(Statement::Struct {
             name: ident,
+            public: false,
+            type_params,
             attributes: args,
             span: Span {
                 start: start_token.span.start,
-                end: start_token.span.end,
+                end,
             },
         })
     }
 
-    fn parse_return(&mut self) -> Option<Statement<'a>> {
-        // return
+    fn parse_enum(&mut self) -> Option<Statement<'a>> {
+        // enum
         let start_token = self.lexer.next().unwrap();
 
-        if let Some(v) = self.lexer.peek() {
-            if v.kind == TokenKind::SemiColon {
-                self.lexer.next();
-                Some(Statement::Return(
-                    None,
-                    Span {
-                        start: start_token.span.start,
-                        end: start_token.span.end,
-                    },
-                ))
-            } else {
-                let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
-                    expr
-                } else {
-                    return None;
-                };
-                if !self.expect_peek(TokenKind::SemiColon) {
-                    return None;
-                }
+        let ident = self.parse_identifier()?;
 
-                let end_token = self.lexer.next().unwrap();
-                Some(Statement::Return(
-                    Some(expr.clone()),
-                    Span {
-                        start: start_token.span.start,
-                        end: end_token.span.end,
-                    },
-                ))
-            }
-        } else {
-            Some(Statement::Return(
-                None,
-                Span {
-                    start: start_token.span.start,
-                    end: start_token.span.end,
-                },
-            ))
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
         }
-    }
+        self.lexer.next();
 
-    fn parse_assign(&mut self, mutable: bool) -> Option<Statement<'a>> {
-        // var
-        let start_token = self.lexer.next().unwrap();
+        let mut variants = vec![];
+        loop {
+            while self.peek_kind().is_comment() {
+                self.lexer.next();
+            }
+            if self.peek_kind() == TokenKind::BraceClose {
+                break;
+            }
 
-        let tok = self.lexer.next();
-        let ident = match tok {
-            Some(v) if matches!(v.kind, TokenKind::Identifier(_)) => {
-                if let TokenKind::Identifier(val) = v.kind {
-                    val
-                } else {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::ExpectedToken(
-                            format!("{:?}", TokenKind::Identifier("")),
-                            Some(format!("{:?}", v.kind)),
-                        ),
-                        v.span.start,
-                        v.span.end,
-                    ));
+            let name = self.parse_identifier()?;
 
-                    return None;
-                }
+            // Variants can carry a payload like Circle(int)
+            let mut payload = vec![];
+            if self.peek_kind() == TokenKind::BracketOpen {
+                payload = self.parse_type_list(TokenKind::BracketOpen);
             }
-            _ => return None,
-        };
+            variants.push((name, payload));
 
-        let mut type_annotation = None;
-        if self.lexer.peek().unwrap().kind == TokenKind::Colon {
+            if self.peek_kind() != TokenKind::Comma {
+                break;
+            }
             self.lexer.next();
-
-            let typ = self.parse_type();
-            type_annotation = Some(typ);
         }
 
-        if !self.expect_peek(TokenKind::Assign) {
-            return None;
+        while self.peek_kind().is_comment() {
+            self.lexer.next();
         }
-
-        self.lexer.next();
-
-        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
-            expr
-        } else {
-            return None;
-        };
-
-        if !self.expect_peek(TokenKind::SemiColon) {
+        if !self.expect_peek(TokenKind::BraceClose) {
             return None;
         }
 
         let end_token = self.lexer.next().unwrap();
 
-        Some(Statement::Assign(
-            mutable,
-            ident.to_string(),
-            type_annotation,
-            expr,
-            Span {
+        Some(Statement::Enum {
+            name: ident,
+            public: false,
+            variants,
+            span: Span {
                 start: start_token.span.start,
                 end: end_token.span.end,
             },
-        ))
+        })
     }
 
-    fn parse_reassign(&mut self, ident: ExpressionNode<'a>) -> Option<Statement<'a>> {
-        self.lexer.next();
+    fn parse_impl(&mut self) -> Option<Statement<'a>> {
+        // impl
+        let start_token = self.lexer.next().unwrap();
 
-        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
-            expr
-        } else {
+        // Parse name of the struct the methods belong to
+        let ident = self.parse_identifier()?;
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
             return None;
-        };
+        }
+        self.lexer.next();
 
-        if !self.expect_peek(TokenKind::SemiColon) {
+        let mut methods = vec![];
+        while let Some(tok) = self.lexer.peek() {
+            match tok.kind {
+                TokenKind::BraceClose => break,
+                TokenKind::Function | TokenKind::Hash => {}
+                TokenKind::Comment(_) => {
+                    self.lexer.next();
+                    continue;
+                }
+                TokenKind::DocComment(line) => {
+                    self.doc_lines.push(line);
+                    self.lexer.next();
+                    continue;
+                }
+                _ => {
+                    self.expect_peek(TokenKind::Function);
+                    return None;
+                }
+            }
+
+            let docs = std::mem::take(&mut self.doc_lines);
+            self.in_impl = true;
+            let method = match self.peek_kind() {
+                TokenKind::Hash => self.parse_attributed(),
+                _ => self.parse_function(),
+            };
+            self.in_impl = false;
+            let method = method?;
+            self.attach_docs(&method, docs);
+            if let Statement::Function {
+                name,
+                attributes,
+                type_params,
+                mut args,
+                returns,
+                body,
+                span,
+                ..
+            } = method
+            {
+                // The receiver is always passed as a pointer to the struct
+                if args
+                    .iter()
+                    .skip(1)
+                    .any(|arg| *arg == (AzulaType::Infer, "self"))
+                {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::SelfOutsideMethod,
+                        span.start,
+                        span.end,
+                    ));
+                }
+                match args.first() {
+                    Some((_, "self")) => {
+                        args[0].0 =
+                            AzulaType::Pointer(Rc::new(AzulaType::Named(ident.to_string())));
+                    }
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MethodWithoutSelf(name.to_string()),
+                            span.start,
+                            span.end,
+                        ));
+                        continue;
+                    }
+                }
+
+                methods.push(Statement::Function {
+                    name,
+                    public: false,
+                    attributes,
+                    type_params,
+                    args,
+                    returns,
+                    body,
+                    span,
+                });
+            }
+        }
+
+        if !self.expect_peek(TokenKind::BraceClose) {
             return None;
         }
 
         let end_token = self.lexer.next().unwrap();
 
-        Some(Statement::Reassign(
-            ident.clone(),
-            expr,
-            Span {
-                start: ident.span.start,
+        Some(Statement::Impl {
+            name: ident,
+            methods,
+            span: Span {
+                start: start_token.span.start,
                 end: end_token.span.end,
             },
-        ))
+        })
     }
 
-    fn parse_if(&mut self) -> Option<Statement<'a>> {
-        // if
+    fn parse_pub(&mut self) -> Option<Statement<'a>> {
+        // pub
         let start_token = self.lexer.next().unwrap();
 
-        let expr = self.parse_expression(LOWEST, false);
+        let stmt = match self.lexer.peek() {
+            Some(tok) if tok.kind == TokenKind::Function => self.parse_function()?,
+            Some(tok) if tok.kind == TokenKind::Struct => self.parse_struct()?,
+            Some(tok) if tok.kind == TokenKind::Enum => self.parse_enum()?,
+            _ => {
+                self.expect_peek(TokenKind::Function);
+                return None;
+            }
+        };
 
-        if !self.expect_peek(TokenKind::BraceOpen) {
-            return None;
+        match stmt {
+            Statement::Function {
+                name,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+                ..
+            } => Some(Statement::Function {
+                name,
+                public: true,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span: Span {
+                    start: start_token.span.start,
+                    end: span.end,
+                },
+            }),
+            Statement::Struct {
+                name,
+                type_params,
+                attributes,
+                span,
+                ..
+            } => Some(Statement::Struct {
+                name,
+                public: true,
+                type_params,
+                attributes,
+                span: Span {
+                    start: start_token.span.start,
+                    end: span.end,
+                },
+            }),
+            Statement::Enum {
+                name,
+                variants,
+                span,
+                ..
+            } => Some(Statement::Enum {
+                name,
+                public: true,
+                variants,
+                span: Span {
+                    start: start_token.span.start,
+                    end: span.end,
+                },
+            }),
+            _ => unreachable!(),
         }
+    }
 
-        self.lexer.next();
-
-        let body = self.parse_block(TokenKind::BraceClose);
+    /// Parses the attributes before a function, then the function itself
+    fn parse_attributed(&mut self) -> Option<Statement<'a>> {
+        let mut attributes = vec![];
+        while self.peek_kind() == TokenKind::Hash {
+            attributes.push(self.parse_attribute()?);
+        }
 
-        let end_token = self.lexer.next().unwrap();
+        let stmt = match self.peek_kind() {
+            TokenKind::Function => self.parse_function()?,
+            TokenKind::Pub => self.parse_pub()?,
+            _ => {
+                self.expect_peek(TokenKind::Function);
+                return None;
+            }
+        };
 
-        Some(Statement::If(
-            expr.unwrap(),
-            body,
-            Span {
-                start: start_token.span.start,
-                end: end_token.span.end,
-            },
-        ))
+        match stmt {
+            Statement::Function {
+                name,
+                public,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+                ..
+            } => Some(Statement::Function {
+                name,
+                public,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+            }),
+            _ => {
+                let span = &attributes[0].span;
+                self.errors.push(AzulaError::new(
+                    ErrorType::MisplacedAttribute,
+                    span.start,
+                    span.end,
+                ));
+                None
+            }
+        }
     }
 
-    fn parse_while(&mut self) -> Option<Statement<'a>> {
-        // while
+    /// Parses `#[name]` or `#[name(arg, ...)]`. The attributes are `allow`,
+    /// which takes the names of warnings to turn off, and `test`
+    fn parse_attribute(&mut self) -> Option<Attribute<'a>> {
+        // #
         let start_token = self.lexer.next().unwrap();
 
-        let expr = self.parse_expression(LOWEST, false);
-
-        if !self.expect_peek(TokenKind::BraceOpen) {
+        if !self.expect_peek(TokenKind::SquareOpen) {
             return None;
         }
-
         self.lexer.next();
 
-        let body = self.parse_block(TokenKind::BraceClose);
+        let name = self.parse_identifier()?;
+        let mut args = vec![];
+        if self.peek_kind() == TokenKind::BracketOpen {
+            self.lexer.next();
+            while self.peek_kind() != TokenKind::BracketClose {
+                args.push(self.parse_identifier()?);
+                if self.peek_kind() != TokenKind::Comma {
+                    break;
+                }
+                self.lexer.next();
+            }
 
-        let end_token = self.lexer.next().unwrap();
+            if !self.expect_peek(TokenKind::BracketClose) {
+                return None;
+            }
+            self.lexer.next();
+        }
 
-        Some(Statement::While(
-            expr.unwrap(),
-            body,
+        if !self.expect_peek(TokenKind::SquareClose) {
+            return None;
+        }
+        let end_token = self.lexer.next().unwrap();
+        let span = Span {
+            start: start_token.span.start,
+            end: end_token.span.end,
+        };
+
+        match name {
+            "allow" => {}
+            "test" if args.is_empty() => {}
+            "test" => {
+                self.errors.push(
+                    AzulaError::new(
+                        ErrorType::UnknownAttribute(format!("{}({})", name, args.join(", "))),
+                        span.start,
+                        span.end,
+                    )
+                    .with_help("test doesn't take any arguments"),
+                );
+                return None;
+            }
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnknownAttribute(name.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                return None;
+            }
+        }
+        for arg in &args {
+            if !LINTS.contains(arg) {
+                self.errors.push(
+                    AzulaError::new(
+                        ErrorType::UnknownLint(arg.to_string()),
+                        span.start,
+                        span.end,
+                    )
+                    .with_help(format!("the warnings are {}", LINTS.join(", "))),
+                );
+                return None;
+            }
+        }
+
+        Some(Attribute { name, args, span })
+    }
+
+    fn parse_import(&mut self) -> Option<Statement<'a>> {
+        // import
+        let start_token = self.lexer.next().unwrap();
+
+        let tok = self.lexer.next();
+        let path = match tok {
+            Some(v) if matches!(v.kind, TokenKind::String(_)) => {
+                if let TokenKind::String(val) = v.kind {
+                    val
+                } else {
+                    unreachable!()
+                }
+            }
+            Some(v) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ExpectedToken("String".to_string(), Some(format!("{:?}", v.kind))),
+                    v.span.start,
+                    v.span.end,
+                ));
+                return None;
+            }
+            None => return None,
+        };
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Import(
+            path,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_return(&mut self) -> Option<Statement<'a>> {
+        // return
+        let start_token = self.lexer.next().unwrap();
+
+        if let Some(v) = self.lexer.peek() {
+            if v.kind == TokenKind::SemiColon {
+                self.lexer.next();
+                Some(Statement::Return(
+                    None,
+                    Span {
+                        start: start_token.span.start,
+                        end: start_token.span.end,
+                    },
+                ))
+            } else {
+                let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
+                    expr
+                } else {
+                    return None;
+                };
+                if !self.expect_peek(TokenKind::SemiColon) {
+                    return None;
+                }
+
+                let end_token = self.lexer.next().unwrap();
+                Some(Statement::Return(
+                    Some(expr.clone()),
+                    Span {
+                        start: start_token.span.start,
+                        end: end_token.span.end,
+                    },
+                ))
+            }
+        } else {
+            Some(Statement::Return(
+                None,
+                Span {
+                    start: start_token.span.start,
+                    end: start_token.span.end,
+                },
+            ))
+        }
+    }
+
+    fn parse_loop_jump(&mut self) -> Option<Statement<'a>> {
+        // break or continue
+        let start_token = self.lexer.next().unwrap();
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+        let span = Span {
+            start: start_token.span.start,
+            end: end_token.span.end,
+        };
+
+        match start_token.kind {
+            TokenKind::Break => Some(Statement::Break(span)),
+            _ => Some(Statement::Continue(span)),
+        }
+    }
+
+    fn parse_match_statement(&mut self) -> Option<Statement<'a>> {
+        let expr = self.parse_expression(LOWEST, false)?;
+
+        // Like if and while, a match on its own doesn't need a semicolon
+        if self.peek_kind() == TokenKind::SemiColon {
+            self.lexer.next();
+        }
+
+        Some(Statement::ExpressionStatement(expr.clone(), expr.span))
+    }
+
+    fn parse_assign(&mut self, mutable: bool) -> Option<Statement<'a>> {
+        // var
+        let start_token = self.lexer.next().unwrap();
+
+        let ident = self.parse_identifier()?;
+
+        let mut type_annotation = None;
+        if self.peek_kind() == TokenKind::Colon {
+            self.lexer.next();
+
+            let typ = self.parse_type();
+            type_annotation = Some(typ);
+        }
+
+        if !self.expect_peek(TokenKind::Assign) {
+            return None;
+        }
+
+        self.lexer.next();
+
+        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
+            expr
+        } else {
+            return None;
+        };
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Assign(
+            mutable,
+            ident.to_string(),
+            type_annotation,
+            expr,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_reassign(&mut self, ident: ExpressionNode<'a>) -> Option<Statement<'a>> {
+        self.lexer.next();
+
+        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
+            expr
+        } else {
+            return None;
+        };
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Reassign(
+            ident.clone(),
+            expr,
+            Span {
+                start: ident.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_if(&mut self) -> Option<Statement<'a>> {
+        // if
+        let start_token = self.lexer.next().unwrap();
+
+        let expr = self.parse_expression(LOWEST, false);
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+
+        self.lexer.next();
+
+        let body = self.parse_block(TokenKind::BraceClose);
+
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+        let mut end = end_token.span.end;
+
+        // Comments can come between the closing brace and `else`
+        while let Some(tok) = self.lexer.peek() {
+            match tok.kind {
+                TokenKind::Comment(_) => {}
+                TokenKind::DocComment(line) => self.doc_lines.push(line),
+                _ => break,
+            }
+            self.lexer.next();
+        }
+
+        let mut else_body = None;
+        if let Some(tok) = self.lexer.peek() {
+            if tok.kind == TokenKind::Else {
+                self.lexer.next();
+
+                // else if - nest the following if statement as the whole else body
+                if let Some(tok) = self.lexer.peek() {
+                    if tok.kind == TokenKind::If {
+                        let nested = self.parse_if()?;
+
+                        if let Statement::If(.., ref span) = nested {
+                            end = span.end;
+                        }
+
+                        else_body = Some(vec![nested]);
+                    }
+                }
+
+                if else_body.is_none() {
+                    if !self.expect_peek(TokenKind::BraceOpen) {
+                        return None;
+                    }
+
+                    self.lexer.next();
+
+                    let body = self.parse_block(TokenKind::BraceClose);
+
+                    if !self.expect_peek(TokenKind::BraceClose) {
+                        return None;
+                    }
+
+                    end = self.lexer.next().unwrap().span.end;
+                    else_body = Some(body);
+                }
+            }
+        }
+
+        Some(Statement::If(
+            expr?,
+            body,
+            else_body,
+            Span {
+                start: start_token.span.start,
+                end,
+            },
+        ))
+    }
+
+    fn parse_while(&mut self) -> Option<Statement<'a>> {
+        // while
+        let start_token = self.lexer.next().unwrap();
+
+        let expr = self.parse_expression(LOWEST, false);
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+
+        self.lexer.next();
+
+        let body = self.parse_block(TokenKind::BraceClose);
+
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::While(
+            expr?,
+            body,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_for(&mut self) -> Option<Statement<'a>> {
+        // for
+        let start_token = self.lexer.next().unwrap();
+
+        let ident = self.parse_identifier()?;
+
+        if !self.expect_peek(TokenKind::In) {
+            return None;
+        }
+
+        self.lexer.next();
+
+        let mut iterable = self.parse_expression(LOWEST, false)?;
+
+        // Ranges can only be iterated over, so they're parsed here rather than as an infix
+        if self.peek_kind() == TokenKind::DotDot {
+            self.lexer.next();
+
+            let end = self.parse_expression(LOWEST, false)?;
+            iterable = ExpressionNode {
+                span: Span {
+                    start: iterable.span.start,
+                    end: end.span.end,
+                },
+                expression: Expression::Range(Rc::new(iterable), Rc::new(end)),
+                typed: AzulaType::Infer,
+            };
+        }
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+
+        self.lexer.next();
+
+        let body = self.parse_block(TokenKind::BraceClose);
+
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::For(
+            ident.to_string(),
+            iterable,
+            body,
             Span {
                 start: start_token.span.start,
                 end: end_token.span.end,
@@ -449,6 +1051,23 @@ impl<'a> Parser<'a> {
     fn parse_type(&mut self) -> AzulaType<'a> {
         if let Some(tok) = self.lexer.next() {
             if let TokenKind::Identifier(ident) = tok.kind {
+                if self.peek_kind() == TokenKind::Less {
+                    self.lexer.next();
+
+                    let mut args = vec![self.parse_type()];
+                    while self.peek_kind() == TokenKind::Comma {
+                        self.lexer.next();
+                        args.push(self.parse_type());
+                    }
+
+                    if !self.expect_peek(TokenKind::Greater) {
+                        return AzulaType::Void;
+                    }
+                    self.lexer.next();
+
+                    return AzulaType::Generic(ident.to_string(), args);
+                }
+
                 return ident.into();
             }
 
@@ -459,10 +1078,11 @@ impl<'a> Parser<'a> {
             if let TokenKind::SquareOpen = tok.kind {
                 let internal_type = self.parse_type();
                 let mut size = None;
-                if self.lexer.peek().unwrap().kind == TokenKind::SemiColon {
+                if self.peek_kind() == TokenKind::SemiColon {
                     self.lexer.next();
 
-                    if let TokenKind::Integer(i) = self.lexer.next().unwrap().kind {
+                    if let TokenKind::Integer(i) = self.peek_kind() {
+                        self.lexer.next();
                         size = Some(i as usize);
                     }
                 }
@@ -484,10 +1104,32 @@ impl<'a> Parser<'a> {
         AzulaType::Void
     }
 
+    /// Parses the `<A, B>` after the name of a generic function or struct, if there is one
+    fn parse_type_params(&mut self) -> Option<Vec<&'a str>> {
+        let mut params = vec![];
+        if self.peek_kind() != TokenKind::Less {
+            return Some(params);
+        }
+        self.lexer.next();
+
+        params.push(self.parse_identifier()?);
+        while self.peek_kind() == TokenKind::Comma {
+            self.lexer.next();
+            params.push(self.parse_identifier()?);
+        }
+
+        if !self.expect_peek(TokenKind::Greater) {
+            return None;
+        }
+        self.lexer.next();
+
+        Some(params)
+    }
+
     fn parse_typed_identifier(&mut self) -> Option<TypedIdentifier<'a>> {
-        let name = if let Some(tok) = self.lexer.next() {
+        let (name, start, end) = if let Some(tok) = self.lexer.next() {
             if let TokenKind::Identifier(ident) = tok.kind {
-                ident
+                (ident, tok.span.start, tok.span.end)
             } else {
                 self.errors.push(AzulaError::new(
                     ErrorType::ExpectedToken(
@@ -500,14 +1142,23 @@ impl<'a> Parser<'a> {
                 return None;
             }
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+            self.unexpected_eof();
             return None;
         };
 
+        // Method receivers are written as a bare `self`, typed by the impl block
+        if name == "self" {
+            if let Some(tok) = self.lexer.peek() {
+                if tok.kind != TokenKind::Colon {
+                    if !self.in_impl {
+                        self.errors
+                            .push(AzulaError::new(ErrorType::SelfOutsideMethod, start, end));
+                    }
+                    return Some((AzulaType::Infer, name));
+                }
+            }
+        }
+
         if !self.expect_peek(TokenKind::Colon) {
             return None;
         }
@@ -519,26 +1170,24 @@ impl<'a> Parser<'a> {
         Some((ident_type, name))
     }
 
+    /// Parses identifiers with their types between delimiters, also giving
+    /// where the closing delimiter ends
     fn parse_typed_identifier_list(
         &mut self,
         opening_delimiter: TokenKind,
-    ) -> Vec<TypedIdentifier<'a>> {
+    ) -> (Vec<TypedIdentifier<'a>>, usize) {
         let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
 
-        self.lexer.next();
+        let open = self.lexer.next().unwrap();
 
         if let Some(peek) = self.lexer.peek() {
             if peek.kind == closing_delimiter {
-                self.lexer.next();
-                return vec![];
+                let close = self.lexer.next().unwrap();
+                return (vec![], close.span.end);
             }
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
-            return vec![];
+            self.unexpected_eof();
+            return (vec![], open.span.end);
         }
 
         let mut identifiers = vec![];
@@ -547,23 +1196,24 @@ impl<'a> Parser<'a> {
             identifiers.push((typ, name));
         }
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
+        while self.peek_kind() == TokenKind::Comma {
             self.lexer.next();
-            if self.lexer.peek().unwrap().kind == closing_delimiter {
+            if self.peek_kind() == closing_delimiter {
                 break;
             }
             if let Some((typ, name)) = self.parse_typed_identifier() {
                 identifiers.push((typ, name));
             }
-            peek = self.lexer.peek().unwrap().kind.clone();
         }
 
         self.expect_peek(closing_delimiter);
 
-        self.lexer.next();
+        let end = match self.lexer.next() {
+            Some(close) => close.span.end,
+            None => open.span.end,
+        };
 
-        identifiers
+        (identifiers, end)
     }
 
     fn parse_type_list(&mut self, opening_delimiter: TokenKind) -> Vec<AzulaType<'a>> {
@@ -574,11 +1224,7 @@ impl<'a> Parser<'a> {
                 return vec![];
             }
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+            self.unexpected_eof();
             return vec![];
         }
 
@@ -588,11 +1234,9 @@ impl<'a> Parser<'a> {
 
         types.push(self.parse_type());
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
+        while self.peek_kind() == TokenKind::Comma {
             self.lexer.next();
             types.push(self.parse_type());
-            peek = self.lexer.peek().unwrap().kind.clone();
         }
 
         self.expect_peek(closing_delimiter);
@@ -621,12 +1265,47 @@ impl<'a> Parser<'a> {
             };
         }
 
+        self.unexpected_eof();
+        false
+    }
+
+    fn peek_kind(&mut self) -> TokenKind<'a> {
+        match self.lexer.peek() {
+            Some(tok) => tok.kind.clone(),
+            None => TokenKind::EOF,
+        }
+    }
+
+    fn parse_identifier(&mut self) -> Option<&'a str> {
+        match self.lexer.next() {
+            Some(Token {
+                kind: TokenKind::Identifier(ident),
+                ..
+            }) => Some(ident),
+            Some(tok) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ExpectedToken(
+                        format!("{:?}", TokenKind::Identifier("")),
+                        Some(format!("{:?}", tok.kind)),
+                    ),
+                    tok.span.start,
+                    tok.span.end,
+                ));
+                None
+            }
+            None => {
+                self.unexpected_eof();
+                None
+            }
+        }
+    }
+
+    fn unexpected_eof(&mut self) {
         self.errors.push(AzulaError::new(
             ErrorType::UnexpectedEOF,
-            self.source.len() - 1,
+            self.source.len().saturating_sub(1),
             self.source.len(),
         ));
-        false
     }
 
     fn parse_expression(
@@ -637,11 +1316,7 @@ impl<'a> Parser<'a> {
         let tok = if let Some(tok) = self.lexer.next() {
             tok
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+            self.unexpected_eof();
             return None;
         };
 
@@ -650,7 +1325,7 @@ impl<'a> Parser<'a> {
                 if let Some(peek) = self.lexer.peek() {
                     if peek.kind == TokenKind::Dot {
                         self.lexer.next();
-                        let second_number = self.parse_expression(CALL, false).unwrap();
+                        let second_number = self.parse_expression(CALL, false)?;
                         match second_number.expression {
                             Expression::Integer(y) => Some(ExpressionNode {
                                 expression: Expression::Float(
@@ -662,7 +1337,17 @@ impl<'a> Parser<'a> {
                                     end: second_number.span.end,
                                 },
                             }),
-                            _ => panic!(),
+                            _ => {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::ExpectedToken(
+                                        format!("{:?}", TokenKind::Integer(0)),
+                                        Some(format!("{:?}", second_number.expression)),
+                                    ),
+                                    second_number.span.start,
+                                    second_number.span.end,
+                                ));
+                                None
+                            }
                         }
                     } else {
                         Some(ExpressionNode {
@@ -731,16 +1416,18 @@ impl<'a> Parser<'a> {
                 },
             }),
             TokenKind::BracketOpen => {
-                let expr = self.parse_expression(LOWEST, true).unwrap();
+                let expr = self.parse_expression(LOWEST, true)?;
 
-                self.expect_peek(TokenKind::BracketClose);
+                if !self.expect_peek(TokenKind::BracketClose) {
+                    return None;
+                }
 
                 self.lexer.next();
 
                 Some(expr)
             }
             TokenKind::Bang => {
-                let expr = self.parse_expression(PREFIX, allow_struct_init).unwrap();
+                let expr = self.parse_expression(PREFIX, allow_struct_init)?;
 
                 Some(ExpressionNode {
                     expression: Expression::Not(Rc::new(expr.clone())),
@@ -752,7 +1439,7 @@ impl<'a> Parser<'a> {
                 })
             }
             TokenKind::Ampersand => {
-                let expr = self.parse_expression(PREFIX, true).unwrap();
+                let expr = self.parse_expression(PREFIX, true)?;
 
                 Some(ExpressionNode {
                     expression: Expression::Pointer(Rc::new(expr.clone())),
@@ -764,6 +1451,7 @@ impl<'a> Parser<'a> {
                 })
             }
             TokenKind::SquareOpen => self.parse_array(tok),
+            TokenKind::Match => self.parse_match(tok),
             _ => {
                 self.errors.push(AzulaError::new(
                     ErrorType::ExpectedExpression(format!("{:?}", tok.kind)),
@@ -809,11 +1497,7 @@ impl<'a> Parser<'a> {
                 return vec![];
             }
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+            self.unexpected_eof();
             return vec![];
         }
 
@@ -823,13 +1507,11 @@ impl<'a> Parser<'a> {
             expressions.push(expr);
         }
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
+        while self.peek_kind() == TokenKind::Comma {
             self.lexer.next();
             if let Some(expr) = self.parse_expression(LOWEST, true) {
                 expressions.push(expr);
             }
-            peek = self.lexer.peek().unwrap().kind.clone();
         }
 
         self.expect_peek(closing_delimiter);
@@ -842,8 +1524,7 @@ impl<'a> Parser<'a> {
         left: ExpressionNode<'a>,
         allow_struct_init: bool,
     ) -> Option<ExpressionNode<'a>> {
-        let operator = self.lexer.peek().unwrap();
-        let kind = operator.kind.clone();
+        let kind = self.peek_kind();
 
         match kind {
             TokenKind::Dot => self.parse_struct_access(left),
@@ -864,7 +1545,7 @@ impl<'a> Parser<'a> {
             | TokenKind::LessEqual
             | TokenKind::Greater
             | TokenKind::GreaterEqual => {
-                let precedence = operator_precedence(operator.kind.clone(), allow_struct_init);
+                let precedence = operator_precedence(kind.clone(), allow_struct_init);
 
                 self.lexer.next();
 
@@ -898,12 +1579,18 @@ impl<'a> Parser<'a> {
     fn parse_function_call(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
         let exprs = self.parse_expression_list(TokenKind::BracketOpen);
 
+        // The list has already reported a missing closing bracket
+        if self.peek_kind() != TokenKind::BracketClose {
+            return None;
+        }
+
         let end_token = self.lexer.next().unwrap();
 
         Some(ExpressionNode {
             expression: Expression::FunctionCall {
                 function: Rc::new(left.clone()),
                 args: exprs,
+                type_args: vec![],
             },
             typed: AzulaType::Infer,
             span: Span {
@@ -927,11 +1614,7 @@ impl<'a> Parser<'a> {
                 });
             }
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+            self.unexpected_eof();
             return Some(ExpressionNode {
                 expression: Expression::Array(vec![]),
                 typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
@@ -948,16 +1631,21 @@ impl<'a> Parser<'a> {
             expressions.push(expr);
         }
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
+        let mut peek = self.peek_kind();
         // Parse initialiser like [0; 20]
         if peek == TokenKind::SemiColon {
             self.lexer.next();
 
-            if let TokenKind::Integer(v) = self.lexer.next().unwrap().kind {
+            if let TokenKind::Integer(v) = self.peek_kind() {
+                self.lexer.next();
+
+                let first = expressions.first()?.clone();
                 for _ in 0..v - 1 {
-                    expressions.push(expressions[0].clone());
+                    expressions.push(first.clone());
+                }
+                if !self.expect_peek(TokenKind::SquareClose) {
+                    return None;
                 }
-                self.expect_peek(TokenKind::SquareClose);
 
                 let close = self.lexer.next().unwrap();
                 return Some(ExpressionNode {
@@ -969,9 +1657,7 @@ impl<'a> Parser<'a> {
                     },
                 });
             } else {
-                self.expect_peek(TokenKind::SquareClose);
-
-                let close = self.lexer.next().unwrap();
+                let close = self.lexer.next()?;
                 self.errors.push(AzulaError::new(
                     ErrorType::ArrayInitialiserSizeNonConstant,
                     tok.span.start,
@@ -986,10 +1672,12 @@ impl<'a> Parser<'a> {
             if let Some(expr) = self.parse_expression(LOWEST, true) {
                 expressions.push(expr);
             }
-            peek = self.lexer.peek().unwrap().kind.clone();
+            peek = self.peek_kind();
         }
 
-        self.expect_peek(TokenKind::SquareClose);
+        if !self.expect_peek(TokenKind::SquareClose) {
+            return None;
+        }
 
         let close = self.lexer.next().unwrap();
 
@@ -1003,6 +1691,127 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn parse_match(&mut self, tok: Token) -> Option<ExpressionNode<'a>> {
+        let subject = self.parse_expression(LOWEST, false)?;
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+        self.lexer.next();
+
+        let mut arms = vec![];
+        loop {
+            while self.peek_kind().is_comment() {
+                self.lexer.next();
+            }
+            if self.peek_kind() == TokenKind::BraceClose {
+                break;
+            }
+
+            let arm = self.parse_match_arm()?;
+
+            // Arms are separated by commas, which can be left out after a block
+            if self.peek_kind() == TokenKind::Comma {
+                self.lexer.next();
+            } else if arm.value.is_some() && self.peek_kind() != TokenKind::BraceClose {
+                self.expect_peek(TokenKind::Comma);
+                return None;
+            }
+
+            arms.push(arm);
+        }
+
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::Match(Rc::new(subject), arms),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: tok.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    fn parse_match_arm(&mut self) -> Option<MatchArm<'a>> {
+        let start = match self.lexer.peek() {
+            Some(tok) => tok.span.start,
+            None => {
+                self.unexpected_eof();
+                return None;
+            }
+        };
+
+        let pattern = match self.parse_identifier()? {
+            "_" => Pattern::Wildcard,
+            name => {
+                let mut bindings = vec![];
+                if self.peek_kind() == TokenKind::BracketOpen {
+                    self.lexer.next();
+
+                    while self.peek_kind() != TokenKind::BracketClose {
+                        bindings.push(self.parse_identifier()?);
+
+                        if self.peek_kind() != TokenKind::Comma {
+                            break;
+                        }
+                        self.lexer.next();
+                    }
+
+                    if !self.expect_peek(TokenKind::BracketClose) {
+                        return None;
+                    }
+                    self.lexer.next();
+                }
+
+                Pattern::Variant(name, bindings)
+            }
+        };
+
+        if !self.expect_peek(TokenKind::FatArrow) {
+            return None;
+        }
+        self.lexer.next();
+
+        if self.peek_kind() == TokenKind::BraceOpen {
+            self.lexer.next();
+
+            let body = self.parse_block(TokenKind::BraceClose);
+
+            if !self.expect_peek(TokenKind::BraceClose) {
+                return None;
+            }
+
+            let end_token = self.lexer.next().unwrap();
+
+            return Some(MatchArm {
+                pattern,
+                body,
+                value: None,
+                span: Span {
+                    start,
+                    end: end_token.span.end,
+                },
+            });
+        }
+
+        let value = self.parse_expression(LOWEST, true)?;
+
+        Some(MatchArm {
+            pattern,
+            body: vec![],
+            span: Span {
+                start,
+                end: value.span.end,
+            },
+            value: Some(value),
+        })
+    }
+
     fn parse_array_access(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
         self.lexer.next();
         let index = match self.parse_expression(LOWEST, true) {
@@ -1052,11 +1861,7 @@ impl<'a> Parser<'a> {
         let mut peek = match self.lexer.peek() {
             Some(peek) => peek.kind.clone(),
             None => {
-                self.errors.push(AzulaError::new(
-                    ErrorType::UnexpectedEOF,
-                    self.source.len() - 2,
-                    self.source.len() - 1,
-                ));
+                self.unexpected_eof();
                 return None;
             }
         };
@@ -1072,14 +1877,7 @@ impl<'a> Parser<'a> {
                     }
                 }
             }
-            let tok = self.lexer.next();
-            let name = match tok {
-                Some(Token {
-                    kind: TokenKind::Identifier(val),
-                    span: _,
-                }) => val,
-                _ => todo!("{:?}", tok),
-            };
+            let name = self.parse_identifier()?;
 
             if !self.expect_peek(TokenKind::Colon) {
                 return None;
@@ -1087,18 +1885,14 @@ impl<'a> Parser<'a> {
 
             self.lexer.next();
 
-            let expr = self.parse_expression(LOWEST, true).unwrap();
+            let expr = self.parse_expression(LOWEST, true)?;
 
             attrs.push((name, expr));
 
             peek = match self.lexer.peek() {
                 Some(peek) => peek.kind.clone(),
                 None => {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::UnexpectedEOF,
-                        self.source.len() - 2,
-                        self.source.len() - 1,
-                    ));
+                    self.unexpected_eof();
                     return None;
                 }
             };
@@ -1156,14 +1950,16 @@ fn string_transform(str: &str) -> Result<String, usize> {
                 }
                 Some((_, 'x')) => {
                     chars.next();
-                    let (_, char1) = chars.next().unwrap();
-                    let (_, char2) = chars.next().unwrap();
-                    result.push(
-                        char::from_u32(
-                            i64::from_str_radix(&format!("{}{}", char1, char2), 16).unwrap() as u32,
-                        )
-                        .unwrap(),
-                    );
+                    let code = match (chars.next(), chars.next()) {
+                        (Some((_, char1)), Some((_, char2))) => {
+                            u32::from_str_radix(&format!("{}{}", char1, char2), 16).ok()
+                        }
+                        _ => None,
+                    };
+                    match code.and_then(char::from_u32) {
+                        Some(char) => result.push(char),
+                        None => return Err(index + 1),
+                    }
                 }
                 _ => return Err(index + 1),
             }
@@ -1238,6 +2034,9 @@ mod tests {
             "func test(x: bool): int { return 5; } func test2(x: int): int { return x; }",
             vec![Statement::Function {
                 name: "test",
+                public: false,
+                attributes: vec![],
+                type_params: vec![],
                 args: vec![(AzulaType::Bool, "x")],
                 returns: AzulaType::Int,
                 body: Rc::new(Statement::Block(vec![Statement::Return(Some(ExpressionNode {
@@ -1248,6 +2047,9 @@ mod tests {
                 span: Span { start: 0, end: 37},
             }, Statement::Function {
                 name: "test2",
+                public: false,
+                attributes: vec![],
+                type_params: vec![],
                 args: vec![(AzulaType::Int, "x")],
                 returns: AzulaType::Int,
                 body: Rc::new(Statement::Block(vec![Statement::Return(Some(ExpressionNode {
@@ -1340,139 +2142,509 @@ mod tests {
             assert_eq!(body, Rc::new(Statement::Block(vec![])));
         }
 
-        // Args & return type
-        let input = "func test(x: int, y: bool): bool { }";
+        // Args & return type
+        let input = "func test(x: int, y: bool): bool { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_function().unwrap();
+        assert!(matches!(func, Statement::Function { .. }));
+        if let Statement::Function {
+            name,
+            args,
+            returns,
+            body,
+            ..
+        } = func
+        {
+            assert_eq!(name, "test");
+            assert_eq!(args, vec![(AzulaType::Int, "x"), (AzulaType::Bool, "y")]);
+            assert_eq!(returns, AzulaType::Bool);
+            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        }
+    }
+
+    #[test]
+    fn test_parse_extern_function() {
+        let input = "extern varargs func test(int): bool;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        println!("{:?}", func);
+        assert!(matches!(func, Statement::ExternFunction { .. }));
+        if let Statement::ExternFunction {
+            name,
+            varargs,
+            args,
+            returns,
+            ..
+        } = func
+        {
+            assert_eq!(name, "test");
+            assert_eq!(varargs, true);
+            assert_eq!(args, vec![AzulaType::Int]);
+            assert_eq!(returns, AzulaType::Bool);
+        }
+    }
+
+    #[test]
+    fn test_parse_assign() {
+        let input = "var test = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+
+        let input = "const test = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+
+        // Type annotation
+        let input = "var test: int = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let assign = parser.parse_assign(true).unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+        if let Statement::Assign(mutable, name, type_annotation, value, ..) = assign {
+            assert_eq!(mutable, true);
+            assert_eq!(name, "test".to_string());
+            assert_eq!(type_annotation, Some(AzulaType::Int));
+            assert_eq!(
+                value,
+                ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Int,
+                    span: Span { start: 16, end: 17 }
+                }
+            )
+        }
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        // No attributes
+        let input = "struct Test { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+
+        // Single attribute
+        let input = "struct Test { test: int }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+        if let Statement::Struct {
+            name,
+            public,
+            attributes,
+            span,
+            ..
+        } = struc
+        {
+            assert_eq!(name, "Test");
+            assert!(!public);
+            assert_eq!(attributes, vec![(AzulaType::Int, "test")]);
+            assert_eq!(span, Span { start: 0, end: 25 });
+        }
+
+        // Multiple attribute
+        let input = "struct Test { test: int, test1: &str, }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+        if let Statement::Struct {
+            name,
+            public,
+            attributes,
+            span,
+            ..
+        } = struc
+        {
+            assert_eq!(name, "Test");
+            assert!(!public);
+            assert_eq!(
+                attributes,
+                vec![
+                    (AzulaType::Int, "test"),
+                    (AzulaType::Pointer(Rc::new(AzulaType::Str)), "test1")
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_pub() {
+        let input = "pub func test() {} pub struct Test { test: int }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(matches!(stmt, Statement::Function { public: true, .. }));
+        if let Statement::Function { span, .. } = stmt {
+            assert_eq!(span, Span { start: 0, end: 18 });
+        }
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(matches!(stmt, Statement::Struct { public: true, .. }));
+        assert!(parser.errors.is_empty());
+
+        // Only functions and structs can be public
+        let input = "pub var x = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        assert!(parser.parse_statement().is_none());
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let input = "#[allow(unused_variables, never_reassigned)] pub func test() {}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::Function {
+            public, attributes, ..
+        } = stmt
+        {
+            assert!(public);
+            assert_eq!(
+                attributes,
+                vec![Attribute {
+                    name: "allow",
+                    args: vec!["unused_variables", "never_reassigned"],
+                    span: Span { start: 0, end: 44 },
+                }]
+            );
+        } else {
+            panic!("expected a function, got {:?}", stmt);
+        }
+
+        let input = "impl Test { #[allow(unused_variables)] func test(self) {} }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::Impl { methods, .. } = stmt {
+            assert!(
+                matches!(&methods[0], Statement::Function { attributes, .. } if attributes.len() == 1)
+            );
+        }
+
+        let input = "#[test] func adds() {}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        assert!(stmt.is_test());
+
+        for (input, error) in [
+            ("#[inline] func test() {}", "UnknownAttribute(\"inline\")"),
+            ("#[allow(unused)] func test() {}", "UnknownLint(\"unused\")"),
+            (
+                "#[test(fast)] func test() {}",
+                "UnknownAttribute(\"test(fast)\")",
+            ),
+            (
+                "#[allow(unused_variables)] pub struct Test {}",
+                "MisplacedAttribute",
+            ),
+        ] {
+            let lexer: Lexer = input.into();
+            let mut parser = Parser::new(input, lexer);
+
+            assert!(parser.parse_statement().is_none());
+            assert_eq!(format!("{:?}", parser.errors[0].error_type), error);
+        }
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let input = "import \"lib/math.azl\";";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            stmt,
+            Statement::Import("lib/math.azl", Span { start: 0, end: 22 })
+        );
+
+        // Path must be a string
+        let input = "import math;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        assert!(parser.parse_statement().is_none());
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
+    }
+
+    #[test]
+    fn test_parse_impl() {
+        let input =
+            "impl Point { func len(self): int { return self.x; } func scale(self, by: int) { } }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        assert!(matches!(stmt, Statement::Impl { .. }));
+        if let Statement::Impl {
+            name,
+            methods,
+            span,
+        } = stmt
+        {
+            assert_eq!(name, "Point");
+            assert_eq!(methods.len(), 2);
+            assert_eq!(span, Span { start: 0, end: 83 });
+
+            let receiver = AzulaType::Pointer(Rc::new(AzulaType::Named("Point".to_string())));
+            if let Statement::Function { name, args, .. } = &methods[1] {
+                assert_eq!(*name, "scale");
+                assert_eq!(
+                    args.clone(),
+                    vec![(receiver, "self"), (AzulaType::Int, "by")]
+                );
+            }
+        }
+
+        // Missing self
+        let input = "impl Point { func len(x: int): int { return x; } }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        parser.parse_statement().unwrap();
+        assert_eq!(parser.errors.len(), 1);
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::MethodWithoutSelf(..)
+        ));
+
+        // self anywhere but first in a method
+        for input in [
+            "func len(self): int { return 1; }",
+            "impl Point { func len(self, self): int { return 1; } }",
+        ] {
+            let lexer: Lexer = input.into();
+            let mut parser = Parser::new(input, lexer);
+
+            parser.parse_statement().unwrap();
+            assert_eq!(parser.errors.len(), 1, "{}", input);
+            assert!(matches!(
+                parser.errors[0].error_type,
+                ErrorType::SelfOutsideMethod
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_method_call() {
+        let input = "p.len(1)";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        if let Expression::FunctionCall { function, args, .. } = expr.expression {
+            assert!(matches!(function.expression, Expression::StructAccess(..)));
+            assert_eq!(args.len(), 1);
+        } else {
+            panic!("expected function call");
+        }
+    }
+
+    #[test]
+    fn test_parse_for() {
+        let input = "for i in 0..n + 1 { x = i; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::For(ident, iterable, body, span) = stmt {
+            assert_eq!(ident, "i");
+            assert_eq!(body.len(), 1);
+            assert_eq!(span, Span { start: 0, end: 28 });
+            assert_eq!(iterable.span, Span { start: 9, end: 17 });
+            if let Expression::Range(start, end) = iterable.expression {
+                assert_eq!(start.expression, Expression::Integer(0));
+                assert!(matches!(end.expression, Expression::Infix(..)));
+            } else {
+                panic!("expected range");
+            }
+        } else {
+            panic!("expected for");
+        }
+
+        let input = "for x in arr { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::For(ident, iterable, ..) = stmt {
+            assert_eq!(ident, "x");
+            assert_eq!(
+                iterable.expression,
+                Expression::Identifier("arr".to_string())
+            );
+        }
+
+        // Missing in
+        let input = "for x arr { }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_function().unwrap();
-        assert!(matches!(func, Statement::Function { .. }));
-        if let Statement::Function {
-            name,
-            args,
-            returns,
-            body,
-            ..
-        } = func
-        {
-            assert_eq!(name, "test");
-            assert_eq!(args, vec![(AzulaType::Int, "x"), (AzulaType::Bool, "y")]);
-            assert_eq!(returns, AzulaType::Bool);
-            assert_eq!(body, Rc::new(Statement::Block(vec![])));
-        }
+        assert!(parser.parse_statement().is_none());
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
     }
 
     #[test]
-    fn test_parse_extern_function() {
-        let input = "extern varargs func test(int): bool;";
+    fn test_parse_loop_jump() {
+        let input = "while true { break; continue; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        println!("{:?}", func);
-        assert!(matches!(func, Statement::ExternFunction { .. }));
-        if let Statement::ExternFunction {
-            name,
-            varargs,
-            args,
-            returns,
-            ..
-        } = func
-        {
-            assert_eq!(name, "test");
-            assert_eq!(varargs, true);
-            assert_eq!(args, vec![AzulaType::Int]);
-            assert_eq!(returns, AzulaType::Bool);
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::While(_, body, _) = stmt {
+            assert_eq!(
+                body,
+                vec![
+                    Statement::Break(Span { start: 13, end: 19 }),
+                    Statement::Continue(Span { start: 20, end: 29 })
+                ]
+            );
+        } else {
+            panic!("expected while");
         }
-    }
 
-    #[test]
-    fn test_parse_assign() {
-        let input = "var test = 5;";
+        // Missing semicolon
+        let input = "break }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
+        assert!(parser.parse_statement().is_none());
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
+    }
 
-        let input = "const test = 5;";
+    #[test]
+    fn test_parse_enum() {
+        let input = "pub enum Shape { Circle(int), Rect(int, int), Empty }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            stmt,
+            Statement::Enum {
+                name: "Shape",
+                public: true,
+                variants: vec![
+                    ("Circle", vec![AzulaType::Int]),
+                    ("Rect", vec![AzulaType::Int, AzulaType::Int]),
+                    ("Empty", vec![]),
+                ],
+                span: Span { start: 0, end: 53 },
+            }
+        );
 
-        // Type annotation
-        let input = "var test: int = 5;";
+        // Trailing comma
+        let input = "enum Colour { Red, Green, }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let assign = parser.parse_assign(true).unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
-        if let Statement::Assign(mutable, name, type_annotation, value, ..) = assign {
-            assert_eq!(mutable, true);
-            assert_eq!(name, "test".to_string());
-            assert_eq!(type_annotation, Some(AzulaType::Int));
-            assert_eq!(
-                value,
-                ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Int,
-                    span: Span { start: 16, end: 17 }
-                }
-            )
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::Enum { variants, .. } = stmt {
+            assert_eq!(variants.len(), 2);
         }
     }
 
     #[test]
-    fn test_parse_struct() {
-        // No attributes
-        let input = "struct Test { }";
+    fn test_parse_match() {
+        let input = "match s { Circle(r) => r * r, Rect(w, _) => { return w; } _ => 0 }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
+        let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        let expr = match stmt {
+            Statement::ExpressionStatement(expr, _) => expr,
+            _ => panic!("expected expression statement"),
+        };
+        assert_eq!(expr.span, Span { start: 0, end: 66 });
+        if let Expression::Match(subject, arms) = expr.expression {
+            assert_eq!(subject.expression, Expression::Identifier("s".to_string()));
+            assert_eq!(arms.len(), 3);
+            assert_eq!(arms[0].pattern, Pattern::Variant("Circle", vec!["r"]));
+            assert!(matches!(
+                arms[0].value,
+                Some(ExpressionNode {
+                    expression: Expression::Infix(..),
+                    ..
+                })
+            ));
+            assert_eq!(arms[1].pattern, Pattern::Variant("Rect", vec!["w", "_"]));
+            assert_eq!(arms[1].body.len(), 1);
+            assert_eq!(arms[1].value, None);
+            assert_eq!(arms[2].pattern, Pattern::Wildcard);
+        } else {
+            panic!("expected match");
+        }
 
-        // Single attribute
-        let input = "struct Test { test: int }";
+        // As an expression
+        let input = "var area = match s { Empty => 0, _ => 1 };";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
-        if let Statement::Struct {
-            name,
-            attributes,
-            span,
-        } = struc
-        {
-            assert_eq!(name, "Test");
-            assert_eq!(attributes, vec![(AzulaType::Int, "test")]);
-        }
+        assert!(matches!(
+            parser.parse_statement(),
+            Some(Statement::Assign(..))
+        ));
+        assert!(parser.errors.is_empty());
 
-        // Multiple attribute
-        let input = "struct Test { test: int, test1: &str, }";
+        // Missing comma between expression arms
+        let input = "match s { Empty => 0 _ => 1 }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
-        if let Statement::Struct {
-            name,
-            attributes,
-            span,
-        } = struc
-        {
-            assert_eq!(name, "Test");
-            assert_eq!(
-                attributes,
-                vec![
-                    (AzulaType::Int, "test"),
-                    (AzulaType::Pointer(Rc::new(AzulaType::Str)), "test1")
-                ]
-            );
-        }
+        assert!(parser.parse_statement().is_none());
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
     }
 
     #[test]
@@ -1535,6 +2707,59 @@ mod tests {
                 )
             );
         }
+
+        // Else
+        let input = "if x { return 5; } else { return 6; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_if().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::If(_, body, else_body, span) = func {
+            assert_eq!(body.len(), 1);
+            assert_eq!(
+                else_body,
+                Some(vec![Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(6),
+                        typed: AzulaType::Int,
+                        span: Span { start: 33, end: 34 }
+                    }),
+                    Span { start: 26, end: 35 }
+                )])
+            );
+            assert_eq!(span, Span { start: 0, end: 37 });
+        }
+
+        // Else if
+        let input = "if x { return 5; } else if y { return 6; } else { return 7; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_if().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::If(_, _, else_body, span) = func {
+            let else_body = else_body.unwrap();
+            assert_eq!(else_body.len(), 1);
+            assert!(matches!(else_body[0], Statement::If(_, _, Some(_), _)));
+            if let Statement::If(ref expr, _, _, ref nested_span) = else_body[0] {
+                assert_eq!(expr.expression, Expression::Identifier("y".to_string()));
+                assert_eq!(nested_span.clone(), Span { start: 24, end: 61 });
+            }
+            assert_eq!(span, Span { start: 0, end: 61 });
+        }
+
+        // Comments before else
+        let input = "if x { return 5; }\n// otherwise\nelse if y { return 6; } // no\nelse { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_if().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::If(_, _, else_body, _) = func {
+            let else_body = else_body.unwrap();
+            assert!(matches!(else_body[0], Statement::If(_, _, Some(_), _)));
+        }
     }
 
     #[test]
@@ -1597,6 +2822,78 @@ mod tests {
 
         let typ = parser.parse_type();
         assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), Some(20)));
+
+        // Generic struct
+        let input = "Pair<int, &Pair<T, bool>>";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            typ,
+            AzulaType::Generic(
+                "Pair".to_string(),
+                vec![
+                    AzulaType::Int,
+                    AzulaType::Pointer(Rc::new(AzulaType::Generic(
+                        "Pair".to_string(),
+                        vec![AzulaType::Named("T".to_string()), AzulaType::Bool]
+                    )))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_generics() {
+        let input = "func max<T>(a: T, b: T): T { return a; } pub struct Pair<A, B> { first: A, second: B }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmts = match parser.parse() {
+            Statement::Root(stmts) => stmts,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
+
+        if let Statement::Function {
+            name,
+            type_params,
+            args,
+            returns,
+            ..
+        } = &stmts[0]
+        {
+            assert_eq!(*name, "max");
+            assert_eq!(*type_params, vec!["T"]);
+            assert_eq!(args[1], (AzulaType::Named("T".to_string()), "b"));
+            assert_eq!(*returns, AzulaType::Named("T".to_string()));
+        } else {
+            panic!("expected function");
+        }
+
+        if let Statement::Struct {
+            name,
+            public,
+            type_params,
+            ..
+        } = &stmts[1]
+        {
+            assert_eq!(*name, "Pair");
+            assert!(public);
+            assert_eq!(*type_params, vec!["A", "B"]);
+        } else {
+            panic!("expected struct");
+        }
+
+        // An unclosed parameter list is reported rather than panicking
+        let input = "func max<T(a: T) {}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        parser.parse();
+        assert!(!parser.errors.is_empty());
     }
 
     #[test]
@@ -1852,7 +3149,8 @@ mod tests {
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let identifiers = parser.parse_typed_identifier_list(TokenKind::BracketOpen);
+        let (identifiers, end) = parser.parse_typed_identifier_list(TokenKind::BracketOpen);
+        assert_eq!(end, input.len());
 
         let (typ, name) = &identifiers[0];
         assert_eq!(typ.clone(), AzulaType::Int);
@@ -2145,6 +3443,7 @@ mod tests {
                     span: Span { start: 0, end: 4 },
                 }),
                 args: vec![],
+                type_args: vec![],
             }
         );
 
@@ -2175,6 +3474,7 @@ mod tests {
                         span: Span { start: 8, end: 14 }
                     }
                 ],
+                type_args: vec![],
             }
         );
     }
@@ -2267,4 +3567,117 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_parse_recovery() {
+        let input = "var x = ; var y = 2; func main { var z = 1 + ; return z; } }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let body = if let Statement::Root(body) = parser.parse() {
+            body
+        } else {
+            unreachable!()
+        };
+
+        assert_eq!(parser.errors.len(), 3);
+        assert_eq!(body.len(), 2);
+        assert!(matches!(body[0], Statement::Assign(_, ref name, ..) if name == "y"));
+        if let Statement::Function { body, .. } = &body[1] {
+            assert!(matches!(body.as_ref(), Statement::Block(stmts) if stmts.len() == 1));
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_parse_docs() {
+        let input = "/// Adds one\n///\n///  to x\n// Not docs\npub func add(x: int): int {\n    /// Unused\n    return x + 1;\n}\n\nimpl Point {\n    /// Moves it\n    #[allow(unused_variables)]\n    func shift(self) {}\n}\n/// Nothing after";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        parser.parse();
+
+        assert!(parser.errors.is_empty());
+        assert_eq!(parser.docs.len(), 2);
+        assert_eq!(
+            parser
+                .docs
+                .get(&input.find("pub").unwrap())
+                .map(String::as_str),
+            Some("Adds one\n\n to x")
+        );
+        assert_eq!(
+            parser
+                .docs
+                .get(&input.find("func shift").unwrap())
+                .map(String::as_str),
+            Some("Moves it")
+        );
+    }
+
+    fn parse_source(input: &str) -> (Statement<'_>, Vec<AzulaError>) {
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        let root = parser.parse();
+        (root, parser.errors)
+    }
+
+    fn read_corpus(dir: &std::path::Path, sources: &mut Vec<(String, String)>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                read_corpus(&path, sources);
+            } else if path.extension().is_some_and(|ext| ext == "azl") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                sources.push((path.display().to_string(), source));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_broken_corpus() {
+        let mut broken = vec![];
+        read_corpus(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus"),
+            &mut broken,
+        );
+        assert!(!broken.is_empty());
+
+        for (path, source) in &broken {
+            let (root, errors) = parse_source(source);
+            assert!(matches!(root, Statement::Root(_)), "{}", path);
+            assert!(!errors.is_empty(), "{} parsed without errors", path);
+        }
+    }
+
+    #[test]
+    fn test_parse_mangled_examples() {
+        let mut sources = vec![];
+        read_corpus(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples"),
+            &mut sources,
+        );
+        read_corpus(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus"),
+            &mut sources,
+        );
+
+        for (_, source) in &sources {
+            let boundaries = source
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(source.len()));
+
+            for i in boundaries {
+                // Every truncation of the file
+                parse_source(&source[..i]);
+
+                // Every file with a single character removed
+                if let Some(c) = source[i..].chars().next() {
+                    let removed = format!("{}{}", &source[..i], &source[i + c.len_utf8()..]);
+                    parse_source(&removed);
+                }
+            }
+        }
+    }
 }
@@ -19,6 +19,7 @@ pub enum TokenKind<'a> {
     BraceClose,   // }
 
     Dot,       // .
+    DotDot,    // ..
     Comma,     // ,
     SemiColon, // ;
     Colon,     // :
@@ -29,6 +30,7 @@ pub enum TokenKind<'a> {
     Asterisk,     // *
     Power,        // **
     Assign,       // =
+    FatArrow,     // =>
     Equal,        // ==
     NotEqual,     // !=
     Bar,          // |
@@ -41,6 +43,7 @@ pub enum TokenKind<'a> {
     Greater,      // >
     GreaterEqual, // >=
     Modulo,       // %
+    Hash,         // #
 
     Function, // func
     Return,   // return
@@ -49,12 +52,26 @@ pub enum TokenKind<'a> {
     True,     // true
     False,    // false
     If,       // If
+    Else,     // else
     Extern,   // extern
     VarArgs,  // varargs
     While,    // while
     Struct,   // struct
+    Impl,     // impl
+    Pub,      // pub
+    Import,   // import
+    For,      // for
+    In,       // in
+    Break,    // break
+    Continue, // continue
+    Enum,     // enum
+    Match,    // match
 
-    Comment,
+    // The text after the slashes, without the line break
+    Comment(&'a str),    // //
+    DocComment(&'a str), // ///
+    // Only kept by a lossless lexer
+    Whitespace(&'a str),
     UnknownToken,
     EOF,
 }
@@ -68,6 +85,11 @@ impl<'a> TokenKind<'a> {
             _ => None,
         }
     }
+
+    /// Whether the token is a comment, which the parser skips over
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Self::Comment(_) | Self::DocComment(_))
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -0,0 +1,343 @@
+use std::rc::Rc;
+
+use azula_ast::prelude::*;
+use azula_error::prelude::*;
+use azula_type::prelude::AzulaType;
+use azula_typecheck::prelude::diverges;
+
+/// Rewrites the asserts in a file, and either turns each `#[test]` function
+/// into one that gives whether it passed or, for builds that aren't running
+/// the file's tests, removes them.
+/// `assert(cond, msg)` in a test prints the message and returns false when
+/// the condition doesn't hold, and the test returns true if it gets to the end.
+/// Other functions can assert in any build, but can't fail the test that
+/// called them, so a failed assert in one prints the message and ends the
+/// whole run
+pub fn rewrite_asserts<'a>(
+    source: &str,
+    stmts: Vec<Statement<'a>>,
+    tests: bool,
+) -> (Vec<Statement<'a>>, Vec<AzulaError>) {
+    let mut rewriter = Rewriter {
+        source,
+        errors: vec![],
+        exits: false,
+    };
+    let mut stmts: Vec<_> = stmts
+        .into_iter()
+        .filter(|stmt| tests || !stmt.is_test())
+        .map(|stmt| match stmt {
+            Statement::Function { .. } if stmt.is_test() => rewriter.test(stmt),
+            Statement::Function { .. } => rewriter.function(stmt),
+            Statement::Impl {
+                name,
+                methods,
+                span,
+            } => Statement::Impl {
+                name,
+                methods: methods
+                    .into_iter()
+                    .map(|method| rewriter.function(method))
+                    .collect(),
+                span,
+            },
+            _ => stmt,
+        })
+        .collect();
+
+    let declares_exit = stmts.iter().any(|stmt| {
+        matches!(
+            stmt,
+            Statement::ExternFunction { name: "exit", .. }
+                | Statement::Function { name: "exit", .. }
+        )
+    });
+    if rewriter.exits && !declares_exit {
+        stmts.push(Statement::ExternFunction {
+            name: "exit",
+            varargs: false,
+            args: vec![AzulaType::Int],
+            returns: AzulaType::Void,
+            span: Span { start: 0, end: 0 },
+        });
+    }
+
+    (stmts, rewriter.errors)
+}
+
+struct Rewriter<'s> {
+    source: &'s str,
+    errors: Vec<AzulaError>,
+    /// Whether an assert outside a test needs `exit`
+    exits: bool,
+}
+
+impl<'s> Rewriter<'s> {
+    fn test<'a>(&mut self, stmt: Statement<'a>) -> Statement<'a> {
+        match stmt {
+            Statement::Function {
+                name,
+                public,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+            } => {
+                if !args.is_empty() || !type_params.is_empty() || returns != AzulaType::Void {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::InvalidTest(name.to_string()),
+                        span.start,
+                        span.end,
+                    ));
+                }
+
+                let mut stmts = match body.as_ref() {
+                    Statement::Block(stmts) => self.body(stmts, true),
+                    _ => unreachable!(),
+                };
+                if !stmts.last().is_some_and(diverges) {
+                    let end = Span {
+                        start: span.end - 1,
+                        end: span.end,
+                    };
+                    stmts.push(Statement::Return(Some(boolean(true, &end)), end));
+                }
+
+                Statement::Function {
+                    name,
+                    public,
+                    attributes,
+                    type_params,
+                    args,
+                    returns: AzulaType::Bool,
+                    body: Rc::new(Statement::Block(stmts)),
+                    span,
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Rewrites the asserts in a function that isn't a test, leaving the rest
+    fn function<'a>(&mut self, stmt: Statement<'a>) -> Statement<'a> {
+        match stmt {
+            Statement::Function {
+                name,
+                public,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+            } => {
+                let body = match body.as_ref() {
+                    Statement::Block(stmts) => Rc::new(Statement::Block(self.body(stmts, false))),
+                    _ => body,
+                };
+
+                Statement::Function {
+                    name,
+                    public,
+                    attributes,
+                    type_params,
+                    args,
+                    returns,
+                    body,
+                    span,
+                }
+            }
+            _ => stmt,
+        }
+    }
+
+    fn body<'a>(&mut self, stmts: &[Statement<'a>], test: bool) -> Vec<Statement<'a>> {
+        stmts
+            .iter()
+            .map(|stmt| self.statement(stmt, test))
+            .collect()
+    }
+
+    fn statement<'a>(&mut self, stmt: &Statement<'a>, test: bool) -> Statement<'a> {
+        match stmt {
+            Statement::ExpressionStatement(expr, span) => match &expr.expression {
+                Expression::FunctionCall { function, args, .. }
+                    if function.expression == Expression::Identifier("assert".to_string()) =>
+                {
+                    if args.len() != 2 {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::WrongArgumentCount("assert".to_string(), 2, args.len()),
+                            span.start,
+                            span.end,
+                        ));
+                        return stmt.clone();
+                    }
+                    self.exits |= !test;
+                    assert(self.source, &args[0], &args[1], test, span)
+                }
+                Expression::Match(subject, arms) => {
+                    let arms = arms
+                        .iter()
+                        .map(|arm| MatchArm {
+                            body: self.body(&arm.body, test),
+                            ..arm.clone()
+                        })
+                        .collect();
+                    Statement::ExpressionStatement(
+                        ExpressionNode {
+                            expression: Expression::Match(subject.clone(), arms),
+                            ..expr.clone()
+                        },
+                        span.clone(),
+                    )
+                }
+                _ => stmt.clone(),
+            },
+            Statement::Return(None, span) if test => {
+                Statement::Return(Some(boolean(true, span)), span.clone())
+            }
+            Statement::If(condition, then, otherwise, span) => Statement::If(
+                condition.clone(),
+                self.body(then, test),
+                otherwise
+                    .as_ref()
+                    .map(|otherwise| self.body(otherwise, test)),
+                span.clone(),
+            ),
+            Statement::While(condition, body, span) => {
+                Statement::While(condition.clone(), self.body(body, test), span.clone())
+            }
+            Statement::For(name, iterable, body, span) => Statement::For(
+                name.clone(),
+                iterable.clone(),
+                self.body(body, test),
+                span.clone(),
+            ),
+            Statement::Block(body) => Statement::Block(self.body(body, test)),
+            _ => stmt.clone(),
+        }
+    }
+}
+
+/// The variable an assert's message is kept in
+const ASSERT_MESSAGE: &str = "assert-message";
+
+/// `assert(cond, msg);` on line 3 becomes
+/// ```text
+/// if !cond {
+///     const assert-message: &str = msg;
+///     printf("    assertion failed on line 3: %s\n", assert-message);
+///     return false;
+/// }
+/// ```
+/// so the message has to be a string and the test stops at the first failure.
+/// `assert-message` can't be written in source, so it can't clash with the
+/// test's own variables. Outside a test `return false` is `exit(1)` instead
+fn assert<'a>(
+    source: &str,
+    condition: &ExpressionNode<'a>,
+    message: &ExpressionNode<'a>,
+    test: bool,
+    span: &Span,
+) -> Statement<'a> {
+    let line = source[..span.start].matches('\n').count() + 1;
+    let node = |expression| ExpressionNode {
+        expression,
+        typed: AzulaType::Infer,
+        span: span.clone(),
+    };
+
+    let stop = if test {
+        Statement::Return(Some(boolean(false, span)), span.clone())
+    } else {
+        Statement::ExpressionStatement(
+            node(Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier("exit".to_string()))),
+                args: vec![node(Expression::Integer(1))],
+                type_args: vec![],
+            }),
+            span.clone(),
+        )
+    };
+
+    let failed = vec![
+        Statement::Assign(
+            false,
+            ASSERT_MESSAGE.to_string(),
+            Some(AzulaType::Pointer(Rc::new(AzulaType::Str))),
+            message.clone(),
+            span.clone(),
+        ),
+        Statement::ExpressionStatement(
+            node(Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier("printf".to_string()))),
+                args: vec![
+                    node(Expression::String(format!(
+                        "    assertion failed on line {}: %s\n",
+                        line
+                    ))),
+                    node(Expression::Identifier(ASSERT_MESSAGE.to_string())),
+                ],
+                type_args: vec![],
+            }),
+            span.clone(),
+        ),
+        stop,
+    ];
+
+    Statement::If(
+        node(Expression::Not(Rc::new(condition.clone()))),
+        failed,
+        None,
+        span.clone(),
+    )
+}
+
+fn boolean<'a>(value: bool, span: &Span) -> ExpressionNode<'a> {
+    ExpressionNode {
+        expression: Expression::Boolean(value),
+        typed: AzulaType::Bool,
+        span: span.clone(),
+    }
+}
+
+/// The source of a `main` that runs each test, printing whether it passed
+/// and a summary at the end. It exits with 1 if any of them failed
+pub fn harness(tests: &[&str]) -> String {
+    let mut out = String::from("func main: int {\n");
+    out += &format!(
+        "    printf(\"running {} test{}\\n\");\n",
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" }
+    );
+    if tests.is_empty() {
+        out += "    printf(\"\\ntest result: ok. 0 passed; 0 failed\\n\");\n";
+        out += "    return 0;\n}\n";
+        return out;
+    }
+
+    out += "    var failed = 0;\n";
+    for test in tests {
+        out += &format!("    if {}() {{\n", test);
+        out += &format!("        printf(\"test {} ... ok\\n\");\n", test);
+        out += "    } else {\n";
+        out += &format!("        printf(\"test {} ... FAILED\\n\");\n", test);
+        out += "        failed = failed + 1;\n";
+        out += "    }\n";
+    }
+    out += "    if failed > 0 {\n";
+    out += &format!(
+        "        printf(\"\\ntest result: FAILED. %d passed; %d failed\\n\", {} - failed, failed);\n",
+        tests.len()
+    );
+    out += "        return 1;\n";
+    out += "    }\n";
+    out += &format!(
+        "    printf(\"\\ntest result: ok. {} passed; 0 failed\\n\");\n",
+        tests.len()
+    );
+    out += "    return 0;\n}\n";
+    out
+}
@@ -0,0 +1,8 @@
+mod harness;
+mod rename;
+mod resolver;
+
+pub mod prelude {
+    pub use crate::harness::rewrite_asserts;
+    pub use crate::resolver::{public_declarations, Resolver, SourceFile};
+}
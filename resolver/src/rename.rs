@@ -0,0 +1,260 @@
+use std::{collections::HashMap, rc::Rc};
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+/// Renames structs and enums throughout a typed file, wherever they're
+/// declared, named in a type or initialised. Monomorphised names like
+/// `Pair<Point, int>` have each name inside them renamed too
+pub fn rename_types<'a>(stmt: Statement<'a>, names: &HashMap<String, String>) -> Statement<'a> {
+    Renamer { names }.statement(stmt)
+}
+
+struct Renamer<'n> {
+    names: &'n HashMap<String, String>,
+}
+
+impl<'n> Renamer<'n> {
+    fn statement<'a>(&self, stmt: Statement<'a>) -> Statement<'a> {
+        match stmt {
+            Statement::Root(stmts) => Statement::Root(self.body(stmts)),
+            Statement::Block(stmts) => Statement::Block(self.body(stmts)),
+            Statement::Function {
+                name,
+                public,
+                attributes,
+                type_params,
+                args,
+                returns,
+                body,
+                span,
+            } => Statement::Function {
+                name,
+                public,
+                attributes,
+                type_params,
+                args: args
+                    .into_iter()
+                    .map(|(typ, name)| (self.typ(typ), name))
+                    .collect(),
+                returns: self.typ(returns),
+                body: Rc::new(self.statement(body.as_ref().clone())),
+                span,
+            },
+            Statement::Return(value, span) => {
+                Statement::Return(value.map(|value| self.expression(value)), span)
+            }
+            Statement::Assign(mutable, name, typ, value, span) => Statement::Assign(
+                mutable,
+                name,
+                typ.map(|typ| self.typ(typ)),
+                self.expression(value),
+                span,
+            ),
+            Statement::ExpressionStatement(expr, span) => {
+                Statement::ExpressionStatement(self.expression(expr), span)
+            }
+            Statement::If(condition, then, otherwise, span) => Statement::If(
+                self.expression(condition),
+                self.body(then),
+                otherwise.map(|otherwise| self.body(otherwise)),
+                span,
+            ),
+            Statement::ExternFunction {
+                name,
+                varargs,
+                args,
+                returns,
+                span,
+            } => Statement::ExternFunction {
+                name,
+                varargs,
+                args: args.into_iter().map(|typ| self.typ(typ)).collect(),
+                returns: self.typ(returns),
+                span,
+            },
+            Statement::Reassign(target, value, span) => {
+                Statement::Reassign(self.expression(target), self.expression(value), span)
+            }
+            Statement::While(condition, body, span) => {
+                Statement::While(self.expression(condition), self.body(body), span)
+            }
+            Statement::For(name, iterable, body, span) => {
+                Statement::For(name, self.expression(iterable), self.body(body), span)
+            }
+            Statement::Struct {
+                name,
+                public,
+                type_params,
+                attributes,
+                span,
+            } => Statement::Struct {
+                name: self.declared(name),
+                public,
+                type_params,
+                attributes: attributes
+                    .into_iter()
+                    .map(|(typ, name)| (self.typ(typ), name))
+                    .collect(),
+                span,
+            },
+            Statement::Enum {
+                name,
+                public,
+                variants,
+                span,
+            } => Statement::Enum {
+                name: self.declared(name),
+                public,
+                variants: variants
+                    .into_iter()
+                    .map(|(name, fields)| {
+                        (name, fields.into_iter().map(|typ| self.typ(typ)).collect())
+                    })
+                    .collect(),
+                span,
+            },
+            Statement::Impl {
+                name,
+                methods,
+                span,
+            } => Statement::Impl {
+                name: self.declared(name),
+                methods: self.body(methods),
+                span,
+            },
+            Statement::Instance(type_args, def) => Statement::Instance(
+                type_args.into_iter().map(|typ| self.typ(typ)).collect(),
+                Rc::new(self.statement(def.as_ref().clone())),
+            ),
+            Statement::Break(..) | Statement::Continue(..) | Statement::Import(..) => stmt,
+        }
+    }
+
+    fn body<'a>(&self, stmts: Vec<Statement<'a>>) -> Vec<Statement<'a>> {
+        stmts.into_iter().map(|stmt| self.statement(stmt)).collect()
+    }
+
+    fn expression<'a>(&self, expr: ExpressionNode<'a>) -> ExpressionNode<'a> {
+        let node = |expr: &Rc<ExpressionNode<'a>>| Rc::new(self.expression(expr.as_ref().clone()));
+        let expression = match expr.expression {
+            Expression::Infix(left, operator, right) => {
+                Expression::Infix(node(&left), operator, node(&right))
+            }
+            Expression::FunctionCall {
+                function,
+                args,
+                type_args,
+            } => Expression::FunctionCall {
+                function: node(&function),
+                args: args.into_iter().map(|arg| self.expression(arg)).collect(),
+                type_args: type_args.into_iter().map(|typ| self.typ(typ)).collect(),
+            },
+            Expression::Not(value) => Expression::Not(node(&value)),
+            Expression::Pointer(value) => Expression::Pointer(node(&value)),
+            Expression::Array(items) => Expression::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.expression(item))
+                    .collect(),
+            ),
+            Expression::ArrayAccess(array, index) => {
+                Expression::ArrayAccess(node(&array), node(&index))
+            }
+            // Only the struct's name is renamed, not variables sharing it
+            Expression::StructInitialisation(struc, fields) => {
+                let struc = match &struc.expression {
+                    Expression::Identifier(name) => Rc::new(ExpressionNode {
+                        expression: Expression::Identifier(self.name(name)),
+                        typed: self.typ(struc.typed.clone()),
+                        span: struc.span.clone(),
+                    }),
+                    _ => node(&struc),
+                };
+                Expression::StructInitialisation(
+                    struc,
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| (name, self.expression(value)))
+                        .collect(),
+                )
+            }
+            Expression::StructAccess(struc, member) => {
+                Expression::StructAccess(node(&struc), member)
+            }
+            Expression::Range(start, end) => Expression::Range(node(&start), node(&end)),
+            Expression::Match(subject, arms) => Expression::Match(
+                node(&subject),
+                arms.into_iter()
+                    .map(|arm| MatchArm {
+                        pattern: arm.pattern,
+                        body: self.body(arm.body),
+                        value: arm.value.map(|value| self.expression(value)),
+                        span: arm.span,
+                    })
+                    .collect(),
+            ),
+            Expression::EnumVariant(enu, variant, args) => Expression::EnumVariant(
+                self.name(&enu),
+                variant,
+                args.into_iter().map(|arg| self.expression(arg)).collect(),
+            ),
+            expression @ (Expression::Integer(..)
+            | Expression::Float(..)
+            | Expression::Identifier(..)
+            | Expression::Boolean(..)
+            | Expression::String(..)) => expression,
+        };
+
+        ExpressionNode {
+            expression,
+            typed: self.typ(expr.typed),
+            span: expr.span,
+        }
+    }
+
+    fn typ<'a>(&self, typ: AzulaType<'a>) -> AzulaType<'a> {
+        match typ {
+            AzulaType::Named(name) => AzulaType::Named(self.name(&name)),
+            AzulaType::Pointer(nested) => {
+                AzulaType::Pointer(Rc::new(self.typ(nested.as_ref().clone())))
+            }
+            AzulaType::Array(nested, size) => {
+                AzulaType::Array(Rc::new(self.typ(nested.as_ref().clone())), size)
+            }
+            AzulaType::Generic(name, args) => AzulaType::Generic(
+                self.name(&name),
+                args.into_iter().map(|arg| self.typ(arg)).collect(),
+            ),
+            _ => typ,
+        }
+    }
+
+    /// Renames each name in `name`, so `Pair<Point, int>` becomes
+    /// `Pair<file::Point, int>` when only `Point` is renamed
+    fn name(&self, name: &str) -> String {
+        let mut renamed = String::new();
+        let mut word = String::new();
+        for c in name.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+
+            renamed += self.names.get(&word).unwrap_or(&word);
+            word.clear();
+            renamed.push(c);
+        }
+        renamed.pop();
+        renamed
+    }
+
+    /// The AST borrows declared names from the source, so new ones are leaked
+    /// like the sources themselves
+    fn declared<'a>(&self, name: &'a str) -> &'a str {
+        match self.names.get(name) {
+            Some(renamed) => Box::leak(renamed.clone().into_boxed_str()),
+            None => name,
+        }
+    }
+}
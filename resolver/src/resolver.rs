@@ -0,0 +1,806 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use azula_ast::prelude::*;
+use azula_codegen::prelude::Codegen;
+use azula_error::prelude::*;
+use azula_ir::prelude::Module;
+use azula_parser::prelude::{Lexer, Parser};
+use azula_typecheck::prelude::{Linter, Typechecker};
+
+use crate::{
+    harness::{harness, rewrite_asserts},
+    rename::rename_types,
+};
+
+pub struct SourceFile {
+    pub path: String,
+    pub source: &'static str,
+
+    /// Prefix for the names this file's functions and globals are emitted
+    /// under, so private names can't clash across files. `None` for the
+    /// entry file, which keeps `main` and friends unmangled.
+    namespace: Option<String>,
+    ast: Statement<'static>,
+    imports: Vec<usize>,
+}
+
+pub struct Resolver {
+    pub files: Vec<SourceFile>,
+    /// Errors along with the index of the file they were found in
+    pub errors: Vec<(usize, AzulaError)>,
+    /// Warnings found in files that typechecked, in the same form as errors
+    pub warnings: Vec<(usize, AzulaError)>,
+    /// Whether the entry file is built to run its `#[test]` functions, with
+    /// a generated `main` in place of its own
+    pub tests: bool,
+
+    loaded: HashMap<PathBuf, usize>,
+    base: PathBuf,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            files: vec![],
+            errors: vec![],
+            warnings: vec![],
+            tests: false,
+            loaded: HashMap::new(),
+            base: PathBuf::new(),
+        }
+    }
+
+    /// Loads `entry` and everything it imports, typechecks each file once and
+    /// lowers them all into a single module
+    pub fn resolve(&mut self, entry: &str) -> Result<Module<'static>, String> {
+        let path = match fs::canonicalize(entry) {
+            Ok(path) => path,
+            Err(e) => return Err(format!("Could not read {}: {}", entry, e)),
+        };
+        self.base = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+        if let Err(e) = self.load(path) {
+            return Err(format!("Could not read {}: {}", entry, e));
+        }
+
+        if !self.errors.is_empty() {
+            return Err("Failed to parse".to_string());
+        }
+
+        let mut typecheckers = vec![];
+        let mut typed = vec![];
+        for index in 0..self.files.len() {
+            let mut typechecker = Typechecker::new(self.files[index].ast.clone());
+            for import in self.files[index].imports.clone() {
+                typechecker.import(self.public_declarations(import));
+            }
+
+            typed.push(typechecker.typecheck().ok());
+            typecheckers.push(typechecker);
+        }
+
+        // Generics are instantiated by the file that declares them, so their
+        // bodies see its private declarations and report errors against it.
+        // Those instances can use generics from further files in turn
+        loop {
+            let mut requested = vec![];
+            for (index, typechecker) in typecheckers.iter_mut().enumerate() {
+                for (name, type_args) in typechecker.foreign_instances.drain(..) {
+                    if let Some(file) = self.declaring_file(index, name) {
+                        requested.push((file, name, type_args));
+                    }
+                }
+            }
+            if requested.is_empty() {
+                break;
+            }
+
+            for (file, name, type_args) in requested {
+                if let Some(Statement::Root(stmts)) = &mut typed[file] {
+                    let instances = typecheckers[file].instantiate(name, type_args);
+                    stmts.splice(0..0, instances);
+                }
+            }
+        }
+
+        for (index, typechecker) in typecheckers.into_iter().enumerate() {
+            for err in typechecker.errors {
+                self.errors.push((index, err));
+            }
+        }
+
+        self.check_duplicate_structs();
+
+        if !self.errors.is_empty() {
+            return Err("Failed to typecheck".to_string());
+        }
+
+        let typed: Vec<_> = typed.into_iter().flatten().collect();
+        for (index, root) in typed.iter().enumerate() {
+            let mut linter = Linter::new(self.files[index].source);
+            linter.check(root);
+            for warning in linter.warnings {
+                self.warnings.push((index, warning));
+            }
+        }
+
+        let name = Path::new(entry)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("main")
+            .to_string();
+
+        let mut codegen = Codegen::new(Box::leak(name.into_boxed_str()), Statement::Root(vec![]));
+        for (index, root) in typed.into_iter().enumerate() {
+            let symbols = self.symbols(index);
+            let root = rename_types(root, &self.private_types(index));
+            codegen.codegen_unit(root, symbols);
+        }
+        codegen.insert_implicit_return();
+
+        Ok(codegen.module)
+    }
+
+    fn load(&mut self, path: PathBuf) -> std::io::Result<usize> {
+        if let Some(index) = self.loaded.get(&path) {
+            return Ok(*index);
+        }
+
+        // Sources are borrowed by the AST for the rest of the compilation
+        let mut source: &'static str = Box::leak(fs::read_to_string(&path)?.into_boxed_str());
+
+        let index = self.files.len();
+        self.loaded.insert(path.clone(), index);
+
+        let (mut ast, errors) = parse(source);
+        let parsed = errors.is_empty();
+        for err in errors {
+            self.errors.push((index, err));
+        }
+        if index == 0 && self.tests && parsed {
+            (source, ast) = self.test_harness(index, source, ast);
+        } else if let Statement::Root(stmts) = ast {
+            // Asserts work in every build, not just when running tests
+            let (stmts, errors) = rewrite_asserts(source, stmts, false);
+            for err in errors {
+                self.errors.push((index, err));
+            }
+            ast = Statement::Root(stmts);
+        }
+
+        self.files.push(SourceFile {
+            path: path.display().to_string(),
+            source,
+            namespace: if index == 0 {
+                None
+            } else {
+                Some(self.namespace(&path))
+            },
+            ast: ast.clone(),
+            imports: vec![],
+        });
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        let mut imports = vec![];
+        if let Statement::Root(stmts) = ast {
+            for stmt in stmts {
+                if let Statement::Import(import, span) = stmt {
+                    let imported = match fs::canonicalize(dir.join(import)) {
+                        Ok(imported) => imported,
+                        Err(_) => {
+                            self.errors.push((
+                                index,
+                                AzulaError::new(
+                                    ErrorType::ImportNotFound(import.to_string()),
+                                    span.start,
+                                    span.end,
+                                ),
+                            ));
+                            continue;
+                        }
+                    };
+
+                    match self.load(imported) {
+                        Ok(imported) => imports.push(imported),
+                        Err(_) => self.errors.push((
+                            index,
+                            AzulaError::new(
+                                ErrorType::ImportNotFound(import.to_string()),
+                                span.start,
+                                span.end,
+                            ),
+                        )),
+                    }
+                }
+            }
+        }
+
+        self.files[index].imports = imports;
+
+        Ok(index)
+    }
+
+    /// Adds a `main` running the tests to the end of the entry file, replacing
+    /// the file's own, and turns the tests into ones the harness can call
+    fn test_harness(
+        &mut self,
+        index: usize,
+        source: &'static str,
+        ast: Statement<'static>,
+    ) -> (&'static str, Statement<'static>) {
+        let tests: Vec<_> = match &ast {
+            Statement::Root(stmts) => stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::Function { name, .. } if stmt.is_test() => Some(*name),
+                    _ => None,
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        // Parsed again as one file, so the harness has spans like everything else
+        let with_harness = format!("{}\n\n{}", source, harness(&tests));
+        let with_harness: &'static str = Box::leak(with_harness.into_boxed_str());
+        let stmts = match parse(with_harness).0 {
+            Statement::Root(stmts) => stmts
+                .into_iter()
+                .filter(|stmt| {
+                    !matches!(stmt, Statement::Function { name: "main", span, .. } if span.start < source.len())
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        let (stmts, errors) = rewrite_asserts(with_harness, stmts, true);
+        for err in errors {
+            self.errors.push((index, err));
+        }
+        (with_harness, Statement::Root(stmts))
+    }
+
+    /// `lib/math.azl` relative to the entry file becomes `lib::math`
+    fn namespace(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.base).unwrap_or(path);
+        let relative = relative.with_extension("");
+
+        relative
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .filter(|c| *c != "/")
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    fn mangle(&self, file: usize, name: &str) -> String {
+        match &self.files[file].namespace {
+            Some(namespace) => format!("{}::{}", namespace, name),
+            None => name.to_string(),
+        }
+    }
+
+    fn public_declarations(&self, file: usize) -> Vec<Statement<'static>> {
+        match &self.files[file].ast {
+            Statement::Root(stmts) => public_declarations(stmts),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The file imported by `file` that declares the generic function `name`
+    fn declaring_file(&self, file: usize, name: &str) -> Option<usize> {
+        // Later imports shadow earlier ones, as they do in the typechecker
+        self.files[file]
+            .imports
+            .iter()
+            .rev()
+            .cloned()
+            .find(|import| {
+                self.public_declarations(*import).iter().any(|stmt| {
+                matches!(stmt, Statement::Function { name: declared, .. } if *declared == name)
+            })
+            })
+    }
+
+    fn symbols(&self, file: usize) -> HashMap<String, String> {
+        let mut symbols = HashMap::new();
+
+        for import in &self.files[file].imports {
+            for stmt in self.public_declarations(*import) {
+                if let Statement::Function { name, .. } = stmt {
+                    symbols.insert(name.to_string(), self.mangle(*import, name));
+                }
+            }
+        }
+
+        if let Statement::Root(stmts) = &self.files[file].ast {
+            for stmt in stmts {
+                match stmt {
+                    Statement::Function { name, .. } => {
+                        symbols.insert(name.to_string(), self.mangle(file, name));
+                    }
+                    Statement::Assign(_, name, ..) => {
+                        symbols.insert(name.clone(), self.mangle(file, name));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        symbols
+    }
+
+    /// The structs and enums `file` declares, and whether each is public
+    fn declared_types(&self, file: usize) -> Vec<(&'static str, bool, &Span)> {
+        match &self.files[file].ast {
+            Statement::Root(stmts) => stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::Struct {
+                        name, public, span, ..
+                    }
+                    | Statement::Enum {
+                        name, public, span, ..
+                    } => Some((*name, *public, span)),
+                    _ => None,
+                })
+                .collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Public structs and enums share a single namespace in the IR, so the
+    /// same name can't be made public by two files. Private ones only have to
+    /// be unique among the names their file can see
+    fn check_duplicate_structs(&mut self) {
+        let mut public = HashSet::new();
+        let mut errors = vec![];
+
+        for index in 0..self.files.len() {
+            let imported: HashSet<_> = self.files[index]
+                .imports
+                .iter()
+                .flat_map(|import| self.declared_types(*import))
+                .filter(|(_, public, _)| *public)
+                .map(|(name, ..)| name)
+                .collect();
+
+            let mut seen = HashSet::new();
+            for (name, is_public, span) in self.declared_types(index) {
+                let duplicate = !seen.insert(name)
+                    || if is_public {
+                        !public.insert(name)
+                    } else {
+                        imported.contains(name)
+                    };
+
+                if duplicate {
+                    errors.push((
+                        index,
+                        AzulaError::new(
+                            ErrorType::DuplicateStruct(name.to_string()),
+                            span.start,
+                            span.end,
+                        ),
+                    ));
+                }
+            }
+        }
+
+        self.errors.extend(errors);
+    }
+
+    /// Private structs and enums sharing a name with another file's are
+    /// emitted under their file's namespace, like private functions are
+    fn private_types(&self, file: usize) -> HashMap<String, String> {
+        let namespace = match &self.files[file].namespace {
+            Some(namespace) => namespace.clone(),
+            None => self.namespace(Path::new(&self.files[file].path)),
+        };
+
+        self.declared_types(file)
+            .into_iter()
+            .filter(|(name, public, _)| {
+                !public
+                    && (0..self.files.len()).any(|other| {
+                        other != file
+                            && self
+                                .declared_types(other)
+                                .iter()
+                                .any(|(declared, ..)| declared == name)
+                    })
+            })
+            .map(|(name, ..)| (name.to_string(), format!("{}::{}", namespace, name)))
+            .collect()
+    }
+}
+
+fn parse(source: &str) -> (Statement<'_>, Vec<AzulaError>) {
+    let lexer: Lexer = source.into();
+    let mut parser = Parser::new(source, lexer);
+    let ast = parser.parse();
+    (ast, parser.errors)
+}
+
+/// Everything another file is allowed to see: public functions and
+/// structs, along with the methods of public structs
+pub fn public_declarations<'a>(stmts: &[Statement<'a>]) -> Vec<Statement<'a>> {
+    let public_structs: Vec<_> = stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Struct {
+                name, public: true, ..
+            } => Some(*name),
+            _ => None,
+        })
+        .collect();
+
+    stmts
+        .iter()
+        .filter(|stmt| match stmt {
+            Statement::Function { public, .. } => *public,
+            Statement::Struct { public, .. } => *public,
+            Statement::Enum { public, .. } => *public,
+            Statement::Impl { name, .. } => public_structs.contains(name),
+            _ => false,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azula_ir::prelude::{parse_module, verify};
+
+    fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(dir);
+        let _ = fs::remove_dir_all(&dir);
+        for (name, source) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let dir = write_files(
+            "azula_resolver_imports",
+            &[
+                (
+                    "main.azl",
+                    "import \"lib/math.azl\"; func helper(): int { return 1; } func main { printf(\"%d\", add(helper(), 2)); }",
+                ),
+                (
+                    "lib/math.azl",
+                    "pub func add(x: int, y: int): int { return helper(x) + y; } func helper(x: int): int { return x; }",
+                ),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        let module = resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(resolver.files.len(), 2);
+        let mut names: Vec<_> = module.functions.keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["helper", "lib::math::add", "lib::math::helper", "main"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_visibility() {
+        let dir = write_files(
+            "azula_resolver_visibility",
+            &[
+                ("main.azl", "import \"math.azl\"; func main { helper(1); }"),
+                ("math.azl", "func helper(x: int): int { return x; }"),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        let result = resolver.resolve(dir.join("main.azl").to_str().unwrap());
+
+        assert!(result.is_err());
+        assert_eq!(resolver.errors.len(), 1);
+        assert!(matches!(
+            resolver.errors[0].1.error_type,
+            ErrorType::FunctionNotFound(..)
+        ));
+    }
+
+    #[test]
+    fn test_resolve_imported_generics() {
+        // Instances are checked in the declaring file, where `helper` is visible
+        let dir = write_files(
+            "azula_resolver_generics",
+            &[
+                (
+                    "main.azl",
+                    "import \"lib.azl\"; func main { printf(\"%d\", twice(2)); }",
+                ),
+                (
+                    "lib.azl",
+                    "pub func twice<T>(x: T): T { return helper(x); } func helper<T>(x: T): T { return x; }",
+                ),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        let module = resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .unwrap();
+        assert!(module.functions.contains_key("lib::twice<int>"));
+        assert!(module.functions.contains_key("lib::helper<int>"));
+
+        // Generic bodies are checked even when nothing uses them, and errors
+        // are reported against the file they're in
+        let dir = write_files(
+            "azula_resolver_generic_errors",
+            &[
+                ("main.azl", "import \"lib.azl\"; func main { }"),
+                ("lib.azl", "pub func id<T>(x: T): T { return nope; }"),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        let result = resolver.resolve(dir.join("main.azl").to_str().unwrap());
+
+        assert!(result.is_err());
+        assert_eq!(resolver.errors.len(), 1);
+        assert_eq!(resolver.errors[0].0, 1);
+        assert!(matches!(
+            resolver.errors[0].1.error_type,
+            ErrorType::UnknownVariable(..)
+        ));
+    }
+
+    #[test]
+    fn test_resolve_private_structs() {
+        // Each file can have its own private `Point`, emitted under its namespace
+        let dir = write_files(
+            "azula_resolver_private_structs",
+            &[
+                (
+                    "main.azl",
+                    "import \"shapes.azl\"; struct Point { x: int } func main { var p = Point { x: 1 }; printf(\"%d %d\", p.x, area()); }",
+                ),
+                (
+                    "shapes.azl",
+                    "struct Point { x: int, y: int } impl Point { func area(self): int { return self.x * self.y; } } pub func area(): int { var p = Point { x: 2, y: 3 }; return p.area(); }",
+                ),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        let module = resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(module.structs["main::Point"].attributes.len(), 1);
+        assert_eq!(module.structs["shapes::Point"].attributes.len(), 2);
+        assert!(module.functions.contains_key("shapes::Point.area"));
+        assert!(verify(&module).is_ok());
+
+        // Public ones still can't share a name, and a private one can't hide
+        // one that's imported
+        let dir = write_files(
+            "azula_resolver_duplicate_structs",
+            &[
+                (
+                    "main.azl",
+                    "import \"a.azl\"; import \"b.azl\"; struct Point { x: int } func main { }",
+                ),
+                ("a.azl", "pub struct Point { x: int }"),
+                ("b.azl", "pub struct Point { x: int }"),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        assert!(resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .is_err());
+        let duplicates: Vec<_> = resolver
+            .errors
+            .iter()
+            .filter(|(_, err)| matches!(err.error_type, ErrorType::DuplicateStruct(..)))
+            .map(|(file, _)| *file)
+            .collect();
+        assert_eq!(duplicates, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_resolve_cycles_and_missing() {
+        // Files importing each other are only loaded once
+        let dir = write_files(
+            "azula_resolver_cycles",
+            &[
+                (
+                    "main.azl",
+                    "import \"other.azl\"; pub func one(): int { return 1; } func main { two(); }",
+                ),
+                (
+                    "other.azl",
+                    "import \"main.azl\"; pub func two(): int { return one() + 1; }",
+                ),
+            ],
+        );
+
+        let mut resolver = Resolver::new();
+        assert!(resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .is_ok());
+        assert_eq!(resolver.files.len(), 2);
+
+        let dir = write_files(
+            "azula_resolver_missing",
+            &[("main.azl", "import \"missing.azl\"; func main { }")],
+        );
+
+        let mut resolver = Resolver::new();
+        assert!(resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .is_err());
+        assert!(matches!(
+            resolver.errors[0].1.error_type,
+            ErrorType::ImportNotFound(..)
+        ));
+    }
+
+    const EXAMPLES: [&str; 9] = [
+        "enums.azl",
+        "factorial.azl",
+        "fib.azl",
+        "fizzbuzz.azl",
+        "generics.azl",
+        "loops.azl",
+        "methods.azl",
+        "modules/main.azl",
+        "testing.azl",
+    ];
+
+    fn compile_example(example: &str) -> Module<'static> {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let mut resolver = Resolver::new();
+        resolver
+            .resolve(examples.join(example).to_str().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_examples_ir() {
+        for example in EXAMPLES {
+            let module = compile_example(example);
+
+            if let Err(errors) = verify(&module) {
+                panic!("{} failed to verify: {}", example, errors[0]);
+            }
+
+            let printed = module.to_string();
+            let parsed = parse_module(&printed).unwrap();
+            assert!(parsed == module, "{} changed when read back", example);
+        }
+    }
+
+    #[test]
+    fn test_examples_ir_deterministic() {
+        // The C each example lowers to is compared the same way in azula_codegen_c
+        for example in EXAMPLES {
+            let first = compile_example(example).to_string();
+            let second = compile_example(example).to_string();
+            assert_eq!(first, second, "{} compiled differently twice", example);
+        }
+    }
+
+    #[test]
+    fn test_examples_no_warnings() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        for example in EXAMPLES {
+            let mut resolver = Resolver::new();
+            resolver
+                .resolve(examples.join(example).to_str().unwrap())
+                .unwrap();
+            let warnings: Vec<_> = resolver
+                .warnings
+                .iter()
+                .map(|(_, warning)| format!("{:?}", warning.error_type))
+                .collect();
+            assert!(warnings.is_empty(), "{}: {:?}", example, warnings);
+        }
+    }
+
+    #[test]
+    fn test_resolve_tests() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let path = examples.join("testing.azl");
+
+        // Tests are left out of ordinary builds
+        let mut resolver = Resolver::new();
+        let module = resolver.resolve(path.to_str().unwrap()).unwrap();
+        let mut names: Vec<_> = module.functions.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["gcd", "main"]);
+        assert!(!module.extern_functions.contains_key("exit"));
+
+        let mut resolver = Resolver::new();
+        resolver.tests = true;
+        let module = resolver.resolve(path.to_str().unwrap()).unwrap();
+        let mut names: Vec<_> = module.functions.keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["gcd", "gcd_is_symmetric", "gcd_of_coprimes", "main"]
+        );
+        assert!(resolver.warnings.is_empty());
+        assert!(verify(&module).is_ok());
+
+        let dir = write_files(
+            "azula_resolver_tests",
+            &[(
+                "main.azl",
+                "#[test] func takes(x: int) { }
+                #[test] func returns: int { return 1; }
+                #[test] func asserts { assert(true); }",
+            )],
+        );
+        let mut resolver = Resolver::new();
+        resolver.tests = true;
+        assert!(resolver
+            .resolve(dir.join("main.azl").to_str().unwrap())
+            .is_err());
+        let errors: Vec<_> = resolver
+            .errors
+            .iter()
+            .map(|(_, err)| format!("{:?}", err.error_type))
+            .collect();
+        assert_eq!(
+            errors[..3],
+            [
+                "InvalidTest(\"takes\")",
+                "InvalidTest(\"returns\")",
+                "WrongArgumentCount(\"assert\", 2, 1)",
+            ]
+        );
+
+        // Functions that aren't tests can assert in any build, in any file
+        let dir = write_files(
+            "azula_resolver_asserts",
+            &[
+                (
+                    "main.azl",
+                    "import \"checks.azl\";
+                    func main { check(1); }
+                    #[test] func checks_one { check(1); }",
+                ),
+                (
+                    "checks.azl",
+                    "pub func check(x: int) { assert(x > 0, \"not positive\"); }",
+                ),
+            ],
+        );
+        for tests in [false, true] {
+            let mut resolver = Resolver::new();
+            resolver.tests = tests;
+            let module = resolver
+                .resolve(dir.join("main.azl").to_str().unwrap())
+                .unwrap();
+            assert!(module.extern_functions.contains_key("exit"));
+            assert!(verify(&module).is_ok());
+        }
+    }
+}
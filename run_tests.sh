@@ -1,3 +1,9 @@
 cd parser && cargo test
 cd ../typecheck && cargo test
-cd ../codegen && cargo test
\ No newline at end of file
+cd ../codegen && cargo test
+cd ../resolver && cargo test
+cd ../interpreter && cargo test
+cd ../azula_ir && cargo test
+cd ../codegen_c && cargo test
+cd ../formatter && cargo test
+cd ../docgen && cargo test
\ No newline at end of file
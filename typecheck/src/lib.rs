@@ -1,8 +1,10 @@
 #[macro_use]
 extern crate maplit;
 
+mod lint;
 mod typecheck;
 
 pub mod prelude {
+    pub use crate::lint::{diverges, name_span, Linter};
     pub use crate::typecheck::Typechecker;
 }
@@ -0,0 +1,528 @@
+use std::collections::HashSet;
+
+use azula_ast::prelude::*;
+use azula_error::prelude::*;
+
+/// Looks over a typechecked file for code that's probably a mistake, like
+/// variables that are never used or statements that can never run. Nothing
+/// it finds stops the program compiling, so it only gives warnings
+pub struct Linter<'a> {
+    source: &'a str,
+    /// The warnings allowed in the function being checked
+    allowed: Vec<&'a str>,
+    /// Scoped like the typechecker: one for each function and match arm,
+    /// with if, while and block bodies declaring into the one around them
+    scopes: Vec<Vec<Local>>,
+    /// Every function referred to in the file, to find those never called.
+    /// Names that resolve to a variable or method aren't counted
+    referenced: HashSet<String>,
+
+    pub warnings: Vec<AzulaError>,
+}
+
+struct Local {
+    name: String,
+    span: Span,
+    // Declared with var, so could be const if it's never reassigned
+    var: bool,
+    used: bool,
+    reassigned: bool,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            allowed: vec![],
+            scopes: vec![],
+            referenced: HashSet::new(),
+            warnings: vec![],
+        }
+    }
+
+    pub fn check(&mut self, root: &Statement<'a>) {
+        let stmts = match root {
+            Statement::Root(stmts) => stmts,
+            _ => unreachable!(),
+        };
+
+        for stmt in stmts {
+            match stmt {
+                // Generic functions are checked as declared rather than once
+                // for each of their instances
+                Statement::Function { .. } => self.check_function(stmt),
+                Statement::Impl { methods, .. } => {
+                    for method in methods {
+                        self.check_function(method);
+                    }
+                }
+                Statement::Assign(_, _, _, value, _) => self.check_expression(value),
+                _ => {}
+            }
+        }
+
+        for stmt in stmts {
+            if let Statement::Function {
+                name,
+                public: false,
+                attributes,
+                span,
+                ..
+            } = stmt
+            {
+                // Tests are called by the harness `azula test` builds
+                if *name == "main" || stmt.is_test() || self.referenced.contains(*name) {
+                    continue;
+                }
+
+                self.allowed = allowed(attributes);
+                let span = name_span(self.source, span, name, span.start);
+                self.warn(AzulaError::new(
+                    ErrorType::UnusedFunction(name.to_string()),
+                    span.start,
+                    span.end,
+                ));
+            }
+        }
+    }
+
+    fn check_function(&mut self, function: &Statement<'a>) {
+        if let Statement::Function {
+            name,
+            attributes,
+            args,
+            body,
+            span,
+            ..
+        } = function
+        {
+            self.allowed = allowed(attributes);
+
+            let mut from = name_span(self.source, span, name, span.start).end;
+            self.scopes.push(vec![]);
+            for (_, arg) in args {
+                let arg_span = name_span(self.source, span, arg, from);
+                from = arg_span.end;
+                if *arg != "self" {
+                    self.declare(arg, arg_span, false);
+                }
+            }
+            if let Statement::Block(stmts) = body.as_ref() {
+                self.check_body(stmts);
+            }
+            self.close_scope();
+        }
+    }
+
+    fn check_body(&mut self, stmts: &[Statement<'a>]) {
+        let mut unreachable = false;
+        for (i, stmt) in stmts.iter().enumerate() {
+            if !unreachable && i > 0 && diverges(&stmts[i - 1]) {
+                unreachable = true;
+
+                let start = stmt.span().start;
+                let end = stmts[stmts.len() - 1].span().end;
+                let jump = stmts[i - 1].span();
+                self.warn(
+                    AzulaError::new(ErrorType::UnreachableCode, start, end).with_label(
+                        jump.start,
+                        jump.end,
+                        "nothing after this can run",
+                    ),
+                );
+            }
+
+            self.check_statement(stmt);
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement<'a>) {
+        match stmt {
+            Statement::Block(stmts) => self.check_body(stmts),
+            Statement::Return(Some(value), _) => self.check_expression(value),
+            Statement::Assign(mutable, name, _, value, span) => {
+                self.check_expression(value);
+                let name_span = name_span(self.source, span, name, span.start);
+                self.declare(name, name_span, *mutable);
+            }
+            Statement::ExpressionStatement(expr, _) => self.check_expression(expr),
+            Statement::If(condition, then, otherwise, _) => {
+                self.check_expression(condition);
+                self.check_body(then);
+                if let Some(otherwise) = otherwise {
+                    self.check_body(otherwise);
+                }
+            }
+            Statement::Reassign(target, value, _) => {
+                self.check_expression(value);
+                // Only assigning to a variable doesn't count as using it
+                if !matches!(target.expression, Expression::Identifier(_)) {
+                    self.check_expression(target);
+                }
+                self.mutated(target);
+            }
+            Statement::While(condition, body, _) => {
+                self.check_expression(condition);
+                self.check_body(body);
+            }
+            Statement::For(name, iterable, body, span) => {
+                self.check_expression(iterable);
+                let name_span = name_span(self.source, span, name, span.start);
+                let start = self.scopes.last().unwrap().len();
+                self.declare(name, name_span, false);
+                self.check_body(body);
+
+                // The loop variable goes out of scope after the loop, taking
+                // anything in it with the same name, and the outer one is back
+                let scope = self.scopes.last_mut().unwrap();
+                let mut index = start;
+                let mut ended = vec![];
+                while index < scope.len() {
+                    if scope[index].name == *name {
+                        ended.push(scope.remove(index));
+                    } else {
+                        index += 1;
+                    }
+                }
+                for local in ended {
+                    self.close(local);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_expression(&mut self, expr: &ExpressionNode<'a>) {
+        match &expr.expression {
+            Expression::Identifier(name) => match self.lookup(name) {
+                Some(local) => local.used = true,
+                None => {
+                    self.referenced.insert(name.clone());
+                }
+            },
+            Expression::Infix(left, _, right) => {
+                self.check_expression(left);
+                self.check_expression(right);
+            }
+            Expression::FunctionCall { function, args, .. } => {
+                // Methods are given a pointer to the struct they're called on
+                if let Expression::StructAccess(receiver, _) = &function.expression {
+                    self.mutated(receiver);
+                }
+                self.check_expression(function);
+                for arg in args {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::Not(inner) => self.check_expression(inner),
+            Expression::Pointer(inner) => {
+                // The variable could be changed through the pointer
+                self.mutated(inner);
+                self.check_expression(inner);
+            }
+            Expression::Array(items) => {
+                for item in items {
+                    self.check_expression(item);
+                }
+            }
+            Expression::ArrayAccess(array, index) => {
+                self.check_expression(array);
+                self.check_expression(index);
+            }
+            Expression::StructInitialisation(_, fields) => {
+                for (_, value) in fields {
+                    self.check_expression(value);
+                }
+            }
+            // The right hand side is a member name rather than a variable
+            Expression::StructAccess(left, _) => self.check_expression(left),
+            Expression::Range(start, end) => {
+                self.check_expression(start);
+                self.check_expression(end);
+            }
+            Expression::Match(subject, arms) => {
+                self.check_expression(subject);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    if let Pattern::Variant(_, bindings) = &arm.pattern {
+                        let mut from = arm.span.start;
+                        for binding in bindings {
+                            let span = name_span(self.source, &arm.span, binding, from);
+                            from = span.end;
+                            self.declare(binding, span, false);
+                        }
+                    }
+                    self.check_body(&arm.body);
+                    if let Some(value) = &arm.value {
+                        self.check_expression(value);
+                    }
+                    self.close_scope();
+                }
+            }
+            Expression::EnumVariant(_, _, args) => {
+                for arg in args {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_) => {}
+        }
+    }
+
+    /// Marks the variable an assignment target or pointer comes from as
+    /// changed, so `var` is needed for it
+    fn mutated(&mut self, target: &ExpressionNode<'a>) {
+        match &target.expression {
+            Expression::Identifier(name) => {
+                if let Some(local) = self.lookup(name) {
+                    local.reassigned = true;
+                }
+            }
+            Expression::ArrayAccess(inner, _) | Expression::StructAccess(inner, _) => {
+                self.mutated(inner)
+            }
+            _ => {}
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: Span, var: bool) {
+        self.scopes.last_mut().unwrap().push(Local {
+            name: name.to_string(),
+            span,
+            var,
+            used: false,
+            reassigned: false,
+        });
+    }
+
+    /// The innermost variable with a name, which shadows any outside it
+    fn lookup(&mut self, name: &str) -> Option<&mut Local> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|local| local.name == name)
+    }
+
+    /// Warns about the variables declared in the scope being left
+    fn close_scope(&mut self) {
+        for local in self.scopes.pop().unwrap() {
+            self.close(local);
+        }
+    }
+
+    /// Warns about a variable going out of scope
+    fn close(&mut self, local: Local) {
+        // Names starting with _ are meant to be unused
+        if !local.used && !local.name.starts_with('_') {
+            self.warn(
+                AzulaError::new(
+                    ErrorType::UnusedVariable(local.name.clone()),
+                    local.span.start,
+                    local.span.end,
+                )
+                .with_help(format!(
+                    "if it's meant to be unused, call it _{}",
+                    local.name
+                )),
+            );
+        } else if local.var && !local.reassigned {
+            self.warn(
+                AzulaError::new(
+                    ErrorType::NeverReassigned(local.name),
+                    local.span.start,
+                    local.span.end,
+                )
+                .with_help("declare it with const instead"),
+            );
+        }
+    }
+
+    fn warn(&mut self, warning: AzulaError) {
+        if let Some(lint) = warning.error_type.lint() {
+            if self.allowed.contains(&lint) {
+                return;
+            }
+        }
+        self.warnings.push(warning);
+    }
+}
+
+/// Finds where a name is written in a statement, starting at `from`. The
+/// AST only keeps spans for whole statements
+pub fn name_span(source: &str, within: &Span, name: &str, from: usize) -> Span {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let text = &source[from..within.end];
+
+    let mut offset = 0;
+    while let Some(found) = text[offset..].find(name) {
+        let start = offset + found;
+        let end = start + name.len();
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if !before.is_some_and(is_ident) && !after.is_some_and(is_ident) {
+            return Span {
+                start: from + start,
+                end: from + end,
+            };
+        }
+        offset = end;
+    }
+
+    within.clone()
+}
+
+fn allowed<'a>(attributes: &[Attribute<'a>]) -> Vec<&'a str> {
+    attributes
+        .iter()
+        .filter(|attribute| attribute.name == "allow")
+        .flat_map(|attribute| attribute.args.iter().copied())
+        .collect()
+}
+
+/// Whether control never carries on past a statement
+pub fn diverges(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(..) | Statement::Break(_) | Statement::Continue(_) => true,
+        Statement::If(_, then, Some(otherwise), _) => {
+            then.iter().any(diverges) && otherwise.iter().any(diverges)
+        }
+        Statement::Block(stmts) => stmts.iter().any(diverges),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azula_parser::prelude::{Lexer, Parser};
+
+    use super::*;
+    use crate::typecheck::Typechecker;
+
+    fn lint(source: &'static str) -> Vec<String> {
+        let lexer: Lexer = source.into();
+        let mut parser = Parser::new(source, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let mut typechecker = Typechecker::new(ast);
+        let root = typechecker.typecheck().unwrap();
+
+        let mut linter = Linter::new(source);
+        linter.check(&root);
+        linter
+            .warnings
+            .iter()
+            .map(|warning| {
+                format!(
+                    "{:?} {}",
+                    warning.error_type,
+                    &source[warning.start..warning.end]
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unused() {
+        assert_eq!(
+            lint(
+                "func helper(a: int, _b: int): int { const x = 1; return a; }
+                func unused_helper { }
+                pub func exported { }
+                #[test] func checks { }
+                func main { helper(1, 2); }"
+            ),
+            [
+                "UnusedVariable(\"x\") x",
+                "UnusedFunction(\"unused_helper\") unused_helper",
+            ]
+        );
+
+        // Shadowed variables are separate
+        assert_eq!(
+            lint("func main(x: int) { if true { const x = 2; printf(\"%d\", x); } }"),
+            ["UnusedVariable(\"x\") x"]
+        );
+        assert_eq!(
+            lint("func main { for i in 0..3 { } const s = [1]; printf(\"%d\", s[0]); }"),
+            ["UnusedVariable(\"i\") i"]
+        );
+        assert_eq!(
+            lint("func main { const i = 5; for i in 0..3 { printf(\"%d\", i); } }"),
+            ["UnusedVariable(\"i\") i"]
+        );
+
+        // Variables declared in an if or while live on after it
+        assert!(lint(
+            "func main { if true { const x = 1; } while false { const y = 2; } printf(\"%d %d\", x, y); }"
+        )
+        .is_empty());
+
+        // Neither a variable nor a method calls a function sharing its name
+        assert_eq!(
+            lint(
+                "struct Square { side: int }
+                impl Square { func area(self): int { return self.side * self.side; } }
+                func area(side: int): int { return side * side; }
+                func count: int { return 1; }
+                func main {
+                    const count = 2;
+                    const square = Square { side: count };
+                    printf(\"%d\", square.area());
+                }"
+            ),
+            [
+                "UnusedFunction(\"area\") area",
+                "UnusedFunction(\"count\") count",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_never_reassigned() {
+        assert_eq!(
+            lint(
+                "func main {
+                    var a = 1; var b = 2; var c = 3; var d = [1]; var e = [1];
+                    b = 3; d[0] = 2;
+                    printf(\"%d %d %d %d %d\", a, b, c, d[0], e[0]);
+                    takes(&c);
+                }
+                func takes(p: &int) { printf(\"%p\", p); }"
+            ),
+            ["NeverReassigned(\"a\") a", "NeverReassigned(\"e\") e",]
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(
+            lint(
+                "func main: int {
+                    while true { break; printf(\"never\"); }
+                    if true { return 1; } else { return 2; }
+                    const x = 1;
+                    return x;
+                }"
+            ),
+            [
+                "UnreachableCode printf(\"never\")",
+                "UnreachableCode const x = 1;\n                    return x;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allow() {
+        assert!(lint(
+            "#[allow(unused_variables, unused_functions)]
+            func helper(a: int) { }
+            #[allow(unreachable_code, never_reassigned)]
+            func main { var x = 1; printf(\"%d\", x); return; printf(\"never\"); }"
+        )
+        .is_empty());
+    }
+}
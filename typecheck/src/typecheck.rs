@@ -1,8 +1,12 @@
-use std::{collections::HashMap, ops::Deref, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Deref,
+    rc::Rc,
+};
 
 use azula_ast::prelude::*;
 use azula_error::prelude::*;
-use azula_type::prelude::AzulaType;
+use azula_type::prelude::{mangle, AzulaType};
 
 pub struct Typechecker<'a> {
     ast: Statement<'a>,
@@ -10,12 +14,40 @@ pub struct Typechecker<'a> {
     functions: HashMap<&'a str, FunctionDefinition<'a>>,
     globals: HashMap<String, VariableDefinition<'a>>,
     structs: HashMap<String, StructDefinition<'a>>,
+    enums: HashMap<String, EnumDefinition<'a>>,
+    imports: Vec<Statement<'a>>,
+
+    /// Generic declarations, which are typechecked once for every set of type
+    /// arguments they're used with
+    generic_functions: HashMap<&'a str, Statement<'a>>,
+    generic_structs: HashMap<String, Statement<'a>>,
+    /// The generic struct and type arguments each monomorphised struct came from
+    struct_instances: HashMap<String, (String, Vec<AzulaType<'a>>)>,
+    function_instances: HashSet<String>,
+    /// Generic functions used with new type arguments, waiting to be typechecked
+    pending_instances: VecDeque<(&'a str, Vec<AzulaType<'a>>)>,
+    instances: Vec<Statement<'a>>,
+    /// Generic functions declared by another file. Their instances are
+    /// checked by that file, so their bodies see its private declarations
+    imported_generics: HashSet<&'a str>,
+    /// Instances of imported generics used here, for the resolver to pass on
+    /// to the file that declares them
+    pub foreign_instances: Vec<(&'a str, Vec<AzulaType<'a>>)>,
+    /// Generic functions whose bodies failed to typecheck, which aren't
+    /// checked again for each instance
+    invalid_generics: HashSet<&'a str>,
+    /// Type parameters standing for any type while a generic body is checked
+    abstract_params: Vec<&'a str>,
+    /// Set while imported declarations are registered. Problems with them are
+    /// reported by the file that declares them, against its own source
+    registering_imports: bool,
 
     pub errors: Vec<AzulaError>,
 }
 
 struct FunctionDefinition<'a> {
     name: &'a str,
+    type_params: Vec<&'a str>,
     args: Vec<(AzulaType<'a>, &'a str)>,
     varargs: bool,
     returns: AzulaType<'a>,
@@ -24,23 +56,38 @@ struct FunctionDefinition<'a> {
 struct StructDefinition<'a> {
     name: &'a str,
     attrs: Vec<(AzulaType<'a>, &'a str)>,
+    methods: HashMap<&'a str, FunctionDefinition<'a>>,
+    /// Methods that assign to their receiver's fields, which can't be called
+    /// on a constant
+    mutating: HashSet<&'a str>,
 }
 
-#[derive(Debug)]
+struct EnumDefinition<'a> {
+    variants: Vec<Variant<'a>>,
+}
+
+#[derive(Debug, Clone)]
 pub struct VariableDefinition<'a> {
     name: String,
     mutable: bool,
     typ: AzulaType<'a>,
 }
 
+#[derive(Clone)]
 pub struct Environment<'a> {
     variable_definitions: HashMap<String, VariableDefinition<'a>>,
+    /// How many loops the current statement is nested in
+    loop_depth: usize,
+    /// The types given for the type parameters of the function being checked
+    type_args: HashMap<&'a str, AzulaType<'a>>,
 }
 
 impl<'a> Environment<'a> {
     pub fn new() -> Self {
         Self {
             variable_definitions: HashMap::new(),
+            loop_depth: 0,
+            type_args: HashMap::new(),
         }
     }
 
@@ -56,60 +103,578 @@ impl<'a> Typechecker<'a> {
             functions: HashMap::new(),
             globals: HashMap::new(),
             structs: HashMap::new(),
+            enums: HashMap::new(),
+            imports: vec![],
+            generic_functions: HashMap::new(),
+            generic_structs: HashMap::new(),
+            struct_instances: HashMap::new(),
+            function_instances: HashSet::new(),
+            pending_instances: VecDeque::new(),
+            instances: vec![],
+            imported_generics: HashSet::new(),
+            foreign_instances: vec![],
+            invalid_generics: HashSet::new(),
+            abstract_params: vec![],
+            registering_imports: false,
             errors: vec![],
         }
     }
 
-    pub fn typecheck(&mut self) -> Result<Statement<'a>, String> {
-        if let Statement::Root(mut x) = self.ast.clone() {
-            for stmt in x.iter_mut() {
-                match stmt {
-                    Statement::Function {
-                        name,
-                        args,
-                        returns,
-                        ..
-                    } => {
-                        let args_converted: Vec<_> = args
-                            .iter()
-                            .map(|(typ, name)| (AzulaType::from(typ.clone()), *name))
-                            .collect();
+    /// Makes the public declarations of another file visible to this one
+    pub fn import(&mut self, stmts: Vec<Statement<'a>>) {
+        self.imports.extend(stmts);
+    }
+
+    fn register_declarations(&mut self, stmts: &[Statement<'a>]) {
+        for stmt in stmts.iter() {
+            match stmt {
+                Statement::Function {
+                    name,
+                    type_params,
+                    args,
+                    returns,
+                    ..
+                } => {
+                    if !type_params.is_empty() {
+                        self.generic_functions.insert(name, stmt.clone());
+                    }
 
-                        let returns_converted: AzulaType = returns.clone().into();
+                    let args_converted: Vec<_> = args
+                        .iter()
+                        .map(|(typ, name)| (AzulaType::from(typ.clone()), *name))
+                        .collect();
 
-                        self.functions.insert(
+                    let returns_converted: AzulaType = returns.clone().into();
+
+                    self.functions.insert(
+                        name,
+                        FunctionDefinition {
                             name,
-                            FunctionDefinition {
-                                name,
-                                varargs: true,
-                                args: args_converted.clone(),
-                                returns: returns_converted.clone(),
-                            },
-                        );
+                            type_params: type_params.clone(),
+                            varargs: false,
+                            args: args_converted.clone(),
+                            returns: returns_converted.clone(),
+                        },
+                    );
+                }
+                Statement::ExternFunction {
+                    name,
+                    varargs,
+                    args,
+                    returns,
+                    ..
+                } => {
+                    let args_converted: Vec<_> =
+                        args.iter().map(|typ| (typ.clone(), "xyz")).collect();
+
+                    let returns_converted: AzulaType = returns.clone().into();
+
+                    self.functions.insert(
+                        name,
+                        FunctionDefinition {
+                            name,
+                            type_params: vec![],
+                            varargs: *varargs,
+                            args: args_converted.clone(),
+                            returns: returns_converted.clone(),
+                        },
+                    );
+                }
+                Statement::Struct {
+                    name, type_params, ..
+                } if !type_params.is_empty() => {
+                    self.generic_structs.insert(name.to_string(), stmt.clone());
+                }
+                Statement::Struct {
+                    name, attributes, ..
+                } => {
+                    self.structs.insert(
+                        name.to_string(),
+                        StructDefinition {
+                            name,
+                            attrs: attributes.clone(),
+                            methods: HashMap::new(),
+                            mutating: HashSet::new(),
+                        },
+                    );
+                }
+                Statement::Enum { name, variants, .. } => {
+                    self.enums.insert(
+                        name.to_string(),
+                        EnumDefinition {
+                            variants: variants.clone(),
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        // Methods are attached once every struct is known, so impl blocks
+        // may appear before the struct they belong to
+        for stmt in stmts.iter() {
+            if let Statement::Impl {
+                name,
+                methods,
+                span,
+            } = stmt
+            {
+                let struc = match self.structs.get_mut(*name) {
+                    Some(struc) => struc,
+                    None => {
+                        self.declaration_error(ErrorType::UnknownStruct(name.to_string()), span);
+                        continue;
                     }
-                    Statement::ExternFunction {
+                };
+
+                for method in methods {
+                    if let Statement::Function {
                         name,
-                        varargs,
+                        type_params,
                         args,
                         returns,
+                        span,
                         ..
-                    } => {
-                        let args_converted: Vec<_> =
-                            args.iter().map(|typ| (typ.clone(), "xyz")).collect();
+                    } = method
+                    {
+                        if !type_params.is_empty() {
+                            if !self.registering_imports {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::GenericMethod(name.to_string()),
+                                    span.start,
+                                    span.end,
+                                ));
+                            }
+                            continue;
+                        }
 
-                        let returns_converted: AzulaType = returns.clone().into();
+                        // Methods can be split across impl blocks, but not defined twice
+                        if struc.methods.contains_key(name) {
+                            if !self.registering_imports {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::DuplicateMethod(
+                                        struc.name.to_string(),
+                                        name.to_string(),
+                                    ),
+                                    span.start,
+                                    span.end,
+                                ));
+                            }
+                            continue;
+                        }
 
-                        self.functions.insert(
+                        struc.methods.insert(
                             name,
                             FunctionDefinition {
                                 name,
+                                type_params: vec![],
                                 varargs: false,
-                                args: args_converted.clone(),
-                                returns: returns_converted.clone(),
+                                args: args.clone(),
+                                returns: returns.clone(),
                             },
                         );
                     }
-                    _ => {}
+                }
+            }
+        }
+
+        self.find_mutating_methods(stmts);
+
+        // Signatures can use generic structs, which can only be instantiated
+        // once every struct is known
+        for stmt in stmts.iter() {
+            match stmt {
+                Statement::Function {
+                    name,
+                    type_params,
+                    span,
+                    ..
+                } if type_params.is_empty() => {
+                    if let Some(def) = self.functions.get(name) {
+                        let (args, returns) = (def.args.clone(), def.returns.clone());
+                        let args = self.resolve_declared_types(args, span);
+                        let returns = self.resolve_declared_type(&returns, span);
+
+                        let def = self.functions.get_mut(name).unwrap();
+                        def.args = args;
+                        def.returns = returns;
+                    }
+                }
+                Statement::Struct {
+                    name,
+                    type_params,
+                    span,
+                    ..
+                } if type_params.is_empty() => {
+                    if let Some(struc) = self.structs.get(*name) {
+                        let attrs = self.resolve_declared_types(struc.attrs.clone(), span);
+                        self.structs.get_mut(*name).unwrap().attrs = attrs;
+                    }
+                }
+                Statement::Enum { name, span, .. } => {
+                    if let Some(enu) = self.enums.get(*name) {
+                        let mut variants = vec![];
+                        for (variant, payload) in enu.variants.clone() {
+                            let payload = payload
+                                .iter()
+                                .map(|typ| self.resolve_declared_type(typ, span))
+                                .collect();
+                            variants.push((variant, payload));
+                        }
+                        self.enums.get_mut(*name).unwrap().variants = variants;
+                    }
+                }
+                Statement::Impl { name, span, .. } => {
+                    let methods: Vec<_> = match self.structs.get(*name) {
+                        Some(struc) => struc.methods.keys().cloned().collect(),
+                        None => continue,
+                    };
+
+                    for method in methods {
+                        let def = &self.structs[*name].methods[method];
+                        let (args, returns) = (def.args.clone(), def.returns.clone());
+                        let args = self.resolve_declared_types(args, span);
+                        let returns = self.resolve_declared_type(&returns, span);
+
+                        let def = self
+                            .structs
+                            .get_mut(*name)
+                            .unwrap()
+                            .methods
+                            .get_mut(method)
+                            .unwrap();
+                        def.args = args;
+                        def.returns = returns;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Substitutes type parameters for their arguments, and instantiates any
+    /// generic structs the type uses
+    fn resolve_type(
+        &mut self,
+        typ: &AzulaType<'a>,
+        type_args: &HashMap<&'a str, AzulaType<'a>>,
+    ) -> Result<AzulaType<'a>, ErrorType> {
+        match typ {
+            AzulaType::Named(name) => {
+                if let Some(arg) = type_args.get(name.as_str()) {
+                    return Ok(arg.clone());
+                }
+
+                if let Some(Statement::Struct { type_params, .. }) = self.generic_structs.get(name)
+                {
+                    return Err(ErrorType::WrongTypeArgumentCount(
+                        name.clone(),
+                        type_params.len(),
+                        0,
+                    ));
+                }
+
+                Ok(typ.clone())
+            }
+            AzulaType::Pointer(nested) => Ok(AzulaType::Pointer(Rc::new(
+                self.resolve_type(nested, type_args)?,
+            ))),
+            AzulaType::Array(nested, size) => Ok(AzulaType::Array(
+                Rc::new(self.resolve_type(nested, type_args)?),
+                *size,
+            )),
+            AzulaType::Generic(name, args) => {
+                let mut resolved = vec![];
+                for arg in args {
+                    resolved.push(self.resolve_type(arg, type_args)?);
+                }
+
+                Ok(AzulaType::Named(self.instantiate_struct(name, resolved)?))
+            }
+            _ => Ok(typ.clone()),
+        }
+    }
+
+    /// Resolves a type written in a declaration, reporting it if it's invalid
+    fn resolve_declared_type(&mut self, typ: &AzulaType<'a>, span: &Span) -> AzulaType<'a> {
+        match self.resolve_type(typ, &HashMap::new()) {
+            Ok(typ) => typ,
+            Err(err) => {
+                self.declaration_error(err, span);
+                AzulaType::Error
+            }
+        }
+    }
+
+    fn declaration_error(&mut self, err: ErrorType, span: &Span) {
+        if !self.registering_imports {
+            self.errors.push(AzulaError::new(err, span.start, span.end));
+        }
+    }
+
+    fn resolve_declared_types(
+        &mut self,
+        types: Vec<(AzulaType<'a>, &'a str)>,
+        span: &Span,
+    ) -> Vec<(AzulaType<'a>, &'a str)> {
+        types
+            .iter()
+            .map(|(typ, name)| (self.resolve_declared_type(typ, span), *name))
+            .collect()
+    }
+
+    /// Monomorphises a generic struct for some type arguments, returning the
+    /// name of the instance
+    fn instantiate_struct(
+        &mut self,
+        name: &str,
+        type_args: Vec<AzulaType<'a>>,
+    ) -> Result<String, ErrorType> {
+        let (struct_name, public, type_params, attributes, span) =
+            match self.generic_structs.get(name) {
+                Some(Statement::Struct {
+                    name,
+                    public,
+                    type_params,
+                    attributes,
+                    span,
+                }) => (
+                    *name,
+                    *public,
+                    type_params.clone(),
+                    attributes.clone(),
+                    span.clone(),
+                ),
+                _ if self.structs.contains_key(name) => {
+                    return Err(ErrorType::WrongTypeArgumentCount(
+                        name.to_string(),
+                        0,
+                        type_args.len(),
+                    ))
+                }
+                _ => return Err(ErrorType::UnknownStruct(name.to_string())),
+            };
+
+        if type_params.len() != type_args.len() {
+            return Err(ErrorType::WrongTypeArgumentCount(
+                name.to_string(),
+                type_params.len(),
+                type_args.len(),
+            ));
+        }
+
+        let instance = mangle(name, &type_args);
+        if self.struct_instances.contains_key(&instance) {
+            return Ok(instance);
+        }
+
+        // Registered before resolving the attributes, so a struct can point to itself
+        self.struct_instances
+            .insert(instance.clone(), (name.to_string(), type_args.clone()));
+
+        let bindings = type_params.into_iter().zip(type_args.clone()).collect();
+        let mut attrs = vec![];
+        for (typ, attr) in attributes {
+            match self.resolve_type(&typ, &bindings) {
+                Ok(typ) => attrs.push((typ, attr)),
+                Err(err) => {
+                    self.struct_instances.remove(&instance);
+                    return Err(err);
+                }
+            }
+        }
+
+        self.structs.insert(
+            instance.clone(),
+            StructDefinition {
+                name: struct_name,
+                attrs: attrs.clone(),
+                methods: HashMap::new(),
+                mutating: HashSet::new(),
+            },
+        );
+        self.instances.push(Statement::Instance(
+            type_args,
+            Rc::new(Statement::Struct {
+                name: struct_name,
+                public,
+                type_params: vec![],
+                attributes: attrs,
+                span,
+            }),
+        ));
+
+        Ok(instance)
+    }
+
+    /// Queues a generic function to be typechecked for some type arguments,
+    /// unless it already has been
+    fn instantiate_function(&mut self, name: &'a str, type_args: Vec<AzulaType<'a>>) {
+        if self.function_instances.insert(mangle(name, &type_args)) {
+            self.pending_instances.push_back((name, type_args));
+        }
+    }
+
+    /// Binds the type parameters used in a declared type by matching it against
+    /// the type of the value given for it
+    fn bind_type_params(
+        &self,
+        declared: &AzulaType<'a>,
+        given: &AzulaType<'a>,
+        type_params: &[&'a str],
+        bindings: &mut HashMap<&'a str, AzulaType<'a>>,
+    ) {
+        match (declared, given) {
+            // Nothing can be learned from nil
+            (_, AzulaType::Void) | (_, AzulaType::Infer) => {}
+            (AzulaType::Named(name), _) => {
+                // Later mismatches are reported as wrong argument types
+                if let Some(param) = type_params.iter().find(|param| **param == name) {
+                    bindings.entry(param).or_insert_with(|| given.clone());
+                }
+            }
+            (AzulaType::Pointer(declared), AzulaType::Pointer(given))
+            | (AzulaType::Array(declared, _), AzulaType::Array(given, _)) => {
+                self.bind_type_params(declared, given, type_params, bindings)
+            }
+            (AzulaType::Generic(name, declared), AzulaType::Named(instance)) => {
+                if let Some((generic, given)) = self.struct_instances.get(instance) {
+                    if generic == name {
+                        for (declared, given) in declared.iter().zip(given) {
+                            self.bind_type_params(declared, given, type_params, bindings);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Orders the inferred type arguments by their parameters, reporting any
+    /// that couldn't be inferred
+    fn collect_type_args(
+        &mut self,
+        name: &str,
+        type_params: &[&'a str],
+        bindings: &HashMap<&'a str, AzulaType<'a>>,
+        span: &Span,
+    ) -> Result<Vec<AzulaType<'a>>, String> {
+        let mut type_args = vec![];
+        for param in type_params {
+            match bindings.get(param) {
+                Some(typ) => type_args.push(typ.clone()),
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::CannotInferType(name.to_string(), param.to_string()),
+                        span.start,
+                        span.end,
+                    ));
+                    return Err("cannot infer type argument".to_string());
+                }
+            }
+        }
+
+        Ok(type_args)
+    }
+
+    /// Infers the type arguments of a generic struct from the values it's
+    /// initialised with, returning the name of the instance
+    fn instantiate_initialisation(
+        &mut self,
+        name: &str,
+        attrs: &[(&'a str, ExpressionNode<'a>)],
+        span: &Span,
+    ) -> Result<String, String> {
+        let (type_params, attributes) = match &self.generic_structs[name] {
+            Statement::Struct {
+                type_params,
+                attributes,
+                ..
+            } => (type_params.clone(), attributes.clone()),
+            _ => unreachable!(),
+        };
+
+        let mut bindings = HashMap::new();
+        for (attr, value) in attrs {
+            if let Some((declared, _)) = attributes.iter().find(|(_, name)| name == attr) {
+                self.bind_type_params(declared, &value.typed, &type_params, &mut bindings);
+            }
+        }
+        let type_args = self.collect_type_args(name, &type_params, &bindings, span)?;
+
+        self.instantiate_struct(name, type_args).map_err(|err| {
+            self.errors.push(AzulaError::new(err, span.start, span.end));
+            "invalid struct".to_string()
+        })
+    }
+
+    /// Infers the type arguments of a call to a generic function from its
+    /// arguments, returning them along with the substituted signature
+    #[allow(clippy::type_complexity)]
+    fn instantiate_call(
+        &mut self,
+        name: &'a str,
+        params: Vec<(AzulaType<'a>, &'a str)>,
+        returns: AzulaType<'a>,
+        arg_types: &[AzulaType<'a>],
+        span: &Span,
+    ) -> Result<
+        (
+            Vec<(AzulaType<'a>, &'a str)>,
+            AzulaType<'a>,
+            Vec<AzulaType<'a>>,
+        ),
+        String,
+    > {
+        let type_params = self.functions[name].type_params.clone();
+
+        let mut bindings = HashMap::new();
+        for ((param, _), arg) in params.iter().zip(arg_types) {
+            self.bind_type_params(param, arg, &type_params, &mut bindings);
+        }
+        let type_args = self.collect_type_args(name, &type_params, &bindings, span)?;
+
+        let mut resolve = |typ: &AzulaType<'a>| {
+            self.resolve_type(typ, &bindings).map_err(|err| {
+                self.errors.push(AzulaError::new(err, span.start, span.end));
+                "invalid type".to_string()
+            })
+        };
+
+        let mut resolved = vec![];
+        for (param, arg) in &params {
+            resolved.push((resolve(param)?, *arg));
+        }
+        let returns = resolve(&returns)?;
+
+        self.instantiate_function(name, type_args.clone());
+
+        Ok((resolved, returns, type_args))
+    }
+
+    pub fn typecheck(&mut self) -> Result<Statement<'a>, String> {
+        if let Statement::Root(x) = self.ast.clone() {
+            // Declarations from imported files are visible here, but their
+            // bodies are typechecked along with the file they belong to
+            let imports = self.imports.clone();
+            self.registering_imports = true;
+            self.register_declarations(&imports);
+            self.registering_imports = false;
+            self.register_declarations(&x);
+
+            for stmt in &imports {
+                if let Statement::Function {
+                    name, type_params, ..
+                } = stmt
+                {
+                    if !type_params.is_empty() {
+                        self.imported_generics.insert(name);
+                    }
+                }
+            }
+            for stmt in &x {
+                if let Statement::Function { name, .. } = stmt {
+                    self.imported_generics.remove(name);
                 }
             }
         } else {
@@ -117,49 +682,350 @@ impl<'a> Typechecker<'a> {
         }
 
         if let Statement::Root(mut x) = self.ast.clone() {
+            // Carry on past failed statements so every error is reported
             for stmt in x.iter_mut() {
-                *stmt = match self.typecheck_top_level_statement(stmt.clone()) {
-                    Ok(stmt) => stmt,
-                    Err(e) => return Err(e),
-                };
+                match self.typecheck_top_level_statement(stmt.clone()) {
+                    Ok(typed) => *stmt = typed,
+                    Err(_) => {
+                        if let Statement::Assign(mutable, name, type_annotation, ..) = stmt {
+                            self.globals.insert(
+                                name.clone(),
+                                VariableDefinition {
+                                    name: name.clone(),
+                                    mutable: *mutable,
+                                    typ: type_annotation.clone().unwrap_or(AzulaType::Error),
+                                },
+                            );
+                        }
+                    }
+                }
             }
+
+            self.check_instances();
+
+            if !self.errors.is_empty() {
+                return Err("Failed to typecheck".to_string());
+            }
+
+            // Instanced structs have to be known before anything uses them
+            x.splice(0..0, self.instances.drain(..));
+
             Ok(Statement::Root(x))
         } else {
             Err("Not a root node".to_string())
         }
     }
 
+    /// Typechecks the queued instances of this file's generic functions, and
+    /// hands those of imported ones on through `foreign_instances`
+    fn check_instances(&mut self) {
+        // Instances can use other generics, queueing more instances
+        while let Some((name, type_args)) = self.pending_instances.pop_front() {
+            if self.imported_generics.contains(name) {
+                self.foreign_instances.push((name, type_args));
+                continue;
+            }
+            // Errors in the body were already reported once
+            if self.invalid_generics.contains(name) {
+                continue;
+            }
+
+            let stmt = self.generic_functions[name].clone();
+            let bindings = match &stmt {
+                Statement::Function { type_params, .. } => {
+                    type_params.iter().cloned().zip(type_args.clone()).collect()
+                }
+                _ => unreachable!(),
+            };
+
+            if let Ok(typed) = self.typecheck_function(stmt, bindings) {
+                self.instances
+                    .push(Statement::Instance(type_args, Rc::new(typed)));
+            }
+        }
+    }
+
+    /// Typechecks an instance of one of this file's generic functions used by
+    /// another file, returning it along with any instances it needed in turn
+    pub fn instantiate(&mut self, name: &str, type_args: Vec<AzulaType<'a>>) -> Vec<Statement<'a>> {
+        if let Some((name, _)) = self.generic_functions.get_key_value(name) {
+            self.instantiate_function(name, type_args);
+            self.check_instances();
+        }
+
+        self.instances.drain(..).collect()
+    }
+
+    /// Typechecks a generic function once with its type parameters standing
+    /// for any type, so its body is checked even if nothing uses it. Instances
+    /// made along the way are thrown away, as they don't have real types
+    fn typecheck_generic(&mut self, stmt: Statement<'a>) -> Result<Statement<'a>, String> {
+        let (name, type_params) = match &stmt {
+            Statement::Function {
+                name, type_params, ..
+            } => (*name, type_params.clone()),
+            _ => unreachable!(),
+        };
+
+        let struct_instances: HashSet<_> = self.struct_instances.keys().cloned().collect();
+        let function_instances = self.function_instances.clone();
+        let (pending, instances) = (self.pending_instances.len(), self.instances.len());
+
+        let bindings = type_params
+            .iter()
+            .map(|param| (*param, AzulaType::Named(param.to_string())))
+            .collect();
+        self.abstract_params = type_params;
+        let result = self.typecheck_function(stmt.clone(), bindings);
+        self.abstract_params.clear();
+
+        let made: Vec<_> = self
+            .struct_instances
+            .keys()
+            .filter(|instance| !struct_instances.contains(*instance))
+            .cloned()
+            .collect();
+        for instance in made {
+            self.struct_instances.remove(&instance);
+            self.structs.remove(&instance);
+        }
+        self.function_instances = function_instances;
+        self.pending_instances.truncate(pending);
+        self.instances.truncate(instances);
+
+        match result {
+            Ok(_) => Ok(stmt),
+            Err(e) => {
+                self.invalid_generics.insert(name);
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether a type is a type parameter of the generic body being checked
+    fn is_abstract(&self, typ: &AzulaType<'a>) -> bool {
+        matches!(typ, AzulaType::Named(name) if self.abstract_params.contains(&name.as_str()))
+    }
+
     pub fn typecheck_top_level_statement(
         &mut self,
         stmt: Statement<'a>,
     ) -> Result<Statement<'a>, String> {
         match stmt {
-            Statement::Function { .. } => self.typecheck_function(stmt),
+            // Generic bodies are checked once on their own, and again for each
+            // set of type arguments they're used with
+            Statement::Function {
+                ref type_params, ..
+            } if !type_params.is_empty() => self.typecheck_generic(stmt),
+            Statement::Struct {
+                ref type_params, ..
+            } if !type_params.is_empty() => Ok(stmt),
+            Statement::Function { .. } => self.typecheck_function(stmt, HashMap::new()),
             Statement::ExternFunction { .. } => Ok(stmt),
+            Statement::Import(..) => Ok(stmt),
+            Statement::Enum {
+                name, public, span, ..
+            } => Ok(Statement::Enum {
+                name,
+                public,
+                variants: self.enums[name].variants.clone(),
+                span,
+            }),
             Statement::Assign(..) => self.typecheck_global_assign(stmt),
             Statement::Struct {
                 name,
-                attributes,
+                public,
+                type_params,
+                span,
+                ..
+            } => Ok(Statement::Struct {
+                name: name,
+                public,
+                type_params,
+                attributes: self.structs[name].attrs.clone(),
+                span: span,
+            }),
+            Statement::Impl {
+                name,
+                methods,
                 span,
             } => {
-                self.structs.insert(
-                    name.to_string(),
-                    StructDefinition {
-                        name,
-                        attrs: attributes.clone(),
-                    },
-                );
+                let mut typed_methods = vec![];
+                for method in methods {
+                    typed_methods.push(self.typecheck_function(method, HashMap::new())?);
+                }
 
-                Ok(Statement::Struct {
-                    name: name,
-                    attributes: attributes,
-                    span: span,
+                Ok(Statement::Impl {
+                    name,
+                    methods: typed_methods,
+                    span,
                 })
             }
-            _ => unreachable!(),
+            _ => self.misplaced(&stmt),
+        }
+    }
+
+    /// Finds the methods that assign to a field of their receiver, either
+    /// directly or by calling another method that does
+    fn find_mutating_methods(&mut self, stmts: &[Statement<'a>]) {
+        let methods: Vec<_> = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Impl { name, methods, .. } if self.structs.contains_key(*name) => {
+                    Some(methods.iter().map(move |method| (*name, method)))
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        // Calls between methods can go either way, so this runs until
+        // nothing more is found
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (struc, method) in &methods {
+                if let Statement::Function { name, body, .. } = method {
+                    if !self.structs[*struc].mutating.contains(name)
+                        && self.mutates_receiver(struc, body)
+                    {
+                        self.structs.get_mut(*struc).unwrap().mutating.insert(name);
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn mutates_receiver(&self, struc: &str, stmt: &Statement<'a>) -> bool {
+        let body =
+            |stmts: &[Statement<'a>]| stmts.iter().any(|stmt| self.mutates_receiver(struc, stmt));
+        match stmt {
+            Statement::Reassign(target, value, _) => {
+                let assigns_field = match &target.expression {
+                    Expression::StructAccess(held, _) => {
+                        self.receiver_struct(struc, held).is_some()
+                    }
+                    _ => false,
+                };
+                assigns_field || self.mutates_in(struc, target) || self.mutates_in(struc, value)
+            }
+            Statement::Block(stmts) => body(stmts),
+            Statement::If(condition, then, otherwise, _) => {
+                self.mutates_in(struc, condition)
+                    || body(then)
+                    || otherwise.as_ref().is_some_and(|otherwise| body(otherwise))
+            }
+            Statement::While(value, stmts, _) | Statement::For(_, value, stmts, _) => {
+                self.mutates_in(struc, value) || body(stmts)
+            }
+            Statement::Assign(_, _, _, value, _)
+            | Statement::ExpressionStatement(value, _)
+            | Statement::Return(Some(value), _) => self.mutates_in(struc, value),
+            _ => false,
+        }
+    }
+
+    /// Whether an expression calls a mutating method on the receiver
+    fn mutates_in(&self, struc: &str, expr: &ExpressionNode<'a>) -> bool {
+        let any =
+            |exprs: &[ExpressionNode<'a>]| exprs.iter().any(|expr| self.mutates_in(struc, expr));
+        match &expr.expression {
+            Expression::FunctionCall { function, args, .. } => {
+                let mutating = match &function.expression {
+                    Expression::StructAccess(receiver, method) => {
+                        match (self.receiver_struct(struc, receiver), &method.expression) {
+                            (Some(called), Expression::Identifier(method)) => self
+                                .structs
+                                .get(&called)
+                                .is_some_and(|called| called.mutating.contains(method.as_str())),
+                            _ => false,
+                        }
+                    }
+                    _ => false,
+                };
+                mutating || self.mutates_in(struc, function) || any(args)
+            }
+            Expression::Infix(left, _, right)
+            | Expression::ArrayAccess(left, right)
+            | Expression::Range(left, right) => {
+                self.mutates_in(struc, left) || self.mutates_in(struc, right)
+            }
+            Expression::Not(value)
+            | Expression::Pointer(value)
+            | Expression::StructAccess(value, _) => self.mutates_in(struc, value),
+            Expression::Array(values) | Expression::EnumVariant(_, _, values) => any(values),
+            Expression::StructInitialisation(_, fields) => fields
+                .iter()
+                .any(|(_, value)| self.mutates_in(struc, value)),
+            Expression::Match(subject, arms) => {
+                self.mutates_in(struc, subject)
+                    || arms.iter().any(|arm| {
+                        arm.body
+                            .iter()
+                            .any(|stmt| self.mutates_receiver(struc, stmt))
+                            || arm
+                                .value
+                                .as_ref()
+                                .is_some_and(|value| self.mutates_in(struc, value))
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// The struct `expr` is, when it's the receiver `self` or a field held in
+    /// it by value
+    fn receiver_struct(&self, struc: &str, expr: &ExpressionNode<'a>) -> Option<String> {
+        match &expr.expression {
+            Expression::Identifier(name) if name == "self" => Some(struc.to_string()),
+            Expression::StructAccess(held, field) => {
+                let held = self.receiver_struct(struc, held)?;
+                let field = match &field.expression {
+                    Expression::Identifier(field) => field,
+                    _ => return None,
+                };
+                match self
+                    .structs
+                    .get(&held)?
+                    .attrs
+                    .iter()
+                    .find(|(_, name)| name == field)
+                {
+                    Some((AzulaType::Named(name), _)) => Some(name.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a typed `expr` is a constant struct, or a struct held in one
+    /// by value, so its fields can't change
+    fn held_by_constant(&self, expr: &ExpressionNode<'a>, env: &Environment<'a>) -> bool {
+        if !matches!(expr.typed, AzulaType::Named(_)) {
+            return false;
+        }
+        match &expr.expression {
+            Expression::Identifier(name) => env
+                .variable_definitions
+                .get(name)
+                .is_some_and(|var| !var.mutable),
+            Expression::StructAccess(held, _) => self.held_by_constant(held, env),
+            _ => false,
         }
     }
 
+    fn misplaced<T>(&mut self, stmt: &Statement<'a>) -> Result<T, String> {
+        let span = stmt.span();
+        self.errors.push(AzulaError::new(
+            ErrorType::MisplacedStatement(stmt.kind().to_string()),
+            span.start,
+            span.end,
+        ));
+        Err("Misplaced statement".to_string())
+    }
+
     pub fn typecheck_statement(
         &mut self,
         stmt: Statement<'a>,
@@ -169,7 +1035,13 @@ impl<'a> Typechecker<'a> {
             Statement::Assign(..) => self.typecheck_assign(stmt, env),
             Statement::Return(..) => self.typecheck_return(stmt, env),
             Statement::ExpressionStatement(expr, span) => {
-                let result = match self.typecheck_expression(expr, env) {
+                // A match on its own doesn't need its arms to give values
+                let result = if let Expression::Match(..) = expr.expression {
+                    self.typecheck_match(expr, env, false)
+                } else {
+                    self.typecheck_expression(expr, env)
+                };
+                let result = match result {
                     Ok((expr, _)) => expr,
                     Err(e) => return Err(e),
                 };
@@ -180,26 +1052,57 @@ impl<'a> Typechecker<'a> {
             }
             Statement::If(..) => self.typecheck_if(stmt, env),
             Statement::While(..) => self.typecheck_while(stmt, env),
+            Statement::For(..) => self.typecheck_for(stmt, env),
+            Statement::Break(ref span) | Statement::Continue(ref span) => {
+                if env.loop_depth == 0 {
+                    let keyword = match stmt {
+                        Statement::Break(..) => "break",
+                        _ => "continue",
+                    };
+                    self.errors.push(AzulaError::new(
+                        ErrorType::OutsideLoop(keyword.to_string()),
+                        span.start,
+                        span.end,
+                    ));
+                    return Err("Loop jump outside loop".to_string());
+                }
+
+                Ok((stmt, AzulaType::Void))
+            }
             Statement::Reassign(..) => self.typecheck_reassign(stmt, env),
-            _ => unreachable!("{:?}", stmt),
+            _ => self.misplaced(&stmt),
         }
     }
 
-    fn typecheck_function(&mut self, stmt: Statement<'a>) -> Result<Statement<'a>, String> {
+    /// Typechecks a function, substituting the given types for its type parameters
+    fn typecheck_function(
+        &mut self,
+        stmt: Statement<'a>,
+        type_args: HashMap<&'a str, AzulaType<'a>>,
+    ) -> Result<Statement<'a>, String> {
         if let Statement::Function {
             name,
+            public,
+            attributes,
             args,
             returns,
             body,
             span,
+            ..
         } = stmt.clone()
         {
-            let args_converted: Vec<_> = args
-                .iter()
-                .map(|(typ, name)| (AzulaType::from(typ.clone()), *name))
-                .collect();
+            // Invalid signatures are reported where they're declared or called
+            let mut args_converted = vec![];
+            for (typ, name) in &args {
+                let typ = self.resolve_type(typ, &type_args);
+                args_converted.push((typ.unwrap_or(AzulaType::Error), *name));
+            }
+            let returns = self
+                .resolve_type(&returns, &type_args)
+                .unwrap_or(AzulaType::Error);
 
             let mut environment = Environment::new();
+            environment.type_args = type_args;
             for (typ, name) in &args_converted {
                 environment.add_variable(
                     name.to_string(),
@@ -211,27 +1114,59 @@ impl<'a> Typechecker<'a> {
                 )
             }
             let mut statements = vec![];
-            if let Statement::Block(mut stmts) = body.deref().clone() {
-                for stmt in stmts.iter_mut() {
-                    statements.push(
-                        match self.typecheck_statement(stmt.clone(), &mut environment) {
-                            Ok((stmt, _)) => stmt,
-                            Err(e) => return Err(e),
-                        },
-                    );
-                }
+            if let Statement::Block(stmts) = body.deref().clone() {
+                statements = self.typecheck_body(stmts, &mut environment)?;
             }
 
             return Ok(Statement::Function {
                 name,
-                args,
+                public,
+                attributes,
+                type_params: vec![],
+                args: args_converted,
                 returns,
                 body: Rc::new(Statement::Block(statements)),
                 span,
             });
         }
 
-        unreachable!()
+        unreachable!()
+    }
+
+    /// Typechecks each statement in a body, carrying on past any that fail.
+    /// Variables whose assign failed are poisoned rather than left undefined,
+    /// so their uses don't report errors of their own.
+    fn typecheck_body(
+        &mut self,
+        body: Vec<Statement<'a>>,
+        env: &mut Environment<'a>,
+    ) -> Result<Vec<Statement<'a>>, String> {
+        let mut stmts = vec![];
+        let mut failed = false;
+        for stmt in body {
+            match self.typecheck_statement(stmt.clone(), env) {
+                Ok((stmt, _)) => stmts.push(stmt),
+                Err(_) => {
+                    failed = true;
+                    if let Statement::Assign(mutable, ref name, ref type_annotation, ..) = stmt {
+                        env.add_variable(
+                            name.clone(),
+                            VariableDefinition {
+                                name: name.clone(),
+                                mutable,
+                                typ: type_annotation.clone().unwrap_or(AzulaType::Error),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        if failed {
+            return Err("Failed to typecheck body".to_string());
+        }
+
+        Ok(stmts)
     }
 
     fn typecheck_global_assign(&mut self, expr: Statement<'a>) -> Result<Statement<'a>, String> {
@@ -274,8 +1209,8 @@ impl<'a> Typechecker<'a> {
                 if type_annotation != typ {
                     self.errors.push(AzulaError::new(
                         ErrorType::MismatchedAssignTypes(
-                            format!("{:?}", type_annotation),
-                            format!("{:?}", typ),
+                            type_annotation.to_string(),
+                            typ.to_string(),
                         ),
                         span.start,
                         value.span.end,
@@ -316,6 +1251,17 @@ impl<'a> Typechecker<'a> {
                 Err(e) => return Err(e),
             };
 
+            let type_annotation = match type_annotation {
+                Some(typ) => match self.resolve_type(&typ, &env.type_args) {
+                    Ok(typ) => Some(typ),
+                    Err(err) => {
+                        self.errors.push(AzulaError::new(err, span.start, span.end));
+                        return Err("invalid type annotation".to_string());
+                    }
+                },
+                None => None,
+            };
+
             if type_annotation.is_some() {
                 let mut type_annotation = type_annotation.clone().unwrap();
 
@@ -324,8 +1270,8 @@ impl<'a> Typechecker<'a> {
                         if arr_typ != inner_type {
                             self.errors.push(AzulaError::new(
                                 ErrorType::MismatchedAssignTypes(
-                                    format!("{:?}", type_annotation),
-                                    format!("{:?}", typ),
+                                    type_annotation.to_string(),
+                                    typ.to_string(),
                                 ),
                                 span.start,
                                 expr.span.end,
@@ -337,8 +1283,8 @@ impl<'a> Typechecker<'a> {
                             if size.unwrap() != inner_size.unwrap() {
                                 self.errors.push(AzulaError::new(
                                     ErrorType::MismatchedAssignTypes(
-                                        format!("{:?}", type_annotation),
-                                        format!("{:?}", typ),
+                                        type_annotation.to_string(),
+                                        typ.to_string(),
                                     ),
                                     span.start,
                                     expr.span.end,
@@ -354,8 +1300,8 @@ impl<'a> Typechecker<'a> {
                 if type_annotation != typ {
                     self.errors.push(AzulaError::new(
                         ErrorType::MismatchedAssignTypes(
-                            format!("{:?}", type_annotation),
-                            format!("{:?}", typ),
+                            type_annotation.to_string(),
+                            typ.to_string(),
                         ),
                         span.start,
                         expr.span.end,
@@ -411,16 +1357,20 @@ impl<'a> Typechecker<'a> {
                 Expression::ArrayAccess(..) => {}
                 Expression::StructAccess(..) => {}
                 _ => {
-                    unreachable!("{:?}", var.expression)
+                    self.errors.push(AzulaError::new(
+                        ErrorType::InvalidAssignTarget,
+                        var.span.start,
+                        var.span.end,
+                    ));
+                    return Err("invalid assign target".to_string());
                 }
             }
 
             if !mutable {
-                self.errors.push(AzulaError::new(
-                    ErrorType::ConstantAssign,
-                    var.span.start,
-                    var.span.end,
-                ));
+                self.errors.push(
+                    AzulaError::new(ErrorType::ConstantAssign, var.span.start, var.span.end)
+                        .with_help("declare it with var to be able to assign to it"),
+                );
                 return Err("constant assign".to_string());
             }
 
@@ -429,12 +1379,25 @@ impl<'a> Typechecker<'a> {
                 Err(e) => return Err(e),
             };
 
+            if let Expression::StructAccess(held, _) = &variable.expression {
+                if self.held_by_constant(held, env) {
+                    self.errors.push(
+                        AzulaError::new(
+                            ErrorType::ConstantAssign,
+                            variable.span.start,
+                            variable.span.end,
+                        )
+                        .with_help(
+                            "declare the struct with var to be able to assign to its fields",
+                        ),
+                    );
+                    return Err("constant assign".to_string());
+                }
+            }
+
             if var_type != typ {
                 self.errors.push(AzulaError::new(
-                    ErrorType::MismatchedAssignTypes(
-                        format!("{:?}", var_type),
-                        format!("{:?}", typ),
-                    ),
+                    ErrorType::MismatchedAssignTypes(var_type.to_string(), typ.to_string()),
                     span.start,
                     val.span.end,
                 ));
@@ -468,35 +1431,48 @@ impl<'a> Typechecker<'a> {
         }
     }
 
+    fn typecheck_condition(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<ExpressionNode<'a>, String> {
+        let (expr, typ) = self.typecheck_expression(expr, env)?;
+
+        if typ != AzulaType::Bool {
+            self.errors.push(AzulaError::new(
+                ErrorType::NonBoolCondition(typ.to_string()),
+                expr.span.start,
+                expr.span.end,
+            ));
+            return Err("Non boolean condition".to_string());
+        }
+
+        Ok(expr)
+    }
+
     fn typecheck_if(
         &mut self,
         stmt: Statement<'a>,
         env: &mut Environment<'a>,
     ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::If(ref expr, ref body, ref span) = stmt {
-            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
-            };
+        if let Statement::If(ref expr, ref body, ref else_body, ref span) = stmt {
+            // The bodies are still checked if the condition is bad
+            let condition = self.typecheck_condition(expr.clone(), env);
 
-            if typ != AzulaType::Bool {
-                self.errors.push(AzulaError::new(
-                    ErrorType::NonBoolCondition(format!("{:?}", typ)),
-                    expr.span.start,
-                    expr.span.end,
-                ));
-                return Err("Non boolean condition".to_string());
-            }
+            let stmts = self.typecheck_body(body.clone(), env);
 
-            let mut stmts = vec![];
-            for stmt in body {
-                match self.typecheck_statement(stmt.clone(), env) {
-                    Ok((stmt, _)) => stmts.push(stmt),
-                    Err(e) => return Err(e),
-                };
-            }
+            let else_stmts = else_body
+                .as_ref()
+                .map(|else_body| self.typecheck_body(else_body.clone(), env));
+
+            let expr = condition?;
+            let stmts = stmts?;
+            let else_stmts = else_stmts.transpose()?;
 
-            Ok((Statement::If(expr, stmts, span.clone()), AzulaType::Void))
+            Ok((
+                Statement::If(expr, stmts, else_stmts, span.clone()),
+                AzulaType::Void,
+            ))
         } else {
             unreachable!()
         }
@@ -508,34 +1484,550 @@ impl<'a> Typechecker<'a> {
         env: &mut Environment<'a>,
     ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
         if let Statement::While(ref expr, ref body, ref span) = stmt {
-            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
+            let condition = self.typecheck_condition(expr.clone(), env);
+
+            env.loop_depth += 1;
+            let stmts = self.typecheck_body(body.clone(), env);
+            env.loop_depth -= 1;
+
+            let expr = condition?;
+            let stmts = stmts?;
+
+            Ok((Statement::While(expr, stmts, span.clone()), AzulaType::Void))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_for(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::For(ref ident, ref iterable, ref body, ref span) = stmt {
+            // The body is still checked if the iterable is bad, with the loop variable poisoned
+            let iterable = self.typecheck_iterable(iterable.clone(), env);
+
+            let typ = match &iterable {
+                Ok((_, typ)) => typ.clone(),
+                Err(_) => AzulaType::Error,
             };
 
-            if typ != AzulaType::Bool {
+            // The loop variable only lives as long as the loop, shadowing any outer one
+            let outer = env.variable_definitions.get(ident).cloned();
+            env.add_variable(
+                ident.clone(),
+                VariableDefinition {
+                    name: ident.clone(),
+                    mutable: false,
+                    typ,
+                },
+            );
+
+            env.loop_depth += 1;
+            let stmts = self.typecheck_body(body.clone(), env);
+            env.loop_depth -= 1;
+
+            match outer {
+                Some(def) => env.add_variable(ident.clone(), def),
+                None => {
+                    env.variable_definitions.remove(ident);
+                }
+            }
+
+            let (iterable, _) = iterable?;
+            let stmts = stmts?;
+
+            Ok((
+                Statement::For(ident.clone(), iterable, stmts, span.clone()),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Typechecks what a for loop iterates over, returning it along with the
+    /// type of the loop variable
+    fn typecheck_iterable(
+        &mut self,
+        iterable: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        if let Expression::Range(ref start, ref end) = iterable.expression {
+            let start = self.typecheck_range_bound(start.deref().clone(), env);
+            let end = self.typecheck_range_bound(end.deref().clone(), env);
+
+            return Ok((
+                ExpressionNode {
+                    expression: Expression::Range(Rc::new(start?), Rc::new(end?)),
+                    typed: AzulaType::Int,
+                    span: iterable.span,
+                },
+                AzulaType::Int,
+            ));
+        }
+
+        let (iterable, typ) = self.typecheck_expression(iterable, env)?;
+
+        match typ {
+            AzulaType::Array(ref element, Some(_)) => Ok((iterable, element.deref().clone())),
+            _ => {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonBoolCondition(format!("{:?}", typ)),
-                    expr.span.start,
-                    expr.span.end,
+                    ErrorType::NotIterable(typ.to_string()),
+                    iterable.span.start,
+                    iterable.span.end,
                 ));
-                return Err("Non boolean condition".to_string());
+                Err("Not iterable".to_string())
             }
+        }
+    }
 
-            let mut stmts = vec![];
-            for stmt in body {
-                match self.typecheck_statement(stmt.clone(), env) {
-                    Ok((stmt, _)) => stmts.push(stmt),
-                    Err(e) => return Err(e),
+    fn typecheck_range_bound(
+        &mut self,
+        bound: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<ExpressionNode<'a>, String> {
+        let (bound, typ) = self.typecheck_expression(bound, env)?;
+
+        if typ != AzulaType::Int {
+            self.errors.push(AzulaError::new(
+                ErrorType::NonIntRange(typ.to_string()),
+                bound.span.start,
+                bound.span.end,
+            ));
+            return Err("Non int range".to_string());
+        }
+
+        Ok(bound)
+    }
+
+    /// Typechecks every argument before failing, so each bad one is reported
+    fn typecheck_call_arguments(
+        &mut self,
+        args: Vec<ExpressionNode<'a>>,
+        env: &Environment<'a>,
+    ) -> Result<(Vec<ExpressionNode<'a>>, Vec<AzulaType<'a>>), String> {
+        let mut new_args = vec![];
+        let mut arg_types = vec![];
+        let mut failed = false;
+        for arg in args {
+            match self.typecheck_expression(arg, env) {
+                Ok((arg, typ)) => {
+                    new_args.push(arg);
+                    arg_types.push(typ);
+                }
+                Err(_) => failed = true,
+            }
+        }
+
+        if failed {
+            return Err("Invalid argument".to_string());
+        }
+
+        Ok((new_args, arg_types))
+    }
+
+    fn check_call_arguments(
+        &mut self,
+        function: &Expression<'a>,
+        params: &[(AzulaType<'a>, &'a str)],
+        varargs: bool,
+        args: &[ExpressionNode<'a>],
+        arg_types: &[AzulaType<'a>],
+        span: &Span,
+    ) -> Result<(), String> {
+        let name = match function {
+            Expression::Identifier(name) => name.clone(),
+            _ => unreachable!(),
+        };
+
+        // Varargs functions take at least as many arguments as they declare
+        if args.len() < params.len() || (!varargs && args.len() > params.len()) {
+            self.errors.push(AzulaError::new(
+                ErrorType::WrongArgumentCount(name, params.len(), args.len()),
+                span.start,
+                span.end,
+            ));
+            return Err("wrong number of arguments".to_string());
+        }
+
+        let mut mismatched = false;
+        for ((param, _), (arg, typ)) in params.iter().zip(args.iter().zip(arg_types)) {
+            // nil can be passed anywhere a pointer is expected, and invalid
+            // parameter types were reported with their declaration
+            if typ == param
+                || (*typ == AzulaType::Void && matches!(param, AzulaType::Pointer(_)))
+                || *param == AzulaType::Error
+            {
+                continue;
+            }
+
+            self.errors.push(AzulaError::new(
+                ErrorType::WrongArgumentType(name.clone(), param.to_string(), typ.to_string()),
+                arg.span.start,
+                arg.span.end,
+            ));
+            mismatched = true;
+        }
+
+        if mismatched {
+            return Err("mismatched argument types".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Finds accesses like `Shape.Circle`, where the left side names an enum rather than a variable
+    fn enum_variant_access(
+        &self,
+        expr: &Expression<'a>,
+        env: &Environment<'a>,
+    ) -> Option<(String, String)> {
+        if let Expression::StructAccess(left, right) = expr {
+            if let (Expression::Identifier(enu), Expression::Identifier(variant)) =
+                (&left.expression, &right.expression)
+            {
+                let is_variable =
+                    env.variable_definitions.contains_key(enu) || self.globals.contains_key(enu);
+                if self.enums.contains_key(enu) && !is_variable {
+                    return Some((enu.clone(), variant.clone()));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn typecheck_enum_variant(
+        &mut self,
+        enu: String,
+        variant: String,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let payload = match self
+            .enums
+            .get(&enu)
+            .and_then(|e| e.variants.iter().find(|(name, _)| *name == variant))
+        {
+            Some((_, payload)) => payload.clone(),
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnknownVariant(enu, variant),
+                    span.start,
+                    span.end,
+                ));
+                return Err("unknown variant".to_string());
+            }
+        };
+
+        let (new_args, arg_types) = self.typecheck_call_arguments(args, env)?;
+
+        let params: Vec<_> = payload.into_iter().map(|typ| (typ, "")).collect();
+        self.check_call_arguments(
+            &Expression::Identifier(format!("{}.{}", enu, variant)),
+            &params,
+            false,
+            &new_args,
+            &arg_types,
+            &span,
+        )?;
+
+        let typ = AzulaType::Named(enu.clone());
+        Ok((
+            ExpressionNode {
+                expression: Expression::EnumVariant(enu, variant, new_args),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    /// Typechecks a match, making sure every variant is covered. When the match is
+    /// used as a value every arm has to give one, all of the same type.
+    fn typecheck_match(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        env: &Environment<'a>,
+        needs_value: bool,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        if let Expression::Match(subject, arms) = expr.expression {
+            let (subject, subject_type) =
+                self.typecheck_expression(subject.deref().clone(), env)?;
+
+            let enum_name = match &subject_type {
+                AzulaType::Named(name) if self.enums.contains_key(name) => Some(name.clone()),
+                AzulaType::Pointer(nested) => match nested.deref() {
+                    AzulaType::Named(name) if self.enums.contains_key(name) => Some(name.clone()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let enum_name = match enum_name {
+                Some(name) => name,
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::MatchNonEnum(subject_type.to_string()),
+                        subject.span.start,
+                        subject.span.end,
+                    ));
+                    return Err("match on non-enum".to_string());
+                }
+            };
+            let variants = self.enums.get(&enum_name).unwrap().variants.clone();
+
+            let mut failed = false;
+            let mut covered = vec![];
+            let mut wildcard = false;
+            let mut value_type: Option<AzulaType> = None;
+            let mut typed_arms = vec![];
+            for arm in arms {
+                // Bindings are only visible inside their own arm
+                let mut arm_env = env.clone();
+
+                match &arm.pattern {
+                    Pattern::Wildcard => wildcard = true,
+                    Pattern::Variant(name, bindings) => {
+                        let payload = match variants.iter().find(|(variant, _)| variant == name) {
+                            Some((_, payload)) => payload,
+                            None => {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::UnknownVariant(enum_name.clone(), name.to_string()),
+                                    arm.span.start,
+                                    arm.span.end,
+                                ));
+                                failed = true;
+                                continue;
+                            }
+                        };
+                        covered.push(*name);
+
+                        if bindings.len() != payload.len() {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::WrongArgumentCount(
+                                    name.to_string(),
+                                    payload.len(),
+                                    bindings.len(),
+                                ),
+                                arm.span.start,
+                                arm.span.end,
+                            ));
+                            failed = true;
+                            continue;
+                        }
+
+                        for (binding, typ) in bindings.iter().zip(payload) {
+                            if *binding == "_" {
+                                continue;
+                            }
+                            arm_env.add_variable(
+                                binding.to_string(),
+                                VariableDefinition {
+                                    name: binding.to_string(),
+                                    mutable: false,
+                                    typ: typ.clone(),
+                                },
+                            );
+                        }
+                    }
+                }
+
+                let body = self.typecheck_body(arm.body.clone(), &mut arm_env);
+                let value = arm
+                    .value
+                    .clone()
+                    .map(|value| self.typecheck_expression(value, &arm_env))
+                    .transpose();
+
+                let (body, value) = match (body, value) {
+                    (Ok(body), Ok(value)) => (body, value),
+                    _ => {
+                        failed = true;
+                        continue;
+                    }
                 };
+
+                if needs_value {
+                    match (&value, &value_type) {
+                        (None, _) => {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::MatchArmWithoutValue,
+                                arm.span.start,
+                                arm.span.end,
+                            ));
+                            failed = true;
+                        }
+                        (Some((_, typ)), None) => value_type = Some(typ.clone()),
+                        (Some((node, typ)), Some(expected)) if typ != expected => {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::MismatchedTypes(expected.to_string(), typ.to_string()),
+                                node.span.start,
+                                node.span.end,
+                            ));
+                            failed = true;
+                        }
+                        _ => {}
+                    }
+                }
+
+                typed_arms.push(MatchArm {
+                    pattern: arm.pattern,
+                    body,
+                    value: value.map(|(node, _)| node),
+                    span: arm.span,
+                });
             }
 
-            Ok((Statement::While(expr, stmts, span.clone()), AzulaType::Void))
+            if !wildcard {
+                let missing: Vec<_> = variants
+                    .iter()
+                    .filter(|(variant, _)| !covered.contains(variant))
+                    .map(|(variant, _)| variant.to_string())
+                    .collect();
+
+                if !missing.is_empty() {
+                    self.errors.push(
+                        AzulaError::new(
+                            ErrorType::NonExhaustiveMatch(enum_name, missing.join(", ")),
+                            expr.span.start,
+                            expr.span.end,
+                        )
+                        .with_help("add an arm for each missing variant, or a _ arm"),
+                    );
+                    failed = true;
+                }
+            }
+
+            if failed {
+                return Err("Failed to typecheck match".to_string());
+            }
+
+            let typ = value_type.unwrap_or(AzulaType::Void);
+            Ok((
+                ExpressionNode {
+                    expression: Expression::Match(Rc::new(subject), typed_arms),
+                    typed: typ.clone(),
+                    span: expr.span,
+                },
+                typ,
+            ))
         } else {
             unreachable!()
         }
     }
 
+    fn typecheck_method_call(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        if let Expression::FunctionCall { function, args, .. } = expr.expression {
+            if let Expression::StructAccess(receiver, method) = &function.expression {
+                let (receiver, receiver_type) =
+                    self.typecheck_expression(receiver.deref().clone(), env)?;
+
+                let struc_name = match receiver_type {
+                    AzulaType::Named(s) => s,
+                    AzulaType::Pointer(nested) => match nested.deref().clone() {
+                        AzulaType::Named(s) => s,
+                        _ => {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::AccessNonStruct,
+                                receiver.span.start,
+                                receiver.span.end,
+                            ));
+                            return Err("accessing non-struct".to_string());
+                        }
+                    },
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::AccessNonStruct,
+                            receiver.span.start,
+                            receiver.span.end,
+                        ));
+                        return Err("accessing non-struct".to_string());
+                    }
+                };
+
+                let method_name = match &method.expression {
+                    Expression::Identifier(m) => m.clone(),
+                    _ => unreachable!(),
+                };
+
+                let mutating = self
+                    .structs
+                    .get(&struc_name)
+                    .is_some_and(|s| s.mutating.contains(method_name.as_str()));
+                if mutating && self.held_by_constant(&receiver, env) {
+                    self.errors.push(
+                        AzulaError::new(ErrorType::ConstantAssign, expr.span.start, expr.span.end)
+                            .with_help(format!(
+                                "{} assigns to its receiver's fields, declare the struct with var to be able to call it",
+                                method_name
+                            )),
+                    );
+                    return Err("constant assign".to_string());
+                }
+
+                let (return_type, params) = match self
+                    .structs
+                    .get(&struc_name)
+                    .and_then(|s| s.methods.get(method_name.as_str()))
+                {
+                    // The receiver is passed implicitly
+                    Some(m) => (
+                        m.returns.clone(),
+                        m.args.iter().skip(1).cloned().collect::<Vec<_>>(),
+                    ),
+                    None => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::UnknownMethod(struc_name, method_name),
+                            method.span.start,
+                            method.span.end,
+                        ));
+                        return Err("unknown method".to_string());
+                    }
+                };
+
+                let (new_args, arg_types) = self.typecheck_call_arguments(args.clone(), env)?;
+
+                self.check_call_arguments(
+                    &method.expression,
+                    &params,
+                    false,
+                    &new_args,
+                    &arg_types,
+                    &expr.span,
+                )?;
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::FunctionCall {
+                            function: Rc::new(ExpressionNode {
+                                expression: Expression::StructAccess(
+                                    Rc::new(receiver),
+                                    method.clone(),
+                                ),
+                                typed: return_type.clone(),
+                                span: function.span.clone(),
+                            }),
+                            args: new_args,
+                            type_args: vec![],
+                        },
+                        typed: return_type.clone(),
+                        span: expr.span,
+                    },
+                    return_type,
+                ));
+            }
+        }
+
+        unreachable!()
+    }
+
     fn typecheck_expression(
         &mut self,
         mut expr: ExpressionNode<'a>,
@@ -563,11 +2055,16 @@ impl<'a> Typechecker<'a> {
                 if name == "nil" {
                     return Ok((expr.clone(), AzulaType::Void));
                 }
-                if let Some(variable) = env.variable_definitions.get(name) {
-                    expr.typed = variable.typ.clone().into();
+                if let Some(variable) = env
+                    .variable_definitions
+                    .get(name)
+                    .or_else(|| self.globals.get(name))
+                {
+                    // Already reported where the variable was defined
+                    if variable.typ == AzulaType::Error {
+                        return Err("Poisoned variable".to_string());
+                    }
 
-                    Ok((expr.clone(), variable.typ.clone()))
-                } else if let Some(variable) = self.globals.get(name) {
                     expr.typed = variable.typ.clone().into();
 
                     Ok((expr.clone(), variable.typ.clone()))
@@ -580,13 +2077,29 @@ impl<'a> Typechecker<'a> {
                     return Err("Unknown variable".to_string());
                 }
             }
-            Expression::FunctionCall { function, args } => {
-                let return_type = match &function.expression {
+            Expression::FunctionCall {
+                ref function,
+                ref args,
+                ..
+            } => {
+                if let Some((enu, variant)) = self.enum_variant_access(&function.expression, env) {
+                    return self.typecheck_enum_variant(enu, variant, args.clone(), expr.span, env);
+                }
+
+                if let Expression::StructAccess(..) = function.expression {
+                    return self.typecheck_method_call(expr, env);
+                }
+
+                // Builtins that haven't been declared aren't checked
+                let (mut return_type, params) = match &function.expression {
                     Expression::Identifier(i) => match self.functions.get(&i.as_str()) {
-                        Some(f) => &f.returns,
+                        Some(f) => (
+                            f.returns.clone(),
+                            Some((f.name, f.args.clone(), f.varargs, !f.type_params.is_empty())),
+                        ),
                         None => {
                             if i == "printf" || i == "sprintf" || i == "puts" {
-                                &AzulaType::Void
+                                (AzulaType::Void, None)
                             } else {
                                 self.errors.push(AzulaError::new(
                                     ErrorType::FunctionNotFound(i.to_string()),
@@ -597,17 +2110,40 @@ impl<'a> Typechecker<'a> {
                             }
                         }
                     },
-                    _ => todo!(),
-                }
-                .clone();
+                    // Only functions can be called, and they're always named
+                    _ => {
+                        let (_, typ) = self.typecheck_expression(function.deref().clone(), env)?;
+                        self.errors.push(AzulaError::new(
+                            ErrorType::NotCallable(typ.to_string()),
+                            function.span.start,
+                            function.span.end,
+                        ));
+                        return Err("Not callable".to_string());
+                    }
+                };
 
-                let mut new_args = vec![];
-                for arg in args.clone() {
-                    let (arg, _) = match self.typecheck_expression(arg, env) {
-                        Ok((arg, typ)) => (arg, typ),
-                        Err(e) => return Err(e),
-                    };
-                    new_args.push(arg);
+                let (new_args, arg_types) = self.typecheck_call_arguments(args.clone(), env)?;
+
+                let mut type_args = vec![];
+                if let Some((name, mut params, varargs, generic)) = params {
+                    if generic {
+                        (params, return_type, type_args) = self.instantiate_call(
+                            name,
+                            params,
+                            return_type,
+                            &arg_types,
+                            &expr.span,
+                        )?;
+                    }
+
+                    self.check_call_arguments(
+                        &function.expression,
+                        &params,
+                        varargs,
+                        &new_args,
+                        &arg_types,
+                        &expr.span,
+                    )?;
                 }
 
                 return Ok((
@@ -615,6 +2151,7 @@ impl<'a> Typechecker<'a> {
                         expression: Expression::FunctionCall {
                             function: function.clone(),
                             args: new_args,
+                            type_args,
                         },
                         typed: return_type.clone(),
                         span: expr.span,
@@ -630,7 +2167,7 @@ impl<'a> Typechecker<'a> {
 
                 if typ != AzulaType::Bool {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonBoolCondition(format!("{:?}", typ)),
+                        ErrorType::NonBoolCondition(typ.to_string()),
                         expr.span.start,
                         expr.span.end,
                     ));
@@ -679,52 +2216,49 @@ impl<'a> Typechecker<'a> {
                     ));
                 }
 
-                let first_typ = &typs[0].as_ref().unwrap().1;
+                // Items are checked against the first that typechecked, and
+                // any that failed were reported already
+                let checked: Vec<_> = typs.iter().flatten().cloned().collect();
+                let first_typ = match checked.first() {
+                    Some((_, typ)) => typ.clone(),
+                    None => return Err("Failed to typecheck array".to_string()),
+                };
 
-                for val in typs.clone() {
-                    let (node, typ) = match val {
-                        Ok((node, typ)) => (node, typ),
-                        Err(_) => continue,
-                    };
-                    if typ != first_typ.clone() {
+                for (node, typ) in &checked {
+                    if *typ != first_typ {
                         self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", typ),
-                                format!("{:?}", first_typ),
-                            ),
+                            ErrorType::MismatchedTypes(typ.to_string(), first_typ.to_string()),
                             node.span.start,
                             node.span.end,
                         ));
                     }
                 }
+                if checked.len() != typs.len() {
+                    return Err("Failed to typecheck array".to_string());
+                }
 
                 Ok((
                     ExpressionNode {
                         expression: Expression::Array(
-                            typs.clone()
-                                .iter()
-                                .map(|s| s.as_ref().unwrap().clone())
-                                .map(|(node, _)| node)
-                                .collect(),
+                            checked.into_iter().map(|(node, _)| node).collect(),
                         ),
                         typed: AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
                         span: expr.span,
                     },
-                    AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
+                    AzulaType::Array(Rc::new(first_typ), Some(typs.len())),
                 ))
             }
             Expression::ArrayAccess(array, index) => {
-                let (array, array_typ) = self
-                    .typecheck_expression(array.deref().clone(), env)
-                    .unwrap();
+                // Check both before bailing so errors in either are reported
+                let array = self.typecheck_expression(array.deref().clone(), env);
+                let index = self.typecheck_expression(index.deref().clone(), env);
 
-                let (index, typ) = self
-                    .typecheck_expression(index.deref().clone(), env)
-                    .unwrap();
+                let (array, array_typ) = array?;
+                let (index, typ) = index?;
 
                 if typ != AzulaType::Int {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonIntIndex(format!("{:?}", typ)),
+                        ErrorType::NonIntIndex(typ.to_string()),
                         array.span.start,
                         index.span.end,
                     ));
@@ -743,7 +2277,7 @@ impl<'a> Typechecker<'a> {
                     }
                 } else {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonArrayInIndex(format!("{:?}", array_typ)),
+                        ErrorType::NonArrayInIndex(array_typ.to_string()),
                         array.span.start,
                         array.span.end,
                     ));
@@ -774,6 +2308,18 @@ impl<'a> Typechecker<'a> {
                     attrs_new.push((*name, expr));
                 }
 
+                // Generic structs are initialised as the instance for their attributes' types
+                let mut struc = struc;
+                let mut name = name;
+                if self.generic_structs.contains_key(&name) {
+                    name = self.instantiate_initialisation(&name, &attrs_new, &expr.span)?;
+                    struc = Rc::new(ExpressionNode {
+                        expression: Expression::Identifier(name.clone()),
+                        typed: struc.typed.clone(),
+                        span: struc.span.clone(),
+                    });
+                }
+
                 return Ok((
                     ExpressionNode {
                         expression: Expression::StructInitialisation(struc, attrs_new),
@@ -783,6 +2329,12 @@ impl<'a> Typechecker<'a> {
                     AzulaType::Named(name.clone()),
                 ));
             }
+            Expression::StructAccess(..)
+                if self.enum_variant_access(&expr.expression, env).is_some() =>
+            {
+                let (enu, variant) = self.enum_variant_access(&expr.expression, env).unwrap();
+                self.typecheck_enum_variant(enu, variant, vec![], expr.span, env)
+            }
             Expression::StructAccess(struc, access) => {
                 let (struc, struc_type) =
                     match self.typecheck_expression(struc.deref().clone(), env) {
@@ -861,6 +2413,11 @@ impl<'a> Typechecker<'a> {
                     typ.clone(),
                 ));
             }
+            Expression::Range(..) => unreachable!("ranges are only parsed in for loops"),
+            Expression::Match(..) => self.typecheck_match(expr, env, true),
+            Expression::EnumVariant(..) => {
+                unreachable!("enum variants are created by the typechecker")
+            }
         }
     }
 
@@ -870,15 +2427,12 @@ impl<'a> Typechecker<'a> {
         env: &Environment<'a>,
     ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
         if let Expression::Infix(ref left, ref operator, ref right) = expr.expression {
-            let (left, left_typ) = match self.typecheck_expression(left.deref().clone(), env) {
-                Ok((left, typ)) => (left, typ),
-                Err(e) => return Err(e),
-            };
+            // Check both sides before bailing so errors in either are reported
+            let left = self.typecheck_expression(left.deref().clone(), env);
+            let right = self.typecheck_expression(right.deref().clone(), env);
 
-            let (right, right_typ) = match self.typecheck_expression(right.deref().clone(), env) {
-                Ok((right, typ)) => (right, typ),
-                Err(e) => return Err(e),
-            };
+            let (left, left_typ) = left?;
+            let (right, right_typ) = right?;
 
             let allowed = hashmap! {
                 Operator::Add => vec![AzulaType::Int, AzulaType::Float],
@@ -897,25 +2451,20 @@ impl<'a> Typechecker<'a> {
                 Operator::Gte => vec![AzulaType::Int, AzulaType::Float],
             };
 
+            // Type parameters in a generic body may be given any type
             let allowed = allowed.get(operator).unwrap();
-            if !allowed.contains(&left_typ) {
+            if !allowed.contains(&left_typ) && !self.is_abstract(&left_typ) {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonOperatorType(
-                        format!("{:?}", left_typ),
-                        format!("{:?}", operator),
-                    ),
+                    ErrorType::NonOperatorType(left_typ.to_string(), operator.to_string()),
                     left.span.start,
                     left.span.end,
                 ));
                 return Err("cannot use operator with type".to_string());
             }
 
-            if !allowed.contains(&right_typ) {
+            if !allowed.contains(&right_typ) && !self.is_abstract(&right_typ) {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonOperatorType(
-                        format!("{:?}", right_typ),
-                        format!("{:?}", operator),
-                    ),
+                    ErrorType::NonOperatorType(right_typ.to_string(), operator.to_string()),
                     right.span.start,
                     right.span.end,
                 ));
@@ -923,16 +2472,14 @@ impl<'a> Typechecker<'a> {
             }
 
             match operator {
-                Operator::Add => {
+                Operator::Add
+                | Operator::Sub
+                | Operator::Mul
+                | Operator::Div
+                | Operator::Mod
+                | Operator::Power => {
                     if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
+                        self.mismatched_types(&left, &left_typ, &right, &right_typ);
                         return Err("mismatched types in infix".to_string());
                     }
 
@@ -950,20 +2497,15 @@ impl<'a> Typechecker<'a> {
                         left_typ,
                     ))
                 }
-                Operator::Sub => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
-
-                    expr.typed = left.clone().typed;
+                Operator::Or
+                | Operator::And
+                | Operator::Eq
+                | Operator::Neq
+                | Operator::Lt
+                | Operator::Lte
+                | Operator::Gt
+                | Operator::Gte => {
+                    expr.typed = AzulaType::Bool;
                     Ok((
                         ExpressionNode {
                             expression: Expression::Infix(
@@ -971,167 +2513,653 @@ impl<'a> Typechecker<'a> {
                                 operator.clone(),
                                 Rc::new(right),
                             ),
-                            typed: left_typ.clone().into(),
+                            typed: AzulaType::Bool,
                             span: expr.span,
                         },
-                        left_typ,
+                        AzulaType::Bool,
                     ))
                 }
-                Operator::Mul => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
+            }
+        } else {
+            unreachable!()
+        }
+    }
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
-                        },
-                        left_typ,
-                    ))
-                }
-                Operator::Div => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
+    /// Reports the two sides of a binary expression having different types,
+    /// labelling each side with its type
+    fn mismatched_types(
+        &mut self,
+        left: &ExpressionNode<'a>,
+        left_typ: &AzulaType<'a>,
+        right: &ExpressionNode<'a>,
+        right_typ: &AzulaType<'a>,
+    ) {
+        self.errors.push(
+            AzulaError::new(
+                ErrorType::MismatchedTypes(left_typ.to_string(), right_typ.to_string()),
+                left.span.start,
+                right.span.end,
+            )
+            .with_label(left.span.start, left.span.end, left_typ.to_string())
+            .with_label(right.span.start, right.span.end, right_typ.to_string()),
+        );
+    }
+}
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
-                        },
-                        left_typ,
-                    ))
-                }
-                Operator::Mod => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azula_ast::prelude::Span;
+    use std::rc::Rc;
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
+    #[test]
+    fn test_function() {
+        let root = Statement::Root(vec![Statement::Function {
+            name: "main",
+            public: false,
+            attributes: vec![],
+            type_params: vec![],
+            args: vec![(AzulaType::Int, "x")],
+            returns: AzulaType::Bool,
+            body: Rc::new(Statement::Block(vec![])),
+            span: Span { start: 0, end: 1 },
+        }]);
+
+        let mut typechecker = Typechecker::new(root);
+        typechecker.typecheck().unwrap();
+    }
+
+    #[test]
+    fn test_function_call_arguments() {
+        let call = |name: &str, args: Vec<Expression<'static>>| ExpressionNode {
+            expression: Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::Identifier(name.to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 3 },
+                }),
+                args: args
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, expression)| ExpressionNode {
+                        expression,
+                        typed: AzulaType::Infer,
+                        span: Span {
+                            start: 4 + index * 3,
+                            end: 5 + index * 3,
                         },
-                        left_typ,
-                    ))
-                }
-                Operator::Power => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
+                    })
+                    .collect(),
+                type_args: vec![],
+            },
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 10 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker.functions.insert(
+            "add",
+            FunctionDefinition {
+                name: "add",
+                type_params: vec![],
+                args: vec![(AzulaType::Int, "x"), (AzulaType::Int, "y")],
+                varargs: false,
+                returns: AzulaType::Int,
+            },
+        );
+        typechecker.functions.insert(
+            "print",
+            FunctionDefinition {
+                name: "print",
+                type_params: vec![],
+                args: vec![(AzulaType::Pointer(Rc::new(AzulaType::Str)), "xyz")],
+                varargs: true,
+                returns: AzulaType::Void,
+            },
+        );
+        let environment = Environment::new();
+
+        let (_, typ) = typechecker
+            .typecheck_expression(
+                call("add", vec![Expression::Integer(1), Expression::Integer(2)]),
+                &environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::Int);
+
+        // Varargs
+        typechecker
+            .typecheck_expression(
+                call(
+                    "print",
+                    vec![
+                        Expression::String("%d %d".to_string()),
+                        Expression::Integer(1),
+                        Expression::Boolean(true),
+                    ],
+                ),
+                &environment,
+            )
+            .unwrap();
+        assert!(typechecker.errors.is_empty());
+
+        // Too few
+        let result = typechecker
+            .typecheck_expression(call("add", vec![Expression::Integer(1)]), &environment);
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::WrongArgumentCount(_, 2, 1)
+        ));
+
+        // Too many
+        let result = typechecker.typecheck_expression(
+            call(
+                "add",
+                vec![
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                    Expression::Integer(3),
+                ],
+            ),
+            &environment,
+        );
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[1].error_type,
+            ErrorType::WrongArgumentCount(_, 2, 3)
+        ));
+
+        // Wrong type points at the argument
+        let result = typechecker.typecheck_expression(
+            call(
+                "add",
+                vec![Expression::Integer(1), Expression::Boolean(true)],
+            ),
+            &environment,
+        );
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[2].error_type,
+            ErrorType::WrongArgumentType(..)
+        ));
+        assert_eq!(typechecker.errors[2].start, 7);
+        assert_eq!(typechecker.errors[2].end, 8);
+
+        // Calling the result of a call, like `add(1, 2)(3)`, points at the callee
+        let callee = call("add", vec![Expression::Integer(1), Expression::Integer(2)]);
+        let result = typechecker.typecheck_expression(
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(callee),
+                    args: vec![],
+                    type_args: vec![],
+                },
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 13 },
+            },
+            &environment,
+        );
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[3].error_type,
+            ErrorType::NotCallable(ref typ) if typ == "int"
+        ));
+        assert_eq!(typechecker.errors[3].end, 10);
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let node = |expression: Expression<'static>| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 1 },
+        };
+        let ident = |name: &str| node(Expression::Identifier(name.to_string()));
+        let span = Span { start: 0, end: 1 };
+
+        let body = vec![
+            // Unknown variable, x is poisoned
+            Statement::Assign(true, "x".to_string(), None, ident("unknown"), span.clone()),
+            // No cascade from using x
+            Statement::Assign(
+                true,
+                "y".to_string(),
+                None,
+                node(Expression::Infix(
+                    Rc::new(ident("x")),
+                    Operator::Add,
+                    Rc::new(node(Expression::Integer(1))),
+                )),
+                span.clone(),
+            ),
+            // Bad condition, but the body is still checked
+            Statement::If(
+                node(Expression::Integer(5)),
+                vec![Statement::ExpressionStatement(ident("other"), span.clone())],
+                None,
+                span.clone(),
+            ),
+            // Bad array items, assigns to things that can't be assigned to and
+            // statements in the wrong place are reported rather than panicking
+            Statement::Assign(
+                true,
+                "a".to_string(),
+                None,
+                node(Expression::Array(vec![
+                    ident("missing"),
+                    node(Expression::Integer(1)),
+                ])),
+                span.clone(),
+            ),
+            Statement::Reassign(
+                node(Expression::Integer(1)),
+                node(Expression::Integer(2)),
+                span.clone(),
+            ),
+            Statement::Import("other.azl", span.clone()),
+        ];
+
+        let root = Statement::Root(vec![
+            Statement::Function {
+                name: "main",
+                public: false,
+                attributes: vec![],
+                type_params: vec![],
+                args: vec![],
+                returns: AzulaType::Void,
+                body: Rc::new(Statement::Block(body)),
+                span: span.clone(),
+            },
+            Statement::Assign(false, "g".to_string(), None, ident("x"), span.clone()),
+            Statement::If(node(Expression::Boolean(true)), vec![], None, span.clone()),
+        ]);
+
+        let mut typechecker = Typechecker::new(root);
+        assert!(typechecker.typecheck().is_err());
+
+        let errors: Vec<_> = typechecker
+            .errors
+            .iter()
+            .map(|e| format!("{:?}", e.error_type))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "UnknownVariable(\"unknown\")",
+                "NonBoolCondition(\"int\")",
+                "UnknownVariable(\"other\")",
+                "UnknownVariable(\"missing\")",
+                "InvalidAssignTarget",
+                "MisplacedStatement(\"import\")",
+                "NonGlobalConstant",
+                "MisplacedStatement(\"if\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum_match() {
+        let node = |expression: Expression<'static>| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 1 },
+        };
+        let ident = |name: &str| node(Expression::Identifier(name.to_string()));
+        let span = Span { start: 0, end: 1 };
+        let arm = |pattern, value| MatchArm {
+            pattern,
+            body: vec![],
+            value,
+            span: Span { start: 0, end: 1 },
+        };
+        let shape = AzulaType::Named("Shape".to_string());
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![Statement::Enum {
+            name: "Shape",
+            public: false,
+            variants: vec![("Circle", vec![AzulaType::Int]), ("Empty", vec![])],
+            span: span.clone(),
+        }]));
+        typechecker.typecheck().unwrap();
+
+        // Constructing variants
+        let env = Environment::new();
+        let circle = node(Expression::FunctionCall {
+            function: Rc::new(node(Expression::StructAccess(
+                Rc::new(ident("Shape")),
+                Rc::new(ident("Circle")),
+            ))),
+            args: vec![node(Expression::Integer(5))],
+            type_args: vec![],
+        });
+        let (expr, typ) = typechecker.typecheck_expression(circle, &env).unwrap();
+        assert_eq!(typ, shape);
+        assert!(matches!(
+            expr.expression,
+            Expression::EnumVariant(ref enu, ref variant, ref args)
+                if enu == "Shape" && variant == "Circle" && args.len() == 1
+        ));
+        let empty = node(Expression::StructAccess(
+            Rc::new(ident("Shape")),
+            Rc::new(ident("Empty")),
+        ));
+        assert!(typechecker.typecheck_expression(empty, &env).is_ok());
+
+        // Matching, with the payload bound in the arm
+        let mut env = Environment::new();
+        env.add_variable(
+            "s".to_string(),
+            VariableDefinition {
+                name: "s".to_string(),
+                mutable: false,
+                typ: shape,
+            },
+        );
+        let exhaustive = node(Expression::Match(
+            Rc::new(ident("s")),
+            vec![
+                arm(Pattern::Variant("Circle", vec!["r"]), Some(ident("r"))),
+                arm(
+                    Pattern::Variant("Empty", vec![]),
+                    Some(node(Expression::Integer(0))),
+                ),
+            ],
+        ));
+        let (_, typ) = typechecker.typecheck_expression(exhaustive, &env).unwrap();
+        assert_eq!(typ, AzulaType::Int);
+        assert!(typechecker.errors.is_empty());
+
+        let wildcard = node(Expression::Match(
+            Rc::new(ident("s")),
+            vec![arm(Pattern::Wildcard, None)],
+        ));
+        assert!(typechecker.typecheck_match(wildcard, &env, false).is_ok());
+
+        // Missing a variant
+        let missing = node(Expression::Match(
+            Rc::new(ident("s")),
+            vec![arm(Pattern::Variant("Empty", vec![]), None)],
+        ));
+        assert!(typechecker.typecheck_match(missing, &env, false).is_err());
+
+        // Unknown variant, wrong bindings and an arm without a value
+        let bad = node(Expression::Match(
+            Rc::new(ident("s")),
+            vec![
+                arm(Pattern::Variant("Square", vec![]), Some(ident("s"))),
+                arm(Pattern::Variant("Circle", vec![]), Some(ident("s"))),
+                arm(Pattern::Wildcard, None),
+            ],
+        ));
+        assert!(typechecker.typecheck_expression(bad, &env).is_err());
+
+        let errors: Vec<_> = typechecker
+            .errors
+            .iter()
+            .map(|e| format!("{:?}", e.error_type))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "NonExhaustiveMatch(\"Shape\", \"Circle\")",
+                "UnknownVariant(\"Shape\", \"Square\")",
+                "WrongArgumentCount(\"Circle\", 1, 0)",
+                "MatchArmWithoutValue",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generics() {
+        let node = |expression: Expression<'static>| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 1 },
+        };
+        let ident = |name: &str| node(Expression::Identifier(name.to_string()));
+        let call = |name: &str, args| {
+            node(Expression::FunctionCall {
+                function: Rc::new(ident(name)),
+                args,
+                type_args: vec![],
+            })
+        };
+        let span = Span { start: 0, end: 1 };
+        let param = |name: &str| AzulaType::Named(name.to_string());
+
+        let generic_function = Statement::Function {
+            name: "first",
+            public: false,
+            attributes: vec![],
+            type_params: vec!["A", "B"],
+            args: vec![(
+                AzulaType::Generic("Pair".to_string(), vec![param("A"), param("B")]),
+                "p",
+            )],
+            returns: param("A"),
+            body: Rc::new(Statement::Block(vec![Statement::Return(
+                Some(node(Expression::StructAccess(
+                    Rc::new(ident("p")),
+                    Rc::new(ident("first")),
+                ))),
+                span.clone(),
+            )])),
+            span: span.clone(),
+        };
+        let main = |body| Statement::Function {
+            name: "main",
+            public: false,
+            attributes: vec![],
+            type_params: vec![],
+            args: vec![],
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(body)),
+            span: span.clone(),
+        };
+        let pair = Statement::Struct {
+            name: "Pair",
+            public: false,
+            type_params: vec!["A", "B"],
+            attributes: vec![(param("A"), "first"), (param("B"), "second")],
+            span: span.clone(),
+        };
+
+        // The struct's type arguments come from its attributes, the function's
+        // from its argument. Instances are put before everything else
+        let init = node(Expression::StructInitialisation(
+            Rc::new(ident("Pair")),
+            vec![
+                ("first", node(Expression::Integer(1))),
+                ("second", node(Expression::Boolean(true))),
+            ],
+        ));
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            pair.clone(),
+            generic_function.clone(),
+            main(vec![Statement::Assign(
+                false,
+                "x".to_string(),
+                Some(AzulaType::Int),
+                call("first", vec![init]),
+                span.clone(),
+            )]),
+        ]));
+        let stmts = match typechecker.typecheck() {
+            Ok(Statement::Root(stmts)) => stmts,
+            _ => panic!("{:?}", typechecker.errors),
+        };
+
+        let instance_types = vec![AzulaType::Int, AzulaType::Bool];
+        let pair_instance = AzulaType::Named("Pair<int, bool>".to_string());
+        assert!(matches!(
+            &stmts[0],
+            Statement::Instance(args, def) if *args == instance_types
+                && matches!(def.deref(), Statement::Struct { attributes, .. }
+                    if *attributes == vec![(AzulaType::Int, "first"), (AzulaType::Bool, "second")])
+        ));
+        if let Statement::Instance(args, def) = &stmts[1] {
+            assert_eq!(*args, instance_types);
+            assert!(matches!(
+                def.deref(),
+                Statement::Function { name: "first", args, returns: AzulaType::Int, .. }
+                    if args[0].0 == pair_instance
+            ));
+        } else {
+            panic!("expected function instance");
+        }
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
-                        },
-                        left_typ,
-                    ))
-                }
-                Operator::Or
-                | Operator::And
-                | Operator::Eq
-                | Operator::Neq
-                | Operator::Lt
-                | Operator::Lte
-                | Operator::Gt
-                | Operator::Gte => {
-                    expr.typed = AzulaType::Bool;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: AzulaType::Bool,
-                            span: expr.span,
-                        },
-                        AzulaType::Bool,
-                    ))
+        if let Statement::Function { body, .. } = &stmts[4] {
+            if let Statement::Block(body) = body.deref() {
+                if let Statement::Assign(_, _, _, value, _) = &body[0] {
+                    assert_eq!(value.typed, AzulaType::Int);
+                    if let Expression::FunctionCall {
+                        type_args, args, ..
+                    } = &value.expression
+                    {
+                        assert_eq!(*type_args, instance_types);
+                        assert_eq!(args[0].typed, pair_instance);
+                    } else {
+                        panic!("expected call");
+                    }
                 }
             }
         } else {
-            unreachable!()
+            panic!("expected main");
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use azula_ast::prelude::Span;
-    use std::rc::Rc;
+        // Type parameters that can't be inferred, and generic structs used without arguments
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            pair,
+            generic_function,
+            Statement::Function {
+                name: "none",
+                public: false,
+                attributes: vec![],
+                type_params: vec!["T"],
+                args: vec![],
+                returns: param("T"),
+                body: Rc::new(Statement::Block(vec![])),
+                span: span.clone(),
+            },
+            main(vec![
+                Statement::ExpressionStatement(call("none", vec![]), span.clone()),
+                Statement::Assign(
+                    false,
+                    "y".to_string(),
+                    Some(param("Pair")),
+                    node(Expression::Integer(1)),
+                    span.clone(),
+                ),
+            ]),
+        ]));
+        assert!(typechecker.typecheck().is_err());
+        assert_eq!(typechecker.errors.len(), 2);
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::CannotInferType(ref name, ref param) if name == "none" && param == "T"
+        ));
+        assert!(matches!(
+            typechecker.errors[1].error_type,
+            ErrorType::WrongTypeArgumentCount(ref name, 2, 0) if name == "Pair"
+        ));
+
+        // Bodies are checked with their type parameters standing for any type,
+        // whether or not anything uses them, and only reported once
+        let generic = |name, body| Statement::Function {
+            name,
+            public: false,
+            attributes: vec![],
+            type_params: vec!["T"],
+            args: vec![(param("T"), "a"), (param("T"), "b")],
+            returns: AzulaType::Bool,
+            body: Rc::new(Statement::Block(vec![Statement::Return(
+                Some(body),
+                span.clone(),
+            )])),
+            span: span.clone(),
+        };
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            generic(
+                "bigger",
+                node(Expression::Infix(
+                    Rc::new(ident("a")),
+                    Operator::Gt,
+                    Rc::new(ident("b")),
+                )),
+            ),
+            generic("broken", ident("nope")),
+            main(vec![Statement::ExpressionStatement(
+                call(
+                    "broken",
+                    vec![node(Expression::Integer(1)), node(Expression::Integer(2))],
+                ),
+                span.clone(),
+            )]),
+        ]));
+        assert!(typechecker.typecheck().is_err());
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::UnknownVariable(ref name) if name == "nope"
+        ));
+    }
 
     #[test]
-    fn test_function() {
+    fn test_import() {
+        let call = Statement::ExpressionStatement(
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("add".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 },
+                    }),
+                    args: vec![],
+                    type_args: vec![],
+                },
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 1 },
+            },
+            Span { start: 0, end: 1 },
+        );
         let root = Statement::Root(vec![Statement::Function {
             name: "main",
-            args: vec![(AzulaType::Int, "x")],
-            returns: AzulaType::Bool,
-            body: Rc::new(Statement::Block(vec![])),
+            public: false,
+            attributes: vec![],
+            type_params: vec![],
+            args: vec![],
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![call])),
             span: Span { start: 0, end: 1 },
         }]);
 
+        // Not visible without the import
+        let mut typechecker = Typechecker::new(root.clone());
+        assert!(typechecker.typecheck().is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::FunctionNotFound(..)
+        ));
+
         let mut typechecker = Typechecker::new(root);
-        typechecker.typecheck().unwrap();
+        typechecker.import(vec![Statement::Function {
+            name: "add",
+            public: true,
+            attributes: vec![],
+            type_params: vec![],
+            args: vec![],
+            returns: AzulaType::Int,
+            body: Rc::new(Statement::Block(vec![])),
+            span: Span { start: 0, end: 1 },
+        }]);
+        let typed = typechecker.typecheck().unwrap();
+
+        // Imported bodies aren't part of this file's output
+        if let Statement::Root(stmts) = typed {
+            assert_eq!(stmts.len(), 1);
+        }
+        assert!(typechecker.errors.is_empty());
     }
 
     #[test]
@@ -1162,6 +3190,154 @@ mod tests {
         assert_eq!(var.typ, AzulaType::Int);
     }
 
+    #[test]
+    fn test_for() {
+        let node = |expression| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 1 },
+        };
+        let span = Span { start: 0, end: 1 };
+
+        let body = |name: &str| {
+            vec![Statement::ExpressionStatement(
+                node(Expression::Identifier(name.to_string())),
+                span.clone(),
+            )]
+        };
+        fn body_type(stmt: Statement<'_>) -> AzulaType<'_> {
+            match stmt {
+                Statement::For(_, _, body, _) => match &body[0] {
+                    Statement::ExpressionStatement(expr, _) => expr.typed.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            }
+        }
+
+        // Ranges give an int loop variable
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        let range = node(Expression::Range(
+            Rc::new(node(Expression::Integer(0))),
+            Rc::new(node(Expression::Integer(10))),
+        ));
+        let (stmt, _) = typechecker
+            .typecheck_for(
+                Statement::For("i".to_string(), range.clone(), body("i"), span.clone()),
+                &mut environment,
+            )
+            .unwrap();
+        assert_eq!(body_type(stmt), AzulaType::Int);
+
+        // Arrays give their element type
+        let array = node(Expression::Array(vec![
+            node(Expression::Boolean(true)),
+            node(Expression::Boolean(false)),
+        ]));
+        let (stmt, _) = typechecker
+            .typecheck_for(
+                Statement::For("x".to_string(), array, body("x"), span.clone()),
+                &mut environment,
+            )
+            .unwrap();
+        assert_eq!(body_type(stmt), AzulaType::Bool);
+
+        // The loop variable is gone after the loop, and only shadows an outer one
+        assert!(!environment.variable_definitions.contains_key("i"));
+        environment.add_variable(
+            "flag".to_string(),
+            VariableDefinition {
+                name: "flag".to_string(),
+                mutable: false,
+                typ: AzulaType::Bool,
+            },
+        );
+        let (stmt, _) = typechecker
+            .typecheck_for(
+                Statement::For("flag".to_string(), range, body("flag"), span.clone()),
+                &mut environment,
+            )
+            .unwrap();
+        assert_eq!(body_type(stmt), AzulaType::Int);
+        let var = environment.variable_definitions.get("flag").unwrap();
+        assert_eq!(var.typ, AzulaType::Bool);
+
+        // Bad ranges and non-arrays
+        let range = node(Expression::Range(
+            Rc::new(node(Expression::Integer(0))),
+            Rc::new(node(Expression::Boolean(true))),
+        ));
+        assert!(typechecker
+            .typecheck_for(
+                Statement::For("i".to_string(), range, vec![], span.clone()),
+                &mut environment,
+            )
+            .is_err());
+        assert!(typechecker
+            .typecheck_for(
+                Statement::For("i".to_string(), node(Expression::Integer(5)), vec![], span),
+                &mut environment,
+            )
+            .is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::NonIntRange(..)
+        ));
+        assert!(matches!(
+            typechecker.errors[1].error_type,
+            ErrorType::NotIterable(..)
+        ));
+    }
+
+    #[test]
+    fn test_loop_jump() {
+        let span = Span { start: 0, end: 1 };
+        let condition = ExpressionNode {
+            expression: Expression::Boolean(true),
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        typechecker
+            .typecheck_while(
+                Statement::While(
+                    condition.clone(),
+                    vec![
+                        Statement::Break(span.clone()),
+                        Statement::If(
+                            condition,
+                            vec![Statement::Continue(span.clone())],
+                            None,
+                            span.clone(),
+                        ),
+                    ],
+                    span.clone(),
+                ),
+                &mut environment,
+            )
+            .unwrap();
+        assert!(typechecker.errors.is_empty());
+
+        // Outside of a loop
+        assert!(typechecker
+            .typecheck_statement(Statement::Continue(span.clone()), &mut environment)
+            .is_err());
+        assert!(typechecker
+            .typecheck_statement(Statement::Break(span), &mut environment)
+            .is_err());
+        assert!(matches!(
+            &typechecker.errors[0].error_type,
+            ErrorType::OutsideLoop(keyword) if keyword == "continue"
+        ));
+        assert!(matches!(
+            &typechecker.errors[1].error_type,
+            ErrorType::OutsideLoop(keyword) if keyword == "break"
+        ));
+    }
+
     #[test]
     fn test_return() {
         // Return value
@@ -1196,6 +3372,76 @@ mod tests {
         assert_eq!(typ, AzulaType::Void);
     }
 
+    #[test]
+    fn test_if_else() {
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+
+        let mut environment = Environment::new();
+        let (stmt, _) = typechecker
+            .typecheck_if(
+                Statement::If(
+                    ExpressionNode {
+                        expression: Expression::Boolean(true),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 },
+                    },
+                    vec![],
+                    Some(vec![Statement::Return(
+                        Some(ExpressionNode {
+                            expression: Expression::Integer(5),
+                            typed: AzulaType::Infer,
+                            span: Span { start: 0, end: 1 },
+                        }),
+                        Span { start: 0, end: 1 },
+                    )]),
+                    Span { start: 0, end: 1 },
+                ),
+                &mut environment,
+            )
+            .unwrap();
+
+        if let Statement::If(_, _, Some(else_body), _) = stmt {
+            if let Statement::Return(Some(expr), _) = &else_body[0] {
+                assert_eq!(expr.typed, AzulaType::Int);
+            }
+        } else {
+            panic!("expected else body");
+        }
+
+        // Non-bool condition in else if
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+
+        let mut environment = Environment::new();
+        let result = typechecker.typecheck_if(
+            Statement::If(
+                ExpressionNode {
+                    expression: Expression::Boolean(true),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                },
+                vec![],
+                Some(vec![Statement::If(
+                    ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 },
+                    },
+                    vec![],
+                    None,
+                    Span { start: 0, end: 1 },
+                )]),
+                Span { start: 0, end: 1 },
+            ),
+            &mut environment,
+        );
+
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::NonBoolCondition(..)
+        ));
+    }
+
     #[test]
     fn test_integer_expression() {
         let integer_node = ExpressionNode {
@@ -1331,6 +3577,47 @@ mod tests {
             typechecker.errors[0].error_type,
             ErrorType::NonOperatorType(..)
         ));
+        assert_eq!(
+            typechecker.errors[0].error_type.error_message(),
+            "Type bool cannot be used with operator +"
+        );
+
+        // Mismatched types, labelled the way they're written
+        let infix_node = ExpressionNode {
+            expression: Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                Operator::Mul,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Float(2.5),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 4, end: 7 },
+                }),
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 7 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let environment = Environment::new();
+        assert!(typechecker
+            .typecheck_expression(infix_node, &environment)
+            .is_err());
+
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::MismatchedTypes(ref left, ref right) if left == "int" && right == "float"
+        ));
+        let labels: Vec<_> = typechecker.errors[0]
+            .labels
+            .iter()
+            .map(|label| label.message.as_str())
+            .collect();
+        assert_eq!(labels, vec!["int", "float"]);
     }
 
     #[test]
@@ -1422,6 +3709,8 @@ mod tests {
             StructDefinition {
                 name: "Test",
                 attrs: vec![(AzulaType::Int, "test")],
+                methods: HashMap::new(),
+                mutating: HashSet::new(),
             },
         );
         let mut environment = Environment::new();
@@ -1440,4 +3729,139 @@ mod tests {
         assert_eq!(typ, AzulaType::Int);
         assert_eq!(expr.typed, AzulaType::Int);
     }
+
+    #[test]
+    fn test_method_call_expression() {
+        let call = ExpressionNode {
+            expression: Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::StructAccess(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Identifier("x".to_string()),
+                            typed: AzulaType::Infer,
+                            span: Span { start: 0, end: 1 },
+                        }),
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Identifier("len".to_string()),
+                            typed: AzulaType::Infer,
+                            span: Span { start: 2, end: 5 },
+                        }),
+                    ),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 5 },
+                }),
+                args: vec![],
+                type_args: vec![],
+            },
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 7 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker.structs.insert(
+            "Test".to_string(),
+            StructDefinition {
+                name: "Test",
+                attrs: vec![(AzulaType::Int, "test")],
+                methods: hashmap! {
+                    "len" => FunctionDefinition {
+                        name: "len",
+                        type_params: vec![],
+                        args: vec![(
+                            AzulaType::Pointer(Rc::new(AzulaType::Named("Test".to_string()))),
+                            "self",
+                        )],
+                        varargs: false,
+                        returns: AzulaType::Int,
+                    },
+                },
+                mutating: HashSet::new(),
+            },
+        );
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "x".to_string(),
+            VariableDefinition {
+                name: "x".to_string(),
+                mutable: false,
+                typ: AzulaType::Named("Test".to_string()),
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(call.clone(), &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+
+        // Methods assigning to the receiver can't be called on a constant
+        typechecker
+            .structs
+            .get_mut("Test")
+            .unwrap()
+            .mutating
+            .insert("len");
+        assert!(typechecker
+            .typecheck_expression(call.clone(), &environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors.remove(0).error_type,
+            ErrorType::ConstantAssign
+        ));
+
+        // Unknown method
+        let test = typechecker.structs.get_mut("Test").unwrap();
+        test.methods.clear();
+        test.mutating.clear();
+        let result = typechecker.typecheck_expression(call, &environment);
+
+        assert!(result.is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::UnknownMethod(..)
+        ));
+    }
+
+    #[test]
+    fn test_methods_across_impls() {
+        let errors = |source: &'static str| {
+            let lexer: azula_parser::prelude::Lexer = source.into();
+            let ast = azula_parser::prelude::Parser::new(source, lexer).parse();
+            let mut typechecker = Typechecker::new(ast);
+            let _ = typechecker.typecheck();
+            typechecker
+                .errors
+                .iter()
+                .map(|err| err.error_type.code())
+                .collect::<Vec<_>>()
+        };
+
+        // Mutating a constant through a method, directly or through another
+        // method or a field, is caught like assigning to its fields
+        let source = "struct Count { n: int }
+            impl Count { func bump(self) { self.n = self.n + 1; } }
+            struct Pair { count: Count }
+            impl Pair {
+                func get(self): int { return self.count.n; }
+                func bump(self) { if true { self.count.bump(); } }
+                func both(self) { self.bump(); }
+            }
+            func main {
+                const pair = Pair { count: Count { n: 0 } };
+                pair.get();
+                pair.both();
+                pair.count.bump();
+                pair.count.n = 1;
+                var other = pair;
+                other.both();
+            }";
+        assert_eq!(errors(source), vec!["E0019", "E0019", "E0019"]);
+
+        // Methods can be split across impl blocks, but not defined twice
+        let source = "struct Count { n: int }
+            impl Count { func get(self): int { return self.n; } }
+            impl Count { func get(self): int { return 0; } func set(self) { } }
+            func main { }";
+        assert_eq!(errors(source), vec!["E0054"]);
+    }
 }